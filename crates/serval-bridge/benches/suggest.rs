@@ -0,0 +1,46 @@
+//! How fast the address bar keeps up with typing on a large history: every
+//! keystroke of a query, each scanning 100 000 pages.
+//!
+//! ```sh
+//! cargo bench -p serval-bridge
+//! ```
+
+use std::hint::black_box;
+use std::time::{Duration, SystemTime};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use serval_bridge::omnibox::{self, Places, SearchEngines};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 100 000 pages on 997 sites, visited over a year.
+fn history() -> Places {
+    let now = SystemTime::now();
+    let mut places = Places::new();
+    for i in 0..100_000u32 {
+        let url = format!("https://site{}.example/articles/{i}", i % 997);
+        let title = format!("Article {i} about topic {}", i % 113);
+        places.visit(&url, &title, now - DAY * (i % 365), i % 7 == 0);
+    }
+    places
+}
+
+fn typing(c: &mut Criterion) {
+    let places = history();
+    let engines = SearchEngines::default();
+    let typed = "site42 topic 7";
+
+    let mut group = c.benchmark_group("suggestions");
+    group.throughput(Throughput::Elements(typed.len() as u64));
+    group.bench_function("typing on 100 000 pages", |b| {
+        b.iter(|| {
+            for end in 1..=typed.len() {
+                black_box(omnibox::suggest(&typed[..end], 8, &places, &[], &engines));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, typing);
+criterion_main!(benches);
@@ -0,0 +1,110 @@
+//! `serval-shot`: captures a web page as PNG or PDF with the bridge's engine.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Parser;
+use log::{error, info};
+use serval_bridge::engine::Viewport;
+use serval_bridge::shot::Options;
+
+#[derive(Parser)]
+#[command(version, about = "Captures a web page as PNG or PDF with Servo")]
+struct Args {
+    /// The page to capture.
+    url: String,
+
+    /// Width of the viewport, in CSS pixels.
+    #[arg(long, default_value_t = 1280)]
+    width: u32,
+
+    /// Height of the viewport, in CSS pixels.
+    #[arg(long, default_value_t = 800)]
+    height: u32,
+
+    /// Device pixels per CSS pixel.
+    #[arg(long, default_value_t = 1.0)]
+    scale: f32,
+
+    /// Write a PDF instead of a PNG.
+    #[arg(long)]
+    pdf: bool,
+
+    /// Where to write the capture. Defaults to `screenshot.png`, or
+    /// `screenshot.pdf` with `--pdf`.
+    #[arg(long, short)]
+    out: Option<PathBuf>,
+
+    /// Milliseconds the page must stay quiet after loading before it is
+    /// captured.
+    #[arg(long, default_value_t = 500)]
+    network_idle: u64,
+
+    /// Seconds to wait for the page before giving up.
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+}
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let args = Args::parse();
+
+    let viewport = match Viewport::new(args.width as f32, args.height as f32, args.scale) {
+        Ok(viewport) => viewport,
+        Err(error) => {
+            error!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let options = Options {
+        url: args.url.clone(),
+        viewport,
+        network_idle: Duration::from_millis(args.network_idle),
+        timeout: Duration::from_secs(args.timeout),
+    };
+    let out = args.out.clone().unwrap_or_else(|| {
+        PathBuf::from(if args.pdf {
+            "screenshot.pdf"
+        } else {
+            "screenshot.png"
+        })
+    });
+
+    let result = capture(&options).and_then(|screenshot| {
+        if args.pdf {
+            screenshot.to_pdf()
+        } else {
+            screenshot.to_png()
+        }
+        .map_err(|error| error.to_string())
+    });
+    let bytes = match result {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            error!("cannot capture {}: {error}", args.url);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(error) = std::fs::write(&out, bytes) {
+        error!("cannot write {}: {error}", out.display());
+        return ExitCode::FAILURE;
+    }
+    info!("wrote {}", out.display());
+    // The engine thread keeps running; leave without waiting for it.
+    ExitCode::SUCCESS
+}
+
+#[cfg(feature = "servo")]
+fn capture(options: &Options) -> Result<serval_bridge::shot::Screenshot, String> {
+    use serval_bridge::engine::ServoEngine;
+
+    let (width, height) = options.viewport.device_size();
+    serval_bridge::shot::capture(move |waker| ServoEngine::new(waker, width, height), options)
+        .map_err(|error| error.to_string())
+}
+
+#[cfg(not(feature = "servo"))]
+fn capture(_options: &Options) -> Result<serval_bridge::shot::Screenshot, String> {
+    Err("serval-shot was built without an engine; rebuild it with `--features servo`".to_owned())
+}
@@ -0,0 +1,272 @@
+//! Netscape bookmark files, the HTML Firefox, Chrome and most other browsers
+//! import and export bookmarks as.
+//!
+//! The format predates HTML's parsing rules and every browser writes it a
+//! little differently, so [`parse`] looks for the few tags that matter
+//! instead of parsing HTML: `<H3>` names a folder whose items follow in the
+//! next `<DL>`, `<A HREF>` is a bookmark and `</DL>` ends the folder.
+//! Anything else, like separators, descriptions and icons, is skipped.
+//! Dates are in seconds since the Unix epoch; tags and keywords are the
+//! `TAGS` and `SHORTCUTURL` attributes Firefox writes.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serval_protocol::{Bookmark, BookmarkId};
+
+/// A bookmark or folder read from a bookmark file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Item {
+    pub title: String,
+    /// `None` for a folder.
+    pub url: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub added_at: Option<u64>,
+    pub tags: Vec<String>,
+    pub keyword: Option<String>,
+    /// What the folder holds.
+    pub items: Vec<Item>,
+}
+
+/// The bookmarks and folders of a bookmark file, in the file's order.
+pub fn parse(html: &str) -> Vec<Item> {
+    // The folders being read, innermost last, each with its items. The
+    // first is the file itself, and lists without a heading, like the one
+    // under `<H1>`, add to the folder around them.
+    let mut open: Vec<(Option<Item>, Vec<Item>)> = vec![(None, Vec::new())];
+    // A folder whose `<DL>` did not come yet.
+    let mut heading: Option<Item> = None;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..end];
+        rest = rest.get(end + 1..).unwrap_or_default();
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match name.to_ascii_lowercase().as_str() {
+            "h3" => {
+                let items = &mut open.last_mut().expect("the file stays open").1;
+                items.extend(heading.take());
+                let attributes = parse_attributes(attributes);
+                heading = Some(Item {
+                    title: text(rest),
+                    added_at: date(&attributes),
+                    ..Item::default()
+                });
+            }
+            "a" => {
+                let items = &mut open.last_mut().expect("the file stays open").1;
+                items.extend(heading.take());
+                let mut attributes = parse_attributes(attributes);
+                let Some(url) = attributes.remove("href") else {
+                    continue;
+                };
+                let tags = attributes.get("tags").map_or_else(Vec::new, |tags| {
+                    tags.split(',')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                });
+                items.push(Item {
+                    title: text(rest),
+                    url: Some(url),
+                    added_at: date(&attributes),
+                    tags,
+                    keyword: attributes
+                        .remove("shortcuturl")
+                        .filter(|word| !word.is_empty()),
+                    items: Vec::new(),
+                });
+            }
+            "dl" => open.push((heading.take(), Vec::new())),
+            "/dl" if open.len() > 1 => close(&mut open),
+            _ => {}
+        }
+    }
+    let items = &mut open.last_mut().expect("the file stays open").1;
+    items.extend(heading);
+    while open.len() > 1 {
+        close(&mut open);
+    }
+    open.pop().expect("the file stays open").1
+}
+
+/// Ends the innermost folder being read.
+fn close(open: &mut Vec<(Option<Item>, Vec<Item>)>) {
+    let (folder, items) = open.pop().expect("a folder is open");
+    let outer = &mut open.last_mut().expect("the file stays open").1;
+    match folder {
+        Some(folder) => outer.push(Item { items, ..folder }),
+        None => outer.extend(items),
+    }
+}
+
+/// The text up to the next tag.
+fn text(html: &str) -> String {
+    let end = html.find('<').unwrap_or(html.len());
+    unescape(html[..end].trim())
+}
+
+fn date(attributes: &HashMap<String, String>) -> Option<u64> {
+    let seconds: u64 = attributes.get("add_date")?.trim().parse().ok()?;
+    seconds.checked_mul(1000)
+}
+
+/// The attributes of a tag, by lower-case name.
+fn parse_attributes(mut tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    loop {
+        tag = tag.trim_start();
+        let name_end = tag
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(tag.len());
+        if name_end == 0 {
+            // A stray `=` or the end of the tag.
+            if tag.is_empty() {
+                return attributes;
+            }
+            tag = &tag[1..];
+            continue;
+        }
+        let name = tag[..name_end].to_ascii_lowercase();
+        tag = tag[name_end..].trim_start();
+        let Some(value) = tag.strip_prefix('=') else {
+            attributes.insert(name, String::new());
+            continue;
+        };
+        let value = value.trim_start();
+        let (raw, after) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value = &value[1..];
+                let end = value.find(quote).unwrap_or(value.len());
+                (&value[..end], value.get(end + 1..).unwrap_or_default())
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        attributes.insert(name, unescape(raw));
+        tag = after;
+    }
+}
+
+/// `text` with its character references replaced.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                name => {
+                    let code = name.strip_prefix('#')?;
+                    let code = match code.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16),
+                        None => code.parse(),
+                    };
+                    char::from_u32(code.ok()?)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `bookmarks`, as [`BookmarkStore::all`](super::BookmarkStore::all) lists
+/// them, as a bookmark file.
+pub fn write(bookmarks: &[Bookmark]) -> String {
+    let mut folders: HashMap<Option<BookmarkId>, Vec<&Bookmark>> = HashMap::new();
+    for bookmark in bookmarks {
+        folders
+            .entry(bookmark.parent_id)
+            .or_default()
+            .push(bookmark);
+    }
+    for items in folders.values_mut() {
+        items.sort_by_key(|bookmark| bookmark.index);
+    }
+
+    let mut out = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+         <!-- This is an automatically generated file.\n     \
+         It will be read and overwritten.\n     \
+         DO NOT EDIT! -->\n\
+         <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+         <TITLE>Bookmarks</TITLE>\n\
+         <H1>Bookmarks</H1>\n",
+    );
+    write_folder(&mut out, &folders, None, 0);
+    out
+}
+
+fn write_folder(
+    out: &mut String,
+    folders: &HashMap<Option<BookmarkId>, Vec<&Bookmark>>,
+    folder: Option<BookmarkId>,
+    depth: usize,
+) {
+    let indent = "    ".repeat(depth);
+    writeln!(out, "{indent}<DL><p>").unwrap();
+    for bookmark in folders.get(&folder).into_iter().flatten() {
+        let added = bookmark.added_at / 1000;
+        match &bookmark.url {
+            Some(url) => {
+                write!(
+                    out,
+                    "{indent}    <DT><A HREF=\"{}\" ADD_DATE=\"{added}\"",
+                    escape(url)
+                )
+                .unwrap();
+                if let Some(keyword) = &bookmark.keyword {
+                    write!(out, " SHORTCUTURL=\"{}\"", escape(keyword)).unwrap();
+                }
+                if !bookmark.tags.is_empty() {
+                    write!(out, " TAGS=\"{}\"", escape(&bookmark.tags.join(","))).unwrap();
+                }
+                writeln!(out, ">{}</A>", escape(&bookmark.title)).unwrap();
+            }
+            None => {
+                writeln!(
+                    out,
+                    "{indent}    <DT><H3 ADD_DATE=\"{added}\">{}</H3>",
+                    escape(&bookmark.title)
+                )
+                .unwrap();
+                write_folder(out, folders, Some(bookmark.id), depth + 1);
+            }
+        }
+    }
+    writeln!(out, "{indent}</DL><p>").unwrap();
+}
@@ -0,0 +1,616 @@
+//! The user's bookmarks, in an SQLite database.
+//!
+//! Bookmarks sit in folders, or at the top level, in the order the user put
+//! them there. A bookmark may have tags and a keyword: typing the keyword in
+//! the address bar loads it (see [`BookmarkStore::resolve_keyword`]), and
+//! the address bar suggests bookmarked pages before merely visited ones.
+//! Clients change bookmarks with `addBookmark`, `updateBookmark`,
+//! `moveBookmark` and `removeBookmark`, look them up with `searchBookmarks`
+//! and move them between browsers as Netscape bookmark files (see
+//! [`html`]).
+//!
+//! Each folder's items are numbered from 0 by `position`, without gaps, so
+//! a [`Bookmark`]'s `index` is its place in the folder.
+
+pub mod html;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serval_protocol::{Bookmark, BookmarkId};
+use thiserror::Error;
+use url::Url;
+
+use crate::omnibox::places::{fold, strip_url};
+use crate::profile;
+
+pub use html::Item;
+
+/// How many bookmarks `searchBookmarks` sends when the client does not say.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// The version of the schema below, kept in the database's `user_version`.
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS bookmarks (
+        id INTEGER PRIMARY KEY,
+        -- NULL at the top level.
+        parent INTEGER REFERENCES bookmarks (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        -- NULL for folders.
+        url TEXT,
+        keyword TEXT UNIQUE,
+        -- Milliseconds since the Unix epoch.
+        added_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS bookmarks_parent ON bookmarks (parent, position);
+    CREATE TABLE IF NOT EXISTS tags (
+        bookmark INTEGER NOT NULL REFERENCES bookmarks (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL COLLATE NOCASE,
+        PRIMARY KEY (bookmark, tag)
+    );
+";
+
+/// Why the bookmark store failed or refused a change.
+#[derive(Debug, Error)]
+pub enum BookmarkError {
+    #[error("cannot find a place for the bookmarks database: {0}")]
+    NoDataDir(#[source] io::Error),
+    #[error("cannot create {}: {source}", path.display())]
+    Dir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("bookmarks database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("the bookmarks database has schema version {0}, newer than this bridge knows")]
+    NewerSchema(i32),
+    #[error("there is no bookmark {0}")]
+    NotFound(BookmarkId),
+    #[error("bookmark {0} is not a folder")]
+    NotAFolder(BookmarkId),
+    #[error("folder {0} cannot move into itself")]
+    IntoItself(BookmarkId),
+    #[error("folders have no URL, tags or keyword")]
+    FolderDetails,
+    #[error("invalid bookmark URL `{0}`")]
+    InvalidUrl(String),
+    #[error("a keyword is a single word, not `{0}`")]
+    InvalidKeyword(String),
+    #[error("the keyword `{0}` is taken")]
+    KeywordTaken(String),
+}
+
+/// A bookmark or folder to add.
+#[derive(Debug, Clone, Default)]
+pub struct NewBookmark {
+    /// The folder to add it to, the top level when `None`.
+    pub parent_id: Option<BookmarkId>,
+    /// Its place in the folder, the end when `None`.
+    pub index: Option<usize>,
+    pub title: String,
+    /// `None` for a folder.
+    pub url: Option<String>,
+    pub tags: Vec<String>,
+    pub keyword: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub added_at: u64,
+}
+
+/// What to change of a bookmark; `None` leaves it be.
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// An empty keyword removes it.
+    pub keyword: Option<String>,
+}
+
+/// Which bookmarks a search asks for.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Words every bookmark's URL, title, tags or keyword must contain,
+    /// ignoring case.
+    pub text: String,
+    /// Only bookmarks with this tag, ignoring case.
+    pub tag: Option<String>,
+    /// [`DEFAULT_LIMIT`] when `None`.
+    pub limit: Option<usize>,
+}
+
+/// The user's bookmarks.
+pub struct BookmarkStore {
+    db: Connection,
+}
+
+impl BookmarkStore {
+    /// Opens the database at `path`, creating it and its directory when
+    /// missing.
+    pub fn open(path: &Path) -> Result<Self, BookmarkError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|source| BookmarkError::Dir {
+                path: dir.to_owned(),
+                source,
+            })?;
+        }
+        let db = Connection::open(path)?;
+        db.pragma_update(None, "journal_mode", "WAL")?;
+        db.busy_timeout(Duration::from_secs(1))?;
+        Self::with(db)
+    }
+
+    /// A store that is gone with the bridge.
+    pub fn in_memory() -> Result<Self, BookmarkError> {
+        Self::with(Connection::open_in_memory()?)
+    }
+
+    fn with(db: Connection) -> Result<Self, BookmarkError> {
+        let version: i32 = db.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(BookmarkError::NewerSchema(version));
+        }
+        db.pragma_update(None, "foreign_keys", true)?;
+        db.execute_batch(SCHEMA)?;
+        db.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { db })
+    }
+
+    /// Every bookmark and folder, each folder followed by what it holds.
+    pub fn all(&self) -> Result<Vec<Bookmark>, BookmarkError> {
+        let mut tags: HashMap<BookmarkId, Vec<String>> = HashMap::new();
+        let mut statement = self.db.prepare("SELECT bookmark, tag FROM tags")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            tags.entry(from_sql(row.get(0)?))
+                .or_default()
+                .push(row.get(1)?);
+        }
+
+        let mut statement = self.db.prepare(
+            "SELECT id, parent, position, title, url, keyword, added_at FROM bookmarks
+             ORDER BY position",
+        )?;
+        let mut folders: HashMap<Option<BookmarkId>, Vec<Bookmark>> = HashMap::new();
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let mut bookmark = bookmark(row)?;
+            bookmark.tags = sorted(tags.remove(&bookmark.id).unwrap_or_default());
+            folders
+                .entry(bookmark.parent_id)
+                .or_default()
+                .push(bookmark);
+        }
+
+        let mut all = Vec::new();
+        let mut stack: Vec<_> = folders.remove(&None).unwrap_or_default();
+        stack.reverse();
+        while let Some(bookmark) = stack.pop() {
+            if let Some(items) = folders.remove(&Some(bookmark.id)) {
+                stack.extend(items.into_iter().rev());
+            }
+            all.push(bookmark);
+        }
+        Ok(all)
+    }
+
+    pub fn get(&self, id: BookmarkId) -> Result<Bookmark, BookmarkError> {
+        get(&self.db, id)
+    }
+
+    /// Adds `new` and returns it.
+    pub fn add(&mut self, new: &NewBookmark) -> Result<Bookmark, BookmarkError> {
+        let tx = self.db.transaction()?;
+        let id = insert(&tx, new)?;
+        let bookmark = get(&tx, id)?;
+        tx.commit()?;
+        Ok(bookmark)
+    }
+
+    /// Applies `changes` to the bookmark or folder `id`.
+    pub fn update(&mut self, id: BookmarkId, changes: &Changes) -> Result<(), BookmarkError> {
+        let tx = self.db.transaction()?;
+        let current = get(&tx, id)?;
+        if current.url.is_none()
+            && (changes.url.is_some()
+                || changes.tags.as_ref().is_some_and(|tags| !tags.is_empty())
+                || changes
+                    .keyword
+                    .as_ref()
+                    .is_some_and(|word| !word.is_empty()))
+        {
+            return Err(BookmarkError::FolderDetails);
+        }
+        if let Some(title) = &changes.title {
+            tx.execute(
+                "UPDATE bookmarks SET title = ?2 WHERE id = ?1",
+                params![to_sql(id), title],
+            )?;
+        }
+        if let Some(url) = &changes.url {
+            tx.execute(
+                "UPDATE bookmarks SET url = ?2 WHERE id = ?1",
+                params![to_sql(id), normalize_url(url)?],
+            )?;
+        }
+        if let Some(tags) = &changes.tags {
+            set_tags(&tx, id, tags)?;
+        }
+        if let Some(keyword) = &changes.keyword {
+            set_keyword(&tx, id, normalize_keyword(keyword)?)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Moves the bookmark or folder `id` to `index` in the folder `parent`,
+    /// or to its end.
+    pub fn move_to(
+        &mut self,
+        id: BookmarkId,
+        parent: Option<BookmarkId>,
+        index: Option<usize>,
+    ) -> Result<(), BookmarkError> {
+        let tx = self.db.transaction()?;
+        let current = get(&tx, id)?;
+        check_folder(&tx, parent)?;
+        let mut ancestor = parent;
+        while let Some(folder) = ancestor {
+            if folder == id {
+                return Err(BookmarkError::IntoItself(id));
+            }
+            ancestor = get(&tx, folder)?.parent_id;
+        }
+        take_out(&tx, current.parent_id, current.index)?;
+        let count: i64 = tx.query_row(
+            "SELECT count(*) FROM bookmarks WHERE parent IS ?1 AND id != ?2",
+            params![parent.map(to_sql), to_sql(id)],
+            |row| row.get(0),
+        )?;
+        let position = make_room(&tx, parent, index, count)?;
+        tx.execute(
+            "UPDATE bookmarks SET parent = ?2, position = ?3 WHERE id = ?1",
+            params![to_sql(id), parent.map(to_sql), position],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Removes the bookmark `id`, or the folder `id` with everything in it.
+    pub fn remove(&mut self, id: BookmarkId) -> Result<(), BookmarkError> {
+        let tx = self.db.transaction()?;
+        let current = get(&tx, id)?;
+        tx.execute(
+            "WITH RECURSIVE doomed (id) AS (
+                 SELECT ?1 UNION ALL SELECT bookmarks.id FROM bookmarks JOIN doomed ON bookmarks.parent = doomed.id
+             )
+             DELETE FROM bookmarks WHERE id IN doomed",
+            [to_sql(id)],
+        )?;
+        take_out(&tx, current.parent_id, current.index)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The bookmarks, not folders, `query` asks for, in the order
+    /// [`all`](Self::all) lists them.
+    pub fn search(&self, query: &Query) -> Result<Vec<Bookmark>, BookmarkError> {
+        let words: Vec<_> = fold(&query.text)
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect();
+        let tag = query.tag.as_deref().map(fold);
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        let found = self
+            .all()?
+            .into_iter()
+            .filter(|bookmark| {
+                let Some(url) = &bookmark.url else {
+                    return false;
+                };
+                if let Some(tag) = &tag
+                    && !bookmark.tags.iter().any(|other| fold(other) == *tag)
+                {
+                    return false;
+                }
+                let text = format!(
+                    "{}\n{}\n{}\n{}",
+                    fold(strip_url(url)),
+                    fold(&bookmark.title),
+                    fold(&bookmark.tags.join("\n")),
+                    bookmark.keyword.as_deref().unwrap_or_default(),
+                );
+                words.iter().all(|word| text.contains(word.as_str()))
+            })
+            .take(limit)
+            .collect();
+        Ok(found)
+    }
+
+    /// What `input` typed in the address bar loads when it starts with a
+    /// bookmark's keyword. The words after the keyword replace `%s` in the
+    /// bookmark's URL; bookmarks without `%s` only load for the keyword
+    /// alone.
+    pub fn resolve_keyword(&self, input: &str) -> Result<Option<Url>, BookmarkError> {
+        let input = input.trim();
+        let (keyword, terms) = input
+            .split_once(char::is_whitespace)
+            .map_or((input, ""), |(keyword, terms)| (keyword, terms.trim()));
+        let url: Option<String> = self
+            .db
+            .query_row(
+                "SELECT url FROM bookmarks WHERE keyword = ?1",
+                [fold(keyword)],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        let Some(url) = url else {
+            return Ok(None);
+        };
+        let url = if url.contains("%s") {
+            let terms: String = url::form_urlencoded::byte_serialize(terms.as_bytes()).collect();
+            url.replace("%s", &terms)
+        } else if terms.is_empty() {
+            url
+        } else {
+            return Ok(None);
+        };
+        Ok(Url::parse(&url).ok())
+    }
+
+    /// Adds the bookmarks of a Netscape bookmark file, read with
+    /// [`html::parse`], to the end of the folder `parent`. Keywords already
+    /// taken are dropped, as are bookmarks of addresses that only mean
+    /// something to the browser that wrote the file. Returns how many
+    /// bookmarks and folders were added.
+    pub fn import(
+        &mut self,
+        items: &[Item],
+        parent: Option<BookmarkId>,
+        added_at: u64,
+    ) -> Result<usize, BookmarkError> {
+        let tx = self.db.transaction()?;
+        check_folder(&tx, parent)?;
+        let added = import(&tx, items, parent, added_at)?;
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Every bookmark as a Netscape bookmark file.
+    pub fn export(&self) -> Result<String, BookmarkError> {
+        Ok(html::write(&self.all()?))
+    }
+}
+
+/// The bookmarks database in the user's [data directory](profile::data_dir).
+pub fn default_path() -> Result<PathBuf, BookmarkError> {
+    profile::data_dir()
+        .map(|dir| dir.join("bookmarks.sqlite"))
+        .map_err(BookmarkError::NoDataDir)
+}
+
+fn get(db: &Connection, id: BookmarkId) -> Result<Bookmark, BookmarkError> {
+    let found = db
+        .query_row(
+            "SELECT id, parent, position, title, url, keyword, added_at FROM bookmarks
+             WHERE id = ?1",
+            [to_sql(id)],
+            bookmark,
+        )
+        .optional()?;
+    let mut bookmark = found.ok_or(BookmarkError::NotFound(id))?;
+    let mut statement = db.prepare("SELECT tag FROM tags WHERE bookmark = ?1")?;
+    let tags = statement.query_map([to_sql(id)], |row| row.get(0))?;
+    bookmark.tags = sorted(tags.collect::<Result<_, _>>()?);
+    Ok(bookmark)
+}
+
+/// A row of `bookmarks`, without its tags.
+fn bookmark(row: &Row) -> rusqlite::Result<Bookmark> {
+    Ok(Bookmark {
+        id: from_sql(row.get(0)?),
+        parent_id: row.get::<_, Option<i64>>(1)?.map(from_sql),
+        index: row.get(2)?,
+        title: row.get(3)?,
+        url: row.get(4)?,
+        tags: Vec::new(),
+        keyword: row.get(5)?,
+        added_at: from_sql(row.get(6)?),
+    })
+}
+
+fn insert(db: &Connection, new: &NewBookmark) -> Result<BookmarkId, BookmarkError> {
+    let url = new.url.as_deref().map(normalize_url).transpose()?;
+    let keyword = new
+        .keyword
+        .as_deref()
+        .map(normalize_keyword)
+        .transpose()?
+        .flatten();
+    if url.is_none() && (!new.tags.is_empty() || keyword.is_some()) {
+        return Err(BookmarkError::FolderDetails);
+    }
+    check_folder(db, new.parent_id)?;
+    let count: i64 = db.query_row(
+        "SELECT count(*) FROM bookmarks WHERE parent IS ?1",
+        [new.parent_id.map(to_sql)],
+        |row| row.get(0),
+    )?;
+    let position = make_room(db, new.parent_id, new.index, count)?;
+    db.execute(
+        "INSERT INTO bookmarks (parent, position, title, url, added_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            new.parent_id.map(to_sql),
+            position,
+            new.title,
+            url,
+            to_sql(new.added_at)
+        ],
+    )?;
+    let id = from_sql(db.last_insert_rowid());
+    set_tags(db, id, &new.tags)?;
+    set_keyword(db, id, keyword)?;
+    Ok(id)
+}
+
+fn import(
+    db: &Connection,
+    items: &[Item],
+    parent: Option<BookmarkId>,
+    added_at: u64,
+) -> Result<usize, BookmarkError> {
+    let mut added = 0;
+    for item in items {
+        if item.url.as_deref().is_some_and(|url| !is_importable(url)) {
+            continue;
+        }
+        let keyword = match item.keyword.as_deref().map(normalize_keyword) {
+            Some(Ok(Some(keyword))) if !keyword_taken(db, &keyword, None)? => Some(keyword),
+            _ => None,
+        };
+        let new = NewBookmark {
+            parent_id: parent,
+            index: None,
+            title: item.title.clone(),
+            url: item.url.clone(),
+            tags: item.tags.clone(),
+            keyword,
+            added_at: item.added_at.unwrap_or(added_at),
+        };
+        let id = insert(db, &new)?;
+        added += 1;
+        if item.url.is_none() {
+            added += import(db, &item.items, Some(id), added_at)?;
+        }
+    }
+    Ok(added)
+}
+
+/// Whether `url` loads anywhere but in the browser that exported it, unlike
+/// Firefox's `place:` queries.
+fn is_importable(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| !matches!(url.scheme(), "place" | "chrome"))
+}
+
+/// Fails unless `folder` is a folder, or the top level.
+fn check_folder(db: &Connection, folder: Option<BookmarkId>) -> Result<(), BookmarkError> {
+    let Some(folder) = folder else {
+        return Ok(());
+    };
+    if get(db, folder)?.url.is_some() {
+        return Err(BookmarkError::NotAFolder(folder));
+    }
+    Ok(())
+}
+
+/// Moves the items of `parent` at `index` and after one place down, to
+/// make room there, and returns the position to use. `count` is how many
+/// items `parent` holds.
+fn make_room(
+    db: &Connection,
+    parent: Option<BookmarkId>,
+    index: Option<usize>,
+    count: i64,
+) -> Result<i64, BookmarkError> {
+    let position = index.map_or(count, |index| {
+        i64::try_from(index).unwrap_or(i64::MAX).min(count)
+    });
+    db.execute(
+        "UPDATE bookmarks SET position = position + 1 WHERE parent IS ?1 AND position >= ?2",
+        params![parent.map(to_sql), position],
+    )?;
+    Ok(position)
+}
+
+/// Closes the gap an item leaves at `index` of `parent`.
+fn take_out(db: &Connection, parent: Option<BookmarkId>, index: u32) -> Result<(), BookmarkError> {
+    db.execute(
+        "UPDATE bookmarks SET position = position - 1 WHERE parent IS ?1 AND position > ?2",
+        params![parent.map(to_sql), index],
+    )?;
+    Ok(())
+}
+
+fn set_tags(db: &Connection, id: BookmarkId, tags: &[String]) -> Result<(), BookmarkError> {
+    db.execute("DELETE FROM tags WHERE bookmark = ?1", [to_sql(id)])?;
+    for tag in tags
+        .iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+    {
+        db.execute(
+            "INSERT OR IGNORE INTO tags (bookmark, tag) VALUES (?1, ?2)",
+            params![to_sql(id), tag],
+        )?;
+    }
+    Ok(())
+}
+
+fn set_keyword(
+    db: &Connection,
+    id: BookmarkId,
+    keyword: Option<String>,
+) -> Result<(), BookmarkError> {
+    if let Some(keyword) = &keyword
+        && keyword_taken(db, keyword, Some(id))?
+    {
+        return Err(BookmarkError::KeywordTaken(keyword.clone()));
+    }
+    db.execute(
+        "UPDATE bookmarks SET keyword = ?2 WHERE id = ?1",
+        params![to_sql(id), keyword],
+    )?;
+    Ok(())
+}
+
+/// Whether a bookmark other than `except` has `keyword`.
+fn keyword_taken(
+    db: &Connection,
+    keyword: &str,
+    except: Option<BookmarkId>,
+) -> Result<bool, BookmarkError> {
+    let found = db
+        .query_row(
+            "SELECT 1 FROM bookmarks WHERE keyword = ?1 AND id IS NOT ?2",
+            params![keyword, except.map(to_sql)],
+            |_| Ok(()),
+        )
+        .optional()?;
+    Ok(found.is_some())
+}
+
+fn normalize_url(url: &str) -> Result<String, BookmarkError> {
+    Url::parse(url.trim())
+        .map(String::from)
+        .map_err(|_| BookmarkError::InvalidUrl(url.to_owned()))
+}
+
+/// `keyword` folded, or `None` when it is blank.
+fn normalize_keyword(keyword: &str) -> Result<Option<String>, BookmarkError> {
+    let keyword = keyword.trim();
+    if keyword.contains(char::is_whitespace) {
+        return Err(BookmarkError::InvalidKeyword(keyword.to_owned()));
+    }
+    Ok(Some(fold(keyword)).filter(|keyword| !keyword.is_empty()))
+}
+
+fn sorted(mut tags: Vec<String>) -> Vec<String> {
+    tags.sort_by_cached_key(|tag| fold(tag));
+    tags
+}
+
+fn to_sql(value: u64) -> i64 {
+    value.try_into().unwrap_or(i64::MAX)
+}
+
+fn from_sql(value: i64) -> u64 {
+    value.try_into().unwrap_or(0)
+}
@@ -0,0 +1,1013 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, info, warn};
+use serval_protocol::{
+    Bookmark, Command, Envelope, ErrorCode, Event, Message, PROTOCOL_VERSION, Request, RequestId,
+    TabId, Transition, Visit,
+};
+use thiserror::Error;
+use url::Url;
+
+use crate::bookmarks::{self, BookmarkError, BookmarkStore, Changes, NewBookmark};
+use crate::checkpoint::{self, Checkpoint, SavedTab, SessionFile};
+use crate::engine::{self, Engine, EngineError, Navigation, Viewport};
+use crate::frames::FrameCache;
+use crate::navigation::{self, NavigationError};
+use crate::omnibox::{self, OpenTab, Places, SearchEngines, places};
+use crate::outbox::{Closed, FlowCounters, FlowLimits, Outbox};
+use crate::replay::ReplayBuffer;
+use crate::session::{NotTabOwner, SessionRegistry};
+use crate::visits::{self, Query, VisitStore, VisitStoreError};
+
+/// Identifies one connected frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientId(pub u64);
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "client#{}", self.0)
+    }
+}
+
+/// Something the bridge loop has to react to.
+pub enum Input {
+    /// A frontend connected; events for it go to `events`.
+    Connected { client: ClientId, events: Outbox },
+    /// A frontend sent a command.
+    Command { client: ClientId, request: Request },
+    /// A frontend went away.
+    Disconnected(ClientId),
+    /// The host lookup for a navigation finished.
+    Resolved {
+        client: ClientId,
+        id: Option<RequestId>,
+        tab_id: TabId,
+        /// Whether the tab was open when the lookup started.
+        opened: bool,
+        url: Url,
+        result: Result<(), NavigationError>,
+    },
+    /// The engine has work to do.
+    Wake,
+    /// Time to save the session, see [`Bridge::session_file`].
+    Checkpoint,
+    /// The process is asked to end: the bridge saves the session and exits.
+    Shutdown,
+}
+
+/// Asks the bridge loop to spin the engine. Cheap to clone and `Send`, so
+/// engines can hand it to their own threads.
+#[derive(Clone)]
+pub struct Waker(Sender<Input>);
+
+impl Waker {
+    pub fn wake(&self) {
+        // The loop only goes away when the process exits.
+        let _ = self.0.send(Input::Wake);
+    }
+}
+
+/// The bridge loop: applies client commands to the engine and broadcasts
+/// engine events to every client.
+pub struct Bridge<E> {
+    engine: E,
+    sender: Sender<Input>,
+    receiver: Receiver<Input>,
+    clients: HashMap<ClientId, Outbox>,
+    flow: FlowLimits,
+    counters: Arc<FlowCounters>,
+    frames: FrameCache,
+    session: SessionRegistry,
+    /// Tells this run of the bridge apart from earlier ones, whose sequence
+    /// numbers mean nothing here.
+    session_id: String,
+    replay: ReplayBuffer,
+    search_engines: SearchEngines,
+    /// The pages visited or bookmarked, for address bar suggestions.
+    places: Places,
+    bookmarks: BookmarkStore,
+    /// Every visit, shared with the engine's `serval://history` page.
+    visits: Arc<Mutex<VisitStore>>,
+    /// How tabs that are not following a link got to the page they load.
+    transitions: HashMap<TabId, Transition>,
+    /// The page each tab loaded last, which links it follows come from.
+    referrers: HashMap<TabId, String>,
+    /// Navigations the engine answers later, by ticket.
+    navigations: HashMap<u64, PendingNavigation>,
+    /// Where the session is saved, if anywhere.
+    session_file: Option<SessionFile>,
+    checkpoint_interval: Duration,
+    /// The session that ended in a crash, until a client restores or
+    /// discards it.
+    crashed: Option<Checkpoint>,
+    /// What was saved last, to skip checkpoints that would change nothing.
+    saved: Option<(bool, Vec<SavedTab>)>,
+}
+
+impl<E: Engine> Bridge<E> {
+    /// Creates a bridge around the engine built by `engine`, which receives
+    /// the waker it must use to schedule work.
+    pub fn new(engine: impl FnOnce(Waker) -> E) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            engine: engine(Waker(sender.clone())),
+            sender,
+            receiver,
+            clients: HashMap::new(),
+            flow: FlowLimits::default(),
+            counters: Arc::default(),
+            frames: FrameCache::new(),
+            session: SessionRegistry::new(),
+            session_id: session_id(),
+            replay: ReplayBuffer::default(),
+            search_engines: SearchEngines::default(),
+            places: Places::new(),
+            bookmarks: BookmarkStore::in_memory().expect("an in-memory database opens"),
+            visits: Arc::new(Mutex::new(
+                VisitStore::in_memory().expect("an in-memory database opens"),
+            )),
+            transitions: HashMap::new(),
+            referrers: HashMap::new(),
+            navigations: HashMap::new(),
+            session_file: None,
+            checkpoint_interval: checkpoint::DEFAULT_INTERVAL,
+            crashed: None,
+            saved: None,
+        }
+    }
+
+    /// Keeps the latest `capacity` events for clients that reconnect, instead
+    /// of [`replay::DEFAULT_CAPACITY`](crate::replay::DEFAULT_CAPACITY).
+    pub fn replay_capacity(mut self, capacity: usize) -> Self {
+        self.replay = ReplayBuffer::new(capacity);
+        self
+    }
+
+    /// Lets clients fall behind as far as `limits` instead of the defaults,
+    /// see [`outbox`](crate::outbox).
+    pub fn flow_limits(mut self, limits: FlowLimits) -> Self {
+        self.flow = limits;
+        self
+    }
+
+    /// Sends address bar searches to `engines` instead of the built-in ones.
+    pub fn search_engines(mut self, engines: SearchEngines) -> Self {
+        self.search_engines = engines;
+        self
+    }
+
+    /// Records visits in `visits` instead of a store that is gone with the
+    /// bridge, and suggests the pages it already holds.
+    pub fn visits(mut self, visits: Arc<Mutex<VisitStore>>) -> Self {
+        match lock(&visits).places() {
+            Ok(places) => self.places = places,
+            Err(error) => warn!("cannot suggest earlier visits: {error}"),
+        }
+        self.visits = visits;
+        if let Err(error) = self.bookmark_places() {
+            warn!("cannot suggest bookmarks: {error}");
+        }
+        self
+    }
+
+    /// Keeps bookmarks in `store` rather than in memory.
+    pub fn bookmarks(mut self, store: BookmarkStore) -> Self {
+        self.bookmarks = store;
+        if let Err(error) = self.bookmark_places() {
+            warn!("cannot suggest bookmarks: {error}");
+        }
+        self
+    }
+
+    /// Saves the open tabs to `file` while running and when shutting down,
+    /// and brings back those saved there before: right away when the bridge
+    /// shut down, or when a client asks for them after a crash.
+    pub fn session_file(mut self, file: SessionFile) -> Self {
+        match file.load() {
+            Ok(Some(checkpoint)) if checkpoint.clean => {
+                info!("restoring {} tabs", checkpoint.tabs.len());
+                self.restore(&checkpoint.tabs);
+                // Should a restored page crash the bridge, the next run
+                // asks before restoring it again.
+                let running = Checkpoint {
+                    clean: false,
+                    ..checkpoint
+                };
+                if let Err(error) = file.save(&running) {
+                    warn!("cannot save the session: {error}");
+                }
+            }
+            Ok(Some(checkpoint)) if !checkpoint.tabs.is_empty() => {
+                info!(
+                    "the previous session ended in a crash with {} tabs",
+                    checkpoint.tabs.len()
+                );
+                self.crashed = Some(checkpoint);
+            }
+            Ok(_) => {}
+            Err(error) => warn!("cannot restore the session: {error}"),
+        }
+        self.session_file = Some(file);
+        self
+    }
+
+    /// Saves a session that changed this often instead of every
+    /// [`checkpoint::DEFAULT_INTERVAL`].
+    pub fn checkpoint_interval(mut self, interval: Duration) -> Self {
+        self.checkpoint_interval = interval;
+        self
+    }
+
+    /// The frames dropped and events coalesced for all clients so far.
+    pub fn flow_counters(&self) -> Arc<FlowCounters> {
+        self.counters.clone()
+    }
+
+    /// Returns a handle transports use to feed the loop.
+    pub fn inputs(&self) -> Sender<Input> {
+        self.sender.clone()
+    }
+
+    /// Runs the loop on the current thread. It never returns.
+    pub fn run(mut self) -> ! {
+        if self.session_file.is_some() {
+            let inputs = self.sender.clone();
+            let interval = self.checkpoint_interval;
+            thread::spawn(move || {
+                while inputs.send(Input::Checkpoint).is_ok() {
+                    thread::sleep(interval);
+                }
+            });
+        }
+        loop {
+            let input = self
+                .receiver
+                .recv()
+                .expect("the bridge holds its own sender");
+            self.handle(input);
+            while let Ok(input) = self.receiver.try_recv() {
+                self.handle(input);
+            }
+
+            let events = self.engine.spin();
+            // Answers come before the events of the pages they started.
+            for (ticket, result) in self.engine.answers() {
+                if let Some(navigation) = self.navigations.remove(&ticket) {
+                    let PendingNavigation { client, id, tab_id } = navigation;
+                    self.navigated(client, id, &tab_id, result.map_err(Into::into));
+                }
+            }
+            for event in events {
+                self.frames.record(&event);
+                self.session.record(&event);
+                self.remember(&event);
+                self.broadcast(event);
+            }
+        }
+    }
+
+    fn handle(&mut self, input: Input) {
+        match input {
+            Input::Connected { client, events } => {
+                info!("{client} connected");
+                events.attach(self.flow, self.counters.clone());
+                // Paint the tabs the client has not seen yet.
+                for frame in self.frames.snapshot() {
+                    let _ = events.send(frame);
+                }
+                self.clients.insert(client, events);
+            }
+            Input::Command { client, request } => self.handle_request(client, request),
+            Input::Disconnected(client) => {
+                match self.clients.remove(&client) {
+                    Some(events) => info!("{client} disconnected ({})", events.counters()),
+                    None => info!("{client} disconnected"),
+                }
+                if self.session.release(client) {
+                    self.broadcast(self.session.snapshot());
+                }
+            }
+            Input::Resolved {
+                client,
+                id,
+                tab_id,
+                opened,
+                url,
+                result,
+            } => {
+                let result = self
+                    .still_controls(client, &tab_id, opened)
+                    .and_then(|()| Ok(result?));
+                match result {
+                    Ok(()) => self.start_navigation(client, id, tab_id, url),
+                    Err(error) => self.navigated(client, id, &tab_id, Err(error)),
+                }
+            }
+            Input::Wake => {}
+            Input::Checkpoint => self.checkpoint(false),
+            Input::Shutdown => {
+                self.checkpoint(true);
+                info!("shutting down");
+                std::process::exit(0);
+            }
+        }
+    }
+
+    fn handle_request(&mut self, client: ClientId, request: Request) {
+        debug!("{client}: {request:?}");
+        let Request { id, command } = request;
+        let kind = command.kind();
+        let mut tabs_changed = false;
+        let mut bookmarks_changed = false;
+        if let Some(tab_id) = command.tab_id()
+            && !matches!(
+                command,
+                Command::ClaimTab { .. } | Command::FrameShown { .. }
+            )
+        {
+            match self.session.authorize(client, tab_id) {
+                Ok(changed) => tabs_changed = changed,
+                Err(error) => {
+                    self.answer(client, id, kind, Err(error.into()));
+                    return;
+                }
+            }
+        }
+
+        let result = match command {
+            Command::Ready {
+                protocol_version,
+                resume,
+                encoding,
+                ack_frames,
+            } => {
+                if let Some(events) = self.clients.get(&client) {
+                    events.ack_frames(ack_frames.unwrap_or(false));
+                }
+                let supported = protocol_version.is_none_or(|version| version == PROTOCOL_VERSION);
+                let missed = resume
+                    .filter(|resume| supported && resume.session_id == self.session_id)
+                    .and_then(|resume| self.replay.since(resume.seq));
+                self.send(
+                    client,
+                    Event::Ready {
+                        protocol_version: PROTOCOL_VERSION,
+                        servo_version: self.engine.servo_version(),
+                        capabilities: self.engine.capabilities(),
+                        client_id: client.0,
+                        session_id: self.session_id.clone(),
+                        latest_seq: self.replay.latest(),
+                        resumed: missed.is_some(),
+                        // A refused `ready` agrees to nothing.
+                        encoding: encoding.filter(|_| supported).unwrap_or_default(),
+                    },
+                );
+                if let Some(crashed) = &self.crashed {
+                    let event = Event::CrashedSession {
+                        saved_at: crashed.saved_at,
+                        tabs: crashed.tabs.iter().map(SavedTab::snapshot).collect(),
+                    };
+                    self.send(client, event);
+                }
+                match (protocol_version, missed) {
+                    (Some(version), _) if !supported => {
+                        Err(CommandError::UnsupportedProtocol(version))
+                    }
+                    (_, Some(missed)) => {
+                        for event in missed {
+                            self.send(client, event);
+                        }
+                        Ok(())
+                    }
+                    (_, None) => {
+                        self.send(client, self.session.snapshot());
+                        match self.bookmarks.all() {
+                            Ok(bookmarks) => {
+                                self.send(client, Event::BookmarksChanged { bookmarks });
+                                Ok(())
+                            }
+                            Err(error) => Err(error.into()),
+                        }
+                    }
+                }
+            }
+            Command::Ping {} => Ok(()),
+            Command::ClaimTab { tab_id } => {
+                tabs_changed = self.session.claim(client, &tab_id);
+                Ok(())
+            }
+            Command::FrameShown { tab_id } => {
+                if let Some(events) = self.clients.get(&client) {
+                    events.frame_shown(&tab_id);
+                }
+                Ok(())
+            }
+            Command::Navigate { tab_id, url } => match navigation::parse(&url) {
+                Ok(url) => {
+                    if tabs_changed {
+                        self.broadcast(self.session.snapshot());
+                    }
+                    self.transition(&tab_id, Transition::Typed);
+                    if !self.engine.resolves_hosts() && navigation::needs_lookup(&url) {
+                        self.resolve(client, id, tab_id, url);
+                    } else {
+                        self.start_navigation(client, id, tab_id, url);
+                    }
+                    return;
+                }
+                Err(error) => Err(error.into()),
+            },
+            Command::Back { tab_id } => self
+                .engine
+                .go_back(&tab_id)
+                .map(|()| self.transition(&tab_id, Transition::BackForward))
+                .map_err(Into::into),
+            Command::Forward { tab_id } => self
+                .engine
+                .go_forward(&tab_id)
+                .map(|()| self.transition(&tab_id, Transition::BackForward))
+                .map_err(Into::into),
+            Command::Refresh { tab_id } => self
+                .engine
+                .reload(&tab_id)
+                .map(|()| self.transition(&tab_id, Transition::Reload))
+                .map_err(Into::into),
+            Command::Close { tab_id } => {
+                self.frames.remove(&tab_id);
+                for events in self.clients.values() {
+                    events.forget(&tab_id);
+                }
+                tabs_changed |= self.session.close(&tab_id);
+                self.transitions.remove(&tab_id);
+                self.referrers.remove(&tab_id);
+                self.engine.close(&tab_id).map_err(Into::into)
+            }
+            Command::Input { tab_id, event } => {
+                self.engine.input(&tab_id, event).map_err(Into::into)
+            }
+            Command::Resize {
+                tab_id,
+                width,
+                height,
+                device_pixel_ratio,
+            } => Viewport::new(width, height, device_pixel_ratio)
+                .and_then(|viewport| self.engine.resize(&tab_id, viewport))
+                .map_err(Into::into),
+            Command::Zoom { tab_id, factor } => engine::zoom_factor(factor)
+                .and_then(|factor| self.engine.set_zoom(&tab_id, factor))
+                .map_err(Into::into),
+            Command::ReloadCrashed { tab_id } => self
+                .engine
+                .reload_crashed(&tab_id)
+                .map(|()| self.transition(&tab_id, Transition::Reload))
+                .map_err(Into::into),
+            Command::ResolveInput { text } => {
+                // Keywords of bookmarks come before those of search engines.
+                let bookmarked = self
+                    .bookmarks
+                    .resolve_keyword(&text)
+                    .unwrap_or_else(|error| {
+                        warn!("cannot look up bookmark keywords: {error}");
+                        None
+                    });
+                let resolved = match bookmarked {
+                    Some(url) => Some((url, None)),
+                    None => omnibox::resolve(&text, &self.search_engines)
+                        .map(|resolved| (resolved.url, resolved.search_engine)),
+                };
+                match resolved {
+                    Some((url, search_engine)) => {
+                        self.send(
+                            client,
+                            Event::InputResolved {
+                                id,
+                                url: url.into(),
+                                search_engine,
+                            },
+                        );
+                        return;
+                    }
+                    None => Err(CommandError::BlankInput),
+                }
+            }
+            Command::Suggest { text, limit } => {
+                let tabs: Vec<_> = self
+                    .session
+                    .pages()
+                    .into_iter()
+                    .map(|(tab_id, url, title)| OpenTab { tab_id, url, title })
+                    .collect();
+                let limit = limit.map_or(omnibox::suggest::DEFAULT_LIMIT, |limit| limit as usize);
+                let suggestions =
+                    omnibox::suggest(&text, limit, &self.places, &tabs, &self.search_engines);
+                self.send(
+                    client,
+                    Event::Suggestions {
+                        id,
+                        text,
+                        suggestions,
+                    },
+                );
+                return;
+            }
+            Command::QueryHistory {
+                text,
+                from,
+                to,
+                limit,
+            } => {
+                let query = Query {
+                    text: text.unwrap_or_default(),
+                    from,
+                    to,
+                    limit: limit.map(|limit| limit as usize),
+                };
+                let found = lock(&self.visits).query(&query);
+                match found {
+                    Ok(visits) => {
+                        self.send(client, Event::HistoryVisits { id, visits });
+                        return;
+                    }
+                    Err(error) => Err(error.into()),
+                }
+            }
+            Command::RestoreSession {} | Command::DiscardSession {} => match self.crashed.take() {
+                Some(crashed) => {
+                    if matches!(command, Command::RestoreSession {}) {
+                        tabs_changed |= self.restore(&crashed.tabs);
+                    }
+                    self.broadcast(Event::CrashedSession {
+                        saved_at: crashed.saved_at,
+                        tabs: Vec::new(),
+                    });
+                    Ok(())
+                }
+                None => Err(CommandError::NoCrashedSession),
+            },
+            Command::DeleteHistory { site, from, to } => {
+                let mut store = lock(&self.visits);
+                let result = store.delete(site.as_deref(), from, to).and_then(|deleted| {
+                    info!("{client}: forgot {deleted} visits");
+                    store.places()
+                });
+                drop(store);
+                result
+                    .map(|places| self.places = places)
+                    .map_err(Into::into)
+                    .and_then(|()| self.bookmark_places().map_err(Into::into))
+                    .map(drop)
+            }
+            Command::AddBookmark {
+                parent_id,
+                index,
+                title,
+                url,
+                tags,
+                keyword,
+            } => {
+                let new = NewBookmark {
+                    parent_id,
+                    index: index.map(|index| index as usize),
+                    title,
+                    url,
+                    tags: tags.unwrap_or_default(),
+                    keyword,
+                    added_at: visits::millis(SystemTime::now()),
+                };
+                match self.bookmarks.add(&new) {
+                    Ok(bookmark) => {
+                        info!("{client}: bookmarked {}", bookmark.id);
+                        self.bookmarks_changed();
+                        self.send(client, Event::BookmarkAdded { id, bookmark });
+                        return;
+                    }
+                    Err(error) => Err(error.into()),
+                }
+            }
+            Command::UpdateBookmark {
+                bookmark_id,
+                title,
+                url,
+                tags,
+                keyword,
+            } => {
+                let changes = Changes {
+                    title,
+                    url,
+                    tags,
+                    keyword,
+                };
+                let result = self.bookmarks.update(bookmark_id, &changes);
+                bookmarks_changed = result.is_ok();
+                result.map_err(Into::into)
+            }
+            Command::MoveBookmark {
+                bookmark_id,
+                parent_id,
+                index,
+            } => {
+                let index = index.map(|index| index as usize);
+                let result = self.bookmarks.move_to(bookmark_id, parent_id, index);
+                bookmarks_changed = result.is_ok();
+                result.map_err(Into::into)
+            }
+            Command::RemoveBookmark { bookmark_id } => {
+                let result = self.bookmarks.remove(bookmark_id);
+                bookmarks_changed = result.is_ok();
+                result.map_err(Into::into)
+            }
+            Command::SearchBookmarks { text, tag, limit } => {
+                let query = bookmarks::Query {
+                    text: text.unwrap_or_default(),
+                    tag,
+                    limit: limit.map(|limit| limit as usize),
+                };
+                match self.bookmarks.search(&query) {
+                    Ok(bookmarks) => {
+                        self.send(client, Event::BookmarksFound { id, bookmarks });
+                        return;
+                    }
+                    Err(error) => Err(error.into()),
+                }
+            }
+            Command::ImportBookmarks { html, parent_id } => {
+                let items = bookmarks::html::parse(&html);
+                let now = visits::millis(SystemTime::now());
+                let result = self.bookmarks.import(&items, parent_id, now);
+                if let Ok(added) = result {
+                    info!("{client}: imported {added} bookmarks and folders");
+                }
+                bookmarks_changed = result.is_ok();
+                result.map(drop).map_err(Into::into)
+            }
+            Command::ExportBookmarks {} => match self.bookmarks.export() {
+                Ok(html) => {
+                    self.send(client, Event::BookmarksExported { id, html });
+                    return;
+                }
+                Err(error) => Err(error.into()),
+            },
+        };
+        if tabs_changed {
+            self.broadcast(self.session.snapshot());
+        }
+        if bookmarks_changed {
+            self.bookmarks_changed();
+        }
+        self.answer(client, id, kind, result);
+    }
+
+    /// Tells every client the bookmarks changed, and the address bar.
+    fn bookmarks_changed(&mut self) {
+        match self.bookmark_places() {
+            Ok(bookmarks) => self.broadcast(Event::BookmarksChanged { bookmarks }),
+            Err(error) => warn!("cannot read the bookmarks back: {error}"),
+        }
+    }
+
+    /// Marks the places bookmarked that are, and only those, and returns
+    /// every bookmark.
+    fn bookmark_places(&mut self) -> Result<Vec<Bookmark>, BookmarkError> {
+        let bookmarks = self.bookmarks.all()?;
+        let pages: HashMap<&str, &str> = bookmarks
+            .iter()
+            .filter_map(|bookmark| Some((bookmark.url.as_deref()?, bookmark.title.as_str())))
+            .filter(|(url, _)| places::is_remembered(url))
+            .collect();
+        let unbookmarked: Vec<_> = self
+            .places
+            .iter()
+            .filter(|page| page.bookmarked && !pages.contains_key(page.url.as_str()))
+            .map(|page| page.url.clone())
+            .collect();
+        for url in unbookmarked {
+            self.places.bookmark(&url, "", false);
+        }
+        for (url, title) in pages {
+            self.places.bookmark(url, title, true);
+        }
+        Ok(bookmarks)
+    }
+
+    /// Reopens the tabs of an earlier session that are not open. Returns
+    /// whether any was.
+    fn restore(&mut self, tabs: &[SavedTab]) -> bool {
+        let mut restored = false;
+        for saved in tabs {
+            if !self.session.restore(saved) {
+                continue;
+            }
+            let tab_id = &saved.tab_id;
+            match self
+                .engine
+                .restore(tab_id, &saved.entries, saved.index, saved.state.clone())
+            {
+                Ok(()) => {
+                    self.transition(tab_id, Transition::Reload);
+                    restored = true;
+                }
+                Err(error) => {
+                    warn!("cannot restore tab {tab_id}: {error}");
+                    self.session.close(tab_id);
+                }
+            }
+        }
+        restored
+    }
+
+    /// Saves the open tabs, unless that would change nothing. `clean` says
+    /// the bridge is shutting down.
+    fn checkpoint(&mut self, clean: bool) {
+        let Some(file) = &self.session_file else {
+            return;
+        };
+        let tabs: Vec<_> = self
+            .session
+            .tabs()
+            .into_iter()
+            // Tabs that never showed a page have nothing to restore.
+            .filter(|tab| !tab.entries.is_empty())
+            .map(|tab| SavedTab {
+                state: self.engine.page_state(&tab.tab_id),
+                tab_id: tab.tab_id,
+                url: tab.url,
+                title: tab.title,
+                entries: tab.entries,
+                index: tab.index,
+            })
+            .collect();
+        // A crashed session nobody answered for yet stays until there is
+        // something else to restore.
+        if tabs.is_empty() && self.crashed.is_some() {
+            return;
+        }
+        let saved = (clean, tabs);
+        if self.saved.as_ref() == Some(&saved) {
+            return;
+        }
+        let checkpoint = Checkpoint::new(visits::millis(SystemTime::now()), clean, saved.1.clone());
+        match file.save(&checkpoint) {
+            Ok(()) => {
+                debug!("saved {} tabs to {}", saved.1.len(), file.path().display());
+                self.saved = Some(saved);
+            }
+            Err(error) => warn!("cannot save the session: {error}"),
+        }
+    }
+
+    /// Notes how `tab_id` got to the page it loads next, unless it follows
+    /// a link.
+    fn transition(&mut self, tab_id: &TabId, transition: Transition) {
+        self.transitions.insert(tab_id.clone(), transition);
+    }
+
+    /// Records the pages tabs visit, for history and address bar
+    /// suggestions.
+    fn remember(&mut self, event: &Event) {
+        match event {
+            Event::LoadComplete { tab_id, url } => {
+                let transition = self.transitions.remove(tab_id).unwrap_or(Transition::Link);
+                if places::is_remembered(url) {
+                    let now = SystemTime::now();
+                    let title = self.session.page(tab_id).map_or("", |(_, title)| title);
+                    let visit = Visit {
+                        url: url.clone(),
+                        title: title.to_owned(),
+                        visited_at: visits::millis(now),
+                        transition,
+                        referrer: (transition == Transition::Link)
+                            .then(|| self.referrers.get(tab_id).cloned())
+                            .flatten(),
+                    };
+                    if let Err(error) = lock(&self.visits).record(&visit) {
+                        warn!("cannot record the visit to {url}: {error}");
+                    }
+                    let typed = transition == Transition::Typed;
+                    self.places.visit(url, title, now, typed);
+                }
+                self.referrers.insert(tab_id.clone(), url.clone());
+            }
+            Event::TitleChange { tab_id, title } => {
+                if let Some((url, _)) = self.session.page(tab_id) {
+                    if let Err(error) = lock(&self.visits).retitle(url, title) {
+                        warn!("cannot retitle {url} in history: {error}");
+                    }
+                    self.places.retitle(url, title);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Looks up the host of `url` on a helper thread, then finishes the
+    /// navigation when [`Input::Resolved`] comes back.
+    fn resolve(&self, client: ClientId, id: Option<RequestId>, tab_id: TabId, url: Url) {
+        let inputs = self.sender.clone();
+        let opened = self.session.owner(&tab_id).is_some();
+        thread::spawn(move || {
+            let result = navigation::resolve(&url);
+            let _ = inputs.send(Input::Resolved {
+                client,
+                id,
+                tab_id,
+                opened,
+                url,
+                result,
+            });
+        });
+    }
+
+    /// Hands the navigation `client` asked for to the engine, and answers it
+    /// once the engine tells how it went.
+    fn start_navigation(
+        &mut self,
+        client: ClientId,
+        id: Option<RequestId>,
+        tab_id: TabId,
+        url: Url,
+    ) {
+        match self.engine.navigate(&tab_id, url) {
+            Ok(Navigation::Started) => self.navigated(client, id, &tab_id, Ok(())),
+            Ok(Navigation::Pending(ticket)) => {
+                let navigation = PendingNavigation { client, id, tab_id };
+                self.navigations.insert(ticket, navigation);
+            }
+            Err(error) => self.navigated(client, id, &tab_id, Err(error.into())),
+        }
+    }
+
+    /// Opens the tab a navigation started in, or forgets how the tab was
+    /// getting somewhere if it did not start, and answers `client`.
+    fn navigated(
+        &mut self,
+        client: ClientId,
+        id: Option<RequestId>,
+        tab_id: &TabId,
+        result: Result<(), CommandError>,
+    ) {
+        match result {
+            Ok(()) if self.session.open(client, tab_id) => {
+                self.broadcast(self.session.snapshot());
+            }
+            Ok(()) => {}
+            Err(_) => {
+                self.transitions.remove(tab_id);
+            }
+        }
+        self.answer(client, id, "navigate", result);
+    }
+
+    /// Checks that a tab `client` navigated did not close or change hands
+    /// while its host was looked up. `opened` says whether it was open then.
+    fn still_controls(
+        &self,
+        client: ClientId,
+        tab_id: &TabId,
+        opened: bool,
+    ) -> Result<(), CommandError> {
+        match self.session.owner(tab_id) {
+            Some(owner) if owner != client => Err(NotTabOwner {
+                tab_id: tab_id.clone(),
+                owner,
+            }
+            .into()),
+            None if opened => Err(EngineError::TabNotFound(tab_id.clone()).into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tells `client` how its command went. Successful commands are only
+    /// acknowledged when they carried a request id; failures are always
+    /// reported.
+    fn answer(
+        &mut self,
+        client: ClientId,
+        id: Option<RequestId>,
+        kind: &str,
+        result: Result<(), CommandError>,
+    ) {
+        match result {
+            Ok(()) => {
+                if let Some(id) = id {
+                    self.send(client, Event::Ack { id });
+                }
+            }
+            Err(error) => {
+                warn!("{client}: `{kind}` failed: {error}");
+                self.send(
+                    client,
+                    Event::Error {
+                        id,
+                        code: error.code(),
+                        message: error.to_string(),
+                    },
+                );
+            }
+        }
+    }
+
+    fn send(&mut self, client: ClientId, event: impl Into<Envelope>) {
+        if let Some(events) = self.clients.get(&client)
+            && let Err(closed) = events.send(event)
+        {
+            report(client, closed);
+            self.clients.remove(&client);
+        }
+    }
+
+    /// Sends `event` to every client, numbered for replay.
+    fn broadcast(&mut self, event: Event) {
+        let envelope = self.replay.push(event);
+        self.clients
+            .retain(|client, events| match events.send(envelope.clone()) {
+                Ok(()) => true,
+                Err(closed) => {
+                    report(*client, closed);
+                    false
+                }
+            });
+    }
+}
+
+/// The visit store, even if a thread panicked holding it: every change to it
+/// is a transaction.
+fn lock(visits: &Mutex<VisitStore>) -> std::sync::MutexGuard<'_, VisitStore> {
+    visits.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn report(client: ClientId, closed: Closed) {
+    if let Closed::Behind(counters) = closed {
+        warn!("{client} fell too far behind and is disconnected ({counters})");
+    }
+}
+
+/// A random id for this run of the bridge.
+fn session_id() -> String {
+    let id = getrandom::u64().unwrap_or_else(|_| {
+        // Only has to differ from earlier runs.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        now.as_nanos() as u64 ^ u64::from(std::process::id())
+    });
+    format!("{id:016x}")
+}
+
+/// A navigation waiting for the engine to tell whether it started.
+struct PendingNavigation {
+    client: ClientId,
+    id: Option<RequestId>,
+    tab_id: TabId,
+}
+
+/// Why a command failed.
+#[derive(Debug, Error)]
+enum CommandError {
+    #[error("protocol version {0} is not supported, the bridge speaks version {PROTOCOL_VERSION}")]
+    UnsupportedProtocol(u32),
+    #[error("there is no text to resolve")]
+    BlankInput,
+    #[error("there is no crashed session to restore")]
+    NoCrashedSession,
+    #[error(transparent)]
+    NotTabOwner(#[from] NotTabOwner),
+    #[error(transparent)]
+    Navigation(#[from] NavigationError),
+    #[error(transparent)]
+    Engine(#[from] EngineError),
+    #[error(transparent)]
+    Visits(#[from] VisitStoreError),
+    #[error(transparent)]
+    Bookmarks(#[from] BookmarkError),
+}
+
+impl CommandError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            CommandError::UnsupportedProtocol(_) => ErrorCode::UnsupportedProtocol,
+            CommandError::BlankInput | CommandError::NoCrashedSession => ErrorCode::InvalidArgument,
+            CommandError::NotTabOwner(_) => ErrorCode::NotTabOwner,
+            CommandError::Navigation(error) => error.code(),
+            CommandError::Engine(error) => error.code(),
+            CommandError::Visits(_) => ErrorCode::Internal,
+            CommandError::Bookmarks(error) => match error {
+                BookmarkError::InvalidUrl(_) => ErrorCode::InvalidUrl,
+                BookmarkError::NotFound(_)
+                | BookmarkError::NotAFolder(_)
+                | BookmarkError::IntoItself(_)
+                | BookmarkError::FolderDetails
+                | BookmarkError::InvalidKeyword(_)
+                | BookmarkError::KeywordTaken(_) => ErrorCode::InvalidArgument,
+                BookmarkError::NoDataDir(_)
+                | BookmarkError::Dir { .. }
+                | BookmarkError::Sqlite(_)
+                | BookmarkError::NewerSchema(_) => ErrorCode::Internal,
+            },
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! The open tabs, saved so they outlive the bridge.
+//!
+//! The bridge writes a [`Checkpoint`] of its session to a [`SessionFile`]
+//! every few seconds while anything changed, and once more when it shuts
+//! down. Each tab keeps its place in the tab list, its session history and,
+//! as far as the engine can tell, the [`PageState`] of the page it shows.
+//!
+//! The last checkpoint says whether the bridge shut down or crashed. A
+//! session that was shut down comes back when the bridge starts again; one
+//! that ended in a crash is offered to clients with `crashedSession`, since
+//! one of its pages may be what crashed it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serval_protocol::{HistoryEntry, TabId, TabSnapshot};
+use thiserror::Error;
+
+use crate::profile;
+
+/// How often the bridge saves a session that changed.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The version of the format below.
+const VERSION: u32 = 1;
+
+/// Reads what the user did to a page: its scroll position and the form
+/// fields they changed, as the JSON of a [`PageState`]. Passwords, hidden
+/// and file fields are left out.
+pub const CAPTURE_SCRIPT: &str = "JSON.stringify({
+  scrollX: window.scrollX,
+  scrollY: window.scrollY,
+  fields: Array.from(document.querySelectorAll('input, textarea, select')).flatMap((field, index) => {
+    const name = field.name || undefined;
+    if (['password', 'hidden', 'file'].includes(field.type)) {
+      return [];
+    }
+    if (field.type === 'checkbox' || field.type === 'radio') {
+      return field.checked === field.defaultChecked ? [] : [{ index, name, checked: field.checked }];
+    }
+    if (field.tagName === 'SELECT') {
+      const changed = Array.from(field.options).some((option) => option.selected !== option.defaultSelected);
+      return changed ? [{ index, name, value: field.value }] : [];
+    }
+    return field.value === field.defaultValue ? [] : [{ index, name, value: field.value }];
+  }),
+})";
+
+/// Why a session could not be read or saved.
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("cannot find a place for the session: {0}")]
+    NoDataDir(#[source] io::Error),
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{}: {source}", path.display())]
+    Json {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("{}: version {version} is newer than this bridge knows", path.display())]
+    NewerVersion { path: PathBuf, version: u32 },
+}
+
+/// The session at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub version: u32,
+    /// Milliseconds since the Unix epoch.
+    pub saved_at: u64,
+    /// Whether the bridge shut down after saving it, rather than crashing
+    /// or being killed.
+    pub clean: bool,
+    /// The open tabs, in the order they were opened.
+    pub tabs: Vec<SavedTab>,
+}
+
+impl Checkpoint {
+    pub fn new(saved_at: u64, clean: bool, tabs: Vec<SavedTab>) -> Self {
+        Self {
+            version: VERSION,
+            saved_at,
+            clean,
+            tabs,
+        }
+    }
+}
+
+/// A tab as it was saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedTab {
+    pub tab_id: TabId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub entries: Vec<HistoryEntry>,
+    pub index: usize,
+    /// What the user did to the page of the current entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PageState>,
+}
+
+impl SavedTab {
+    /// The tab as `tabsSnapshot` would list it, without an owner.
+    pub fn snapshot(&self) -> TabSnapshot {
+        TabSnapshot {
+            tab_id: self.tab_id.clone(),
+            owner: None,
+            url: self.url.clone(),
+            title: self.title.clone(),
+            entries: self.entries.clone(),
+            index: self.index,
+            loading: false,
+            crashed: false,
+        }
+    }
+}
+
+/// What the user did to a page, to do it again when the page is restored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageState {
+    /// The scroll position, in CSS pixels.
+    pub scroll_x: f64,
+    pub scroll_y: f64,
+    /// The form fields that no longer hold their initial value.
+    #[serde(default)]
+    pub fields: Vec<FormField>,
+}
+
+impl PageState {
+    /// A script that scrolls the page back and fills the fields in again.
+    /// Fields whose name changed since are left alone.
+    pub fn restore_script(&self) -> String {
+        let state = serde_json::to_string(self).expect("page states serialize");
+        format!(
+            "(state => {{
+  const fields = document.querySelectorAll('input, textarea, select');
+  for (const saved of state.fields) {{
+    const field = fields[saved.index];
+    if (!field || (field.name || undefined) !== saved.name) {{
+      continue;
+    }}
+    if (saved.checked !== undefined) {{
+      field.checked = saved.checked;
+    }}
+    if (saved.value !== undefined) {{
+      field.value = saved.value;
+    }}
+  }}
+  window.scrollTo(state.scrollX, state.scrollY);
+}})({state})"
+        )
+    }
+}
+
+/// A form field the user changed, found again by its position among the
+/// page's `input`, `textarea` and `select` elements.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormField {
+    pub index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+}
+
+/// Where the session is saved.
+#[derive(Debug, Clone)]
+pub struct SessionFile {
+    path: PathBuf,
+}
+
+impl SessionFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// `session.json` in the user's [data directory](profile::data_dir).
+    pub fn default_path() -> Result<PathBuf, CheckpointError> {
+        profile::data_dir()
+            .map(|dir| dir.join("session.json"))
+            .map_err(CheckpointError::NoDataDir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The last checkpoint saved, if any.
+    pub fn load(&self) -> Result<Option<Checkpoint>, CheckpointError> {
+        let json = match fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(self.io_error(source)),
+        };
+        // Read the version first, so newer files are not mistaken for broken
+        // ones.
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let json_error = |source| CheckpointError::Json {
+            path: self.path.clone(),
+            source,
+        };
+        let Versioned { version } = serde_json::from_str(&json).map_err(json_error)?;
+        if version > VERSION {
+            return Err(CheckpointError::NewerVersion {
+                path: self.path.clone(),
+                version,
+            });
+        }
+        serde_json::from_str(&json).map(Some).map_err(json_error)
+    }
+
+    /// Replaces the saved checkpoint with `checkpoint`. A crash while saving
+    /// leaves the previous one.
+    pub fn save(&self, checkpoint: &Checkpoint) -> Result<(), CheckpointError> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|source| self.io_error(source))?;
+        }
+        let json = serde_json::to_vec_pretty(checkpoint).expect("checkpoints serialize");
+        let partial = self.path.with_extension("json.partial");
+        let written = fs::write(&partial, json).and_then(|()| fs::rename(&partial, &self.path));
+        if let Err(source) = written {
+            let _ = fs::remove_file(&partial);
+            return Err(self.io_error(source));
+        }
+        Ok(())
+    }
+
+    fn io_error(&self, source: io::Error) -> CheckpointError {
+        CheckpointError::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! How launchers find a running bridge.
+//!
+//! A bridge listening on WebSocket or a Unix socket publishes a discovery
+//! file, `bridge.json` in the user's [`runtime_dir`]: how to reach it, the
+//! token it requires and the protocol version it speaks. The file is private
+//! to the user, like the token in it. A bridge that was killed leaves its
+//! file behind, so [`find`] only returns bridges whose process still runs.
+//!
+//! `serval-bridge --discover` prints the file of the running bridge, and
+//! with `--launch` starts one first when none runs.
+
+use std::fs;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serval_protocol::PROTOCOL_VERSION;
+use thiserror::Error;
+
+/// What a bridge publishes about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Discovery {
+    /// The bridge's process id.
+    pub pid: u32,
+    /// The protocol version the bridge speaks.
+    pub protocol_version: u32,
+    /// The WebSocket URL to connect to, when the bridge listens on TCP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The TCP port of [`Discovery::url`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// The Unix socket, when the bridge listens on one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<PathBuf>,
+    /// The token WebSocket clients must present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Discovery {
+    /// Describes this process, listening nowhere yet.
+    pub fn new() -> Self {
+        Self {
+            pid: std::process::id(),
+            protocol_version: PROTOCOL_VERSION,
+            url: None,
+            port: None,
+            socket: None,
+            token: None,
+        }
+    }
+
+    /// Adds the WebSocket endpoint at `addr`. Clients on this machine reach
+    /// an unspecified address through loopback.
+    pub fn websocket(mut self, addr: SocketAddr, token: impl Into<String>) -> Self {
+        let host = if addr.ip().is_unspecified() {
+            SocketAddr::new([127, 0, 0, 1].into(), addr.port())
+        } else {
+            addr
+        };
+        self.url = Some(format!("ws://{host}"));
+        self.port = Some(addr.port());
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Adds the Unix socket at `path`.
+    pub fn unix(mut self, path: impl Into<PathBuf>) -> Self {
+        self.socket = Some(path.into());
+        self
+    }
+
+    /// Writes the file at `path`, readable only by the current user. Readers
+    /// never see half a file: it is written aside and renamed into place.
+    pub fn publish(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        let partial = path.with_extension(format!("json.{}", self.pid));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let written = options
+            .open(&partial)
+            .and_then(|mut file| file.write_all(&json))
+            .and_then(|()| fs::rename(&partial, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        written
+    }
+
+    /// Whether the process that published this still runs.
+    pub fn is_running(&self) -> bool {
+        is_running(self.pid)
+    }
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why no bridge was found.
+#[derive(Debug, Error)]
+pub enum NotFound {
+    #[error("no bridge runs: {} does not exist", .0.display())]
+    Missing(PathBuf),
+    #[error("cannot read {}: {source}", path.display())]
+    Unreadable { path: PathBuf, source: io::Error },
+    #[error("{} is not a discovery file: {source}", path.display())]
+    Malformed {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("no bridge runs: process {pid}, which published {}, is gone", path.display())]
+    Gone { path: PathBuf, pid: u32 },
+    #[error(
+        "the bridge in {} speaks protocol version {found}, not {}",
+        path.display(),
+        PROTOCOL_VERSION
+    )]
+    Incompatible { path: PathBuf, found: u32 },
+}
+
+/// Reads the discovery file at `path` and checks that its bridge still runs
+/// and speaks this protocol version.
+pub fn find(path: &Path) -> Result<Discovery, NotFound> {
+    let bytes = fs::read(path).map_err(|source| match source.kind() {
+        io::ErrorKind::NotFound => NotFound::Missing(path.to_owned()),
+        _ => NotFound::Unreadable {
+            path: path.to_owned(),
+            source,
+        },
+    })?;
+    let discovery: Discovery =
+        serde_json::from_slice(&bytes).map_err(|source| NotFound::Malformed {
+            path: path.to_owned(),
+            source,
+        })?;
+    if !discovery.is_running() {
+        return Err(NotFound::Gone {
+            path: path.to_owned(),
+            pid: discovery.pid,
+        });
+    }
+    if discovery.protocol_version != PROTOCOL_VERSION {
+        return Err(NotFound::Incompatible {
+            path: path.to_owned(),
+            found: discovery.protocol_version,
+        });
+    }
+    Ok(discovery)
+}
+
+/// The discovery file in the user's runtime directory.
+pub fn default_path() -> io::Result<PathBuf> {
+    Ok(runtime_dir()?.join("bridge.json"))
+}
+
+/// The directory for the bridge's sockets and discovery file:
+/// `$XDG_RUNTIME_DIR/serval`, or `serval-<uid>` in the temporary directory
+/// without one. The directory is created private to the user, and refused
+/// when it exists but is not.
+#[cfg(unix)]
+pub fn runtime_dir() -> io::Result<PathBuf> {
+    use std::fs::DirBuilder;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    use crate::unix::current_uid;
+
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime) if !runtime.is_empty() => PathBuf::from(runtime).join("serval"),
+        _ => std::env::temp_dir().join(format!("serval-{}", current_uid())),
+    };
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    let metadata = fs::metadata(&dir)?;
+    if metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not private to the current user", dir.display()),
+        ));
+    }
+    Ok(dir)
+}
+
+/// The directory for the bridge's discovery file: `serval` in the user's
+/// local application data.
+#[cfg(not(unix))]
+pub fn runtime_dir() -> io::Result<PathBuf> {
+    let base = std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let dir = base.join("serval");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process exists.
+    let result = unsafe { libc::kill(pid, 0) };
+    // A process of another user answers with `EPERM`, and still runs.
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a cheap way to ask, a published bridge is assumed to run.
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
@@ -0,0 +1,674 @@
+//! A scripted engine for tests and frontend development.
+//!
+//! [`MockEngine`] loads no pages. It plays a [`Scenario`] instead: a list of
+//! pages, each with a title, a colour to paint, how long it takes to load and
+//! a script of what happens while it does. Pages can redirect, fail their
+//! host lookup, change their title mid-load or crash, so the frontend and the
+//! bridge tests can exercise those paths without Servo or a network.
+//!
+//! A scenario is a JSON file:
+//!
+//! ```json
+//! {
+//!   "pages": [
+//!     { "url": "https://example.com/", "title": "Example Domain", "loadMs": 200 },
+//!     { "url": "http://example.com/", "redirect": "https://example.com/" },
+//!     { "url": "https://nowhere.test/", "dnsFailure": true },
+//!     {
+//!       "url": "https://crash.test/",
+//!       "title": "Crashes",
+//!       "loadMs": 500,
+//!       "script": [
+//!         { "at": 100, "title": "Still loading" },
+//!         { "at": 300, "crash": { "signal": 11 } }
+//!       ]
+//!     },
+//!     {
+//!       "url": "https://links.test/",
+//!       "script": [{ "at": 400, "follow": "https://example.com/" }]
+//!     }
+//!   ],
+//!   "default": { "title": "Mock page" }
+//! }
+//! ```
+//!
+//! A navigation starts with `loadStart`. A redirecting page changes the URL
+//! after its `loadMs` and goes on with the page it redirects to; any other
+//! page runs its script, with `at` counted from the start of the load, and
+//! reports its title and `loadComplete` after `loadMs`. URLs that are not
+//! listed load the `default` page or, without one, fail their host lookup;
+//! `about:blank` always loads a blank page and `serval:` URLs load the
+//! bridge's [`Pages`], titled as they are. A page that `follow`s a link
+//! navigates its tab, as if the link was clicked. Actions that are due at
+//! the same time happen in the order they were scheduled, so every run of a
+//! scenario produces the same events.
+//!
+//! Restored tabs get their whole session history back. The page state they
+//! were restored with is what they report until they load another page.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serval_protocol::{Capability, Event, HistoryEntry, InputEvent, TabId};
+use thiserror::Error;
+use url::Url;
+
+use super::{Engine, EngineError, Navigation, Viewport};
+use crate::Waker;
+use crate::checkpoint::PageState;
+use crate::frames::FrameEncoder;
+use crate::history::SessionHistory;
+use crate::navigation::NavigationError;
+use crate::pages::{self, Pages};
+
+/// Redirects followed before a load stops on the page it reached.
+const MAX_REDIRECTS: u8 = 20;
+
+/// The pages a [`MockEngine`] knows.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub pages: Vec<Page>,
+    /// Loaded for URLs that are not listed.
+    #[serde(default)]
+    pub default: Option<Page>,
+}
+
+/// How a page loads.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct Page {
+    /// Ignored for the default page.
+    pub url: String,
+    pub title: Option<String>,
+    /// Painted over the whole viewport once loaded, as `#rrggbb`. White when
+    /// missing.
+    pub color: Option<String>,
+    pub load_ms: u64,
+    /// Where the page redirects to after `load_ms`.
+    pub redirect: Option<String>,
+    /// Navigations to the page fail their host lookup.
+    pub dns_failure: bool,
+    pub script: Vec<Step>,
+}
+
+/// Something that happens `at` milliseconds into a page load.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub at: u64,
+    #[serde(flatten)]
+    pub action: StepAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StepAction {
+    /// The document title changes.
+    Title(String),
+    /// The page's process dies.
+    Crash(Crash),
+    /// The page navigates to this URL.
+    Follow(String),
+}
+
+/// How a scripted crash is reported.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct Crash {
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub hung: bool,
+}
+
+/// Why a scenario could not be loaded.
+#[derive(Debug, Error)]
+pub enum ScenarioError {
+    #[error("cannot read scenario: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid scenario: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid scenario: page `{url}` has an invalid colour `{color}`")]
+    Color { url: String, color: String },
+}
+
+impl Scenario {
+    /// Reads a scenario from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ScenarioError> {
+        let scenario: Scenario = serde_json::from_str(json)?;
+        for page in scenario.pages.iter().chain(&scenario.default) {
+            if let Some(color) = &page.color
+                && parse_color(color).is_none()
+            {
+                return Err(ScenarioError::Color {
+                    url: page.url.clone(),
+                    color: color.clone(),
+                });
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// The page loaded for `url`, if any.
+    fn page(&self, url: &str) -> Option<&Page> {
+        self.pages
+            .iter()
+            .find(|page| page.url == url)
+            .or(self.default.as_ref())
+    }
+}
+
+/// Plays a [`Scenario`], with one simulated webview per tab.
+pub struct MockEngine {
+    scenario: Scenario,
+    default_viewport: Viewport,
+    tabs: HashMap<TabId, Tab>,
+    /// Viewport and zoom of tabs that were sent any before they opened.
+    views: HashMap<TabId, (Viewport, f32)>,
+    queue: BinaryHeap<Reverse<Scheduled>>,
+    next_seq: u64,
+    /// Events produced outside of [`Engine::spin`].
+    events: Vec<Event>,
+    timer: Sender<Instant>,
+    pages: Option<Pages>,
+}
+
+struct Tab {
+    history: SessionHistory,
+    /// Tells the actions of the current load apart from those of loads it
+    /// replaced.
+    generation: u64,
+    crashed: bool,
+    viewport: Viewport,
+    zoom: f32,
+    color: [u8; 3],
+    frames: FrameEncoder,
+    /// The state the page was restored with.
+    state: Option<PageState>,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Scheduled {
+    due: Instant,
+    seq: u64,
+    tab_id: TabId,
+    generation: u64,
+    action: Action,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Action {
+    /// The page redirects to `url`, the `hops`th redirect of this load.
+    Redirect {
+        url: String,
+        hops: u8,
+    },
+    Title(String),
+    Follow(String),
+    Crash {
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        hung: bool,
+    },
+    /// The page finished loading.
+    Complete {
+        title: Option<String>,
+        color: [u8; 3],
+    },
+}
+
+impl MockEngine {
+    /// What a scripted tab supports. Scenarios do not react to input.
+    pub const CAPABILITIES: &[Capability] = &[
+        Capability::Frames,
+        Capability::Viewport,
+        Capability::History,
+        Capability::CrashRecovery,
+    ];
+
+    /// Creates an engine playing `scenario`. Tabs are `width` × `height`
+    /// device pixels until they are resized.
+    pub fn new(waker: Waker, scenario: Scenario, width: u32, height: u32) -> Self {
+        Self {
+            scenario,
+            default_viewport: Viewport::from_device_size(width, height),
+            tabs: HashMap::new(),
+            views: HashMap::new(),
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+            events: Vec::new(),
+            timer: spawn_timer(waker),
+            pages: None,
+        }
+    }
+
+    /// Serves `serval:` URLs from `pages`. Without, they load untitled
+    /// pages.
+    pub fn pages(mut self, pages: Pages) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    fn tab(&mut self, tab_id: &TabId) -> Result<&mut Tab, EngineError> {
+        self.tabs
+            .get_mut(tab_id)
+            .ok_or_else(|| EngineError::TabNotFound(tab_id.clone()))
+    }
+
+    /// A tab that did not crash.
+    fn live_tab(&mut self, tab_id: &TabId) -> Result<&mut Tab, EngineError> {
+        let tab = self.tab(tab_id)?;
+        if tab.crashed {
+            return Err(EngineError::TabCrashed(tab_id.clone()));
+        }
+        Ok(tab)
+    }
+
+    fn schedule(&mut self, tab_id: &TabId, due: Instant, action: Action) {
+        let Some(tab) = self.tabs.get(tab_id) else {
+            return;
+        };
+        let generation = tab.generation;
+        self.queue.push(Reverse(Scheduled {
+            due,
+            seq: self.next_seq,
+            tab_id: tab_id.clone(),
+            generation,
+            action,
+        }));
+        self.next_seq += 1;
+        let _ = self.timer.send(due);
+    }
+
+    /// Starts loading `url`, which the tab's history already points at.
+    fn load(&mut self, tab_id: &TabId, url: String) {
+        let Some(tab) = self.tabs.get_mut(tab_id) else {
+            return;
+        };
+        tab.generation += 1;
+        tab.state = None;
+        self.events.push(Event::LoadStart {
+            tab_id: tab_id.clone(),
+            url: url.clone(),
+        });
+        self.events.push(tab.history.to_event(tab_id.clone()));
+        self.run_page(tab_id, &url, Instant::now(), 0);
+    }
+
+    /// Schedules what the page at `url` does when its load starts at `start`.
+    fn run_page(&mut self, tab_id: &TabId, url: &str, start: Instant, hops: u8) {
+        let page = match url {
+            "about:blank" => None,
+            url if is_internal(url) => Some(self.internal_page(url)),
+            url => self.scenario.page(url).cloned(),
+        }
+        .unwrap_or_default();
+        let done = start + Duration::from_millis(page.load_ms);
+
+        if let Some(target) = page.redirect.filter(|_| hops < MAX_REDIRECTS) {
+            let action = Action::Redirect {
+                url: target,
+                hops: hops + 1,
+            };
+            self.schedule(tab_id, done, action);
+            return;
+        }
+        for step in page.script {
+            let action = match step.action {
+                StepAction::Title(title) => Action::Title(title),
+                StepAction::Follow(url) => Action::Follow(url),
+                StepAction::Crash(crash) => Action::Crash {
+                    exit_code: crash.exit_code,
+                    signal: crash.signal,
+                    hung: crash.hung,
+                },
+            };
+            self.schedule(tab_id, start + Duration::from_millis(step.at), action);
+        }
+        let color = page
+            .color
+            .as_deref()
+            .and_then(parse_color)
+            .unwrap_or([0xff; 3]);
+        let title = page.title;
+        self.schedule(tab_id, done, Action::Complete { title, color });
+    }
+
+    /// A `serval:` page, titled as the bridge renders it.
+    fn internal_page(&self, url: &str) -> Page {
+        let title = self
+            .pages
+            .as_ref()
+            .zip(Url::parse(url).ok())
+            .map(|(pages, url)| pages.render(&url).title);
+        Page {
+            url: url.to_owned(),
+            title,
+            ..Page::default()
+        }
+    }
+
+    fn run(&mut self, scheduled: Scheduled, events: &mut Vec<Event>) {
+        let Scheduled {
+            due,
+            tab_id,
+            generation,
+            action,
+            ..
+        } = scheduled;
+        let Some(tab) = self
+            .tabs
+            .get_mut(&tab_id)
+            .filter(|tab| tab.generation == generation && !tab.crashed)
+        else {
+            return;
+        };
+        match action {
+            Action::Redirect { url, hops } => {
+                tab.history.replace(url.clone());
+                events.push(Event::UrlChange {
+                    tab_id: tab_id.clone(),
+                    url: url.clone(),
+                });
+                events.push(tab.history.to_event(tab_id.clone()));
+                self.run_page(&tab_id, &url, due, hops);
+            }
+            Action::Title(title) => set_title(&tab_id, tab, title, events),
+            Action::Follow(url) => {
+                tab.history.push(url.clone());
+                tab.generation += 1;
+                events.push(Event::LoadStart {
+                    tab_id: tab_id.clone(),
+                    url: url.clone(),
+                });
+                events.push(tab.history.to_event(tab_id.clone()));
+                self.run_page(&tab_id, &url, due, 0);
+            }
+            Action::Crash {
+                exit_code,
+                signal,
+                hung,
+            } => {
+                tab.crashed = true;
+                events.push(Event::TabCrashed {
+                    tab_id: tab_id.clone(),
+                    url: tab.history.current().map(|entry| entry.url.clone()),
+                    exit_code,
+                    signal,
+                    hung,
+                });
+            }
+            Action::Complete { title, color } => {
+                if let Some(title) = title {
+                    set_title(&tab_id, tab, title, events);
+                }
+                tab.color = color;
+                events.extend(tab.paint(&tab_id));
+                let url = tab
+                    .history
+                    .current()
+                    .map(|entry| entry.url.clone())
+                    .unwrap_or_default();
+                events.push(Event::LoadComplete { tab_id, url });
+            }
+        }
+    }
+
+    /// Moves through the tab's history and loads the entry it lands on.
+    fn open(&mut self, tab_id: &TabId, history: SessionHistory) {
+        let (viewport, zoom) = self
+            .views
+            .remove(tab_id)
+            .unwrap_or((self.default_viewport, 1.0));
+        let tab = Tab {
+            history,
+            generation: 0,
+            crashed: false,
+            viewport,
+            zoom,
+            color: [0xff; 3],
+            frames: FrameEncoder::new(),
+            state: None,
+        };
+        self.events.push(tab.viewport_changed(tab_id));
+        self.tabs.insert(tab_id.clone(), tab);
+    }
+
+    fn go(&mut self, tab_id: &TabId, delta: isize) -> Result<(), EngineError> {
+        let tab = self.live_tab(tab_id)?;
+        let Some(entry) = tab.history.go(delta) else {
+            // Like Servo, ignore steps past either end.
+            return Ok(());
+        };
+        let url = entry.url.clone();
+        self.load(tab_id, url);
+        self.wake();
+        Ok(())
+    }
+
+    /// Makes sure events produced outside of [`Engine::spin`] go out.
+    fn wake(&self) {
+        let _ = self.timer.send(Instant::now());
+    }
+}
+
+impl Tab {
+    fn viewport_changed(&self, tab_id: &TabId) -> Event {
+        let (width, height) = self.viewport.device_size();
+        Event::ViewportChanged {
+            tab_id: tab_id.clone(),
+            width,
+            height,
+            device_pixel_ratio: self.viewport.device_pixel_ratio,
+            zoom: self.zoom,
+        }
+    }
+
+    /// Paints the whole viewport in the page's colour.
+    fn paint(&mut self, tab_id: &TabId) -> Option<Event> {
+        let (width, height) = self.viewport.device_size();
+        let [r, g, b] = self.color;
+        let pixels = [r, g, b, 0xff].repeat(width as usize * height as usize);
+        self.frames.encode(tab_id.clone(), width, height, &pixels)
+    }
+}
+
+/// Whether `url` is one of the bridge's own pages.
+fn is_internal(url: &str) -> bool {
+    url.strip_prefix(pages::SCHEME)
+        .is_some_and(|rest| rest.starts_with(':'))
+}
+
+fn set_title(tab_id: &TabId, tab: &mut Tab, title: String, events: &mut Vec<Event>) {
+    tab.history.set_title(title.clone());
+    events.push(Event::TitleChange {
+        tab_id: tab_id.clone(),
+        title,
+    });
+    events.push(tab.history.to_event(tab_id.clone()));
+}
+
+impl Engine for MockEngine {
+    fn capabilities(&self) -> Vec<Capability> {
+        Self::CAPABILITIES.to_vec()
+    }
+
+    fn resolves_hosts(&self) -> bool {
+        true
+    }
+
+    fn navigate(&mut self, tab_id: &TabId, url: Url) -> Result<Navigation, EngineError> {
+        let dns_failure = match url.as_str() {
+            url if is_internal(url) => false,
+            "about:blank" => false,
+            url => self.scenario.page(url).is_none_or(|page| page.dns_failure),
+        };
+        if dns_failure && let Some(host) = url.host_str() {
+            return Err(NavigationError::DnsFailure {
+                host: host.to_owned(),
+                source: io::Error::new(io::ErrorKind::NotFound, "not in the scenario"),
+            }
+            .into());
+        }
+
+        if !self.tabs.contains_key(tab_id) {
+            self.open(tab_id, SessionHistory::new());
+        }
+        let tab = self.tab(tab_id)?;
+        tab.crashed = false;
+        tab.history.push(url.to_string());
+        self.load(tab_id, url.into());
+        self.wake();
+        Ok(Navigation::Started)
+    }
+
+    fn go_back(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.go(tab_id, -1)
+    }
+
+    fn go_forward(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.go(tab_id, 1)
+    }
+
+    fn reload(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.go(tab_id, 0)
+    }
+
+    fn close(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.views.remove(tab_id);
+        self.tabs
+            .remove(tab_id)
+            .map(drop)
+            .ok_or_else(|| EngineError::TabNotFound(tab_id.clone()))
+    }
+
+    fn input(&mut self, tab_id: &TabId, _event: InputEvent) -> Result<(), EngineError> {
+        // Scenarios do not react to input.
+        self.live_tab(tab_id).map(drop)
+    }
+
+    fn resize(&mut self, tab_id: &TabId, viewport: Viewport) -> Result<(), EngineError> {
+        match self.tabs.get_mut(tab_id) {
+            Some(tab) => {
+                tab.viewport = viewport;
+                self.events.push(tab.viewport_changed(tab_id));
+                self.events.extend(tab.paint(tab_id));
+                self.wake();
+            }
+            None => {
+                self.views
+                    .entry(tab_id.clone())
+                    .or_insert((self.default_viewport, 1.0))
+                    .0 = viewport;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_zoom(&mut self, tab_id: &TabId, factor: f32) -> Result<(), EngineError> {
+        match self.tabs.get_mut(tab_id) {
+            Some(tab) => {
+                tab.zoom = factor;
+                self.events.push(tab.viewport_changed(tab_id));
+                self.wake();
+            }
+            None => {
+                self.views
+                    .entry(tab_id.clone())
+                    .or_insert((self.default_viewport, 1.0))
+                    .1 = factor;
+            }
+        }
+        Ok(())
+    }
+
+    fn reload_crashed(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        let tab = self.tab(tab_id)?;
+        if !tab.crashed {
+            return Err(EngineError::TabNotCrashed(tab_id.clone()));
+        }
+        tab.crashed = false;
+        self.go(tab_id, 0)
+    }
+
+    fn restore(
+        &mut self,
+        tab_id: &TabId,
+        entries: &[HistoryEntry],
+        index: usize,
+        state: Option<PageState>,
+    ) -> Result<(), EngineError> {
+        let url = entries
+            .get(index)
+            .ok_or_else(|| EngineError::InvalidArgument(format!("no history entry {index}")))?
+            .url
+            .clone();
+        self.open(tab_id, SessionHistory::restore(entries.to_vec(), index));
+        self.load(tab_id, url);
+        self.tab(tab_id)?.state = state;
+        self.wake();
+        Ok(())
+    }
+
+    fn page_state(&mut self, tab_id: &TabId) -> Option<PageState> {
+        self.tabs.get(tab_id)?.state.clone()
+    }
+
+    fn spin(&mut self) -> Vec<Event> {
+        let mut events = std::mem::take(&mut self.events);
+        let now = Instant::now();
+        while let Some(Reverse(scheduled)) = self.queue.peek()
+            && scheduled.due <= now
+        {
+            let Some(Reverse(scheduled)) = self.queue.pop() else {
+                break;
+            };
+            self.run(scheduled, &mut events);
+        }
+        events
+    }
+}
+
+/// Parses a `#rrggbb` colour.
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let [_, r, g, b] = u32::from_str_radix(hex, 16).ok()?.to_be_bytes();
+    Some([r, g, b])
+}
+
+/// Wakes the bridge loop at every instant sent to the returned channel, until
+/// the engine drops it.
+fn spawn_timer(waker: Waker) -> Sender<Instant> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut deadlines = BTreeSet::<Instant>::new();
+        loop {
+            let received = match deadlines.first() {
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                Some(due) => receiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+            };
+            match received {
+                Ok(due) => {
+                    deadlines.insert(due);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    deadlines.pop_first();
+                    waker.wake();
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    });
+    sender
+}
@@ -0,0 +1,212 @@
+//! Browser engines the bridge can drive.
+
+mod mock;
+mod process;
+#[cfg(feature = "servo")]
+mod servo;
+
+use serval_protocol::{Capability, ErrorCode, Event, HistoryEntry, InputEvent, TabId};
+use thiserror::Error;
+use url::Url;
+
+use crate::checkpoint::PageState;
+use crate::navigation::NavigationError;
+
+pub use self::mock::{Crash, MockEngine, Page, Scenario, ScenarioError, Step, StepAction};
+pub use self::process::ProcessEngine;
+#[cfg(feature = "servo")]
+pub use self::servo::{SERVO_VERSION, ServoEngine};
+
+/// Why an engine refused a command.
+#[derive(Debug, Error)]
+pub enum EngineError {
+    #[error("tab `{0}` does not exist")]
+    TabNotFound(TabId),
+    #[error("tab `{0}` crashed")]
+    TabCrashed(TabId),
+    #[error("tab `{0}` did not crash")]
+    TabNotCrashed(TabId),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error(transparent)]
+    Navigation(#[from] NavigationError),
+    #[error("engine failure: {0}")]
+    Internal(String),
+    /// A failure another bridge reported, like a content process.
+    #[error("{message}")]
+    Reported { code: ErrorCode, message: String },
+}
+
+impl EngineError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            EngineError::TabNotFound(_) => ErrorCode::TabNotFound,
+            EngineError::TabCrashed(_) => ErrorCode::TabCrashed,
+            EngineError::TabNotCrashed(_) => ErrorCode::TabNotCrashed,
+            EngineError::InvalidArgument(_) => ErrorCode::InvalidArgument,
+            EngineError::Navigation(error) => error.code(),
+            EngineError::Internal(_) => ErrorCode::Internal,
+            EngineError::Reported { code, .. } => *code,
+        }
+    }
+}
+
+/// How an engine took a navigation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Navigation {
+    /// The tab started loading the page.
+    Started,
+    /// The engine passed the navigation on and tells whether it started
+    /// later, from [`Engine::answers`] under this ticket.
+    Pending(u64),
+}
+
+/// The size of a tab's content area.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// Width in CSS pixels.
+    pub width: f32,
+    /// Height in CSS pixels.
+    pub height: f32,
+    /// Device pixels per CSS pixel.
+    pub device_pixel_ratio: f32,
+}
+
+impl Viewport {
+    /// Checks that the size is not negative and the ratio is positive.
+    pub fn new(width: f32, height: f32, device_pixel_ratio: f32) -> Result<Self, EngineError> {
+        if !(width.is_finite() && width >= 0.0 && height.is_finite() && height >= 0.0) {
+            return Err(EngineError::InvalidArgument(format!(
+                "viewport size {width}×{height}"
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            device_pixel_ratio: positive("device pixel ratio", device_pixel_ratio)?,
+        })
+    }
+
+    /// A viewport of `width` × `height` device pixels at a ratio of 1.
+    pub fn from_device_size(width: u32, height: u32) -> Self {
+        Self {
+            width: width as f32,
+            height: height as f32,
+            device_pixel_ratio: 1.0,
+        }
+    }
+
+    /// The size in device pixels, at least one pixel each way.
+    pub fn device_size(&self) -> (u32, u32) {
+        let scale = |css: f32| ((css * self.device_pixel_ratio).round() as u32).max(1);
+        (scale(self.width), scale(self.height))
+    }
+}
+
+/// The URL of the entry at `index` of a saved session history.
+pub(crate) fn restored_url(entries: &[HistoryEntry], index: usize) -> Result<Url, EngineError> {
+    let entry = entries
+        .get(index)
+        .ok_or_else(|| EngineError::InvalidArgument(format!("no history entry {index}")))?;
+    Url::parse(&entry.url)
+        .map_err(|error| EngineError::InvalidArgument(format!("{}: {error}", entry.url)))
+}
+
+/// Checks that a page zoom factor is positive.
+pub fn zoom_factor(factor: f32) -> Result<f32, EngineError> {
+    positive("zoom factor", factor)
+}
+
+fn positive(what: &str, value: f32) -> Result<f32, EngineError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err(EngineError::InvalidArgument(format!("{what} {value}")))
+    }
+}
+
+/// A browser engine hosting one webview per tab.
+///
+/// All methods are called from the bridge thread. Engines report progress
+/// through the events returned by [`Engine::spin`] and ask for `spin` to be
+/// called again through the [`Waker`](crate::Waker) they were created with.
+pub trait Engine {
+    /// The optional features of the engine, announced to clients in
+    /// [`Event::Ready`].
+    fn capabilities(&self) -> Vec<Capability>;
+
+    /// The version of the Servo the engine embeds, if it embeds one.
+    fn servo_version(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the engine decides itself which hosts exist. The bridge looks
+    /// up the host of every navigation before it reaches other engines.
+    fn resolves_hosts(&self) -> bool {
+        false
+    }
+
+    /// Loads `url` in `tab`, creating the tab's webview on first use. A crashed
+    /// tab is restarted.
+    fn navigate(&mut self, tab: &TabId, url: Url) -> Result<Navigation, EngineError>;
+
+    /// Goes one step back in the tab's session history.
+    fn go_back(&mut self, tab: &TabId) -> Result<(), EngineError>;
+
+    /// Goes one step forward in the tab's session history.
+    fn go_forward(&mut self, tab: &TabId) -> Result<(), EngineError>;
+
+    /// Reloads the tab's current page.
+    fn reload(&mut self, tab: &TabId) -> Result<(), EngineError>;
+
+    /// Closes the tab and drops its webview.
+    fn close(&mut self, tab: &TabId) -> Result<(), EngineError>;
+
+    /// Delivers user input to the tab's page.
+    fn input(&mut self, tab: &TabId, event: InputEvent) -> Result<(), EngineError>;
+
+    /// Resizes the tab's content area. A tab that is not open yet takes the
+    /// size when it opens. Answered with [`Event::ViewportChanged`].
+    fn resize(&mut self, tab: &TabId, viewport: Viewport) -> Result<(), EngineError>;
+
+    /// Sets the tab's page zoom, 1 being 100%. Like [`Engine::resize`], it
+    /// applies to tabs that are not open yet and is answered with
+    /// [`Event::ViewportChanged`].
+    fn set_zoom(&mut self, tab: &TabId, factor: f32) -> Result<(), EngineError>;
+
+    /// Restarts a tab that crashed and loads the last URL it showed.
+    fn reload_crashed(&mut self, tab: &TabId) -> Result<(), EngineError>;
+
+    /// Opens `tab` as it was saved in an earlier session: on the entry at
+    /// `index` of `entries`, with `state` applied once its page loaded.
+    /// Engines that cannot rebuild a session history, like this default,
+    /// only load the page of that entry.
+    fn restore(
+        &mut self,
+        tab: &TabId,
+        entries: &[HistoryEntry],
+        index: usize,
+        state: Option<PageState>,
+    ) -> Result<(), EngineError> {
+        let _ = state;
+        self.navigate(tab, restored_url(entries, index)?).map(drop)
+    }
+
+    /// What the user did to the page `tab` shows, to save it with the
+    /// session. Engines may answer with what they found out earlier and look
+    /// again in the background, so the state can lag behind the page.
+    fn page_state(&mut self, tab: &TabId) -> Option<PageState> {
+        let _ = tab;
+        None
+    }
+
+    /// Lets the engine make progress and returns the events it produced.
+    fn spin(&mut self) -> Vec<Event>;
+
+    /// How the navigations [`Engine::navigate`] left pending went, by
+    /// ticket, as found out by the last [`Engine::spin`]. Engines that start
+    /// every navigation at once, like this default, have nothing to tell.
+    fn answers(&mut self) -> Vec<(u64, Result<(), EngineError>)> {
+        Vec::new()
+    }
+}
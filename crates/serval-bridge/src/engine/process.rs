@@ -0,0 +1,646 @@
+//! Tab isolation: every tab runs in its own content process.
+//!
+//! The supervisor talks to a content process over its stdin and stdout, one
+//! protocol message per line: commands go in, events come out. Any program
+//! speaking that protocol for a single tab will do; normally it is
+//! `serval-bridge --content-process`.
+//!
+//! The supervisor sends a `ping` heartbeat every quarter of the hang
+//! timeout. A content process that exits, stays silent for longer than the
+//! hang timeout, or leaves more than [`WRITE_QUEUE`] commands unread, is
+//! reported with [`Event::TabCrashed`] and the tab stays crashed until it is
+//! navigated or sent `reloadCrashed`. Commands are written by a thread per
+//! content process, so one that stops reading never blocks the bridge.
+//!
+//! Navigations are passed on with a request id, and the content process's
+//! `ack` or `error` tells whether they started.
+//!
+//! The supervisor remembers each tab's viewport and zoom and passes them on to
+//! every content process it starts for the tab.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command as Process, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serval_protocol::{Capability, Command, ErrorCode, Event, InputEvent, Message, Request, TabId};
+use url::Url;
+
+use super::{Engine, EngineError, Navigation, Viewport};
+use crate::Waker;
+
+/// How many commands may wait for a content process to read them before it
+/// counts as hung.
+const WRITE_QUEUE: usize = 256;
+
+/// Runs every tab in a content process of its own.
+pub struct ProcessEngine {
+    program: PathBuf,
+    args: Vec<OsString>,
+    hang_timeout: Duration,
+    waker: Waker,
+    sender: Sender<Output>,
+    receiver: Receiver<Output>,
+    tabs: HashMap<TabId, Tab>,
+    /// Viewport and zoom of every tab that was sent any, open or not.
+    views: HashMap<TabId, View>,
+    next_generation: u64,
+    /// The request id of the next navigation passed on. Heartbeats use 0.
+    next_ticket: u64,
+    /// How the navigations passed on went, until the bridge asks.
+    answers: Vec<(u64, Result<(), EngineError>)>,
+    running: Arc<AtomicBool>,
+    /// Whether host lookups are left to the content processes.
+    resolves_hosts: bool,
+    /// What the engine of the content processes supports.
+    capabilities: Vec<Capability>,
+    servo_version: Option<String>,
+}
+
+/// A line written by a content process, or `None` once its stdout closed.
+struct Output {
+    tab_id: TabId,
+    generation: u64,
+    line: Option<String>,
+}
+
+struct Tab {
+    /// The last URL the tab showed, reported when it crashes.
+    url: Option<String>,
+    /// `None` while the tab is crashed.
+    process: Option<ContentProcess>,
+}
+
+#[derive(Default)]
+struct View {
+    viewport: Option<Viewport>,
+    zoom: Option<f32>,
+}
+
+impl View {
+    /// The commands that bring a new content process up to date.
+    fn commands(&self, tab_id: &TabId) -> Vec<Command> {
+        let resize = self
+            .viewport
+            .map(|viewport| resize_command(tab_id, viewport));
+        let zoom = self.zoom.map(|factor| Command::Zoom {
+            tab_id: tab_id.clone(),
+            factor,
+        });
+        resize.into_iter().chain(zoom).collect()
+    }
+}
+
+struct ContentProcess {
+    child: Child,
+    /// Lines for the thread writing to the process's stdin.
+    requests: SyncSender<String>,
+    /// Set once the process left [`WRITE_QUEUE`] commands unread.
+    stalled: bool,
+    /// Tells this process's output apart from that of earlier processes of
+    /// the same tab.
+    generation: u64,
+    last_heard: Instant,
+    last_ping: Instant,
+    /// The navigations the process has yet to answer.
+    navigations: Vec<u64>,
+}
+
+impl ProcessEngine {
+    /// Creates a supervisor starting content processes with `program` and
+    /// `args`. A content process that does not answer for `hang_timeout` is
+    /// considered hung and killed.
+    pub fn new(
+        waker: Waker,
+        program: impl Into<PathBuf>,
+        args: Vec<OsString>,
+        hang_timeout: Duration,
+    ) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        spawn_ticker(waker.clone(), hang_timeout / 4, running.clone());
+
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            program: program.into(),
+            args,
+            hang_timeout,
+            waker,
+            sender,
+            receiver,
+            tabs: HashMap::new(),
+            views: HashMap::new(),
+            next_generation: 0,
+            next_ticket: 1,
+            answers: Vec::new(),
+            running,
+            resolves_hosts: false,
+            capabilities: Vec::new(),
+            servo_version: None,
+        }
+    }
+
+    /// Leaves host lookups to the content processes, for engines like
+    /// [`MockEngine`](super::MockEngine) that decide which hosts exist.
+    pub fn resolving_hosts(mut self) -> Self {
+        self.resolves_hosts = true;
+        self
+    }
+
+    /// Announces the capabilities and Servo version of the engine the content
+    /// processes run. Without it, the supervisor only announces crash
+    /// recovery, which it provides itself.
+    pub fn describing(mut self, capabilities: &[Capability], servo_version: Option<&str>) -> Self {
+        self.capabilities = capabilities.to_vec();
+        self.servo_version = servo_version.map(str::to_owned);
+        self
+    }
+
+    fn spawn(&mut self, tab_id: &TabId) -> Result<ContentProcess, EngineError> {
+        let mut child = Process::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|error| {
+                EngineError::Internal(format!(
+                    "cannot start content process `{}`: {error}",
+                    self.program.display()
+                ))
+            })?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        info!("{tab_id}: started content process {}", child.id());
+
+        let (requests, lines) = mpsc::sync_channel(WRITE_QUEUE);
+        thread::spawn(move || write_lines(stdin, lines));
+
+        let tab_id = tab_id.clone();
+        let sender = self.sender.clone();
+        let waker = self.waker.clone();
+        thread::spawn(move || {
+            let mut lines = BufReader::new(stdout).lines();
+            loop {
+                let line = lines.next().and_then(Result::ok);
+                let closed = line.is_none();
+                let output = Output {
+                    tab_id: tab_id.clone(),
+                    generation,
+                    line,
+                };
+                if sender.send(output).is_err() {
+                    return;
+                }
+                waker.wake();
+                if closed {
+                    return;
+                }
+            }
+        });
+
+        let now = Instant::now();
+        Ok(ContentProcess {
+            child,
+            requests,
+            stalled: false,
+            generation,
+            last_heard: now,
+            last_ping: now,
+            navigations: Vec::new(),
+        })
+    }
+
+    /// Starts a content process for the tab and hands it the tab's viewport.
+    fn start(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        let process = self.spawn(tab_id)?;
+        let tab = self.tabs.entry(tab_id.clone()).or_insert(Tab {
+            url: None,
+            process: None,
+        });
+        tab.process = Some(process);
+
+        let commands = self
+            .views
+            .get(tab_id)
+            .map(|view| view.commands(tab_id))
+            .unwrap_or_default();
+        for command in commands {
+            self.send(tab_id, command)?;
+        }
+        Ok(())
+    }
+
+    /// Updates the tab's view and passes `command` on if the tab is live.
+    fn update_view(
+        &mut self,
+        tab_id: &TabId,
+        update: impl FnOnce(&mut View),
+        command: Command,
+    ) -> Result<(), EngineError> {
+        update(self.views.entry(tab_id.clone()).or_default());
+        match self.tabs.get(tab_id) {
+            Some(Tab {
+                process: Some(_), ..
+            }) => self.send(tab_id, command),
+            // Applied when the tab's next content process starts.
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends `command` to the content process of a live tab.
+    fn send(&mut self, tab_id: &TabId, command: Command) -> Result<(), EngineError> {
+        self.request(tab_id, Request::new(command)).map(drop)
+    }
+
+    /// Sends `request` to the content process of a live tab and returns the
+    /// process.
+    fn request(
+        &mut self,
+        tab_id: &TabId,
+        request: Request,
+    ) -> Result<&mut ContentProcess, EngineError> {
+        let tab = self
+            .tabs
+            .get_mut(tab_id)
+            .ok_or_else(|| EngineError::TabNotFound(tab_id.clone()))?;
+        let process = tab
+            .process
+            .as_mut()
+            .ok_or_else(|| EngineError::TabCrashed(tab_id.clone()))?;
+        // A write only fails once the process is gone, which its stdout
+        // closing reports shortly, or stalled, which the next heartbeat
+        // check reports.
+        if process.write(request) {
+            Ok(process)
+        } else {
+            Err(EngineError::TabCrashed(tab_id.clone()))
+        }
+    }
+
+    fn handle_output(&mut self, output: Output, events: &mut Vec<Event>) {
+        let Some(tab) = self.tabs.get_mut(&output.tab_id) else {
+            return;
+        };
+        let Some(process) = tab
+            .process
+            .as_mut()
+            .filter(|process| process.generation == output.generation)
+        else {
+            return;
+        };
+        process.last_heard = Instant::now();
+
+        let Some(line) = output.line else {
+            // Reap the process; one that closed stdout without exiting is
+            // of no use either.
+            let status = match process.child.try_wait() {
+                Ok(Some(status)) => Ok(status),
+                _ => {
+                    let _ = process.child.kill();
+                    process.child.wait()
+                }
+            };
+            events.extend(crash(
+                &output.tab_id,
+                tab,
+                status.ok(),
+                false,
+                &mut self.answers,
+            ));
+            return;
+        };
+
+        match serval_protocol::decode_event(&line) {
+            Ok(Event::Ack { id }) if process.answered(id) => self.answers.push((id, Ok(()))),
+            Ok(Event::Error {
+                id: Some(id),
+                code,
+                message,
+            }) if process.answered(id) => {
+                self.answers
+                    .push((id, Err(EngineError::Reported { code, message })));
+            }
+            // Failures of the commands passed on, which name no tab.
+            Ok(Event::Error {
+                id: None,
+                code,
+                message,
+            }) if !matches!(
+                code,
+                ErrorCode::InvalidMessage
+                    | ErrorCode::InvalidFrame
+                    | ErrorCode::UnsupportedProtocol
+            ) =>
+            {
+                events.push(Event::Error {
+                    id: None,
+                    code,
+                    message,
+                });
+            }
+            // Answers to heartbeats, and what a content process tells its own
+            // clients: its session, bookmarks and connection errors. A
+            // content process only speaks for its tab; the supervisor keeps
+            // the rest itself.
+            Ok(event) if event.tab_id().is_none() => {}
+            Ok(event) if event.tab_id().is_some_and(|id| *id != output.tab_id) => {
+                warn!(
+                    "{}: dropping `{}` event for another tab",
+                    output.tab_id,
+                    event.kind()
+                );
+            }
+            Ok(event) => {
+                if let Event::UrlChange { url, .. } | Event::LoadComplete { url, .. } = &event {
+                    tab.url = Some(url.clone());
+                }
+                events.push(event);
+            }
+            Err(error) => warn!(
+                "{}: ignoring content process output: {error}",
+                output.tab_id
+            ),
+        }
+    }
+
+    /// Kills hung and stalled content processes and pings the others.
+    fn check_heartbeats(&mut self, events: &mut Vec<Event>) {
+        let interval = self.hang_timeout / 4;
+        for (tab_id, tab) in &mut self.tabs {
+            let Some(process) = &mut tab.process else {
+                continue;
+            };
+            if process.stalled || process.last_heard.elapsed() > self.hang_timeout {
+                let _ = process.child.kill();
+                let status = process.child.wait().ok();
+                events.extend(crash(tab_id, tab, status, true, &mut self.answers));
+            } else if process.last_ping.elapsed() >= interval {
+                process.last_ping = Instant::now();
+                let _ = process.write(Request::with_id(0, Command::Ping {}));
+            }
+        }
+    }
+}
+
+impl ContentProcess {
+    /// Queues `request` for the process. Returns `false` once the process
+    /// is gone or left too many commands unread, marking it stalled.
+    fn write(&mut self, request: Request) -> bool {
+        match self.requests.try_send(serval_protocol::encode(&request)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.stalled = true;
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+
+    /// Forgets the navigation `id` the process answered. Returns whether it
+    /// was waiting for an answer.
+    fn answered(&mut self, id: u64) -> bool {
+        let waiting = self.navigations.len();
+        self.navigations.retain(|navigation| *navigation != id);
+        self.navigations.len() < waiting
+    }
+
+    fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Engine for ProcessEngine {
+    fn capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = self.capabilities.clone();
+        if !capabilities.contains(&Capability::CrashRecovery) {
+            capabilities.push(Capability::CrashRecovery);
+        }
+        capabilities
+    }
+
+    fn servo_version(&self) -> Option<String> {
+        self.servo_version.clone()
+    }
+
+    fn resolves_hosts(&self) -> bool {
+        self.resolves_hosts
+    }
+
+    fn navigate(&mut self, tab_id: &TabId, url: Url) -> Result<Navigation, EngineError> {
+        if self
+            .tabs
+            .get(tab_id)
+            .is_none_or(|tab| tab.process.is_none())
+        {
+            self.start(tab_id)?;
+        }
+        let ticket = self.next_ticket;
+        let navigate = Command::Navigate {
+            tab_id: tab_id.clone(),
+            url: url.into(),
+        };
+        self.request(tab_id, Request::with_id(ticket, navigate))?
+            .navigations
+            .push(ticket);
+        self.next_ticket += 1;
+        Ok(Navigation::Pending(ticket))
+    }
+
+    fn go_back(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.send(
+            tab_id,
+            Command::Back {
+                tab_id: tab_id.clone(),
+            },
+        )
+    }
+
+    fn go_forward(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.send(
+            tab_id,
+            Command::Forward {
+                tab_id: tab_id.clone(),
+            },
+        )
+    }
+
+    fn reload(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.send(
+            tab_id,
+            Command::Refresh {
+                tab_id: tab_id.clone(),
+            },
+        )
+    }
+
+    fn input(&mut self, tab_id: &TabId, event: InputEvent) -> Result<(), EngineError> {
+        self.send(
+            tab_id,
+            Command::Input {
+                tab_id: tab_id.clone(),
+                event,
+            },
+        )
+    }
+
+    fn resize(&mut self, tab_id: &TabId, viewport: Viewport) -> Result<(), EngineError> {
+        self.update_view(
+            tab_id,
+            |view| view.viewport = Some(viewport),
+            resize_command(tab_id, viewport),
+        )
+    }
+
+    fn set_zoom(&mut self, tab_id: &TabId, factor: f32) -> Result<(), EngineError> {
+        self.update_view(
+            tab_id,
+            |view| view.zoom = Some(factor),
+            Command::Zoom {
+                tab_id: tab_id.clone(),
+                factor,
+            },
+        )
+    }
+
+    fn close(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        self.views.remove(tab_id);
+        let tab = self
+            .tabs
+            .remove(tab_id)
+            .ok_or_else(|| EngineError::TabNotFound(tab_id.clone()))?;
+        if let Some(process) = tab.process {
+            for navigation in &process.navigations {
+                let closed = EngineError::TabNotFound(tab_id.clone());
+                self.answers.push((*navigation, Err(closed)));
+            }
+            process.kill();
+        }
+        Ok(())
+    }
+
+    fn reload_crashed(&mut self, tab_id: &TabId) -> Result<(), EngineError> {
+        let url = match self.tabs.get(tab_id) {
+            None => return Err(EngineError::TabNotFound(tab_id.clone())),
+            Some(tab) if tab.process.is_some() => {
+                return Err(EngineError::TabNotCrashed(tab_id.clone()));
+            }
+            Some(tab) => tab.url.clone(),
+        };
+        self.start(tab_id)?;
+        match url {
+            Some(url) => self.send(
+                tab_id,
+                Command::Navigate {
+                    tab_id: tab_id.clone(),
+                    url,
+                },
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn spin(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Ok(output) = self.receiver.try_recv() {
+            self.handle_output(output, &mut events);
+        }
+        self.check_heartbeats(&mut events);
+        events
+    }
+
+    fn answers(&mut self) -> Vec<(u64, Result<(), EngineError>)> {
+        std::mem::take(&mut self.answers)
+    }
+}
+
+impl Drop for ProcessEngine {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        for (_, tab) in self.tabs.drain() {
+            if let Some(process) = tab.process {
+                process.kill();
+            }
+        }
+    }
+}
+
+fn resize_command(tab_id: &TabId, viewport: Viewport) -> Command {
+    Command::Resize {
+        tab_id: tab_id.clone(),
+        width: viewport.width,
+        height: viewport.height,
+        device_pixel_ratio: viewport.device_pixel_ratio,
+    }
+}
+
+/// Marks `tab` as crashed, fails the navigations its process had yet to
+/// answer, and returns the event reporting it.
+fn crash(
+    tab_id: &TabId,
+    tab: &mut Tab,
+    status: Option<ExitStatus>,
+    hung: bool,
+    answers: &mut Vec<(u64, Result<(), EngineError>)>,
+) -> Option<Event> {
+    let process = tab.process.take()?;
+    for navigation in process.navigations {
+        answers.push((navigation, Err(EngineError::TabCrashed(tab_id.clone()))));
+    }
+    match status {
+        Some(status) if !hung => warn!("{tab_id}: content process exited with {status}"),
+        _ => warn!("{tab_id}: content process stopped responding"),
+    }
+    Some(Event::TabCrashed {
+        tab_id: tab_id.clone(),
+        url: tab.url.clone(),
+        exit_code: status.and_then(|status| status.code()),
+        signal: status.and_then(signal),
+        hung,
+    })
+}
+
+#[cfg(unix)]
+fn signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Writes `lines` to the stdin of a content process until the process is
+/// gone or the supervisor drops it.
+fn write_lines(mut stdin: ChildStdin, lines: Receiver<String>) {
+    for line in lines {
+        if writeln!(stdin, "{line}")
+            .and_then(|()| stdin.flush())
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Wakes the bridge loop every `interval` so heartbeats go out even while no
+/// client is talking, until `running` is cleared.
+fn spawn_ticker(waker: Waker, interval: Duration, running: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            thread::sleep(interval);
+            waker.wake();
+        }
+    });
+}
@@ -0,0 +1,606 @@
+//! Servo, embedded through its embedding API.
+//!
+//! Every tab gets its own webview backed by a [`SoftwareRenderingContext`], so
+//! the engine runs headless and needs neither a display nor a GPU. Each new
+//! frame is read back from the rendering context and streamed as tiles (see
+//! [`crate::frames`]).
+//!
+//! A tab's rendering context is as big as its viewport in device pixels, so
+//! resizing a tab changes the size of the frames it streams from the next
+//! frame on.
+//!
+//! `serval:` URLs are fetched from the bridge's [`Pages`] through a protocol
+//! handler, on Servo's networking threads.
+//!
+//! Servo cannot be handed a session history, so restored tabs only load the
+//! page they showed. Page state is read and restored by running scripts in
+//! the page.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use dpi::PhysicalSize;
+use euclid::{Point2D, Scale};
+use log::warn;
+use serval_protocol::{
+    Capability, Event, HistoryEntry, InputEvent, KeyInput, KeyLocation, Modifiers, PointerType,
+    TabId, WheelDeltaMode,
+};
+use servo::protocol_handler::{
+    DoneChannel, FetchContext, HttpStatus, ProtocolHandler, ProtocolRegistry, Request,
+    ResourceFetchTiming, Response, ResponseBody,
+};
+use servo::{
+    Code, CompositionEvent, CompositionState, DeviceIntRect, DeviceIntSize, EventLoopWaker,
+    ImeEvent, JSValue, Key, KeyState, KeyboardEvent, LoadStatus, Location, MouseButton,
+    MouseButtonAction, MouseButtonEvent, MouseLeftViewportEvent, MouseMoveEvent, RenderingContext,
+    Servo, ServoBuilder, SoftwareRenderingContext, TouchEvent, TouchEventType, TouchId,
+    TouchPointerType, WebView, WebViewBuilder, WebViewDelegate, WebViewId, WebViewPoint,
+    WheelDelta, WheelEvent, WheelMode,
+};
+use url::Url;
+
+use super::{Engine, EngineError, Navigation, Viewport};
+use crate::Waker;
+use crate::checkpoint::{self, PageState};
+use crate::frames::FrameEncoder;
+use crate::history::SessionHistory;
+use crate::pages::{self, Pages};
+
+impl EventLoopWaker for Waker {
+    fn clone_box(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(self.clone())
+    }
+
+    fn wake(&self) {
+        Waker::wake(self);
+    }
+}
+
+/// The version of the `servo` crate the bridge is built with. Keep it in
+/// sync with `Cargo.lock`.
+pub const SERVO_VERSION: &str = "0.7.0";
+
+/// The Servo engine, with one webview per tab.
+pub struct ServoEngine {
+    servo: Servo,
+    /// The viewport of tabs that were not sent one.
+    default_viewport: Viewport,
+    tabs: HashMap<TabId, WebView>,
+    /// Viewport and zoom of every tab that was sent any, open or not.
+    views: HashMap<TabId, View>,
+    delegate: Rc<Delegate>,
+    /// The latest state read from the page of each tab.
+    page_states: Rc<RefCell<HashMap<TabId, PageState>>>,
+}
+
+#[derive(Clone, Copy)]
+struct View {
+    viewport: Viewport,
+    zoom: f32,
+}
+
+/// Serves `serval:` URLs.
+struct ServalProtocol(Pages);
+
+impl ProtocolHandler for ServalProtocol {
+    fn load<'a>(
+        &'a self,
+        request: &'a mut Request,
+        _done_chan: &mut DoneChannel,
+        _context: &FetchContext,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send + 'a>> {
+        let url = request.current_url();
+        let page = self.0.render(url.as_url());
+        let mut response = Response::new(url, ResourceFetchTiming::new(request.timing_type()));
+        *response.body.lock() = ResponseBody::Done(page.html.into_bytes());
+        response.headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        response.status = HttpStatus::default();
+        Box::pin(future::ready(response))
+    }
+}
+
+impl ServoEngine {
+    /// What a Servo tab supports. Downloads and developer tools are not
+    /// wired up yet.
+    pub const CAPABILITIES: &[Capability] = &[
+        Capability::Frames,
+        Capability::Input,
+        Capability::Viewport,
+        Capability::History,
+    ];
+
+    /// Starts Servo. New webviews are `width` × `height` device pixels.
+    /// `serval:` URLs do not load.
+    pub fn new(waker: Waker, width: u32, height: u32) -> Self {
+        Self::start(waker, width, height, ProtocolRegistry::default())
+    }
+
+    /// Starts Servo, loading `serval:` URLs from `pages`.
+    pub fn serving(waker: Waker, width: u32, height: u32, pages: Pages) -> Self {
+        let mut protocols = ProtocolRegistry::default();
+        protocols
+            .register(pages::SCHEME, ServalProtocol(pages))
+            .expect("`serval` is neither built in nor forbidden");
+        Self::start(waker, width, height, protocols)
+    }
+
+    fn start(waker: Waker, width: u32, height: u32, protocols: ProtocolRegistry) -> Self {
+        // Servo's networking expects a process-wide crypto provider.
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let servo = ServoBuilder::default()
+            .event_loop_waker(Box::new(waker))
+            .protocol_registry(protocols)
+            .build();
+        servo.setup_logging();
+
+        Self {
+            servo,
+            default_viewport: Viewport::from_device_size(width, height),
+            tabs: HashMap::new(),
+            views: HashMap::new(),
+            delegate: Rc::default(),
+            page_states: Rc::default(),
+        }
+    }
+
+    fn webview(&self, tab: &TabId) -> Result<&WebView, EngineError> {
+        self.tabs
+            .get(tab)
+            .ok_or_else(|| EngineError::TabNotFound(tab.clone()))
+    }
+
+    fn view(&mut self, tab: &TabId) -> &mut View {
+        let viewport = self.default_viewport;
+        self.views.entry(tab.clone()).or_insert(View {
+            viewport,
+            zoom: 1.0,
+        })
+    }
+
+    fn open(&mut self, tab: &TabId, url: Url) -> Result<(), EngineError> {
+        let View { viewport, zoom } = *self.view(tab);
+        let (width, height) = viewport.device_size();
+        let rendering_context = SoftwareRenderingContext::new(PhysicalSize::new(width, height))
+            .map_err(|error| EngineError::Internal(format!("{error:?}")))?;
+        rendering_context
+            .make_current()
+            .map_err(|error| EngineError::Internal(format!("{error:?}")))?;
+
+        let rendering_context: Rc<dyn RenderingContext> = Rc::new(rendering_context);
+        let webview = WebViewBuilder::new(&self.servo, rendering_context.clone())
+            .url(url)
+            .hidpi_scale_factor(Scale::new(viewport.device_pixel_ratio))
+            .delegate(self.delegate.clone())
+            .build();
+        webview.set_page_zoom(zoom);
+        self.delegate.tabs.borrow_mut().insert(
+            webview.id(),
+            TabState {
+                tab_id: tab.clone(),
+                history: SessionHistory::new(),
+                crashed: false,
+                restoring: None,
+                rendering_context,
+                frames: FrameEncoder::new(),
+            },
+        );
+        self.delegate.viewport_changed(&webview);
+        self.tabs.insert(tab.clone(), webview);
+        Ok(())
+    }
+}
+
+impl Engine for ServoEngine {
+    fn capabilities(&self) -> Vec<Capability> {
+        Self::CAPABILITIES.to_vec()
+    }
+
+    fn servo_version(&self) -> Option<String> {
+        Some(SERVO_VERSION.to_owned())
+    }
+
+    fn navigate(&mut self, tab: &TabId, url: Url) -> Result<Navigation, EngineError> {
+        self.page_states.borrow_mut().remove(tab);
+        match self.tabs.get(tab) {
+            Some(webview) => {
+                self.delegate.clear_crash(webview);
+                webview.load(url);
+            }
+            None => self.open(tab, url)?,
+        }
+        Ok(Navigation::Started)
+    }
+
+    fn go_back(&mut self, tab: &TabId) -> Result<(), EngineError> {
+        self.webview(tab)?.go_back(1);
+        Ok(())
+    }
+
+    fn go_forward(&mut self, tab: &TabId) -> Result<(), EngineError> {
+        self.webview(tab)?.go_forward(1);
+        Ok(())
+    }
+
+    fn reload(&mut self, tab: &TabId) -> Result<(), EngineError> {
+        self.webview(tab)?.reload();
+        Ok(())
+    }
+
+    fn resize(&mut self, tab: &TabId, viewport: Viewport) -> Result<(), EngineError> {
+        self.view(tab).viewport = viewport;
+        if let Some(webview) = self.tabs.get(tab) {
+            let (width, height) = viewport.device_size();
+            webview.set_hidpi_scale_factor(Scale::new(viewport.device_pixel_ratio));
+            webview.resize(PhysicalSize::new(width, height));
+            self.delegate.viewport_changed(webview);
+        }
+        Ok(())
+    }
+
+    fn set_zoom(&mut self, tab: &TabId, factor: f32) -> Result<(), EngineError> {
+        self.view(tab).zoom = factor;
+        if let Some(webview) = self.tabs.get(tab) {
+            webview.set_page_zoom(factor);
+            self.delegate.viewport_changed(webview);
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, tab: &TabId) -> Result<(), EngineError> {
+        self.views.remove(tab);
+        self.page_states.borrow_mut().remove(tab);
+        let webview = self
+            .tabs
+            .remove(tab)
+            .ok_or_else(|| EngineError::TabNotFound(tab.clone()))?;
+        self.delegate.tabs.borrow_mut().remove(&webview.id());
+        Ok(())
+    }
+
+    fn input(&mut self, tab: &TabId, event: InputEvent) -> Result<(), EngineError> {
+        self.webview(tab)?.notify_input_event(translate(event));
+        Ok(())
+    }
+
+    fn reload_crashed(&mut self, tab: &TabId) -> Result<(), EngineError> {
+        let webview = self.webview(tab)?;
+        if !self.delegate.clear_crash(webview) {
+            return Err(EngineError::TabNotCrashed(tab.clone()));
+        }
+        webview.reload();
+        Ok(())
+    }
+
+    fn restore(
+        &mut self,
+        tab: &TabId,
+        entries: &[HistoryEntry],
+        index: usize,
+        state: Option<PageState>,
+    ) -> Result<(), EngineError> {
+        self.navigate(tab, super::restored_url(entries, index)?)?;
+        let webview = self.webview(tab)?;
+        if let Some(restored) = self.delegate.tabs.borrow_mut().get_mut(&webview.id()) {
+            restored.restoring = state;
+        }
+        Ok(())
+    }
+
+    fn page_state(&mut self, tab: &TabId) -> Option<PageState> {
+        let webview = self.tabs.get(tab)?;
+        let page_states = self.page_states.clone();
+        let tab_id = tab.clone();
+        webview.evaluate_javascript(checkpoint::CAPTURE_SCRIPT, move |result| {
+            let state = match result {
+                Ok(JSValue::String(json)) => serde_json::from_str(&json).ok(),
+                _ => None,
+            };
+            if let Some(state) = state {
+                page_states.borrow_mut().insert(tab_id, state);
+            }
+        });
+        self.page_states.borrow().get(tab).cloned()
+    }
+
+    fn spin(&mut self) -> Vec<Event> {
+        self.servo.spin_event_loop();
+        self.delegate.events.take()
+    }
+}
+
+/// Receives Servo's notifications for every webview and turns them into
+/// protocol events.
+#[derive(Default)]
+struct Delegate {
+    tabs: RefCell<HashMap<WebViewId, TabState>>,
+    events: RefCell<Vec<Event>>,
+}
+
+struct TabState {
+    tab_id: TabId,
+    history: SessionHistory,
+    crashed: bool,
+    /// The state to restore once the page loaded.
+    restoring: Option<PageState>,
+    rendering_context: Rc<dyn RenderingContext>,
+    frames: FrameEncoder,
+}
+
+impl Delegate {
+    fn emit(&self, webview: &WebView, event: impl FnOnce(TabId) -> Event) {
+        if let Some(tab) = self.tabs.borrow().get(&webview.id()) {
+            self.events.borrow_mut().push(event(tab.tab_id.clone()));
+        }
+    }
+
+    /// Marks the tab as live again and returns whether it had crashed.
+    fn clear_crash(&self, webview: &WebView) -> bool {
+        self.tabs
+            .borrow_mut()
+            .get_mut(&webview.id())
+            .is_some_and(|tab| std::mem::take(&mut tab.crashed))
+    }
+
+    /// Reports the viewport the webview ended up with, which Servo may have
+    /// clamped.
+    fn viewport_changed(&self, webview: &WebView) {
+        let size = webview.size();
+        self.emit(webview, |tab_id| Event::ViewportChanged {
+            tab_id,
+            width: size.width as u32,
+            height: size.height as u32,
+            device_pixel_ratio: webview.hidpi_scale_factor().get(),
+            zoom: webview.page_zoom(),
+        });
+    }
+
+    /// Updates the tab's history and reports the result.
+    fn update_history(&self, webview: &WebView, update: impl FnOnce(&mut SessionHistory)) {
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(&webview.id()) {
+            update(&mut tab.history);
+            let event = tab.history.to_event(tab.tab_id.clone());
+            self.events.borrow_mut().push(event);
+        }
+    }
+}
+
+impl WebViewDelegate for Delegate {
+    fn notify_url_changed(&self, webview: WebView, url: Url) {
+        self.emit(&webview, |tab_id| Event::UrlChange {
+            tab_id,
+            url: url.into(),
+        });
+    }
+
+    fn notify_page_title_changed(&self, webview: WebView, title: Option<String>) {
+        let title = title.unwrap_or_default();
+        self.emit(&webview, |tab_id| Event::TitleChange {
+            tab_id,
+            title: title.clone(),
+        });
+        self.update_history(&webview, |history| history.set_title(title));
+    }
+
+    fn notify_history_changed(&self, webview: WebView, entries: Vec<Url>, current: usize) {
+        self.update_history(&webview, |history| {
+            history.sync(entries.into_iter().map(String::from), current)
+        });
+    }
+
+    fn notify_load_status_changed(&self, webview: WebView, status: LoadStatus) {
+        let url = webview.url().map(String::from).unwrap_or_default();
+        match status {
+            LoadStatus::Started => self.emit(&webview, |tab_id| Event::LoadStart { tab_id, url }),
+            LoadStatus::HeadParsed => {}
+            LoadStatus::Complete => {
+                self.emit(&webview, |tab_id| Event::LoadComplete { tab_id, url });
+                let restoring = self
+                    .tabs
+                    .borrow_mut()
+                    .get_mut(&webview.id())
+                    .and_then(|tab| tab.restoring.take());
+                if let Some(state) = restoring {
+                    webview.evaluate_javascript(state.restore_script(), |_| {});
+                }
+            }
+        }
+    }
+
+    fn notify_new_frame_ready(&self, webview: WebView) {
+        webview.paint();
+
+        let mut tabs = self.tabs.borrow_mut();
+        let Some(tab) = tabs.get_mut(&webview.id()) else {
+            return;
+        };
+        let size = tab.rendering_context.size();
+        let rect =
+            DeviceIntRect::from_size(DeviceIntSize::new(size.width as i32, size.height as i32));
+        let Some(image) = tab.rendering_context.read_to_image(rect) else {
+            warn!("{}: cannot read back the rendered frame", tab.tab_id);
+            return;
+        };
+        let (width, height) = image.dimensions();
+        if let Some(event) = tab
+            .frames
+            .encode(tab.tab_id.clone(), width, height, image.as_raw())
+        {
+            self.events.borrow_mut().push(event);
+        }
+    }
+
+    fn notify_crashed(&self, webview: WebView, reason: String, _backtrace: Option<String>) {
+        warn!("webview {:?} crashed: {reason}", webview.id());
+        let Some(tab_id) = self.tabs.borrow_mut().get_mut(&webview.id()).map(|tab| {
+            tab.crashed = true;
+            tab.tab_id.clone()
+        }) else {
+            return;
+        };
+        // Servo runs content in-process, so there is no exit status to report.
+        self.events.borrow_mut().push(Event::TabCrashed {
+            tab_id,
+            url: webview.url().map(String::from),
+            exit_code: None,
+            signal: None,
+            hung: false,
+        });
+    }
+}
+
+/// Turns protocol input into Servo's embedder input event.
+fn translate(event: InputEvent) -> servo::InputEvent {
+    match event {
+        InputEvent::PointerMove {
+            x,
+            y,
+            pointer_type: PointerType::Mouse,
+            ..
+        } => servo::InputEvent::MouseMove(MouseMoveEvent::new(point(x, y))),
+        InputEvent::PointerMove {
+            x,
+            y,
+            pointer_type,
+            pointer_id,
+        } => touch(TouchEventType::Move, x, y, pointer_type, pointer_id),
+        InputEvent::PointerDown {
+            x,
+            y,
+            pointer_type: PointerType::Mouse,
+            button,
+            ..
+        } => mouse_button(MouseButtonAction::Down, x, y, button),
+        InputEvent::PointerDown {
+            x,
+            y,
+            pointer_type,
+            pointer_id,
+            ..
+        } => touch(TouchEventType::Down, x, y, pointer_type, pointer_id),
+        InputEvent::PointerUp {
+            x,
+            y,
+            pointer_type: PointerType::Mouse,
+            button,
+            ..
+        } => mouse_button(MouseButtonAction::Up, x, y, button),
+        InputEvent::PointerUp {
+            x,
+            y,
+            pointer_type,
+            pointer_id,
+            ..
+        } => touch(TouchEventType::Up, x, y, pointer_type, pointer_id),
+        InputEvent::PointerCancel { x, y, pointer_id } => {
+            touch(TouchEventType::Cancel, x, y, PointerType::Touch, pointer_id)
+        }
+        InputEvent::PointerLeave => {
+            servo::InputEvent::MouseLeftViewport(MouseLeftViewportEvent::default())
+        }
+        InputEvent::Wheel {
+            x,
+            y,
+            delta_x,
+            delta_y,
+            delta_z,
+            delta_mode,
+        } => {
+            // Servo's deltas point the other way than the DOM's: positive
+            // values reveal content to the left and above.
+            let delta = WheelDelta {
+                x: -delta_x,
+                y: -delta_y,
+                z: -delta_z,
+                mode: match delta_mode {
+                    WheelDeltaMode::Pixel => WheelMode::DeltaPixel,
+                    WheelDeltaMode::Line => WheelMode::DeltaLine,
+                    WheelDeltaMode::Page => WheelMode::DeltaPage,
+                },
+            };
+            servo::InputEvent::Wheel(WheelEvent::new(delta, point(x, y)))
+        }
+        InputEvent::KeyDown(key) => keyboard(KeyState::Down, key),
+        InputEvent::KeyUp(key) => keyboard(KeyState::Up, key),
+        InputEvent::CompositionStart { data } => composition(CompositionState::Start, data),
+        InputEvent::CompositionUpdate { data } => composition(CompositionState::Update, data),
+        InputEvent::CompositionEnd { data } => composition(CompositionState::End, data),
+    }
+}
+
+fn point(x: f32, y: f32) -> WebViewPoint {
+    WebViewPoint::Page(Point2D::new(x, y))
+}
+
+fn mouse_button(action: MouseButtonAction, x: f32, y: f32, button: i16) -> servo::InputEvent {
+    servo::InputEvent::MouseButton(MouseButtonEvent::new(
+        action,
+        MouseButton::from(button),
+        point(x, y),
+    ))
+}
+
+fn touch(
+    event_type: TouchEventType,
+    x: f32,
+    y: f32,
+    pointer_type: PointerType,
+    pointer_id: i32,
+) -> servo::InputEvent {
+    let pointer_type = match pointer_type {
+        PointerType::Pen => TouchPointerType::Pen,
+        PointerType::Mouse | PointerType::Touch => TouchPointerType::Touch,
+    };
+    servo::InputEvent::Touch(TouchEvent::new(
+        event_type,
+        TouchId(pointer_id),
+        point(x, y),
+        pointer_type,
+    ))
+}
+
+fn keyboard(state: KeyState, key: KeyInput) -> servo::InputEvent {
+    let KeyInput {
+        key,
+        code,
+        location,
+        modifiers,
+        repeat,
+        is_composing,
+    } = key;
+    servo::InputEvent::Keyboard(KeyboardEvent::new_without_event(
+        state,
+        Key::from_str(&key).unwrap_or(Key::Named(servo::NamedKey::Unidentified)),
+        Code::from_str(&code).unwrap_or(Code::Unidentified),
+        match location {
+            KeyLocation::Standard => Location::Standard,
+            KeyLocation::Left => Location::Left,
+            KeyLocation::Right => Location::Right,
+            KeyLocation::Numpad => Location::Numpad,
+        },
+        modifier_flags(modifiers),
+        repeat,
+        is_composing,
+    ))
+}
+
+fn modifier_flags(modifiers: Modifiers) -> servo::Modifiers {
+    let mut flags = servo::Modifiers::empty();
+    flags.set(servo::Modifiers::ALT, modifiers.alt);
+    flags.set(servo::Modifiers::CONTROL, modifiers.ctrl);
+    flags.set(servo::Modifiers::META, modifiers.meta);
+    flags.set(servo::Modifiers::SHIFT, modifiers.shift);
+    flags
+}
+
+fn composition(state: CompositionState, data: String) -> servo::InputEvent {
+    servo::InputEvent::Ime(ImeEvent::Composition(CompositionEvent { state, data }))
+}
@@ -0,0 +1,199 @@
+//! Frame streaming.
+//!
+//! Engines render every tab into an RGBA buffer. A [`FrameEncoder`] cuts each
+//! frame into square tiles, compares them with the previous frame and turns
+//! the tiles that changed into an [`Event::Frame`], compressed as PNG. The
+//! frontend paints the tiles into a canvas at their position, so an unchanged
+//! page costs nothing and a blinking cursor costs one tile.
+//!
+//! Tiles always sit on the same grid, so the latest tile at every grid
+//! position makes up the whole frame. The bridge keeps them in a
+//! [`FrameCache`] to bring clients that connect later up to date.
+
+use std::collections::{BTreeMap, HashMap};
+
+use log::warn;
+use serval_protocol::{Event, FrameTile, TabId};
+
+/// Edge length of a tile, in device pixels. Tiles at the right and bottom
+/// edges of a frame are smaller.
+pub const TILE_SIZE: u32 = 128;
+
+/// Turns a tab's successive frames into `frame` events carrying the damaged
+/// tiles.
+#[derive(Debug, Default)]
+pub struct FrameEncoder {
+    width: u32,
+    height: u32,
+    /// The previous frame, which tiles are compared against.
+    pixels: Vec<u8>,
+}
+
+impl FrameEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encodes a `width` × `height` frame of tightly packed RGBA `pixels`.
+    /// Returns `None` when nothing changed since the previous frame.
+    pub fn encode(
+        &mut self,
+        tab_id: TabId,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Option<Event> {
+        if pixels.len() != width as usize * height as usize * 4 {
+            warn!(
+                "{tab_id}: dropping a {width}×{height} frame of {} bytes",
+                pixels.len()
+            );
+            return None;
+        }
+
+        let full = width != self.width || height != self.height || self.pixels.is_empty();
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(TILE_SIZE as usize) {
+            for x in (0..width).step_by(TILE_SIZE as usize) {
+                let tile = Rect {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(width - x),
+                    height: TILE_SIZE.min(height - y),
+                };
+                if full || tile.differs(width, pixels, &self.pixels) {
+                    match tile.encode(width, pixels) {
+                        Ok(tile) => tiles.push(tile),
+                        Err(error) => warn!("{tab_id}: cannot encode tile: {error}"),
+                    }
+                }
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.pixels.clear();
+        self.pixels.extend_from_slice(pixels);
+
+        if tiles.is_empty() {
+            return None;
+        }
+        Some(Event::Frame {
+            tab_id,
+            width,
+            height,
+            tiles,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    /// The byte range of row `row` of this rectangle in a frame `stride`
+    /// pixels wide.
+    fn row(&self, stride: u32, row: u32) -> std::ops::Range<usize> {
+        let start = ((self.y + row) as usize * stride as usize + self.x as usize) * 4;
+        start..start + self.width as usize * 4
+    }
+
+    fn differs(&self, stride: u32, pixels: &[u8], previous: &[u8]) -> bool {
+        (0..self.height).any(|row| {
+            let range = self.row(stride, row);
+            pixels[range.clone()] != previous[range]
+        })
+    }
+
+    fn encode(&self, stride: u32, pixels: &[u8]) -> Result<FrameTile, png::EncodingError> {
+        let mut data = Vec::with_capacity(self.width as usize * self.height as usize * 4);
+        for row in 0..self.height {
+            data.extend_from_slice(&pixels[self.row(stride, row)]);
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Fast);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+
+        Ok(FrameTile {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            data: png.into(),
+        })
+    }
+}
+
+/// The latest tiles of every tab.
+#[derive(Debug, Default)]
+pub struct FrameCache {
+    tabs: HashMap<TabId, CachedFrame>,
+}
+
+#[derive(Debug)]
+struct CachedFrame {
+    width: u32,
+    height: u32,
+    tiles: BTreeMap<(u32, u32), FrameTile>,
+}
+
+impl FrameCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers the tiles of `event` if it is a frame.
+    pub fn record(&mut self, event: &Event) {
+        let Event::Frame {
+            tab_id,
+            width,
+            height,
+            tiles,
+        } = event
+        else {
+            return;
+        };
+        let frame = self
+            .tabs
+            .entry(tab_id.clone())
+            .or_insert_with(|| CachedFrame {
+                width: *width,
+                height: *height,
+                tiles: BTreeMap::new(),
+            });
+        if (frame.width, frame.height) != (*width, *height) {
+            frame.width = *width;
+            frame.height = *height;
+            frame.tiles.clear();
+        }
+        for tile in tiles {
+            frame.tiles.insert((tile.x, tile.y), tile.clone());
+        }
+    }
+
+    /// Forgets the frames of a closed tab.
+    pub fn remove(&mut self, tab_id: &TabId) {
+        self.tabs.remove(tab_id);
+    }
+
+    /// One `frame` event per tab, covering the whole frame.
+    pub fn snapshot(&self) -> impl Iterator<Item = Event> + '_ {
+        self.tabs.iter().map(|(tab_id, frame)| Event::Frame {
+            tab_id: tab_id.clone(),
+            width: frame.width,
+            height: frame.height,
+            tiles: frame.tiles.values().cloned().collect(),
+        })
+    }
+}
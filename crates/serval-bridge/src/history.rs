@@ -0,0 +1,108 @@
+//! Per-tab joint session history.
+//!
+//! The model mirrors what a browser's back and forward buttons operate on: a
+//! list of entries with a cursor. Same-document navigations (fragment changes
+//! and `history.pushState`) are entries like any other. Engines that track
+//! history themselves, like Servo, [`sync`](SessionHistory::sync) the model
+//! with their own list; others drive it with [`push`](SessionHistory::push)
+//! and [`go`](SessionHistory::go).
+
+use serval_protocol::{Event, HistoryEntry, TabId};
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionHistory {
+    entries: Vec<HistoryEntry>,
+    index: usize,
+}
+
+impl SessionHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A history of `entries` showing the one at `index`, as saved earlier.
+    pub fn restore(entries: Vec<HistoryEntry>, index: usize) -> Self {
+        let index = index.min(entries.len().saturating_sub(1));
+        Self { entries, index }
+    }
+
+    /// The entry the tab is showing.
+    pub fn current(&self) -> Option<&HistoryEntry> {
+        self.entries.get(self.index)
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.index > 0
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.index + 1 < self.entries.len()
+    }
+
+    /// Records a navigation away from the current entry, dropping every entry
+    /// after it.
+    pub fn push(&mut self, url: String) {
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.index + 1);
+        }
+        self.entries.push(HistoryEntry {
+            url,
+            title: String::new(),
+        });
+        self.index = self.entries.len() - 1;
+    }
+
+    /// Replaces the URL of the current entry, e.g. after a redirect or
+    /// `history.replaceState`.
+    pub fn replace(&mut self, url: String) {
+        match self.entries.get_mut(self.index) {
+            Some(entry) => entry.url = url,
+            None => self.push(url),
+        }
+    }
+
+    /// Moves `delta` entries back (negative) or forward (positive) and returns
+    /// the new current entry, or `None` if that would leave the history.
+    pub fn go(&mut self, delta: isize) -> Option<&HistoryEntry> {
+        let index = self
+            .index
+            .checked_add_signed(delta)
+            .filter(|index| *index < self.entries.len())?;
+        self.index = index;
+        self.current()
+    }
+
+    /// Sets the title of the current entry.
+    pub fn set_title(&mut self, title: String) {
+        if let Some(entry) = self.entries.get_mut(self.index) {
+            entry.title = title;
+        }
+    }
+
+    /// Adopts the engine's list of entries and current index, keeping the
+    /// titles of entries whose URL did not change.
+    pub fn sync(&mut self, urls: impl IntoIterator<Item = String>, index: usize) {
+        let mut previous = std::mem::take(&mut self.entries).into_iter();
+        self.entries = urls
+            .into_iter()
+            .map(|url| {
+                let title = previous
+                    .next()
+                    .filter(|entry| entry.url == url)
+                    .map(|entry| entry.title)
+                    .unwrap_or_default();
+                HistoryEntry { url, title }
+            })
+            .collect();
+        self.index = index.min(self.entries.len().saturating_sub(1));
+    }
+
+    /// The `historyChanged` event describing this history.
+    pub fn to_event(&self, tab_id: TabId) -> Event {
+        Event::HistoryChanged {
+            tab_id,
+            entries: self.entries.clone(),
+            index: self.index,
+        }
+    }
+}
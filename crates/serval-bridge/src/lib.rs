@@ -0,0 +1,32 @@
+//! Serval backend bridge.
+//!
+//! The bridge owns a browser [`engine::Engine`] and exposes it to Serval
+//! frontends over the wire protocol defined in [`serval_protocol`]. Commands
+//! from every connected client are funnelled into a single [`Bridge`] loop,
+//! which runs on the engine's thread (Servo is not thread-safe) and
+//! broadcasts the engine's events back to the clients.
+
+pub mod bookmarks;
+mod bridge;
+pub mod checkpoint;
+pub mod discovery;
+pub mod engine;
+pub mod frames;
+pub mod history;
+pub mod navigation;
+pub mod omnibox;
+pub mod outbox;
+pub mod pages;
+pub mod profile;
+pub mod replay;
+pub mod server;
+pub mod session;
+pub mod shot;
+#[cfg(unix)]
+pub mod shutdown;
+pub mod stdio;
+#[cfg(unix)]
+pub mod unix;
+pub mod visits;
+
+pub use bridge::{Bridge, ClientId, Input, Waker};
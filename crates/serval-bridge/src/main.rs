@@ -0,0 +1,605 @@
+//! `serval-bridge`: serves a Servo engine to Serval frontends over WebSocket
+//! or stdio.
+
+use std::ffi::OsString;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use log::{error, info, warn};
+use serval_bridge::bookmarks::{self, BookmarkStore};
+use serval_bridge::checkpoint::SessionFile;
+use serval_bridge::discovery::{self, Discovery};
+use serval_bridge::engine::{Engine, MockEngine, ProcessEngine, Scenario};
+use serval_bridge::omnibox::SearchEngines;
+use serval_bridge::pages::Pages;
+use serval_bridge::server::{self, Access};
+use serval_bridge::stdio::{self, Framing};
+use serval_bridge::visits::{self, VisitStore};
+use serval_bridge::{Bridge, Input};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Address to listen on. The frontend connects to `ws://<listen>`
+    /// (`VITE_SERVO_WEBSOCKET_URL`).
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// The token WebSocket clients must present as `?token=<TOKEN>`. A new
+    /// one is generated for every launch when unset; launchers pass theirs
+    /// through the environment.
+    #[arg(long, env = "SERVAL_BRIDGE_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Write the WebSocket token to this file, readable only by the current
+    /// user, for launchers to pick up.
+    #[arg(long, value_name = "FILE")]
+    token_file: Option<PathBuf>,
+
+    /// Also accept WebSocket connections from pages on this origin, e.g.
+    /// `tauri://localhost`. Pages served from loopback addresses are always
+    /// accepted.
+    #[arg(long = "allow-origin", value_name = "ORIGIN")]
+    allow_origins: Vec<String>,
+
+    /// Width of new webviews, in device pixels.
+    #[arg(long, default_value_t = 1024)]
+    width: u32,
+
+    /// Height of new webviews, in device pixels.
+    #[arg(long, default_value_t = 768)]
+    height: u32,
+
+    /// Run every tab in a content process of its own, so a crashing page only
+    /// takes down its tab.
+    #[arg(long)]
+    process_per_tab: bool,
+
+    /// Seconds a content process may stay unresponsive before it is killed.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    hang_timeout: u64,
+
+    /// Play the scripted scenario in this JSON file instead of running Servo.
+    /// See `scenarios/dev.json`.
+    #[arg(long, value_name = "FILE")]
+    mock: Option<PathBuf>,
+
+    /// Send address bar searches to the engines in this JSON file instead of
+    /// the built-in ones. See `serval_bridge::omnibox::search`.
+    #[arg(long, value_name = "FILE")]
+    search_engines: Option<PathBuf>,
+
+    /// Keep the history of every tab in this SQLite database instead of
+    /// `history.sqlite` in the data directory (`$XDG_DATA_HOME/serval`).
+    #[arg(long, value_name = "FILE", env = "SERVAL_HISTORY_FILE")]
+    history: Option<PathBuf>,
+
+    /// Forget the history when the bridge exits. Implied by `--mock` unless
+    /// `--history` is given.
+    #[arg(long, conflicts_with = "history")]
+    no_history: bool,
+
+    /// Keep bookmarks in this SQLite database instead of `bookmarks.sqlite`
+    /// in the data directory.
+    #[arg(long, value_name = "FILE", env = "SERVAL_BOOKMARKS_FILE")]
+    bookmarks: Option<PathBuf>,
+
+    /// Forget bookmarks when the bridge exits. Implied by `--mock` unless
+    /// `--bookmarks` is given.
+    #[arg(long, conflicts_with = "bookmarks")]
+    no_bookmarks: bool,
+
+    /// Save the open tabs to this file, and restore them from it, instead of
+    /// `session.json` in the data directory.
+    #[arg(long, value_name = "FILE", env = "SERVAL_SESSION_FILE")]
+    session: Option<PathBuf>,
+
+    /// Neither save nor restore the open tabs. Implied by `--mock` unless
+    /// `--session` is given.
+    #[arg(long, conflicts_with = "session")]
+    no_session: bool,
+
+    /// Listen on a Unix domain socket instead of TCP, accepting only
+    /// processes of the same user. Defaults to `bridge.sock` in
+    /// `$XDG_RUNTIME_DIR/serval`.
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH", num_args = 0..=1, conflicts_with = "stdio")]
+    unix: Option<Option<PathBuf>>,
+
+    /// Serve a single client over stdin and stdout instead of listening, for
+    /// shells that spawn the bridge. Logs go to stderr.
+    #[arg(long)]
+    stdio: bool,
+
+    /// How messages are delimited with `--stdio`.
+    #[arg(long, value_enum, default_value_t = Framing::Lines, requires = "stdio")]
+    framing: Framing,
+
+    /// Publish how to reach the bridge in this file instead of `bridge.json`
+    /// in the runtime directory (`$XDG_RUNTIME_DIR/serval`).
+    #[arg(long, value_name = "FILE", env = "SERVAL_DISCOVERY_FILE")]
+    discovery_file: Option<PathBuf>,
+
+    /// Print the discovery file of the running bridge, as JSON, and exit.
+    /// Fails when no bridge runs, unless `--launch` is given.
+    #[arg(long, conflicts_with_all = ["stdio", "content_process"])]
+    discover: bool,
+
+    /// With `--discover`, start a bridge with the other arguments when none
+    /// runs, and print its discovery file once it listens.
+    #[arg(long, requires = "discover")]
+    launch: bool,
+
+    /// Serve a single tab over stdin and stdout. Started by
+    /// `--process-per-tab`.
+    #[arg(long, hide = true, conflicts_with_all = ["stdio", "process_per_tab"])]
+    content_process: bool,
+}
+
+/// How clients reach the bridge.
+enum Transport {
+    WebSocket(TcpListener, Access),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+    Stdio(Framing),
+}
+
+impl Transport {
+    /// How launchers find this transport, unless it is private to the parent.
+    fn discovery(&self) -> Option<Discovery> {
+        match self {
+            Transport::WebSocket(listener, access) => {
+                let addr = listener.local_addr().ok()?;
+                Some(Discovery::new().websocket(addr, access.token()))
+            }
+            #[cfg(unix)]
+            Transport::Unix(listener) => {
+                let addr = listener.local_addr().ok()?;
+                Some(Discovery::new().unix(addr.as_pathname()?.to_owned()))
+            }
+            Transport::Stdio(_) => None,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    if args.content_process {
+        return run(Transport::Stdio(Framing::Lines), &args);
+    }
+    if args.discover {
+        return discover(&args);
+    }
+    if args.stdio {
+        return run(Transport::Stdio(args.framing), &args);
+    }
+    #[cfg(unix)]
+    if let Some(path) = &args.unix {
+        return match bind_unix(path.as_deref()) {
+            Some(listener) => run(Transport::Unix(listener), &args),
+            None => ExitCode::FAILURE,
+        };
+    }
+
+    let listener = match TcpListener::bind(args.listen) {
+        Ok(listener) => listener,
+        Err(error) => {
+            error!("cannot listen on {}: {error}", args.listen);
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some(access) = access(&args) else {
+        return ExitCode::FAILURE;
+    };
+    info!(
+        "listening on ws://{}",
+        listener.local_addr().unwrap_or(args.listen)
+    );
+
+    run(Transport::WebSocket(listener, access), &args)
+}
+
+fn run(transport: Transport, args: &Args) -> ExitCode {
+    let Some((history, visits)) = open_history(args) else {
+        return ExitCode::FAILURE;
+    };
+    if args.process_per_tab {
+        let program = match std::env::current_exe() {
+            Ok(program) => program,
+            Err(error) => {
+                error!("cannot find the serval-bridge executable: {error}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let mut content_args: Vec<OsString> = vec![
+            "--content-process".into(),
+            format!("--width={}", args.width).into(),
+            format!("--height={}", args.height).into(),
+        ];
+        if let Some(scenario) = &args.mock {
+            content_args.push("--mock".into());
+            content_args.push(scenario.into());
+        }
+        if let Some(path) = history {
+            content_args.push("--history".into());
+            content_args.push(path.into());
+        }
+        let hang_timeout = Duration::from_secs(args.hang_timeout);
+        let mock = args.mock.is_some();
+        serve(
+            transport,
+            Bridge::new(|waker| {
+                let engine = ProcessEngine::new(waker, program, content_args, hang_timeout);
+                if mock {
+                    engine
+                        .resolving_hosts()
+                        .describing(MockEngine::CAPABILITIES, None)
+                } else {
+                    describe_servo(engine)
+                }
+            }),
+            visits,
+            args,
+        )
+    } else if let Some(path) = &args.mock {
+        let Some(scenario) = load_scenario(path) else {
+            return ExitCode::FAILURE;
+        };
+        serve(
+            transport,
+            Bridge::new(|waker| {
+                MockEngine::new(waker, scenario, args.width, args.height)
+                    .pages(Pages::new(visits.clone()))
+            }),
+            visits,
+            args,
+        )
+    } else {
+        run_servo(transport, visits, args)
+    }
+}
+
+/// Opens the history database, returning where it is unless it is in
+/// memory.
+fn open_history(args: &Args) -> Option<(Option<PathBuf>, Arc<Mutex<VisitStore>>)> {
+    let path = match &args.history {
+        Some(path) => Some(path.clone()),
+        // Content processes are told where their supervisor's history is.
+        None if args.no_history || args.mock.is_some() || args.content_process => None,
+        None => match visits::default_path() {
+            Ok(path) => Some(path),
+            Err(error) => {
+                error!("{error}; pass `--history` or `--no-history`");
+                return None;
+            }
+        },
+    };
+    let store = match &path {
+        Some(path) => VisitStore::open(path),
+        None => VisitStore::in_memory(),
+    };
+    match store {
+        Ok(store) => Some((path, Arc::new(Mutex::new(store)))),
+        Err(error) => {
+            match &path {
+                Some(path) => error!("{}: {error}", path.display()),
+                None => error!("{error}"),
+            }
+            None
+        }
+    }
+}
+
+fn serve<E: Engine>(
+    transport: Transport,
+    mut bridge: Bridge<E>,
+    visits: Arc<Mutex<VisitStore>>,
+    args: &Args,
+) -> ExitCode {
+    // Content processes only read history: their supervisor records it.
+    if !args.content_process {
+        bridge = bridge.visits(visits);
+    }
+    let bookmarks = match &args.bookmarks {
+        Some(path) => Some(path.clone()),
+        None if args.no_bookmarks || args.mock.is_some() || args.content_process => None,
+        None => match bookmarks::default_path() {
+            Ok(path) => Some(path),
+            Err(error) => {
+                error!("{error}; pass `--bookmarks` or `--no-bookmarks`");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    if let Some(path) = bookmarks {
+        match BookmarkStore::open(&path) {
+            Ok(store) => bridge = bridge.bookmarks(store),
+            Err(error) => {
+                error!("{}: {error}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let session = match &args.session {
+        Some(path) => Some(path.clone()),
+        None if args.no_session || args.mock.is_some() || args.content_process => None,
+        None => match SessionFile::default_path() {
+            Ok(path) => Some(path),
+            Err(error) => {
+                error!("{error}; pass `--session` or `--no-session`");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    if let Some(path) = session {
+        bridge = bridge.session_file(SessionFile::new(path));
+    }
+    #[cfg(unix)]
+    if let Err(error) = serval_bridge::shutdown::on_termination(bridge.inputs()) {
+        warn!("cannot save the session when asked to exit: {error}");
+    }
+    if let Some(path) = &args.search_engines {
+        match SearchEngines::load(path) {
+            Ok(engines) => bridge = bridge.search_engines(engines),
+            Err(error) => {
+                error!("{}: {error}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    // Published only once the engine is up, so launchers never find a bridge
+    // that is about to fail.
+    if let Some(discovery) = transport.discovery() {
+        publish(args, discovery);
+    }
+    match transport {
+        Transport::WebSocket(listener, access) => {
+            server::serve(listener, bridge.inputs(), access);
+        }
+        #[cfg(unix)]
+        Transport::Unix(listener) => {
+            serval_bridge::unix::serve(listener, bridge.inputs());
+        }
+        Transport::Stdio(framing) => {
+            let inputs = bridge.inputs();
+            let transport = stdio::serve(inputs.clone(), framing);
+            // Closing stdin shuts the bridge down, which is also how the
+            // supervisor ends content processes.
+            std::thread::spawn(move || {
+                let _ = transport.join();
+                let _ = inputs.send(Input::Shutdown);
+            });
+        }
+    }
+    bridge.run()
+}
+
+/// The access policy for WebSocket clients, with the token written to
+/// `--token-file`.
+fn access(args: &Args) -> Option<Access> {
+    let token = match &args.token {
+        Some(token) if !token.is_empty() => token.clone(),
+        _ => match server::generate_token() {
+            Ok(token) => token,
+            Err(error) => {
+                error!("cannot generate a token: {error}");
+                return None;
+            }
+        },
+    };
+    if let Some(path) = &args.token_file
+        && let Err(error) = write_private(path, &token)
+    {
+        error!("cannot write the token to {}: {error}", path.display());
+        return None;
+    }
+    Some(Access::new(token, args.allow_origins.clone()))
+}
+
+/// Writes `contents` to a file only the current user can read.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// How long `--launch` waits for the bridge it started to listen.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The discovery file, see `--discovery-file`.
+fn discovery_path(args: &Args) -> Option<PathBuf> {
+    if let Some(path) = &args.discovery_file {
+        return Some(path.clone());
+    }
+    match discovery::default_path() {
+        Ok(path) => Some(path),
+        Err(error) => {
+            error!("cannot create the runtime directory: {error}");
+            None
+        }
+    }
+}
+
+/// Publishes `discovery` for launchers. Clients configured by hand still
+/// connect without it, so failing to is not fatal.
+fn publish(args: &Args, discovery: Discovery) {
+    let Some(path) = discovery_path(args) else {
+        return;
+    };
+    match discovery.publish(&path) {
+        Ok(()) => info!("published {}", path.display()),
+        Err(error) => warn!("cannot publish {}: {error}", path.display()),
+    }
+}
+
+/// `--discover`: prints how to reach the running bridge, launching one with
+/// `--launch` when none runs.
+fn discover(args: &Args) -> ExitCode {
+    let Some(path) = discovery_path(args) else {
+        return ExitCode::FAILURE;
+    };
+    let found = match discovery::find(&path) {
+        Ok(found) => found,
+        Err(not_found) if args.launch => {
+            info!("{not_found}; launching one");
+            match launch(&path) {
+                Some(found) => found,
+                None => return ExitCode::FAILURE,
+            }
+        }
+        Err(not_found) => {
+            error!("{not_found}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match serde_json::to_string(&found) {
+        Ok(json) => {
+            println!("{json}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            error!("cannot print {}: {error}", path.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Starts a bridge with the arguments of this run but `--discover` and
+/// `--launch`, and waits for it to publish `path`. It keeps running after
+/// this process exits, logging to the same stderr.
+fn launch(path: &Path) -> Option<Discovery> {
+    let program = match std::env::current_exe() {
+        Ok(program) => program,
+        Err(error) => {
+            error!("cannot find the serval-bridge executable: {error}");
+            return None;
+        }
+    };
+    let bridge_args = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--discover" && arg != "--launch");
+    let mut child = match Command::new(program)
+        .args(bridge_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(error) => {
+            error!("cannot launch a bridge: {error}");
+            return None;
+        }
+    };
+
+    let deadline = Instant::now() + LAUNCH_TIMEOUT;
+    loop {
+        if let Ok(found) = discovery::find(path)
+            && found.pid == child.id()
+        {
+            return Some(found);
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                error!(
+                    "the launched bridge exited before it listened ({status}); see its log above"
+                );
+                return None;
+            }
+            Ok(None) => {}
+            Err(error) => {
+                error!("cannot watch the launched bridge: {error}");
+                return None;
+            }
+        }
+        if Instant::now() >= deadline {
+            error!(
+                "the launched bridge did not listen within {} s",
+                LAUNCH_TIMEOUT.as_secs()
+            );
+            let _ = child.kill();
+            return None;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: Option<&Path>) -> Option<std::os::unix::net::UnixListener> {
+    use serval_bridge::unix;
+
+    let path = match path {
+        Some(path) => path.to_owned(),
+        None => match unix::default_path() {
+            Ok(path) => path,
+            Err(error) => {
+                error!("cannot create the runtime directory: {error}");
+                return None;
+            }
+        },
+    };
+    match unix::bind(&path) {
+        Ok(listener) => {
+            info!("listening on {}", path.display());
+            Some(listener)
+        }
+        Err(error) => {
+            error!("cannot listen on {}: {error}", path.display());
+            None
+        }
+    }
+}
+
+fn load_scenario(path: &Path) -> Option<Scenario> {
+    match Scenario::load(path) {
+        Ok(scenario) => Some(scenario),
+        Err(error) => {
+            error!("{}: {error}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(feature = "servo")]
+fn run_servo(transport: Transport, visits: Arc<Mutex<VisitStore>>, args: &Args) -> ExitCode {
+    use serval_bridge::engine::ServoEngine;
+
+    let pages = Pages::new(visits.clone());
+    serve(
+        transport,
+        Bridge::new(|waker| ServoEngine::serving(waker, args.width, args.height, pages)),
+        visits,
+        args,
+    )
+}
+
+/// Announces the capabilities of Servo content processes.
+#[cfg(feature = "servo")]
+fn describe_servo(engine: ProcessEngine) -> ProcessEngine {
+    use serval_bridge::engine::{SERVO_VERSION, ServoEngine};
+
+    engine.describing(ServoEngine::CAPABILITIES, Some(SERVO_VERSION))
+}
+
+#[cfg(not(feature = "servo"))]
+fn describe_servo(engine: ProcessEngine) -> ProcessEngine {
+    engine
+}
+
+#[cfg(not(feature = "servo"))]
+fn run_servo(_transport: Transport, _visits: Arc<Mutex<VisitStore>>, _args: &Args) -> ExitCode {
+    error!(
+        "serval-bridge was built without an engine; rebuild it with `--features servo` \
+         or play a scenario with `--mock`"
+    );
+    ExitCode::FAILURE
+}
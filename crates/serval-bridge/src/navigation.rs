@@ -0,0 +1,80 @@
+//! Checks a navigation has to pass before it reaches the engine.
+
+use std::io;
+use std::net::ToSocketAddrs;
+
+use serval_protocol::ErrorCode;
+use thiserror::Error;
+use url::{Host, Url};
+
+use crate::pages;
+
+/// Schemes the engine may load. `serval:` pages are the bridge's own, see
+/// [`pages`](crate::pages).
+pub const ALLOWED_SCHEMES: &[&str] = &["http", "https", "file", "data", "about", "serval"];
+
+/// Why a navigation was refused before reaching the engine.
+#[derive(Debug, Error)]
+pub enum NavigationError {
+    #[error("invalid URL `{url}`: {source}")]
+    InvalidUrl {
+        url: String,
+        #[source]
+        source: url::ParseError,
+    },
+    #[error("scheme `{0}` is not allowed")]
+    BlockedScheme(String),
+    #[error("cannot resolve `{host}`: {source}")]
+    DnsFailure {
+        host: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl NavigationError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            NavigationError::InvalidUrl { .. } => ErrorCode::InvalidUrl,
+            NavigationError::BlockedScheme(_) => ErrorCode::BlockedScheme,
+            NavigationError::DnsFailure { .. } => ErrorCode::DnsFailure,
+        }
+    }
+}
+
+/// Parses `url` and checks its scheme is allowed.
+pub fn parse(url: &str) -> Result<Url, NavigationError> {
+    let parsed = Url::parse(url).map_err(|source| NavigationError::InvalidUrl {
+        url: url.to_owned(),
+        source,
+    })?;
+    if !ALLOWED_SCHEMES.contains(&parsed.scheme()) {
+        return Err(NavigationError::BlockedScheme(parsed.scheme().to_owned()));
+    }
+    Ok(parsed)
+}
+
+/// Returns whether [`resolve`] has to look up the host of `url`. The hosts
+/// of `serval:` URLs name pages, not machines.
+pub fn needs_lookup(url: &Url) -> bool {
+    url.scheme() != pages::SCHEME && matches!(url.host(), Some(Host::Domain(_)))
+}
+
+/// Resolves the host of `url`. This blocks, so the bridge calls it off the
+/// engine thread.
+pub fn resolve(url: &Url) -> Result<(), NavigationError> {
+    let Some(Host::Domain(host)) = url.host() else {
+        return Ok(());
+    };
+    let port = url.port_or_known_default().unwrap_or(0);
+    let found = (host, port)
+        .to_socket_addrs()
+        .and_then(|mut addrs| match addrs.next() {
+            Some(_) => Ok(()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        });
+    found.map_err(|source| NavigationError::DnsFailure {
+        host: host.to_owned(),
+        source,
+    })
+}
@@ -0,0 +1,112 @@
+//! URL fixup: tells addresses typed without a scheme apart from searches.
+//!
+//! `example.com` gains `https://`, while `localhost:3000`, `192.168.1.1`,
+//! `[::1]:8080` and `intranet/path` gain `http://`: they are local or name a
+//! port, so unlikely to serve TLS. Dotted names only count as addresses when
+//! the public suffix list knows their suffix, so `node.js` stays a search
+//! but `nodejs.org` does not. Input with a scheme is taken as typed.
+
+use std::sync::OnceLock;
+
+use publicsuffix::{List, Psl};
+use url::{Host, Url};
+
+use crate::navigation::ALLOWED_SCHEMES;
+
+/// What typed text stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fixup {
+    Url(Url),
+    Search(String),
+}
+
+/// Fixes up `input`, or returns `None` when it is blank.
+///
+/// A leading `?` forces a search, so `?example.com` looks for the site
+/// rather than going there.
+pub fn fixup(input: &str) -> Option<Fixup> {
+    let input = input.trim();
+    if let Some(query) = input.strip_prefix('?') {
+        let query = query.trim();
+        return (!query.is_empty()).then(|| Fixup::Search(query.to_owned()));
+    }
+    if input.is_empty() {
+        return None;
+    }
+    if !input.contains(char::is_whitespace)
+        && let Some(url) = address(input)
+    {
+        return Some(Fixup::Url(url));
+    }
+    Some(Fixup::Search(input.to_owned()))
+}
+
+/// `input` as a URL, if it is one.
+fn address(input: &str) -> Option<Url> {
+    if let Some(url) = with_scheme(input) {
+        return Some(url);
+    }
+    if input.starts_with('/') {
+        return Url::from_file_path(input).ok();
+    }
+
+    let url = Url::parse(&format!("http://{input}")).ok()?;
+    // The host as typed: the URL parser also reads `3.14` as an IPv4 address.
+    let authority = input.split(['/', '?', '#']).next().unwrap_or(input);
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    // A path or a port make it an address even on an unknown host.
+    let explicit = authority.len() < input.len() || url.port().is_some();
+    match url.host()? {
+        Host::Ipv6(_) => Some(url),
+        Host::Ipv4(_) => is_dotted_quad(host_port.split(':').next()?).then_some(url),
+        Host::Domain(domain) if is_registrable(domain) && url.port().is_none() => secure(url),
+        Host::Domain(domain) => {
+            let local = domain == "localhost" || domain.ends_with(".localhost");
+            (local || explicit).then_some(url)
+        }
+    }
+}
+
+/// `input` if it starts with a scheme. `localhost:3000` parses as a URL
+/// with the scheme `localhost`, so only known schemes count, or any scheme
+/// followed by `//` to let navigation report schemes it blocks.
+fn with_scheme(input: &str) -> Option<Url> {
+    let url = Url::parse(input).ok()?;
+    let known = ALLOWED_SCHEMES.contains(&url.scheme());
+    let slashes = input[url.scheme().len()..].starts_with("://");
+    (known || slashes).then_some(url)
+}
+
+fn is_dotted_quad(host: &str) -> bool {
+    let parts: Vec<_> = host.split('.').collect();
+    parts.len() == 4
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()))
+}
+
+/// Whether `domain` is a name registered under a public suffix, rather than
+/// a suffix itself or a name under a suffix nobody knows.
+fn is_registrable(domain: &str) -> bool {
+    public_suffixes()
+        .domain(domain.as_bytes())
+        .is_some_and(|domain| domain.suffix().is_known())
+}
+
+fn secure(mut url: Url) -> Option<Url> {
+    url.set_scheme("https").ok()?;
+    Some(url)
+}
+
+/// The public suffix list, from <https://publicsuffix.org/list/>. Refresh
+/// `data/public_suffix_list.dat` from there now and then.
+fn public_suffixes() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        include_str!("../../data/public_suffix_list.dat")
+            .parse()
+            .expect("the bundled public suffix list parses")
+    })
+}
@@ -0,0 +1,63 @@
+//! The address bar: what typed text loads.
+//!
+//! Text is either an address, fixed up into a URL (see [`fixup`]), or a
+//! search, sent to one of the [`SearchEngines`]. Frontends ask with
+//! `resolveInput` rather than guessing themselves, and for [`suggest`]ions
+//! from open tabs and the [`Places`] visited or bookmarked as the text is
+//! typed.
+
+pub mod fixup;
+pub mod places;
+pub mod search;
+pub mod suggest;
+
+use url::Url;
+
+pub use fixup::{Fixup, fixup};
+pub use places::{Page, Places};
+pub use search::{SearchEngine, SearchEngines, SearchEnginesError};
+pub use suggest::{OpenTab, suggest};
+
+/// What typed text loads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub url: Url,
+    /// The engine `url` searches with, when the text was not an address.
+    pub search_engine: Option<String>,
+    /// What `url` searches for, without the engine's keyword.
+    pub terms: Option<String>,
+}
+
+/// Resolves `input` typed in the address bar, or returns `None` when it is
+/// blank or a search the engine cannot make a URL for. A keyword shortcut
+/// like `w servo` searches the engine with that keyword, even when the rest
+/// looks like an address.
+pub fn resolve(input: &str, engines: &SearchEngines) -> Option<Resolved> {
+    let input = input.trim();
+    if let Some((keyword, terms)) = input.split_once(char::is_whitespace)
+        && let Some(engine) = engines.by_keyword(keyword)
+    {
+        let terms = terms.trim();
+        if !terms.is_empty()
+            && let Some(resolved) = search(engine, terms)
+        {
+            return Some(resolved);
+        }
+    }
+    Some(match fixup(input)? {
+        Fixup::Url(url) => Resolved {
+            url,
+            search_engine: None,
+            terms: None,
+        },
+        Fixup::Search(terms) => search(engines.default_engine(), &terms)?,
+    })
+}
+
+fn search(engine: &SearchEngine, terms: &str) -> Option<Resolved> {
+    Some(Resolved {
+        url: engine.search(terms)?,
+        search_engine: Some(engine.name.clone()),
+        terms: Some(terms.to_owned()),
+    })
+}
@@ -0,0 +1,225 @@
+//! The pages the address bar suggests: visited ones, ranked by frecency,
+//! and bookmarked ones.
+//!
+//! Frecency sums a point for every visit to a page, two for visits to typed
+//! addresses, each worth half as much for every [`HALF_LIFE`] since. A page
+//! visited often and lately ranks above one visited often long ago or once
+//! today. As every point decays alike, pages rank the same whenever they are
+//! compared: scores are worked out when a page is visited, not on every
+//! query, so matching a query against many thousand pages only compares
+//! text.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// How long until a visit counts half as much.
+pub const HALF_LIFE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Points a page earns for being bookmarked, as for visits when it was.
+const BOOKMARK_BONUS: f64 = 3.0;
+
+/// How many units of [`Page::frecency`] make a page worth twice as much.
+pub(crate) const SCALE: u64 = 1024;
+
+/// A page the address bar may suggest.
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub url: String,
+    pub title: String,
+    pub visit_count: u32,
+    pub last_visit: Option<SystemTime>,
+    pub bookmarked: bool,
+    /// The visits' points as `log2` of what they were worth at the epoch.
+    visits: f64,
+    /// When the page was bookmarked.
+    bookmarked_at: SystemTime,
+    /// `url` without its scheme and `www.`, and `title`, folded for matching.
+    pub(crate) folded_url: String,
+    pub(crate) folded_title: String,
+}
+
+impl Page {
+    fn new(url: &str, title: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            title: title.to_owned(),
+            visit_count: 0,
+            last_visit: None,
+            bookmarked: false,
+            visits: f64::NEG_INFINITY,
+            bookmarked_at: SystemTime::UNIX_EPOCH,
+            folded_url: fold(strip_url(url)),
+            folded_title: fold(title),
+        }
+    }
+
+    fn set_title(&mut self, title: &str) {
+        if self.title != title {
+            self.title = title.to_owned();
+            self.folded_title = fold(title);
+        }
+    }
+
+    /// How often and how recently the page was visited, on a log scale:
+    /// pages whose frecency is greater by 1024 were visited twice as much, or
+    /// as much one half-life later.
+    pub fn frecency(&self) -> u64 {
+        let mut points = self.visits;
+        if self.bookmarked {
+            points = log2_add(points, points_at(self.bookmarked_at, BOOKMARK_BONUS));
+        }
+        // Saturates to 0 for pages neither visited nor bookmarked.
+        (points * SCALE as f64) as u64
+    }
+}
+
+/// `log2` of `points` earned at `at`, as worth at the epoch.
+fn points_at(at: SystemTime, points: f64) -> f64 {
+    let since = at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    since.as_secs_f64() / HALF_LIFE.as_secs_f64() + points.log2()
+}
+
+/// `log2(2^a + 2^b)`, without working out either power.
+fn log2_add(a: f64, b: f64) -> f64 {
+    let (high, low) = if a > b { (a, b) } else { (b, a) };
+    if low == f64::NEG_INFINITY {
+        return high;
+    }
+    high + (1.0 + (low - high).exp2()).log2()
+}
+
+/// Every page the address bar may suggest, by URL.
+#[derive(Debug, Default)]
+pub struct Places {
+    pages: Vec<Page>,
+    by_url: HashMap<String, usize>,
+}
+
+impl Places {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a visit to `url` at `at`, typed into the address bar or not.
+    pub fn visit(&mut self, url: &str, title: &str, at: SystemTime, typed: bool) {
+        let page = self.page(url);
+        if !title.is_empty() {
+            page.set_title(title);
+        }
+        page.visit_count += 1;
+        page.last_visit = page.last_visit.max(Some(at));
+        let points = if typed { 2.0 } else { 1.0 };
+        page.visits = log2_add(page.visits, points_at(at, points));
+    }
+
+    /// Gives `url` a title that came after its visit was recorded.
+    pub fn retitle(&mut self, url: &str, title: &str) {
+        if let Some(&index) = self.by_url.get(url) {
+            self.pages[index].set_title(title);
+        }
+    }
+
+    /// Marks `url` as bookmarked under `title`, or no longer bookmarked.
+    pub fn bookmark(&mut self, url: &str, title: &str, bookmarked: bool) {
+        if !bookmarked {
+            let Some(&index) = self.by_url.get(url) else {
+                return;
+            };
+            let page = &mut self.pages[index];
+            page.bookmarked = false;
+            if page.visit_count == 0 {
+                self.remove(index);
+            }
+            return;
+        }
+        let page = self.page(url);
+        if !page.bookmarked {
+            page.bookmarked = true;
+            page.bookmarked_at = SystemTime::now();
+        }
+        if !title.is_empty() {
+            page.set_title(title);
+        }
+    }
+
+    /// Forgets every visit to `url`; bookmarked pages stay suggested.
+    pub fn forget(&mut self, url: &str) {
+        let Some(&index) = self.by_url.get(url) else {
+            return;
+        };
+        let page = &mut self.pages[index];
+        page.visit_count = 0;
+        page.last_visit = None;
+        page.visits = f64::NEG_INFINITY;
+        if !page.bookmarked {
+            self.remove(index);
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<&Page> {
+        self.by_url.get(url).map(|&index| &self.pages[index])
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Page> {
+        self.pages.iter()
+    }
+
+    /// The page `iter` yields at `index`.
+    pub(crate) fn at(&self, index: usize) -> &Page {
+        &self.pages[index]
+    }
+
+    fn remove(&mut self, index: usize) {
+        let page = self.pages.swap_remove(index);
+        self.by_url.remove(&page.url);
+        if let Some(moved) = self.pages.get(index) {
+            self.by_url.insert(moved.url.clone(), index);
+        }
+    }
+
+    fn page(&mut self, url: &str) -> &mut Page {
+        let index = *self.by_url.entry(url.to_owned()).or_insert_with(|| {
+            self.pages.push(Page::new(url, ""));
+            self.pages.len() - 1
+        });
+        &mut self.pages[index]
+    }
+}
+
+/// Whether visits to `url` are worth suggesting again: `about:` and `data:`
+/// URLs are not.
+pub fn is_remembered(url: &str) -> bool {
+    ["http://", "https://", "file://"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// `text` in lower case, one character for each of `text`'s, so positions
+/// in both line up.
+pub(crate) fn fold(text: &str) -> String {
+    text.chars().map(fold_char).collect()
+}
+
+pub(crate) fn fold_char(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+/// What of `url` is worth matching: not its scheme, nor a leading `www.`.
+pub(crate) fn strip_url(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.strip_prefix("www.").unwrap_or(rest)
+}
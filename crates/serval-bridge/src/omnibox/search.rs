@@ -0,0 +1,162 @@
+//! Search engines the address bar sends searches to.
+//!
+//! A JSON file passed with `--search-engines` replaces the built-in list:
+//!
+//! ```json
+//! {
+//!   "default": "DuckDuckGo",
+//!   "engines": [
+//!     { "name": "DuckDuckGo", "keyword": "d", "url": "https://duckduckgo.com/?q=%s" },
+//!     { "name": "Wikipedia", "keyword": "w", "url": "https://en.wikipedia.org/w/index.php?search=%s" }
+//!   ]
+//! }
+//! ```
+//!
+//! `%s` in `url` stands for the search terms, anywhere after the host. Text
+//! starting with an engine's `keyword` and a space searches that engine for
+//! the rest, like `w servo`; everything else goes to the `default` engine,
+//! the first one when unset.
+
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+use thiserror::Error;
+use url::{Position, Url, form_urlencoded};
+
+/// Where search terms go in an engine's URL.
+const TERMS: &str = "%s";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SearchEngine {
+    pub name: String,
+    #[serde(default)]
+    pub keyword: Option<String>,
+    /// The URL of the results, with `%s` for the search terms.
+    pub url: String,
+}
+
+impl SearchEngine {
+    pub fn new(name: &str, keyword: Option<&str>, url: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            keyword: keyword.map(str::to_owned),
+            url: url.to_owned(),
+        }
+    }
+
+    /// The URL of the results for `terms`, or `None` when the engine's URL
+    /// is not a valid template, which [`SearchEngines`] rules out.
+    pub fn search(&self, terms: &str) -> Option<Url> {
+        let terms: String = form_urlencoded::byte_serialize(terms.as_bytes()).collect();
+        Url::parse(&self.url.replace(TERMS, &terms)).ok()
+    }
+
+    /// Whether `url` makes a URL for any terms: it has `%s`, and only after
+    /// the host, where encoded terms are always valid.
+    fn is_valid(&self) -> bool {
+        const MARKER: &str = "serval-search-terms";
+        self.url.contains(TERMS)
+            && Url::parse(&self.url.replace(TERMS, MARKER))
+                .is_ok_and(|url| !url[..Position::BeforePath].contains(MARKER))
+    }
+}
+
+/// The search engines, the default one first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchEngines(Vec<SearchEngine>);
+
+impl SearchEngines {
+    /// Takes `engines`, checking their URLs. The first one is the default.
+    pub fn new(engines: Vec<SearchEngine>) -> Result<Self, SearchEnginesError> {
+        if engines.is_empty() {
+            return Err(SearchEnginesError::Empty);
+        }
+        for engine in &engines {
+            if !engine.is_valid() {
+                return Err(SearchEnginesError::Url {
+                    name: engine.name.clone(),
+                    url: engine.url.clone(),
+                });
+            }
+        }
+        Ok(Self(engines))
+    }
+
+    /// Reads search engines from a JSON file, see the [module docs](self).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SearchEnginesError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SearchEnginesError> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            default: Option<String>,
+            engines: Vec<SearchEngine>,
+        }
+
+        let File {
+            default,
+            mut engines,
+        } = serde_json::from_str(json)?;
+        if let Some(default) = default {
+            let Some(index) = engines.iter().position(|engine| engine.name == default) else {
+                return Err(SearchEnginesError::UnknownDefault(default));
+            };
+            let engine = engines.remove(index);
+            engines.insert(0, engine);
+        }
+        Self::new(engines)
+    }
+
+    /// The engine searches go to unless a keyword picks another.
+    pub fn default_engine(&self) -> &SearchEngine {
+        &self.0[0]
+    }
+
+    /// The engine whose keyword is `keyword`, ignoring case.
+    pub fn by_keyword(&self, keyword: &str) -> Option<&SearchEngine> {
+        self.0.iter().find(|engine| {
+            engine
+                .keyword
+                .as_deref()
+                .is_some_and(|own| own.eq_ignore_ascii_case(keyword))
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SearchEngine> {
+        self.0.iter()
+    }
+}
+
+impl Default for SearchEngines {
+    fn default() -> Self {
+        Self(vec![
+            SearchEngine::new("Google", Some("g"), "https://www.google.com/search?q=%s"),
+            SearchEngine::new("DuckDuckGo", Some("d"), "https://duckduckgo.com/?q=%s"),
+            SearchEngine::new("Bing", Some("b"), "https://www.bing.com/search?q=%s"),
+            SearchEngine::new(
+                "Wikipedia",
+                Some("w"),
+                "https://en.wikipedia.org/w/index.php?search=%s",
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SearchEnginesError {
+    #[error("cannot read search engines: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid search engines: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid search engines: the list is empty")]
+    Empty,
+    #[error(
+        "invalid search engines: `{name}` has a URL without `%s` after the host or an invalid one: `{url}`"
+    )]
+    Url { name: String, url: String },
+    #[error("invalid search engines: the default `{0}` is not in the list")]
+    UnknownDefault(String),
+}
@@ -0,0 +1,229 @@
+//! Address bar suggestions for text typed so far.
+//!
+//! The first suggestion is what pressing Enter loads (see
+//! [`resolve`](super::resolve)). Search engines whose keyword or name start
+//! with a lone word come next, then open tabs, then bookmarked and visited
+//! pages by frecency. A page matches when every typed word is found in its
+//! URL or title, ignoring case; pages whose address starts with the first
+//! word rank higher, like `git` for `github.com`.
+//!
+//! Suggestions are worked out on every keystroke, so ranking keeps only the
+//! best `limit` pages while it scans and highlights are found for those
+//! alone.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+use serval_protocol::{Highlight, Suggestion, SuggestionKind, TabId};
+
+use super::places::{self, Places, fold, fold_char, strip_url};
+use super::{SearchEngines, resolve};
+
+/// How many suggestions `suggest` sends when the client does not say.
+pub const DEFAULT_LIMIT: usize = 8;
+
+/// How many suggestions `suggest` sends at most.
+pub const MAX_LIMIT: usize = 50;
+
+/// An open tab the address bar may switch to.
+#[derive(Debug, Clone, Copy)]
+pub struct OpenTab<'a> {
+    pub tab_id: &'a TabId,
+    pub url: &'a str,
+    pub title: &'a str,
+}
+
+/// Suggests up to `limit` destinations for `text`, best first. Blank text has
+/// none.
+pub fn suggest(
+    text: &str,
+    limit: usize,
+    places: &Places,
+    tabs: &[OpenTab],
+    engines: &SearchEngines,
+) -> Vec<Suggestion> {
+    let limit = limit.clamp(1, MAX_LIMIT);
+    let Some(resolved) = resolve(text, engines) else {
+        return Vec::new();
+    };
+    let folded = fold(text.trim());
+    let terms = Terms::new(&folded);
+    let words = &terms.words[..];
+
+    let mut suggestions = vec![match (resolved.search_engine, &resolved.terms) {
+        (Some(engine), Some(search)) => Suggestion {
+            search_engine: Some(engine),
+            ..suggestion(SuggestionKind::Search, resolved.url.as_str(), search)
+        },
+        _ => suggestion(
+            SuggestionKind::Url,
+            resolved.url.as_str(),
+            resolved.url.as_str(),
+        ),
+    }];
+    let mut seen: HashSet<&str> = HashSet::from([resolved.url.as_str()]);
+
+    if let [word] = *words {
+        for engine in engines.iter() {
+            let keyword = engine
+                .keyword
+                .as_deref()
+                .filter(|keyword| fold(keyword).starts_with(word));
+            if keyword.is_none() && !fold(&engine.name).starts_with(word) {
+                continue;
+            }
+            let Some(url) = engine.search("") else {
+                continue;
+            };
+            suggestions.push(Suggestion {
+                search_engine: Some(engine.name.clone()),
+                keyword: engine.keyword.clone(),
+                ..suggestion(SuggestionKind::Keyword, url.as_str(), &engine.name)
+            });
+        }
+    }
+
+    for tab in tabs {
+        let url = fold(strip_url(tab.url));
+        if places::is_remembered(tab.url)
+            && !seen.contains(tab.url)
+            && terms.matches(&url, &fold(tab.title)).is_some()
+        {
+            seen.insert(tab.url);
+            suggestions.push(Suggestion {
+                tab_id: Some(tab.tab_id.clone()),
+                ..suggestion(SuggestionKind::OpenTab, tab.url, tab.title)
+            });
+        }
+    }
+
+    // The best pages so far, worst on top to be pushed out first.
+    let mut best = BinaryHeap::with_capacity(limit + 1);
+    for (index, page) in places.iter().enumerate() {
+        let Some(boost) = terms.matches(&page.folded_url, &page.folded_title) else {
+            continue;
+        };
+        if seen.contains(page.url.as_str()) {
+            continue;
+        }
+        let score = page.frecency() + boost;
+        best.push(Reverse((score, Reverse(index))));
+        if best.len() > limit {
+            best.pop();
+        }
+    }
+    for Reverse((_, Reverse(index))) in best.into_sorted_vec() {
+        let page = places.at(index);
+        let kind = if page.bookmarked {
+            SuggestionKind::Bookmark
+        } else {
+            SuggestionKind::History
+        };
+        suggestions.push(suggestion(kind, &page.url, &page.title));
+    }
+
+    suggestions.truncate(limit);
+    for suggestion in &mut suggestions {
+        if suggestion.kind != SuggestionKind::Url {
+            suggestion.url_highlights = highlights(&suggestion.url, words);
+        }
+        suggestion.title_highlights = highlights(&suggestion.title, words);
+    }
+    suggestions
+}
+
+fn suggestion(kind: SuggestionKind, url: &str, title: &str) -> Suggestion {
+    Suggestion {
+        kind,
+        url: url.to_owned(),
+        title: title.to_owned(),
+        url_highlights: Vec::new(),
+        title_highlights: Vec::new(),
+        tab_id: None,
+        search_engine: None,
+        keyword: None,
+    }
+}
+
+/// The words of typed text, folded.
+struct Terms<'a> {
+    words: Vec<&'a str>,
+    /// The first word after a space, to find it at the start of a word.
+    spaced: String,
+}
+
+impl<'a> Terms<'a> {
+    fn new(folded: &'a str) -> Self {
+        let mut words: Vec<&str> = folded.split_whitespace().collect();
+        words.dedup();
+        let spaced = words
+            .first()
+            .map(|word| format!(" {word}"))
+            .unwrap_or_default();
+        Self { words, spaced }
+    }
+
+    /// Whether every word is found in `url` or `title`, both folded, and if
+    /// so how much to add to the page's frecency: pages whose address starts
+    /// with the first word rank as if visited four times as much, those with
+    /// a title word that does twice.
+    fn matches(&self, url: &str, title: &str) -> Option<u64> {
+        if !self
+            .words
+            .iter()
+            .all(|word| url.contains(word) || title.contains(word))
+        {
+            return None;
+        }
+        let first = self.words.first()?;
+        if url.starts_with(first) {
+            Some(2 * places::SCALE)
+        } else if title.starts_with(first) || title.contains(&self.spaced) {
+            Some(places::SCALE)
+        } else {
+            Some(0)
+        }
+    }
+}
+
+/// Where the words are found in `text`, ignoring case, merged into ranges.
+fn highlights(text: &str, words: &[&str]) -> Vec<Highlight> {
+    let chars: Vec<char> = text.chars().map(fold_char).collect();
+    let mut marked = vec![false; chars.len()];
+    for word in words {
+        let word: Vec<char> = word.chars().collect();
+        if word.is_empty() || word.len() > chars.len() {
+            continue;
+        }
+        for start in 0..=chars.len() - word.len() {
+            if chars[start..start + word.len()] == word[..] {
+                marked[start..start + word.len()].fill(true);
+            }
+        }
+    }
+
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+    for (c, marked) in text.chars().zip(marked) {
+        match (marked, start) {
+            (true, None) => start = Some(offset),
+            (false, Some(from)) => {
+                ranges.push(Highlight {
+                    start: from,
+                    end: offset,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+        offset += c.len_utf16() as u32;
+    }
+    if let Some(from) = start {
+        ranges.push(Highlight {
+            start: from,
+            end: offset,
+        });
+    }
+    ranges
+}
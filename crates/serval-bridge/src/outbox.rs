@@ -0,0 +1,463 @@
+//! Flow control between the bridge loop and each client.
+//!
+//! Every client gets an [`Outbox`]: the bridge puts events into it and the
+//! client's transport takes them out as fast as it can write them. A page
+//! that animates or retitles itself many times a second can produce events
+//! faster than a slow client reads them, so the outbox pushes back instead of
+//! growing without limit:
+//!
+//! - A `frame` for a tab replaces the frame of the same tab still waiting to
+//!   be written; the tiles of both are merged, so the intermediate frame is
+//!   dropped without losing any part of the picture.
+//! - A `titleChange` or `historyChanged` replaces the one of the same tab
+//!   still waiting, and a `tabsSnapshot` any snapshot still waiting: only the
+//!   latest state matters.
+//! - Clients that acknowledge frames (`ackFrames` in `ready`) have at most
+//!   [`FlowLimits::frame_window`] unacknowledged frames per tab. Newer frames
+//!   wait, merged into one, until the client sends `frameShown`.
+//! - A client with more than [`FlowLimits::capacity`] events waiting is too
+//!   far behind to catch up. Its outbox closes, which ends the connection;
+//!   the client reconnects and resumes from what it last saw.
+//!
+//! [`FlowCounters`] count the frames dropped and the events coalesced.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serval_protocol::{Envelope, Event, TabId};
+use thiserror::Error;
+
+/// How many events may wait for a client by default.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// How many unacknowledged frames per tab a client acknowledging frames gets
+/// by default: one on screen and one on the way.
+pub const DEFAULT_FRAME_WINDOW: usize = 2;
+
+/// How far a client may fall behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowLimits {
+    /// Events waiting to be written, after coalescing, before the client is
+    /// disconnected.
+    pub capacity: usize,
+    /// Frames per tab sent but not acknowledged before newer ones wait.
+    pub frame_window: usize,
+}
+
+impl Default for FlowLimits {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            frame_window: DEFAULT_FRAME_WINDOW,
+        }
+    }
+}
+
+/// Counts what flow control saved a client from, or all clients.
+#[derive(Debug, Default)]
+pub struct FlowCounters {
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl FlowCounters {
+    /// Frames merged into a later one before they were written, and events
+    /// discarded when a client fell too far behind.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Events replaced by a later one of the same kind before they were
+    /// written.
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for FlowCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} dropped, {} coalesced",
+            self.dropped(),
+            self.coalesced()
+        )
+    }
+}
+
+/// Why an outbox takes no more events.
+#[derive(Debug, Error)]
+pub enum Closed {
+    #[error("the client is gone")]
+    Gone,
+    /// The client fell more than [`FlowLimits::capacity`] events behind; its
+    /// connection ends.
+    #[error("the client fell too far behind ({0})")]
+    Behind(Arc<FlowCounters>),
+}
+
+/// Creates the outbox of a client: the bridge's end, and the end its
+/// transport writes from.
+pub fn channel() -> (Outbox, Outgoing) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            events: VecDeque::new(),
+            limits: FlowLimits::default(),
+            acking: false,
+            in_flight: HashMap::new(),
+            held: HashMap::new(),
+            senders: 1,
+            closed: false,
+            counters: Arc::default(),
+            total: None,
+        }),
+        ready: Condvar::new(),
+    });
+    (
+        Outbox {
+            shared: shared.clone(),
+        },
+        Outgoing { shared },
+    )
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Signalled when an event is queued or the outbox closes.
+    ready: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+struct Queue {
+    events: VecDeque<Envelope>,
+    limits: FlowLimits,
+    /// Whether the client acknowledges frames.
+    acking: bool,
+    /// Frames per tab queued or written but not acknowledged.
+    in_flight: HashMap<TabId, usize>,
+    /// The frame per tab waiting for the client to acknowledge one.
+    held: HashMap<TabId, Event>,
+    senders: usize,
+    /// Set when the client went away or fell too far behind.
+    closed: bool,
+    counters: Arc<FlowCounters>,
+    /// The bridge-wide counters, once the bridge attached them.
+    total: Option<Arc<FlowCounters>>,
+}
+
+impl Queue {
+    fn count(&self, counter: impl Fn(&FlowCounters) -> &AtomicU64, n: u64) {
+        counter(&self.counters).fetch_add(n, Ordering::Relaxed);
+        if let Some(total) = &self.total {
+            counter(total).fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    fn push(&mut self, envelope: Envelope) {
+        if let Event::Frame { tab_id, .. } = &envelope.event {
+            let tab_id = tab_id.clone();
+            self.push_frame(tab_id, envelope.event);
+            return;
+        }
+        if let Some(index) = self
+            .events
+            .iter()
+            .position(|queued| supersedes(&envelope.event, &queued.event))
+        {
+            self.events.remove(index);
+            self.count(|counters| &counters.coalesced, 1);
+        }
+        self.events.push_back(envelope);
+        if self.events.len() > self.limits.capacity {
+            self.overflow();
+        }
+    }
+
+    fn push_frame(&mut self, tab_id: TabId, frame: Event) {
+        let queued = self
+            .events
+            .iter()
+            .position(|queued| is_frame_of(&queued.event, &tab_id));
+        if let Some(index) = queued {
+            let older = self.events.remove(index).expect("the index was found");
+            self.count(|counters| &counters.dropped, 1);
+            self.events.push_back(merge(older.event, frame).into());
+            return;
+        }
+
+        let in_flight = self.in_flight.get(&tab_id).copied().unwrap_or(0);
+        if self.acking && in_flight >= self.limits.frame_window {
+            let frame = match self.held.remove(&tab_id) {
+                Some(older) => {
+                    self.count(|counters| &counters.dropped, 1);
+                    merge(older, frame)
+                }
+                None => frame,
+            };
+            self.held.insert(tab_id, frame);
+            return;
+        }
+        if self.acking {
+            self.in_flight.insert(tab_id, in_flight + 1);
+        }
+        self.events.push_back(frame.into());
+        if self.events.len() > self.limits.capacity {
+            self.overflow();
+        }
+    }
+
+    /// Sends the frame held back for `tab_id`, if the window allows.
+    fn release(&mut self, tab_id: &TabId) {
+        let in_flight = self.in_flight.get(tab_id).copied().unwrap_or(0);
+        if (!self.acking || in_flight < self.limits.frame_window)
+            && let Some(frame) = self.held.remove(tab_id)
+        {
+            self.push_frame(tab_id.clone(), frame);
+        }
+    }
+
+    fn overflow(&mut self) {
+        let discarded = self.events.len() + self.held.len();
+        self.count(|counters| &counters.dropped, discarded as u64);
+        self.events.clear();
+        self.held.clear();
+        self.closed = true;
+    }
+}
+
+/// Whether `new` makes `old` pointless to send.
+fn supersedes(new: &Event, old: &Event) -> bool {
+    match (new, old) {
+        (Event::TitleChange { tab_id: new, .. }, Event::TitleChange { tab_id: old, .. })
+        | (Event::HistoryChanged { tab_id: new, .. }, Event::HistoryChanged { tab_id: old, .. }) => {
+            new == old
+        }
+        (Event::TabsSnapshot { .. }, Event::TabsSnapshot { .. }) => true,
+        _ => false,
+    }
+}
+
+fn is_frame_of(event: &Event, tab: &TabId) -> bool {
+    matches!(event, Event::Frame { tab_id, .. } if tab_id == tab)
+}
+
+/// One frame with the tiles of `newer` over those of `older`. A frame of
+/// another size carries every tile, so it replaces `older` outright.
+fn merge(older: Event, newer: Event) -> Event {
+    let (
+        Event::Frame {
+            width: old_width,
+            height: old_height,
+            tiles: old_tiles,
+            ..
+        },
+        Event::Frame {
+            tab_id,
+            width,
+            height,
+            tiles,
+        },
+    ) = (older, newer)
+    else {
+        unreachable!("only frames are merged")
+    };
+    if (old_width, old_height) != (width, height) {
+        return Event::Frame {
+            tab_id,
+            width,
+            height,
+            tiles,
+        };
+    }
+    let mut merged: BTreeMap<_, _> = old_tiles
+        .into_iter()
+        .map(|tile| ((tile.x, tile.y), tile))
+        .collect();
+    merged.extend(tiles.into_iter().map(|tile| ((tile.x, tile.y), tile)));
+    Event::Frame {
+        tab_id,
+        width,
+        height,
+        tiles: merged.into_values().collect(),
+    }
+}
+
+/// The bridge's end of a client's outbox.
+pub struct Outbox {
+    shared: Arc<Shared>,
+}
+
+impl Outbox {
+    /// Queues `envelope`, coalescing it with what is still waiting. Fails
+    /// once the client is gone or too far behind.
+    pub fn send(&self, envelope: impl Into<Envelope>) -> Result<(), Closed> {
+        let mut queue = self.shared.lock();
+        if queue.closed {
+            return Err(Closed::Gone);
+        }
+        queue.push(envelope.into());
+        let closed = queue.closed;
+        let counters = queue.counters.clone();
+        drop(queue);
+        self.shared.ready.notify_one();
+        if closed {
+            Err(Closed::Behind(counters))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Applies the bridge's limits and counts into its counters too.
+    pub fn attach(&self, limits: FlowLimits, total: Arc<FlowCounters>) {
+        let mut queue = self.shared.lock();
+        queue.limits = limits;
+        queue.total = Some(total);
+    }
+
+    /// Whether the client acknowledges frames with `frameShown`. Frames held
+    /// back go out when it stops.
+    pub fn ack_frames(&self, acking: bool) {
+        let mut queue = self.shared.lock();
+        queue.acking = acking;
+        queue.in_flight.clear();
+        let held: Vec<_> = queue.held.keys().cloned().collect();
+        for tab_id in held {
+            queue.release(&tab_id);
+        }
+        drop(queue);
+        self.shared.ready.notify_one();
+    }
+
+    /// The client showed the oldest frame of `tab_id` it had not
+    /// acknowledged.
+    pub fn frame_shown(&self, tab_id: &TabId) {
+        let mut queue = self.shared.lock();
+        if let Some(in_flight) = queue.in_flight.get_mut(tab_id) {
+            *in_flight = in_flight.saturating_sub(1);
+        }
+        queue.release(tab_id);
+        drop(queue);
+        self.shared.ready.notify_one();
+    }
+
+    /// Forgets the frames of a closed tab.
+    pub fn forget(&self, tab_id: &TabId) {
+        let mut queue = self.shared.lock();
+        queue.in_flight.remove(tab_id);
+        queue.held.remove(tab_id);
+    }
+
+    /// What flow control did for this client so far.
+    pub fn counters(&self) -> Arc<FlowCounters> {
+        self.shared.lock().counters.clone()
+    }
+}
+
+impl Clone for Outbox {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Outbox {
+    fn drop(&mut self) {
+        self.shared.lock().senders -= 1;
+        self.shared.ready.notify_one();
+    }
+}
+
+/// The transport's end of a client's outbox. Iterating blocks for the next
+/// event and ends once the outbox closes.
+pub struct Outgoing {
+    shared: Arc<Shared>,
+}
+
+impl Outgoing {
+    /// Waits for the next event, or returns `None` once the outbox closed.
+    pub fn recv(&self) -> Option<Envelope> {
+        self.recv_timeout(Duration::MAX).ok()
+    }
+
+    /// Waits up to `timeout` for the next event.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Envelope, RecvTimeoutError> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut queue = self.shared.lock();
+        loop {
+            match take(&mut queue) {
+                Ok(envelope) => return Ok(envelope),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+            queue = match deadline {
+                None => self
+                    .shared
+                    .ready
+                    .wait(queue)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    self.shared
+                        .ready
+                        .wait_timeout(queue, left)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+
+    /// Takes the next event if there is one.
+    pub fn try_recv(&self) -> Result<Envelope, TryRecvError> {
+        take(&mut self.shared.lock())
+    }
+
+    /// What flow control did for this client so far.
+    pub fn counters(&self) -> Arc<FlowCounters> {
+        self.shared.lock().counters.clone()
+    }
+}
+
+fn take(queue: &mut Queue) -> Result<Envelope, TryRecvError> {
+    if queue.closed {
+        return Err(TryRecvError::Disconnected);
+    }
+    match queue.events.pop_front() {
+        Some(envelope) => Ok(envelope),
+        None if queue.senders == 0 => Err(TryRecvError::Disconnected),
+        None => Err(TryRecvError::Empty),
+    }
+}
+
+impl Iterator for Outgoing {
+    type Item = Envelope;
+
+    fn next(&mut self) -> Option<Envelope> {
+        self.recv()
+    }
+}
+
+impl Drop for Outgoing {
+    fn drop(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.closed = true;
+        queue.events.clear();
+        queue.held.clear();
+    }
+}
@@ -0,0 +1,194 @@
+//! Pages the bridge serves itself, under `serval:`.
+//!
+//! Engines load `serval:` URLs from [`Pages`] rather than the network:
+//!
+//! - `serval://history` lists the [`visits`](crate::visits) of every tab,
+//!   newest first, 100 at a time. `?q=` searches them like `queryHistory`
+//!   and `?before=` pages back, in milliseconds since the Unix epoch.
+//!
+//! Every other `serval:` URL is a page saying it does not exist.
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serval_protocol::{Transition, Visit};
+use url::{Url, form_urlencoded};
+
+use crate::visits::{self, Query, VisitStore};
+
+/// The scheme of the pages the bridge serves.
+pub const SCHEME: &str = "serval";
+
+/// The URL of the history page.
+pub const HISTORY_URL: &str = "serval://history";
+
+/// A page to show for a `serval:` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    pub title: String,
+    /// A whole HTML document, UTF-8 encoded.
+    pub html: String,
+}
+
+/// Renders `serval:` pages, from any thread: Servo loads them off the
+/// engine thread.
+#[derive(Clone)]
+pub struct Pages {
+    visits: Arc<Mutex<VisitStore>>,
+}
+
+impl Pages {
+    /// Pages listing the visits recorded in `visits`.
+    pub fn new(visits: Arc<Mutex<VisitStore>>) -> Self {
+        Self { visits }
+    }
+
+    /// The page `url` shows.
+    pub fn render(&self, url: &Url) -> Page {
+        match url.host_str() {
+            Some("history") if url.scheme() == SCHEME => self.history(url),
+            _ => document("Not found", "<p>There is no such page.</p>"),
+        }
+    }
+
+    fn history(&self, url: &Url) -> Page {
+        let mut query = Query::default();
+        for (name, value) in url.query_pairs() {
+            match &*name {
+                "q" => query.text = value.into_owned(),
+                "before" => query.to = value.parse().ok(),
+                _ => {}
+            }
+        }
+        let limit = visits::DEFAULT_LIMIT;
+        let found = self
+            .visits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .query(&query);
+
+        let mut body = format!(
+            "<h1>History</h1>\n<form action=\"{HISTORY_URL}\">\
+             <input type=\"search\" name=\"q\" value=\"{}\" placeholder=\"Search history\" autofocus>\
+             </form>\n",
+            escape(&query.text)
+        );
+        match found {
+            Ok(found) if found.is_empty() => body.push_str("<p>No visits.</p>\n"),
+            Ok(found) => {
+                body.push_str("<ol>\n");
+                for visit in &found {
+                    list_item(&mut body, visit);
+                }
+                body.push_str("</ol>\n");
+                if let Some(last) = found.last().filter(|_| found.len() == limit) {
+                    let older: String = form_urlencoded::Serializer::new(String::new())
+                        .append_pair("q", &query.text)
+                        .append_pair("before", &last.visited_at.to_string())
+                        .finish();
+                    let _ = writeln!(
+                        body,
+                        "<p><a href=\"{HISTORY_URL}?{older}\">Older visits</a></p>"
+                    );
+                }
+            }
+            Err(error) => {
+                let _ = writeln!(
+                    body,
+                    "<p>History is unavailable: {}</p>",
+                    escape(&error.to_string())
+                );
+            }
+        }
+        document("History", &body)
+    }
+}
+
+fn list_item(out: &mut String, visit: &Visit) {
+    let title = if visit.title.is_empty() {
+        &visit.url
+    } else {
+        &visit.title
+    };
+    let how = match visit.transition {
+        Transition::Typed => "typed",
+        Transition::Link => "link",
+        Transition::Reload => "reload",
+        Transition::BackForward => "back/forward",
+    };
+    let _ = writeln!(
+        out,
+        "<li><time datetime=\"{time}\">{time}</time> <a href=\"{url}\">{title}</a> \
+         <span class=\"url\">{url}</span> <span class=\"how\">{how}</span></li>",
+        time = iso_time(visit.visited_at),
+        url = escape(&visit.url),
+        title = escape(title),
+    );
+}
+
+/// A whole HTML document titled `title` around `body`. Times show in the
+/// reader's time zone.
+fn document(title: &str, body: &str) -> Page {
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\n{STYLE}</style>\n</head>\n<body>\n{body}\
+         <script>\n\
+         for (const time of document.querySelectorAll('time')) {{\n\
+         \x20 time.textContent = new Date(time.dateTime).toLocaleString();\n\
+         }}\n\
+         </script>\n</body>\n</html>\n",
+        title = escape(title),
+    );
+    Page {
+        title: title.to_owned(),
+        html,
+    }
+}
+
+const STYLE: &str = "\
+body { font: 14px system-ui, sans-serif; margin: 2em auto; max-width: 60em; padding: 0 1em; }
+input[type=search] { width: 100%; font: inherit; padding: 0.5em; box-sizing: border-box; }
+ol { list-style: none; padding: 0; }
+li { padding: 0.3em 0; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+time, .url, .how { color: #666; font-size: 0.9em; }
+time { display: inline-block; min-width: 12em; }
+";
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `millis` since the Unix epoch as an ISO 8601 UTC time, like
+/// `2024-03-01T12:34:56Z`.
+fn iso_time(millis: u64) -> String {
+    let seconds = millis / 1000;
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
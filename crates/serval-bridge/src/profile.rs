@@ -0,0 +1,40 @@
+//! Where the bridge keeps what outlives it, like the [`visits`](crate::visits)
+//! of every tab.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The directory for the bridge's databases: `$XDG_DATA_HOME/serval`, or
+/// `~/.local/share/serval` without one. The directory is created when
+/// missing.
+#[cfg(unix)]
+pub fn data_dir() -> io::Result<PathBuf> {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(data) if !data.is_empty() => PathBuf::from(data),
+        _ => match std::env::var_os("HOME") {
+            Some(home) if !home.is_empty() => PathBuf::from(home).join(".local/share"),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "neither XDG_DATA_HOME nor HOME is set",
+                ));
+            }
+        },
+    };
+    let dir = base.join("serval");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// The directory for the bridge's databases: `serval` in the user's roaming
+/// application data.
+#[cfg(not(unix))]
+pub fn data_dir() -> io::Result<PathBuf> {
+    let base = std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "APPDATA is not set"))?;
+    let dir = base.join("serval");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
@@ -0,0 +1,85 @@
+//! Replay of the events a reconnecting client missed.
+//!
+//! The bridge numbers the events it broadcasts and keeps the latest ones in
+//! a [`ReplayBuffer`]. A client that lost its connection sends the session id
+//! and the sequence number of the last event it saw in `ready`; when the
+//! buffer still holds every event after it, the bridge replays them, and
+//! otherwise sends the whole tab list instead (see
+//! [`SessionRegistry`](crate::session::SessionRegistry)).
+//!
+//! Frames are not kept: they are large, and every client that connects gets
+//! the latest tiles of every tab anyway.
+
+use std::collections::VecDeque;
+
+use serval_protocol::{Envelope, Event, Seq};
+
+/// How many events the bridge keeps for replay by default.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// The latest sequenced events, up to a capacity.
+#[derive(Debug)]
+pub struct ReplayBuffer {
+    events: VecDeque<(Seq, Event)>,
+    capacity: usize,
+    /// The sequence number of the latest event, kept or not.
+    latest: Seq,
+}
+
+impl Default for ReplayBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+            latest: 0,
+        }
+    }
+
+    /// The sequence number of the latest event, 0 before the first one.
+    pub fn latest(&self) -> Seq {
+        self.latest
+    }
+
+    /// Numbers `event` and keeps it if it is worth replaying.
+    pub fn push(&mut self, event: Event) -> Envelope {
+        if matches!(event, Event::Frame { .. }) {
+            return event.into();
+        }
+        self.latest += 1;
+        if self.capacity > 0 {
+            if self.events.len() == self.capacity {
+                self.events.pop_front();
+            }
+            self.events.push_back((self.latest, event.clone()));
+        }
+        Envelope::sequenced(self.latest, event)
+    }
+
+    /// The events after `seq`, or `None` when some of them were dropped or
+    /// `seq` is from the future.
+    pub fn since(&self, seq: Seq) -> Option<Vec<Envelope>> {
+        if seq > self.latest {
+            return None;
+        }
+        let first = self
+            .events
+            .front()
+            .map_or(self.latest + 1, |(first, _)| *first);
+        if seq + 1 < first {
+            return None;
+        }
+        Some(
+            self.events
+                .iter()
+                .filter(|(kept, _)| *kept > seq)
+                .map(|(kept, event)| Envelope::sequenced(*kept, event.clone()))
+                .collect(),
+        )
+    }
+}
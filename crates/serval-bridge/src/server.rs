@@ -0,0 +1,337 @@
+//! WebSocket transport.
+//!
+//! Every connection gets a thread that decodes commands into [`Input`]s for
+//! the bridge loop, and one that writes the events the loop sends back as
+//! they arrive. A client that stops reading is dropped once a write stalls
+//! for ten seconds.
+//!
+//! One protocol message travels per WebSocket message: a text message in
+//! JSON, or a binary one once the bridge agreed to a binary encoding in
+//! `ready`.
+//!
+//! Browsers let any page open a WebSocket to `localhost`, so the handshake is
+//! checked against an [`Access`] policy: clients must present the bridge's
+//! per-launch token as the `token` query parameter, and browsers may only
+//! connect from pages on allowed origins. Refused handshakes are logged and
+//! answered with `401` or `403`.
+
+use std::fmt;
+use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use log::{error, warn};
+use serval_protocol::{Command, Encoding, Envelope, ErrorCode, Event};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, WebSocket};
+use url::{Host, Url};
+
+use crate::outbox::{self, Outgoing};
+use crate::{ClientId, Input};
+
+/// How long writing one message may take before the client counts as stalled
+/// and is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long reading waits for more of what a client sent while holding the
+/// socket.
+const READ_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// Who may open a connection.
+#[derive(Debug, Clone)]
+pub struct Access {
+    token: String,
+    origins: Vec<String>,
+}
+
+/// Why a handshake was refused.
+#[derive(Debug)]
+enum Refusal {
+    MissingToken,
+    WrongToken,
+    Origin(String),
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::MissingToken => write!(f, "no token"),
+            Refusal::WrongToken => write!(f, "wrong token"),
+            Refusal::Origin(origin) => write!(f, "origin `{origin}` is not allowed"),
+        }
+    }
+}
+
+impl Access {
+    /// Requires `token` from every client. Besides clients that send no
+    /// `Origin` (anything but a browser), pages served from loopback
+    /// addresses, like the Vite dev server, may connect, and so may pages on
+    /// `origins` (e.g. `tauri://localhost`, or `null` for `file:` pages).
+    pub fn new(token: impl Into<String>, origins: Vec<String>) -> Self {
+        Self {
+            token: token.into(),
+            origins: origins
+                .into_iter()
+                .map(|origin| origin.trim_end_matches('/').to_owned())
+                .collect(),
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    fn check(&self, request: &Request) -> Result<(), Refusal> {
+        if let Some(origin) = request.headers().get("origin") {
+            let origin = String::from_utf8_lossy(origin.as_bytes());
+            if !self.allows_origin(&origin) {
+                return Err(Refusal::Origin(origin.into_owned()));
+            }
+        }
+        let token = request.uri().query().and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(name, _)| name == "token")
+                .map(|(_, value)| value)
+        });
+        match token {
+            None => Err(Refusal::MissingToken),
+            Some(token) if !constant_time_eq(token.as_bytes(), self.token.as_bytes()) => {
+                Err(Refusal::WrongToken)
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        if self.origins.iter().any(|allowed| allowed == origin) {
+            return true;
+        }
+        let Ok(url) = Url::parse(origin) else {
+            return false;
+        };
+        let loopback = match url.host() {
+            Some(Host::Domain(domain)) => domain == "localhost",
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        };
+        matches!(url.scheme(), "http" | "https") && loopback
+    }
+}
+
+/// Compares secrets in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Creates a random token for [`Access`], as 64 hex digits.
+pub fn generate_token() -> io::Result<String> {
+    let mut bytes = [0; 32];
+    getrandom::fill(&mut bytes).map_err(io::Error::from)?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Accepts WebSocket clients that pass `access` on `listener` until the
+/// process exits.
+pub fn serve(
+    listener: TcpListener,
+    inputs: Sender<Input>,
+    access: Access,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for (id, stream) in (1..).zip(listener.incoming()) {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    warn!("failed to accept connection: {error}");
+                    continue;
+                }
+            };
+
+            let client = ClientId(id);
+            let inputs = inputs.clone();
+            let access = access.clone();
+            thread::spawn(move || {
+                if let Err(error) = handle_connection(stream, client, &inputs, &access) {
+                    error!("{client}: {error}");
+                }
+                let _ = inputs.send(Input::Disconnected(client));
+            });
+        }
+    })
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    client: ClientId,
+    inputs: &Sender<Input>,
+    access: &Access,
+) -> Result<(), tungstenite::Error> {
+    let peer = stream.peer_addr()?;
+    // tungstenite decides the shape of the callback.
+    #[allow(clippy::result_large_err)]
+    let check = |request: &Request, response: Response| match access.check(request) {
+        Ok(()) => Ok(response),
+        Err(refusal) => {
+            warn!("{client}: refusing connection from {peer}: {refusal}");
+            let status = match refusal {
+                Refusal::Origin(_) => StatusCode::FORBIDDEN,
+                Refusal::MissingToken | Refusal::WrongToken => StatusCode::UNAUTHORIZED,
+            };
+            let mut response = ErrorResponse::new(Some(refusal.to_string()));
+            *response.status_mut() = status;
+            Err(response)
+        }
+    };
+    let socket = match tungstenite::accept_hdr(stream, check) {
+        Ok(socket) => socket,
+        // Refused by `check`, which logged why.
+        Err(tungstenite::HandshakeError::Failure(tungstenite::Error::Http(_))) => return Ok(()),
+        Err(tungstenite::HandshakeError::Failure(error)) => return Err(error),
+        Err(tungstenite::HandshakeError::Interrupted(_)) => {
+            return Err(tungstenite::Error::Io(io::ErrorKind::WouldBlock.into()));
+        }
+    };
+    let stream = socket.get_ref().try_clone()?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let (events, outgoing) = outbox::channel();
+    let replies = events.clone();
+    // Reading may write too, answering pings and closes, so the reader and
+    // the writer take turns on the socket.
+    let socket = Arc::new(Mutex::new(socket));
+    let writer = Arc::clone(&socket);
+    let writer_stream = stream.try_clone()?;
+    let (agreed, encodings) = mpsc::channel();
+    thread::spawn(move || {
+        write_events(outgoing, &writer, client, &agreed);
+        // Ends the reader too when the bridge dropped the client or it
+        // stopped reading.
+        let _ = writer_stream.shutdown(Shutdown::Both);
+    });
+
+    if inputs.send(Input::Connected { client, events }).is_err() {
+        return Ok(());
+    }
+
+    let mut reading = Encoding::Json;
+    // Whether messages read along with a `ready` may still wait in the
+    // socket's buffer.
+    let mut buffered = false;
+    loop {
+        // Waits for the client without holding the socket, then reads what
+        // arrived.
+        if !buffered {
+            stream.set_read_timeout(None)?;
+            if stream.peek(&mut [0])? == 0 {
+                return Ok(());
+            }
+        }
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut socket = lock(&socket);
+        buffered = false;
+        loop {
+            let decoded = match socket.read() {
+                Ok(Message::Text(text)) => serval_protocol::decode_command(&text),
+                Ok(Message::Binary(bytes)) => reading.decode(&bytes),
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(error)) if is_timeout(&error) => break,
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(());
+                }
+                Err(error) => return Err(error),
+            };
+            match decoded {
+                Ok(request) => {
+                    let ready = matches!(request.command, Command::Ready { .. });
+                    if inputs.send(Input::Command { client, request }).is_err() {
+                        return Ok(());
+                    }
+                    if ready {
+                        // The client switched already, to what the bridge
+                        // may refuse: what follows is in the encoding the
+                        // bridge answers with.
+                        drop(socket);
+                        match encodings.recv() {
+                            Ok(encoding) => reading = encoding,
+                            // The writer is gone with the client.
+                            Err(_) => return Ok(()),
+                        }
+                        buffered = true;
+                        break;
+                    }
+                }
+                Err(error) => {
+                    warn!("{client}: rejecting message: {error}");
+                    let reply = Event::Error {
+                        id: error.request_id(),
+                        code: ErrorCode::InvalidMessage,
+                        message: error.to_string(),
+                    };
+                    if replies.send(reply).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes the events for `client` as they arrive, switching to the encoding
+/// announced in `ready` after it and passing it on to `agreed`, until the
+/// bridge drops the client or a write fails or stalls.
+fn write_events(
+    events: Outgoing,
+    socket: &Mutex<WebSocket<TcpStream>>,
+    client: ClientId,
+    agreed: &Sender<Encoding>,
+) {
+    let mut current = Encoding::Json;
+    for event in events {
+        match lock(socket).send(message(current, &event)) {
+            Ok(()) => {}
+            Err(tungstenite::Error::Io(error)) if is_timeout(&error) => {
+                warn!("{client}: dropping client, a write took over {WRITE_TIMEOUT:?}");
+                return;
+            }
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                return;
+            }
+            Err(error) => {
+                error!("{client}: cannot write: {error}");
+                return;
+            }
+        }
+        if let Event::Ready { encoding, .. } = event.event {
+            current = encoding;
+            let _ = agreed.send(encoding);
+        }
+    }
+    // The bridge dropped the client, maybe for falling too far behind.
+    let mut socket = lock(socket);
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}
+
+fn lock(socket: &Mutex<WebSocket<TcpStream>>) -> MutexGuard<'_, WebSocket<TcpStream>> {
+    socket.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn message(encoding: Encoding, event: &Envelope) -> Message {
+    if encoding.is_binary() {
+        Message::binary(encoding.encode(event))
+    } else {
+        Message::text(serval_protocol::encode(event))
+    }
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
@@ -0,0 +1,227 @@
+//! The tabs of the session shared by every connected client.
+//!
+//! Any number of frontends may attach to one bridge, say the main window, a
+//! window on a second monitor and a debugging dashboard. They all receive
+//! every event, and the [`SessionRegistry`] keeps what a client that joins
+//! later needs to catch up: the open tabs, what they show, and which client
+//! controls each of them.
+//!
+//! A tab opens when the engine accepts a `navigate` for it, and the client
+//! that sent it owns the tab. Commands for a tab from other clients are
+//! refused with `notTabOwner` until they take it over with `claimTab`. When
+//! a client leaves, its tabs stay open without an owner, and the next client
+//! that sends one of them a command owns it.
+
+use std::collections::HashMap;
+
+use serval_protocol::{Event, HistoryEntry, TabId, TabSnapshot};
+use thiserror::Error;
+
+use crate::ClientId;
+use crate::checkpoint::SavedTab;
+
+/// The open tabs and their owners.
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    tabs: HashMap<TabId, Tab>,
+    /// Orders tabs by when they were opened.
+    next_order: u64,
+}
+
+#[derive(Debug)]
+struct Tab {
+    order: u64,
+    owner: Option<ClientId>,
+    url: Option<String>,
+    title: Option<String>,
+    entries: Vec<HistoryEntry>,
+    index: usize,
+    loading: bool,
+    crashed: bool,
+}
+
+/// Why a client may not command a tab.
+#[derive(Debug, Error)]
+#[error("tab {tab_id} is controlled by {owner}; send `claimTab` to take it over")]
+pub struct NotTabOwner {
+    pub tab_id: TabId,
+    pub owner: ClientId,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets `client` command `tab_id`, taking the tab when it has no owner.
+    /// Tabs that are not open are left to the engine, which refuses most
+    /// commands for them. Returns whether the tab list changed.
+    pub fn authorize(&mut self, client: ClientId, tab_id: &TabId) -> Result<bool, NotTabOwner> {
+        let Some(tab) = self.tabs.get_mut(tab_id) else {
+            return Ok(false);
+        };
+        match tab.owner {
+            Some(owner) if owner == client => Ok(false),
+            Some(owner) => Err(NotTabOwner {
+                tab_id: tab_id.clone(),
+                owner,
+            }),
+            None => {
+                tab.owner = Some(client);
+                Ok(true)
+            }
+        }
+    }
+
+    /// The client controlling `tab_id`, if it is open and has an owner.
+    pub fn owner(&self, tab_id: &TabId) -> Option<ClientId> {
+        self.tabs.get(tab_id)?.owner
+    }
+
+    /// Opens `tab_id` for `client`, once the engine opened it. Returns
+    /// whether it was not open yet.
+    pub fn open(&mut self, client: ClientId, tab_id: &TabId) -> bool {
+        if self.tabs.contains_key(tab_id) {
+            return false;
+        }
+        self.tab(tab_id).owner = Some(client);
+        true
+    }
+
+    /// Makes `client` the owner of `tab_id`, opening the tab if needed.
+    /// Returns whether the tab list changed.
+    pub fn claim(&mut self, client: ClientId, tab_id: &TabId) -> bool {
+        self.tab(tab_id).owner.replace(client) != Some(client)
+    }
+
+    /// Opens a tab saved in an earlier session, without an owner. Returns
+    /// whether it was not open yet.
+    pub fn restore(&mut self, saved: &SavedTab) -> bool {
+        if self.tabs.contains_key(&saved.tab_id) {
+            return false;
+        }
+        let tab = self.tab(&saved.tab_id);
+        tab.url = saved.url.clone();
+        tab.title = saved.title.clone();
+        tab.entries = saved.entries.clone();
+        tab.index = saved.index;
+        true
+    }
+
+    /// Forgets a closed tab. Returns whether it was open.
+    pub fn close(&mut self, tab_id: &TabId) -> bool {
+        self.tabs.remove(tab_id).is_some()
+    }
+
+    /// Leaves the tabs of a client that went away without an owner. Returns
+    /// whether it owned any.
+    pub fn release(&mut self, client: ClientId) -> bool {
+        let mut released = false;
+        for tab in self.tabs.values_mut() {
+            if tab.owner == Some(client) {
+                tab.owner = None;
+                released = true;
+            }
+        }
+        released
+    }
+
+    /// Keeps track of what the tab of `event` shows.
+    pub fn record(&mut self, event: &Event) {
+        let Some(tab) = event.tab_id().and_then(|tab_id| self.tabs.get_mut(tab_id)) else {
+            return;
+        };
+        match event {
+            Event::LoadStart { url, .. } => {
+                tab.url = Some(url.clone());
+                tab.loading = true;
+                tab.crashed = false;
+            }
+            Event::UrlChange { url, .. } => tab.url = Some(url.clone()),
+            Event::TitleChange { title, .. } => tab.title = Some(title.clone()),
+            Event::LoadComplete { url, .. } => {
+                tab.url = Some(url.clone());
+                tab.loading = false;
+            }
+            Event::HistoryChanged { entries, index, .. } => {
+                tab.entries = entries.clone();
+                tab.index = *index;
+            }
+            Event::TabCrashed { .. } => {
+                tab.loading = false;
+                tab.crashed = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// The URL and title of the page `tab_id` shows, if any.
+    pub fn page(&self, tab_id: &TabId) -> Option<(&str, &str)> {
+        let tab = self.tabs.get(tab_id)?;
+        Some((tab.url.as_deref()?, tab.page_title()))
+    }
+
+    /// The URL and title of every open tab that shows a page, in the order
+    /// the tabs were opened.
+    pub fn pages(&self) -> Vec<(&TabId, &str, &str)> {
+        let mut tabs: Vec<_> = self.tabs.iter().collect();
+        tabs.sort_by_key(|(_, tab)| tab.order);
+        tabs.into_iter()
+            .filter_map(|(tab_id, tab)| {
+                let url = tab.url.as_deref()?;
+                Some((tab_id, url, tab.page_title()))
+            })
+            .collect()
+    }
+
+    /// The `tabsSnapshot` event describing every open tab.
+    pub fn snapshot(&self) -> Event {
+        Event::TabsSnapshot { tabs: self.tabs() }
+    }
+
+    /// Every open tab, in the order the tabs were opened.
+    pub fn tabs(&self) -> Vec<TabSnapshot> {
+        let mut tabs: Vec<_> = self.tabs.iter().collect();
+        tabs.sort_by_key(|(_, tab)| tab.order);
+        tabs.into_iter()
+            .map(|(tab_id, tab)| TabSnapshot {
+                tab_id: tab_id.clone(),
+                owner: tab.owner.map(|owner| owner.0),
+                url: tab.url.clone(),
+                title: tab.title.clone(),
+                entries: tab.entries.clone(),
+                index: tab.index,
+                loading: tab.loading,
+                crashed: tab.crashed,
+            })
+            .collect()
+    }
+
+    fn tab(&mut self, tab_id: &TabId) -> &mut Tab {
+        self.tabs.entry(tab_id.clone()).or_insert_with(|| {
+            self.next_order += 1;
+            Tab {
+                order: self.next_order,
+                owner: None,
+                url: None,
+                title: None,
+                entries: Vec::new(),
+                index: 0,
+                loading: false,
+                crashed: false,
+            }
+        })
+    }
+}
+
+impl Tab {
+    /// The title of the page the tab shows. The latest `titleChange` may
+    /// still be the previous page's, so that of the current history entry
+    /// wins when it is for the same URL.
+    fn page_title(&self) -> &str {
+        match self.entries.get(self.index) {
+            Some(entry) if self.url.as_ref() == Some(&entry.url) => &entry.title,
+            _ => self.title.as_deref().unwrap_or_default(),
+        }
+    }
+}
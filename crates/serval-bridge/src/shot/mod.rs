@@ -0,0 +1,305 @@
+//! Screenshots of web pages, for batch jobs.
+//!
+//! [`capture`] runs a [`Bridge`] around an engine and drives it like any
+//! other client would: it sizes a tab, loads the page, waits until the page
+//! settled and assembles the frames the bridge streams into a [`Screenshot`].
+//! The `serval-shot` binary writes the result as PNG or PDF.
+//!
+//! Servo does not report network activity to embedders, so "settled" means
+//! the tab finished loading and then stayed quiet, without loads, URL
+//! changes or new frames, for [`Options::network_idle`].
+
+mod pdf;
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serval_protocol::{Command, ErrorCode, Event, FrameTile, Request, TabId};
+use thiserror::Error;
+
+use crate::engine::{Engine, Viewport};
+use crate::outbox::{self, Outbox, Outgoing};
+use crate::{Bridge, ClientId, Input, Waker};
+
+/// The client id the screenshot driver connects with.
+const CLIENT: ClientId = ClientId(0);
+
+/// What to capture and how long to wait for it.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub url: String,
+    pub viewport: Viewport,
+    /// How long the page must stay quiet after loading.
+    pub network_idle: Duration,
+    /// How long to wait for the page altogether.
+    pub timeout: Duration,
+}
+
+/// Why a page could not be captured.
+#[derive(Debug, Error)]
+pub enum ShotError {
+    #[error("cannot load the page: {message}")]
+    Rejected { code: ErrorCode, message: String },
+    #[error("the page crashed")]
+    Crashed,
+    #[error("the page did not settle within {0:?}")]
+    Timeout(Duration),
+    #[error("cannot decode a frame tile: {0}")]
+    Tile(String),
+    #[error("cannot encode the screenshot: {0}")]
+    Encode(#[from] png::EncodingError),
+}
+
+/// A captured page: tightly packed RGBA pixels.
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub device_pixel_ratio: f32,
+    pub pixels: Vec<u8>,
+}
+
+/// Loads `options.url` in an engine built by `engine` and captures it once it
+/// settled.
+///
+/// The bridge runs on a thread of its own, which outlives the capture: the tab
+/// is closed afterwards, but engines cannot be shut down, so callers exit the
+/// process when they are done.
+pub fn capture<E: Engine>(
+    engine: impl FnOnce(Waker) -> E + Send + 'static,
+    options: &Options,
+) -> Result<Screenshot, ShotError> {
+    let (events, incoming) = outbox::channel();
+    let inputs = start(engine, events);
+
+    let tab_id = TabId::from("shot");
+    let send = |id, command| {
+        let _ = inputs.send(Input::Command {
+            client: CLIENT,
+            request: Request::with_id(id, command),
+        });
+    };
+    send(
+        1,
+        Command::Resize {
+            tab_id: tab_id.clone(),
+            width: options.viewport.width,
+            height: options.viewport.height,
+            device_pixel_ratio: options.viewport.device_pixel_ratio,
+        },
+    );
+    send(
+        2,
+        Command::Navigate {
+            tab_id: tab_id.clone(),
+            url: options.url.clone(),
+        },
+    );
+
+    let mut page = Page::new(tab_id.clone(), options.viewport);
+    let result = page
+        .settle(&incoming, options)
+        .and_then(|()| page.screenshot());
+    send(3, Command::Close { tab_id });
+    result
+}
+
+/// Runs a bridge around the engine on a thread of its own and connects to it.
+fn start<E: Engine>(
+    engine: impl FnOnce(Waker) -> E + Send + 'static,
+    events: Outbox,
+) -> Sender<Input> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // Servo must stay on the thread that created it.
+        let bridge = Bridge::new(engine);
+        let _ = sender.send(bridge.inputs());
+        bridge.run()
+    });
+    let inputs = receiver.recv().expect("the bridge thread starts");
+    let _ = inputs.send(Input::Connected {
+        client: CLIENT,
+        events,
+    });
+    inputs
+}
+
+/// What the driver knows about the captured tab.
+struct Page {
+    tab_id: TabId,
+    viewport: Viewport,
+    /// The size frames will have, once the bridge confirmed the viewport.
+    frame_size: Option<(u32, u32)>,
+    loaded: bool,
+    last_activity: Instant,
+    width: u32,
+    height: u32,
+    /// The latest tile at every grid position.
+    tiles: BTreeMap<(u32, u32), FrameTile>,
+}
+
+impl Page {
+    fn new(tab_id: TabId, viewport: Viewport) -> Self {
+        Self {
+            tab_id,
+            viewport,
+            frame_size: None,
+            loaded: false,
+            last_activity: Instant::now(),
+            width: 0,
+            height: 0,
+            tiles: BTreeMap::new(),
+        }
+    }
+
+    /// Waits for the page to load, stay quiet for the idle period and show a
+    /// frame of the requested size.
+    fn settle(&mut self, events: &Outgoing, options: &Options) -> Result<(), ShotError> {
+        let deadline = Instant::now() + options.timeout;
+        loop {
+            let now = Instant::now();
+            if self.is_settled(now, options.network_idle) {
+                return Ok(());
+            }
+            if now >= deadline {
+                return Err(ShotError::Timeout(options.timeout));
+            }
+            // Wake up when the idle period would end, to check again.
+            let idle_end = self.last_activity + options.network_idle;
+            let wait = deadline.min(idle_end.max(now + Duration::from_millis(10))) - now;
+            match events.recv_timeout(wait) {
+                Ok(envelope) => self.handle(envelope.event)?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err(ShotError::Crashed),
+            }
+        }
+    }
+
+    fn is_settled(&self, now: Instant, network_idle: Duration) -> bool {
+        self.loaded
+            && now.duration_since(self.last_activity) >= network_idle
+            && self.frame_size == Some((self.width, self.height))
+            && !self.tiles.is_empty()
+    }
+
+    fn handle(&mut self, event: Event) -> Result<(), ShotError> {
+        match event {
+            // A content process that crashed before taking the navigation.
+            Event::Error {
+                code: ErrorCode::TabCrashed,
+                ..
+            } => return Err(ShotError::Crashed),
+            Event::Error { code, message, .. } => {
+                return Err(ShotError::Rejected { code, message });
+            }
+            _ => {}
+        }
+        if event.tab_id() != Some(&self.tab_id) {
+            return Ok(());
+        }
+        match event {
+            Event::LoadStart { .. } => {
+                self.loaded = false;
+                self.last_activity = Instant::now();
+            }
+            Event::LoadComplete { .. } => {
+                self.loaded = true;
+                self.last_activity = Instant::now();
+            }
+            Event::UrlChange { .. } => self.last_activity = Instant::now(),
+            Event::ViewportChanged {
+                width,
+                height,
+                device_pixel_ratio,
+                ..
+            } => {
+                self.frame_size = Some((width, height));
+                self.viewport.device_pixel_ratio = device_pixel_ratio;
+            }
+            Event::Frame {
+                width,
+                height,
+                tiles,
+                ..
+            } => {
+                if (width, height) != (self.width, self.height) {
+                    self.width = width;
+                    self.height = height;
+                    self.tiles.clear();
+                }
+                for tile in tiles {
+                    self.tiles.insert((tile.x, tile.y), tile);
+                }
+                self.last_activity = Instant::now();
+            }
+            Event::TabCrashed { .. } => return Err(ShotError::Crashed),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Paints the latest tiles into one image.
+    fn screenshot(&self) -> Result<Screenshot, ShotError> {
+        let stride = self.width as usize * 4;
+        let mut pixels = vec![0; stride * self.height as usize];
+        for tile in self.tiles.values() {
+            let rgba = decode_tile(tile.data.as_bytes(), tile)?;
+            let row_len = tile.width as usize * 4;
+            for row in 0..tile.height as usize {
+                let start = (tile.y as usize + row) * stride + tile.x as usize * 4;
+                pixels[start..start + row_len]
+                    .copy_from_slice(&rgba[row * row_len..(row + 1) * row_len]);
+            }
+        }
+        Ok(Screenshot {
+            width: self.width,
+            height: self.height,
+            device_pixel_ratio: self.viewport.device_pixel_ratio,
+            pixels,
+        })
+    }
+}
+
+/// Decodes a tile's PNG into RGBA pixels, checking it has the tile's size.
+fn decode_tile(data: &[u8], tile: &FrameTile) -> Result<Vec<u8>, ShotError> {
+    let error = |message: String| ShotError::Tile(format!("({}, {}): {message}", tile.x, tile.y));
+    let decoder = png::Decoder::new(std::io::Cursor::new(data));
+    let mut reader = decoder.read_info().map_err(|e| error(e.to_string()))?;
+    let mut rgba = vec![0; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader
+        .next_frame(&mut rgba)
+        .map_err(|e| error(e.to_string()))?;
+    if info.color_type != png::ColorType::Rgba
+        || info.bit_depth != png::BitDepth::Eight
+        || (info.width, info.height) != (tile.width, tile.height)
+    {
+        return Err(error(format!(
+            "expected a {}×{} RGBA tile",
+            tile.width, tile.height
+        )));
+    }
+    rgba.truncate(info.buffer_size());
+    Ok(rgba)
+}
+
+impl Screenshot {
+    /// Encodes the screenshot as PNG.
+    pub fn to_png(&self) -> Result<Vec<u8>, ShotError> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(png)
+    }
+
+    /// Encodes the screenshot as a single-page PDF, one page point per CSS
+    /// pixel at 96 pixels per inch.
+    pub fn to_pdf(&self) -> Result<Vec<u8>, ShotError> {
+        pdf::encode(self)
+    }
+}
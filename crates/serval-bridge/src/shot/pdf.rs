@@ -0,0 +1,136 @@
+//! Single-page PDFs showing a screenshot.
+//!
+//! PDF image streams accept the zlib data of a PNG as is, given the same
+//! row predictors, so the screenshot is encoded as an RGB PNG and its `IDAT`
+//! chunks are copied into the PDF.
+
+use std::io::Write as _;
+
+use super::{Screenshot, ShotError};
+
+/// Page points per CSS pixel: 72 points and 96 CSS pixels make an inch.
+const POINTS_PER_CSS_PIXEL: f32 = 0.75;
+
+pub(super) fn encode(screenshot: &Screenshot) -> Result<Vec<u8>, ShotError> {
+    let Screenshot {
+        width,
+        height,
+        device_pixel_ratio,
+        ..
+    } = *screenshot;
+    let image = image_data(screenshot)?;
+    let page_width = width as f32 / device_pixel_ratio * POINTS_PER_CSS_PIXEL;
+    let page_height = height as f32 / device_pixel_ratio * POINTS_PER_CSS_PIXEL;
+    let content = format!("q {page_width:.2} 0 0 {page_height:.2} 0 0 cm /Shot Do Q");
+
+    let mut pdf = Writer::default();
+    pdf.object(b"<< /Type /Catalog /Pages 2 0 R >>");
+    pdf.object(b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>");
+    pdf.object(
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {page_width:.2} {page_height:.2}] \
+             /Resources << /XObject << /Shot 4 0 R >> >> /Contents 5 0 R >>"
+        )
+        .as_bytes(),
+    );
+    pdf.stream(
+        &format!(
+            "/Type /XObject /Subtype /Image /Width {width} /Height {height} \
+             /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /FlateDecode \
+             /DecodeParms << /Predictor 15 /Colors 3 /BitsPerComponent 8 /Columns {width} >>"
+        ),
+        &image,
+    );
+    pdf.stream("", content.as_bytes());
+    Ok(pdf.finish())
+}
+
+/// The zlib stream of the screenshot as an RGB PNG, over a white background.
+fn image_data(screenshot: &Screenshot) -> Result<Vec<u8>, ShotError> {
+    let rgb: Vec<u8> = screenshot
+        .pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let alpha = u16::from(pixel[3]);
+            let over_white =
+                move |channel: u8| ((u16::from(channel) * alpha + 255 * (255 - alpha)) / 255) as u8;
+            [
+                over_white(pixel[0]),
+                over_white(pixel[1]),
+                over_white(pixel[2]),
+            ]
+        })
+        .collect();
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, screenshot.width, screenshot.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb)?;
+    writer.finish()?;
+
+    // Skip the signature, then collect the data of every IDAT chunk.
+    let mut data = Vec::new();
+    let mut chunks = &png[8..];
+    while chunks.len() >= 12 {
+        let length = u32::from_be_bytes(chunks[..4].try_into().unwrap()) as usize;
+        let kind = &chunks[4..8];
+        if kind == b"IDAT" {
+            data.extend_from_slice(&chunks[8..8 + length]);
+        }
+        chunks = &chunks[12 + length..];
+    }
+    Ok(data)
+}
+
+/// Writes numbered objects and the cross-reference table pointing at them.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl Writer {
+    fn begin(&mut self) {
+        if self.bytes.is_empty() {
+            // The binary comment marks the file as binary for transfer tools.
+            self.bytes
+                .extend_from_slice(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+        }
+        self.offsets.push(self.bytes.len());
+        let number = self.offsets.len();
+        let _ = writeln!(self.bytes, "{number} 0 obj");
+    }
+
+    fn object(&mut self, body: &[u8]) {
+        self.begin();
+        self.bytes.extend_from_slice(body);
+        self.bytes.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, dictionary: &str, data: &[u8]) {
+        self.begin();
+        let _ = write!(
+            self.bytes,
+            "<< {dictionary} /Length {} >>\nstream\n",
+            data.len()
+        );
+        self.bytes.extend_from_slice(data);
+        self.bytes.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.bytes.len();
+        let count = self.offsets.len() + 1;
+        let _ = write!(self.bytes, "xref\n0 {count}\n0000000000 65535 f \n");
+        for offset in &self.offsets {
+            let _ = writeln!(self.bytes, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            self.bytes,
+            "trailer\n<< /Size {count} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n"
+        );
+        self.bytes
+    }
+}
@@ -0,0 +1,56 @@
+//! Shutting the bridge down cleanly when the process is asked to end.
+//!
+//! `SIGINT`, `SIGTERM` and `SIGHUP` become [`Input::Shutdown`], so the bridge
+//! saves its session before it exits. Signal handlers may do next to
+//! nothing, so the handler only writes to a pipe a thread waits on.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::FromRawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use crate::Input;
+
+/// The end of the pipe the signal handler writes to.
+static SIGNALLED: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn signalled(_signal: libc::c_int) {
+    let byte = 0u8;
+    // SAFETY: `write` is async-signal-safe and the byte outlives the call.
+    unsafe {
+        libc::write(
+            SIGNALLED.load(Ordering::Relaxed),
+            (&raw const byte).cast(),
+            1,
+        )
+    };
+}
+
+/// Sends [`Input::Shutdown`] to `inputs` when the process is asked to end.
+pub fn on_termination(inputs: Sender<Input>) -> io::Result<()> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for both ends of the pipe.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    SIGNALLED.store(fds[1], Ordering::Relaxed);
+    // SAFETY: the read end was just opened and nothing else owns it.
+    let mut pipe = unsafe { File::from_raw_fd(fds[0]) };
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        // SAFETY: the handler only calls `write`.
+        let previous =
+            unsafe { libc::signal(signal, signalled as *const () as libc::sighandler_t) };
+        if previous == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    thread::spawn(move || {
+        let mut byte = [0];
+        if pipe.read(&mut byte).is_ok() {
+            let _ = inputs.send(Input::Shutdown);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,379 @@
+//! Standard I/O transport.
+//!
+//! Serves a single client over the process's stdin and stdout. Content
+//! processes use it to talk to the supervisor that started them (see
+//! [`ProcessEngine`](crate::engine::ProcessEngine)), and `serval-bridge
+//! --stdio` to talk to the desktop shell that spawned it.
+//!
+//! Messages are framed one per line or behind a `Content-Length` header, see
+//! [`Framing`]. Either way, stdout carries nothing but frames: [`serve`]
+//! keeps the original stdout for itself and points the process's stdout at
+//! stderr, so logs and whatever the engine prints end up there.
+//!
+//! Length framing carries any bytes, so it switches to the binary encoding
+//! the client asks for in `ready`; line framing stays on JSON.
+//!
+//! A malformed frame (invalid UTF-8, oversized, or a broken header) is
+//! answered with an `invalidFrame` error and skipped; reading resumes at the
+//! next frame instead of dropping the client.
+
+use std::io::{self, BufRead, Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use log::{error, warn};
+use serval_protocol::{Command, Encoding, ErrorCode, Event, Request};
+use thiserror::Error;
+
+use crate::outbox::{self, Outbox, Outgoing};
+use crate::{ClientId, Input};
+
+/// The only client of a stdio transport.
+const CLIENT: ClientId = ClientId(0);
+
+/// Frames longer than this are skipped.
+pub const MAX_FRAME_LEN: usize = 16 << 20;
+
+/// Header lines longer than this are not headers.
+const MAX_HEADER_LEN: usize = 1024;
+
+/// How messages are delimited on stdin and stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Framing {
+    /// One JSON message per line. Encoded messages never contain raw
+    /// newlines.
+    #[default]
+    Lines,
+    /// `Content-Length: <bytes>`, an empty line, then the message, as in the
+    /// Language Server Protocol. Other headers are ignored.
+    Length,
+}
+
+impl Framing {
+    /// Whether frames may hold messages in a binary [`Encoding`]. Lines
+    /// cannot: binary messages contain newlines.
+    pub fn carries_binary(self) -> bool {
+        self == Framing::Length
+    }
+}
+
+/// Why a frame was skipped.
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("frame is not valid UTF-8")]
+    Utf8,
+    #[error("frame of {0} bytes exceeds the limit of {MAX_FRAME_LEN} bytes")]
+    TooLong(usize),
+    #[error("invalid frame header `{0}`; skipping to the next `Content-Length` header")]
+    Header(String),
+}
+
+/// Splits a byte stream into frames.
+pub struct FrameReader<R> {
+    reader: R,
+    framing: Framing,
+    /// Set after a broken header, until the next `Content-Length` header.
+    resyncing: bool,
+}
+
+impl<R: BufRead> FrameReader<R> {
+    pub fn new(reader: R, framing: Framing) -> Self {
+        Self {
+            reader,
+            framing,
+            resyncing: false,
+        }
+    }
+
+    /// Reads the next frame as text, or returns `None` at the end of the
+    /// stream. A frame cut short by the end of the stream is dropped.
+    pub fn next_frame(&mut self) -> io::Result<Option<Result<String, FrameError>>> {
+        Ok(self
+            .next_binary_frame()?
+            .map(|frame| frame.and_then(into_text)))
+    }
+
+    /// Reads the next frame as it is, for messages in a binary encoding.
+    pub fn next_binary_frame(&mut self) -> io::Result<Option<Result<Vec<u8>, FrameError>>> {
+        match self.framing {
+            Framing::Lines => self.read_line(MAX_FRAME_LEN),
+            Framing::Length => self.next_length_frame(),
+        }
+    }
+
+    fn next_length_frame(&mut self) -> io::Result<Option<Result<Vec<u8>, FrameError>>> {
+        let mut length = None;
+        let length = loop {
+            let line = match self.read_line(MAX_HEADER_LEN)? {
+                None => return Ok(None),
+                Some(Ok(line)) => String::from_utf8_lossy(&line).into_owned(),
+                Some(Err(_)) if self.resyncing => continue,
+                Some(Err(_)) => {
+                    self.resyncing = true;
+                    let error = FrameError::Header("<overlong header line>".to_owned());
+                    return Ok(Some(Err(error)));
+                }
+            };
+            let line = line.trim_end_matches('\r');
+            let header = line
+                .split_once(':')
+                .map(|(name, value)| (name.trim(), value.trim()));
+
+            match header {
+                Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+                    self.resyncing = false;
+                    match value.parse::<usize>() {
+                        Ok(value) => length = Some(value),
+                        Err(_) => {
+                            self.resyncing = true;
+                            return Ok(Some(Err(FrameError::Header(line.to_owned()))));
+                        }
+                    }
+                }
+                _ if self.resyncing => {}
+                None if line.is_empty() => {
+                    // Blank lines between frames are harmless.
+                    if let Some(length) = length {
+                        break length;
+                    }
+                }
+                Some((name, _)) if is_header_name(name) => {}
+                _ => {
+                    self.resyncing = true;
+                    return Ok(Some(Err(FrameError::Header(line.to_owned()))));
+                }
+            }
+        };
+        if length > MAX_FRAME_LEN {
+            io::copy(&mut (&mut self.reader).take(length as u64), &mut io::sink())?;
+            return Ok(Some(Err(FrameError::TooLong(length))));
+        }
+        let mut frame = vec![0; length];
+        match self.reader.read_exact(&mut frame) {
+            Ok(()) => Ok(Some(Ok(frame))),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Reads a line without its `\n`. A line longer than `limit` is skipped
+    /// and reported as too long.
+    fn read_line(&mut self, limit: usize) -> io::Result<Option<Result<Vec<u8>, FrameError>>> {
+        let mut line = Vec::new();
+        let read = (&mut self.reader)
+            .take(limit as u64 + 1)
+            .read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            return Ok(Some(Ok(line)));
+        }
+        if line.len() <= limit {
+            // The stream ended in the middle of the line.
+            return Ok(None);
+        }
+
+        let mut skipped = line.len();
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(None);
+            }
+            match buffer.iter().position(|byte| *byte == b'\n') {
+                Some(end) => {
+                    self.reader.consume(end + 1);
+                    skipped += end;
+                    return Ok(Some(Err(FrameError::TooLong(skipped))));
+                }
+                None => {
+                    let len = buffer.len();
+                    self.reader.consume(len);
+                    skipped += len;
+                }
+            }
+        }
+    }
+}
+
+fn is_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+}
+
+fn into_text(frame: Vec<u8>) -> Result<String, FrameError> {
+    String::from_utf8(frame).map_err(|_| FrameError::Utf8)
+}
+
+/// Writes `message` as one frame and flushes it.
+pub fn write_frame(
+    writer: &mut impl Write,
+    framing: Framing,
+    message: impl AsRef<[u8]>,
+) -> io::Result<()> {
+    let message = message.as_ref();
+    match framing {
+        Framing::Lines => {
+            writer.write_all(message)?;
+            writer.write_all(b"\n")?;
+        }
+        Framing::Length => {
+            write!(writer, "Content-Length: {}\r\n\r\n", message.len())?;
+            writer.write_all(message)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Feeds commands read from stdin to the bridge loop and writes its events to
+/// stdout. The returned thread finishes once stdin is closed.
+pub fn serve(inputs: Sender<Input>, framing: Framing) -> thread::JoinHandle<()> {
+    let (events, outgoing) = outbox::channel();
+    let stdout = take_stdout().unwrap_or_else(|error| {
+        warn!("cannot keep stdout for the protocol, sharing it with logs: {error}");
+        Box::new(io::stdout())
+    });
+    let (agreed, encodings) = mpsc::channel();
+    thread::spawn(move || write_events(outgoing, stdout, framing, CLIENT, &agreed));
+
+    thread::spawn(move || {
+        let replies = events.clone();
+        if inputs
+            .send(Input::Connected {
+                client: CLIENT,
+                events,
+            })
+            .is_err()
+        {
+            return;
+        }
+        let frames = FrameReader::new(io::stdin().lock(), framing);
+        read_commands(frames, CLIENT, &inputs, &replies, &encodings);
+        let _ = inputs.send(Input::Disconnected(CLIENT));
+    })
+}
+
+/// Writes the events for `client` as frames until the bridge or the writer
+/// goes away, switching to the encoding announced in `ready` after it and
+/// passing it on to `agreed`.
+pub(crate) fn write_events(
+    events: Outgoing,
+    mut writer: impl Write,
+    framing: Framing,
+    client: ClientId,
+    agreed: &Sender<Encoding>,
+) {
+    let mut current = Encoding::Json;
+    for event in events {
+        let message = current.encode(&event);
+        if let Err(error) = write_frame(&mut writer, framing, &message) {
+            error!("{client}: cannot write: {error}");
+            return;
+        }
+        if let Event::Ready { encoding, .. } = event.event {
+            current = encoding;
+            let _ = agreed.send(encoding);
+        }
+    }
+}
+
+/// Decodes the frames `client` sends into commands for the bridge loop,
+/// answering malformed ones through `replies`, until the stream ends.
+/// Frames after a `ready` are decoded in the encoding the bridge answered
+/// with, which [`write_events`] passes on through `encodings`. Framings that
+/// cannot carry binary take the encoding out of the `ready`, so the bridge
+/// stays on JSON.
+pub(crate) fn read_commands(
+    mut frames: FrameReader<impl BufRead>,
+    client: ClientId,
+    inputs: &Sender<Input>,
+    replies: &Outbox,
+    encodings: &Receiver<Encoding>,
+) {
+    let mut current = Encoding::Json;
+    loop {
+        let frame = if current.is_binary() {
+            frames.next_binary_frame()
+        } else {
+            frames
+                .next_frame()
+                .map(|frame| frame.map(|frame| frame.map(String::into_bytes)))
+        };
+        let frame = match frame {
+            Ok(Some(Ok(frame))) => frame,
+            Ok(Some(Err(error))) => {
+                warn!("{client}: skipping frame: {error}");
+                let reply = Event::Error {
+                    id: None,
+                    code: ErrorCode::InvalidFrame,
+                    message: error.to_string(),
+                };
+                let _ = replies.send(reply);
+                continue;
+            }
+            Ok(None) => return,
+            Err(error) => {
+                error!("{client}: cannot read: {error}");
+                return;
+            }
+        };
+        if !current.is_binary() && frame.trim_ascii().is_empty() {
+            continue;
+        }
+        match current.decode::<Request>(&frame) {
+            Ok(mut request) => {
+                let ready = matches!(request.command, Command::Ready { .. });
+                if let Command::Ready { encoding, .. } = &mut request.command
+                    && !frames.framing.carries_binary()
+                {
+                    *encoding = None;
+                }
+                if inputs.send(Input::Command { client, request }).is_err() {
+                    return;
+                }
+                if ready {
+                    match encodings.recv() {
+                        Ok(encoding) => current = encoding,
+                        // The writer is gone with the client.
+                        Err(_) => return,
+                    }
+                }
+            }
+            Err(error) => {
+                warn!("{client}: rejecting message: {error}");
+                let reply = Event::Error {
+                    id: error.request_id(),
+                    code: ErrorCode::InvalidMessage,
+                    message: error.to_string(),
+                };
+                let _ = replies.send(reply);
+            }
+        }
+    }
+}
+
+/// Takes the process's stdout for protocol frames and points it at stderr,
+/// so nothing else written to stdout can corrupt the stream.
+#[cfg(unix)]
+fn take_stdout() -> io::Result<Box<dyn Write + Send>> {
+    use std::os::fd::AsFd;
+
+    // Hold the lock so no print lands between the flush and the switch.
+    let mut stdout = io::stdout().lock();
+    stdout.flush()?;
+    let protocol = stdout.as_fd().try_clone_to_owned()?;
+    // SAFETY: `dup2` only replaces the process's stdout descriptor, which
+    // Rust's stdout handle keeps using by number.
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Box::new(std::fs::File::from(protocol)))
+}
+
+#[cfg(not(unix))]
+fn take_stdout() -> io::Result<Box<dyn Write + Send>> {
+    Ok(Box::new(io::stdout()))
+}
@@ -0,0 +1,155 @@
+//! Unix domain socket transport.
+//!
+//! Unlike a TCP port, which every local process can connect to, the socket
+//! only accepts processes of the user running the bridge: the socket file is
+//! private to the user, and the bridge checks the credentials of every peer
+//! and refuses other users. By default it lives in the user's runtime
+//! directory, see [`default_path`].
+//!
+//! Messages are framed one per line, as with [`Framing::Lines`], and every
+//! connection is a client of its own.
+
+use std::fs;
+use std::io::{self, BufReader};
+use std::net::Shutdown;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use log::{error, warn};
+
+use crate::stdio::{self, FrameReader, Framing};
+use crate::{ClientId, Input};
+use crate::{discovery, outbox};
+
+/// The socket in the user's runtime directory, see
+/// [`discovery::runtime_dir`]: usually `$XDG_RUNTIME_DIR/serval/bridge.sock`.
+pub fn default_path() -> io::Result<PathBuf> {
+    Ok(discovery::runtime_dir()?.join("bridge.sock"))
+}
+
+/// Listens on `path`, replacing the socket of a bridge that is gone. Fails
+/// with [`io::ErrorKind::AddrInUse`] when another bridge still listens there.
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    if fs::symlink_metadata(path).is_ok() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("a bridge already listens on {}", path.display()),
+            ));
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Accepts clients of the current user on `listener` until the process
+/// exits.
+pub fn serve(listener: UnixListener, inputs: Sender<Input>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let uid = current_uid();
+        for (id, stream) in (1..).zip(listener.incoming()) {
+            let client = ClientId(id);
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    warn!("failed to accept connection: {error}");
+                    continue;
+                }
+            };
+            match peer_uid(&stream) {
+                Ok(peer) if peer == uid => {}
+                Ok(peer) => {
+                    warn!("{client}: refusing connection from uid {peer}");
+                    continue;
+                }
+                Err(error) => {
+                    warn!("{client}: refusing connection with unknown credentials: {error}");
+                    continue;
+                }
+            }
+
+            let inputs = inputs.clone();
+            thread::spawn(move || {
+                if let Err(error) = handle_connection(stream, client, &inputs) {
+                    error!("{client}: {error}");
+                }
+                let _ = inputs.send(Input::Disconnected(client));
+            });
+        }
+    })
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    client: ClientId,
+    inputs: &Sender<Input>,
+) -> io::Result<()> {
+    let writer = stream.try_clone()?;
+    let (events, outgoing) = outbox::channel();
+    let replies = events.clone();
+    let (agreed, encodings) = mpsc::channel();
+    thread::spawn(move || {
+        stdio::write_events(outgoing, &writer, Framing::Lines, client, &agreed);
+        // Ends the reader too when the bridge dropped the client.
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+
+    if inputs.send(Input::Connected { client, events }).is_err() {
+        return Ok(());
+    }
+    let frames = FrameReader::new(BufReader::new(stream), Framing::Lines);
+    stdio::read_commands(frames, client, inputs, &replies, &encodings);
+    Ok(())
+}
+
+pub(crate) fn current_uid() -> u32 {
+    // SAFETY: `geteuid` cannot fail.
+    unsafe { libc::geteuid() }
+}
+
+/// The user id of the process at the other end of `stream`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    use std::os::fd::AsRawFd;
+
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `credentials` and `len` describe a `ucred` that outlives the
+    // call.
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&raw mut credentials).cast(),
+            &mut len,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(credentials.uid)
+}
+
+/// The user id of the process at the other end of `stream`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    use std::os::fd::AsRawFd;
+
+    let mut uid = 0;
+    let mut gid = 0;
+    // SAFETY: `uid` and `gid` outlive the call.
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
@@ -0,0 +1,312 @@
+//! Every visit of every tab, in an SQLite database.
+//!
+//! The bridge records a [`Visit`] whenever a tab finishes loading a page the
+//! address bar may suggest again (see
+//! [`is_remembered`](crate::omnibox::places::is_remembered)), with how
+//! the tab got there and the page it came from. Clients look visits up with
+//! `queryHistory` and forget them with `deleteHistory`; the
+//! `serval://history` page (see [`pages`](crate::pages)) lists them too.
+//!
+//! A page's URL and title are kept once, in `pages`, along with a folded copy
+//! of both that text queries match against, so they ignore case the way the
+//! address bar does. Each visit is a row of `visits`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use rusqlite::types::Value;
+use rusqlite::{Connection, params, params_from_iter};
+use serval_protocol::{Transition, Visit};
+use thiserror::Error;
+use url::Url;
+
+use crate::omnibox::Places;
+use crate::omnibox::places::{fold, strip_url};
+use crate::profile;
+
+/// How many visits `queryHistory` sends when the client does not say.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// The version of the schema below, kept in the database's `user_version`.
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS pages (
+        id INTEGER PRIMARY KEY,
+        url TEXT NOT NULL UNIQUE,
+        title TEXT NOT NULL,
+        host TEXT NOT NULL,
+        -- The URL without its scheme and the title, folded, for queries.
+        search TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS pages_host ON pages (host);
+    CREATE TABLE IF NOT EXISTS visits (
+        id INTEGER PRIMARY KEY,
+        page INTEGER NOT NULL REFERENCES pages (id) ON DELETE CASCADE,
+        -- Milliseconds since the Unix epoch.
+        visited_at INTEGER NOT NULL,
+        transition TEXT NOT NULL,
+        referrer TEXT
+    );
+    CREATE INDEX IF NOT EXISTS visits_visited_at ON visits (visited_at);
+    CREATE INDEX IF NOT EXISTS visits_page ON visits (page);
+";
+
+/// Why the visit store failed.
+#[derive(Debug, Error)]
+pub enum VisitStoreError {
+    #[error("cannot find a place for the history database: {0}")]
+    NoDataDir(#[source] io::Error),
+    #[error("cannot create {}: {source}", path.display())]
+    Dir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("history database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("the history database has schema version {0}, newer than this bridge knows")]
+    NewerSchema(i32),
+}
+
+/// Which visits a query asks for, newest first.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Words every visit's URL or title must contain, ignoring case.
+    pub text: String,
+    /// Only visits at or after this time, in milliseconds since the Unix
+    /// epoch.
+    pub from: Option<u64>,
+    /// Only visits before this time.
+    pub to: Option<u64>,
+    /// [`DEFAULT_LIMIT`] when `None`.
+    pub limit: Option<usize>,
+}
+
+/// The visits of every tab.
+pub struct VisitStore {
+    db: Connection,
+}
+
+impl VisitStore {
+    /// Opens the database at `path`, creating it and its directory when
+    /// missing.
+    pub fn open(path: &Path) -> Result<Self, VisitStoreError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|source| VisitStoreError::Dir {
+                path: dir.to_owned(),
+                source,
+            })?;
+        }
+        let db = Connection::open(path)?;
+        // Content processes read the database while the bridge writes it.
+        db.pragma_update(None, "journal_mode", "WAL")?;
+        db.pragma_update(None, "synchronous", "NORMAL")?;
+        db.busy_timeout(Duration::from_secs(1))?;
+        Self::with(db)
+    }
+
+    /// A store that is gone with the bridge.
+    pub fn in_memory() -> Result<Self, VisitStoreError> {
+        Self::with(Connection::open_in_memory()?)
+    }
+
+    fn with(db: Connection) -> Result<Self, VisitStoreError> {
+        let version: i32 = db.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(VisitStoreError::NewerSchema(version));
+        }
+        db.pragma_update(None, "foreign_keys", true)?;
+        db.execute_batch(SCHEMA)?;
+        db.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { db })
+    }
+
+    /// Records `visit`. Its title replaces the page's unless it is empty.
+    pub fn record(&mut self, visit: &Visit) -> Result<(), VisitStoreError> {
+        let host = Url::parse(&visit.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+            .unwrap_or_default();
+        let tx = self.db.transaction()?;
+        tx.execute(
+            "INSERT INTO pages (url, title, host, search) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (url) DO UPDATE SET title = excluded.title, search = excluded.search
+             WHERE excluded.title != ''",
+            params![
+                visit.url,
+                visit.title,
+                host,
+                search_text(&visit.url, &visit.title)
+            ],
+        )?;
+        let page: i64 =
+            tx.query_row("SELECT id FROM pages WHERE url = ?1", [&visit.url], |row| {
+                row.get(0)
+            })?;
+        tx.execute(
+            "INSERT INTO visits (page, visited_at, transition, referrer) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                page,
+                to_sql(visit.visited_at),
+                transition_name(visit.transition),
+                visit.referrer,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Gives `url` a title that came after its visit was recorded.
+    pub fn retitle(&mut self, url: &str, title: &str) -> Result<(), VisitStoreError> {
+        self.db.execute(
+            "UPDATE pages SET title = ?2, search = ?3 WHERE url = ?1",
+            params![url, title, search_text(url, title)],
+        )?;
+        Ok(())
+    }
+
+    /// The visits `query` asks for, newest first.
+    pub fn query(&self, query: &Query) -> Result<Vec<Visit>, VisitStoreError> {
+        let mut sql = String::from(
+            "SELECT pages.url, pages.title, visits.visited_at, visits.transition, visits.referrer
+             FROM visits JOIN pages ON pages.id = visits.page
+             WHERE visits.visited_at >= ?1 AND visits.visited_at < ?2",
+        );
+        let mut values = vec![
+            Value::Integer(to_sql(query.from.unwrap_or(0))),
+            Value::Integer(query.to.map_or(i64::MAX, to_sql)),
+        ];
+        for word in fold(&query.text).split_whitespace() {
+            values.push(Value::Text(word.to_owned()));
+            sql.push_str(&format!(" AND instr(pages.search, ?{}) > 0", values.len()));
+        }
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        values.push(Value::Integer(limit.try_into().unwrap_or(i64::MAX)));
+        sql.push_str(&format!(
+            " ORDER BY visits.visited_at DESC, visits.id DESC LIMIT ?{}",
+            values.len()
+        ));
+
+        let mut statement = self.db.prepare(&sql)?;
+        let visits = statement.query_map(params_from_iter(values), |row| {
+            Ok(Visit {
+                url: row.get(0)?,
+                title: row.get(1)?,
+                visited_at: from_sql(row.get(2)?),
+                transition: parse_transition(&row.get::<_, String>(3)?),
+                referrer: row.get(4)?,
+            })
+        })?;
+        Ok(visits.collect::<Result<_, _>>()?)
+    }
+
+    /// Forgets the visits to `site` and its subdomains between `from` and
+    /// `to`, in milliseconds since the Unix epoch, and the pages left
+    /// without any. Returns how many visits were forgotten.
+    pub fn delete(
+        &mut self,
+        site: Option<&str>,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<usize, VisitStoreError> {
+        let from = to_sql(from.unwrap_or(0));
+        let to = to.map_or(i64::MAX, to_sql);
+        let tx = self.db.transaction()?;
+        let deleted = match site.map(normalize_site) {
+            Some(site) => tx.execute(
+                r"DELETE FROM visits WHERE visited_at >= ?1 AND visited_at < ?2
+                  AND page IN (SELECT id FROM pages WHERE host = ?3 OR host LIKE ?4 ESCAPE '\')",
+                params![from, to, site, format!("%.{}", escape_like(&site))],
+            )?,
+            None => tx.execute(
+                "DELETE FROM visits WHERE visited_at >= ?1 AND visited_at < ?2",
+                params![from, to],
+            )?,
+        };
+        tx.execute(
+            "DELETE FROM pages WHERE NOT EXISTS (SELECT 1 FROM visits WHERE visits.page = pages.id)",
+            [],
+        )?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Every visit, replayed into the pages the address bar suggests.
+    pub fn places(&self) -> Result<Places, VisitStoreError> {
+        let mut places = Places::new();
+        let mut statement = self.db.prepare(
+            "SELECT pages.url, pages.title, visits.visited_at, visits.transition
+             FROM visits JOIN pages ON pages.id = visits.page
+             ORDER BY visits.visited_at, visits.id",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let url: String = row.get(0)?;
+            let title: String = row.get(1)?;
+            let at = SystemTime::UNIX_EPOCH + Duration::from_millis(from_sql(row.get(2)?));
+            let typed = parse_transition(&row.get::<_, String>(3)?) == Transition::Typed;
+            places.visit(&url, &title, at, typed);
+        }
+        Ok(places)
+    }
+}
+
+/// The history database in the user's [data directory](profile::data_dir).
+pub fn default_path() -> Result<PathBuf, VisitStoreError> {
+    profile::data_dir()
+        .map(|dir| dir.join("history.sqlite"))
+        .map_err(VisitStoreError::NoDataDir)
+}
+
+/// `at` in milliseconds since the Unix epoch, as visits are timed.
+pub fn millis(at: SystemTime) -> u64 {
+    let since = at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    since.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+fn search_text(url: &str, title: &str) -> String {
+    format!("{}\n{}", fold(strip_url(url)), fold(title))
+}
+
+fn normalize_site(site: &str) -> String {
+    site.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// `text` matching itself in a `LIKE` pattern escaped with `\`.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', r"\\")
+        .replace('%', r"\%")
+        .replace('_', r"\_")
+}
+
+fn to_sql(millis: u64) -> i64 {
+    millis.try_into().unwrap_or(i64::MAX)
+}
+
+fn from_sql(millis: i64) -> u64 {
+    millis.try_into().unwrap_or(0)
+}
+
+fn transition_name(transition: Transition) -> &'static str {
+    match transition {
+        Transition::Typed => "typed",
+        Transition::Link => "link",
+        Transition::Reload => "reload",
+        Transition::BackForward => "backForward",
+    }
+}
+
+/// Reads what [`transition_name`] wrote, taking anything else for a link.
+fn parse_transition(name: &str) -> Transition {
+    match name {
+        "typed" => Transition::Typed,
+        "reload" => Transition::Reload,
+        "backForward" => Transition::BackForward,
+        _ => Transition::Link,
+    }
+}
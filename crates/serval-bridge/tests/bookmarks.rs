@@ -0,0 +1,518 @@
+//! Bookmarks: folders and their order, tags and keywords, Netscape bookmark
+//! files and the commands that keep clients in sync.
+
+mod support;
+
+use serval_bridge::ClientId;
+use serval_bridge::bookmarks::{
+    self, BookmarkError, BookmarkStore, Changes, Item, NewBookmark, Query,
+};
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_protocol::{Bookmark, Command, ErrorCode, Event, SuggestionKind};
+use support::Harness;
+
+/// Roughly what Firefox exports, with a separator, a description and a
+/// smart folder.
+const FIREFOX: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<meta http-equiv="Content-Security-Policy"
+      content="default-src 'self'; script-src 'none'; img-src data: *; object-src 'none'"></meta>
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks Menu</H1>
+
+<DL><p>
+    <DT><A HREF="place:parent=menu________&amp;queryType=1" ADD_DATE="1700000000">Recent Tags</A>
+    <DT><H3 ADD_DATE="1700000000" LAST_MODIFIED="1700000100">Rust</H3>
+    <DL><p>
+        <DT><A HREF="https://doc.rust-lang.org/std/?search=%s" ADD_DATE="1700000001" LAST_MODIFIED="1700000002" SHORTCUTURL="std" TAGS="docs,rust">Rust std &amp; friends</A>
+<DD>The standard library
+        <HR>
+        <DT><A HREF="https://crates.io/" ADD_DATE="1700000003" ICON="data:image/png;base64,iVBORw0KGgo=">crates.io</A>
+    </DL><p>
+    <DT><H3 ADD_DATE="1700000004" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks Toolbar</H3>
+    <DL><p>
+    </DL><p>
+    <DT><A HREF='https://servo.org/' add_date=1700000005>Servo &#8211; &#x1F600;</A>
+</DL>
+"#;
+
+fn new(parent_id: Option<u64>, title: &str, url: Option<&str>) -> NewBookmark {
+    NewBookmark {
+        parent_id,
+        title: title.into(),
+        url: url.map(Into::into),
+        added_at: 1_000,
+        ..NewBookmark::default()
+    }
+}
+
+/// The titles of `bookmarks` indented by depth, to compare trees at a
+/// glance.
+fn outline(bookmarks: &[Bookmark]) -> Vec<String> {
+    let mut depths = std::collections::HashMap::new();
+    bookmarks
+        .iter()
+        .map(|bookmark| {
+            let depth = bookmark.parent_id.map_or(0, |parent| depths[&parent] + 1);
+            depths.insert(bookmark.id, depth);
+            format!("{}{}", "  ".repeat(depth), bookmark.title)
+        })
+        .collect()
+}
+
+fn titles(bookmarks: &[Bookmark]) -> Vec<&str> {
+    bookmarks
+        .iter()
+        .map(|bookmark| bookmark.title.as_str())
+        .collect()
+}
+
+#[test]
+fn bookmarks_keep_their_folders_and_order() {
+    let mut store = BookmarkStore::in_memory().unwrap();
+    let rust = store.add(&new(None, "Rust", None)).unwrap();
+    let news = store.add(&new(None, "News", None)).unwrap();
+    let std = store
+        .add(&new(
+            Some(rust.id),
+            "std",
+            Some("https://doc.rust-lang.org/std/"),
+        ))
+        .unwrap();
+    store
+        .add(&new(Some(rust.id), "crates.io", Some("https://crates.io")))
+        .unwrap();
+    let first = store
+        .add(&NewBookmark {
+            index: Some(0),
+            ..new(
+                Some(rust.id),
+                "rust-lang.org",
+                Some("https://www.rust-lang.org/"),
+            )
+        })
+        .unwrap();
+    assert_eq!(first.index, 0);
+    store
+        .add(&new(None, "Servo", Some("https://servo.org/")))
+        .unwrap();
+    assert_eq!(
+        outline(&store.all().unwrap()),
+        [
+            "Rust",
+            "  rust-lang.org",
+            "  std",
+            "  crates.io",
+            "News",
+            "Servo"
+        ]
+    );
+    // URLs are kept as the engine reports them.
+    assert_eq!(
+        store.all().unwrap()[3].url.as_deref(),
+        Some("https://crates.io/")
+    );
+
+    store.move_to(std.id, Some(news.id), None).unwrap();
+    store.move_to(news.id, Some(rust.id), Some(1)).unwrap();
+    store.move_to(first.id, Some(rust.id), Some(99)).unwrap();
+    let all = store.all().unwrap();
+    assert_eq!(
+        outline(&all),
+        [
+            "Rust",
+            "  News",
+            "    std",
+            "  crates.io",
+            "  rust-lang.org",
+            "Servo"
+        ]
+    );
+    let indices: Vec<_> = all.iter().map(|bookmark| bookmark.index).collect();
+    assert_eq!(indices, [0, 0, 0, 1, 2, 1]);
+
+    assert!(matches!(
+        store.move_to(rust.id, Some(news.id), None),
+        Err(BookmarkError::IntoItself(id)) if id == rust.id
+    ));
+    assert!(matches!(
+        store.move_to(news.id, Some(first.id), None),
+        Err(BookmarkError::NotAFolder(id)) if id == first.id
+    ));
+    assert!(matches!(
+        store.add(&new(None, "Nope", Some("not a url"))),
+        Err(BookmarkError::InvalidUrl(_))
+    ));
+
+    // Folders go with everything in them.
+    store.remove(news.id).unwrap();
+    assert_eq!(
+        outline(&store.all().unwrap()),
+        ["Rust", "  crates.io", "  rust-lang.org", "Servo"]
+    );
+    assert_eq!(store.get(first.id).unwrap().index, 1);
+    assert!(matches!(store.get(std.id), Err(BookmarkError::NotFound(_))));
+}
+
+#[test]
+fn bookmarks_are_found_by_text_tag_and_keyword() {
+    let mut store = BookmarkStore::in_memory().unwrap();
+    let docs = store
+        .add(&NewBookmark {
+            tags: vec!["Rust".into(), "docs".into(), " ".into()],
+            keyword: Some(" STD ".into()),
+            ..new(
+                None,
+                "Standard library",
+                Some("https://doc.rust-lang.org/std/?search=%s"),
+            )
+        })
+        .unwrap();
+    assert_eq!(docs.tags, ["docs", "Rust"]);
+    assert_eq!(docs.keyword.as_deref(), Some("std"));
+    store
+        .add(&NewBookmark {
+            tags: vec!["rust".into()],
+            ..new(None, "Café Rust", Some("https://www.rust-lang.org/"))
+        })
+        .unwrap();
+    store.add(&new(None, "Rust things", None)).unwrap();
+
+    let search = |text: &str, tag: Option<&str>| {
+        let query = Query {
+            text: text.into(),
+            tag: tag.map(Into::into),
+            limit: None,
+        };
+        titles(&store.search(&query).unwrap())
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    // Folders are never found.
+    assert_eq!(search("rust", None), ["Standard library", "Café Rust"]);
+    assert_eq!(search("CAFÉ", None), ["Café Rust"]);
+    assert_eq!(search("", Some("DOCS")), ["Standard library"]);
+    assert_eq!(search("std", Some("rust")), ["Standard library"]);
+
+    assert_eq!(
+        store
+            .resolve_keyword("Std vec & slices")
+            .unwrap()
+            .unwrap()
+            .as_str(),
+        "https://doc.rust-lang.org/std/?search=vec+%26+slices"
+    );
+    assert!(store.resolve_keyword("stdlib").unwrap().is_none());
+
+    let taken = store.add(&NewBookmark {
+        keyword: Some("std".into()),
+        ..new(None, "Other", Some("https://example.com/"))
+    });
+    assert!(matches!(taken, Err(BookmarkError::KeywordTaken(word)) if word == "std"));
+    assert!(matches!(
+        store.add(&NewBookmark {
+            keyword: Some("two words".into()),
+            ..new(None, "Other", Some("https://example.com/"))
+        }),
+        Err(BookmarkError::InvalidKeyword(_))
+    ));
+    let folder = store.all().unwrap()[2].id;
+    let changes = Changes {
+        keyword: Some("things".into()),
+        ..Changes::default()
+    };
+    assert!(matches!(
+        store.update(folder, &changes),
+        Err(BookmarkError::FolderDetails)
+    ));
+
+    // An empty keyword frees it.
+    let changes = Changes {
+        title: Some("std".into()),
+        keyword: Some(String::new()),
+        tags: Some(Vec::new()),
+        ..Changes::default()
+    };
+    store.update(docs.id, &changes).unwrap();
+    let docs = store.get(docs.id).unwrap();
+    assert_eq!((docs.title.as_str(), docs.keyword), ("std", None));
+    assert!(docs.tags.is_empty());
+    assert!(store.resolve_keyword("std vec").unwrap().is_none());
+}
+
+#[test]
+fn bookmark_files_from_other_browsers_import() {
+    let items = bookmarks::html::parse(FIREFOX);
+    let rust = &items[1];
+    assert_eq!(rust.title, "Rust");
+    assert_eq!(rust.url, None);
+    assert_eq!(rust.added_at, Some(1_700_000_000_000));
+    assert_eq!(
+        rust.items[0],
+        Item {
+            title: "Rust std & friends".into(),
+            url: Some("https://doc.rust-lang.org/std/?search=%s".into()),
+            added_at: Some(1_700_000_001_000),
+            tags: vec!["docs".into(), "rust".into()],
+            keyword: Some("std".into()),
+            items: Vec::new(),
+        }
+    );
+    assert_eq!(rust.items[1].title, "crates.io");
+    assert_eq!(items[2].title, "Bookmarks Toolbar");
+    assert!(items[2].items.is_empty());
+    assert_eq!(items[3].title, "Servo – 😀");
+    assert_eq!(items.len(), 4);
+
+    let mut store = BookmarkStore::in_memory().unwrap();
+    store
+        .add(&NewBookmark {
+            keyword: Some("std".into()),
+            ..new(None, "Mine", Some("https://example.com/"))
+        })
+        .unwrap();
+    let imported = store.add(&new(None, "Imported", None)).unwrap();
+    // Firefox's own queries are left behind.
+    assert_eq!(store.import(&items, Some(imported.id), 5).unwrap(), 5);
+    let all = store.all().unwrap();
+    assert_eq!(
+        outline(&all),
+        [
+            "Mine",
+            "Imported",
+            "  Rust",
+            "    Rust std & friends",
+            "    crates.io",
+            "  Bookmarks Toolbar",
+            "  Servo – 😀"
+        ]
+    );
+    // The keyword was taken already.
+    assert_eq!(all[3].keyword, None);
+    assert_eq!(all[3].tags, ["docs", "rust"]);
+}
+
+#[test]
+fn exported_bookmarks_import_again() {
+    let mut store = BookmarkStore::in_memory().unwrap();
+    store
+        .import(&bookmarks::html::parse(FIREFOX), None, 5)
+        .unwrap();
+    store
+        .add(&new(
+            None,
+            "<Quotes> \"&\"",
+            Some("https://example.com/?a=1&b=\"2\""),
+        ))
+        .unwrap();
+    let html = store.export().unwrap();
+    assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+    assert!(html.contains(r#"SHORTCUTURL="std" TAGS="docs,rust">Rust std &amp; friends</A>"#));
+
+    let mut copy = BookmarkStore::in_memory().unwrap();
+    copy.import(&bookmarks::html::parse(&html), None, 0)
+        .unwrap();
+    let strip = |bookmarks: Vec<Bookmark>| -> Vec<_> {
+        bookmarks
+            .into_iter()
+            .map(|bookmark| {
+                (
+                    bookmark.index,
+                    bookmark.title,
+                    bookmark.url,
+                    bookmark.tags,
+                    bookmark.keyword,
+                    bookmark.added_at,
+                )
+            })
+            .collect()
+    };
+    assert_eq!(strip(copy.all().unwrap()), strip(store.all().unwrap()));
+}
+
+#[test]
+fn bookmarks_outlive_the_bridge() {
+    let dir = std::env::temp_dir().join(format!("serval-bookmarks-test-{}", std::process::id()));
+    let path = dir.join("profile/bookmarks.sqlite");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut store = BookmarkStore::open(&path).unwrap();
+    store
+        .add(&new(None, "Servo", Some("https://servo.org/")))
+        .unwrap();
+    drop(store);
+    let store = BookmarkStore::open(&path).unwrap();
+    assert_eq!(titles(&store.all().unwrap()), ["Servo"]);
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn expect_bookmarks(client: &Harness) -> Vec<Bookmark> {
+    let Event::BookmarksChanged { bookmarks } = client.expect("bookmarksChanged", |event| {
+        matches!(event, Event::BookmarksChanged { .. })
+    }) else {
+        unreachable!()
+    };
+    bookmarks
+}
+
+#[test]
+fn clients_stay_in_sync_with_the_bookmarks() {
+    let mut main = Harness::start(|waker| {
+        MockEngine::new(waker, Scenario::from_json("{}").unwrap(), 200, 100)
+    });
+    main.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    assert!(expect_bookmarks(&main).is_empty());
+    let other = main.connect(ClientId(2));
+
+    let id = main.send(Command::AddBookmark {
+        parent_id: None,
+        index: None,
+        title: "Docs".into(),
+        url: None,
+        tags: None,
+        keyword: None,
+    });
+    let Event::BookmarkAdded {
+        id: answered,
+        bookmark: folder,
+    } = main.expect("bookmarkAdded", |event| {
+        matches!(event, Event::BookmarkAdded { .. })
+    })
+    else {
+        unreachable!()
+    };
+    assert_eq!(answered, Some(id));
+    assert_eq!(titles(&expect_bookmarks(&other)), ["Docs"]);
+
+    main.send(Command::AddBookmark {
+        parent_id: Some(folder.id),
+        index: None,
+        title: "Rust std".into(),
+        url: Some("https://doc.rust-lang.org/std/?search=%s".into()),
+        tags: Some(vec!["rust".into()]),
+        keyword: Some("std".into()),
+    });
+    let bookmarks = expect_bookmarks(&other);
+    assert_eq!(bookmarks[1].parent_id, Some(folder.id));
+
+    // Bookmarks are suggested, and their keywords typed.
+    main.send(Command::Suggest {
+        text: "rust std".into(),
+        limit: None,
+    });
+    let Event::Suggestions { suggestions, .. } = main.expect("suggestions", |event| {
+        matches!(event, Event::Suggestions { .. })
+    }) else {
+        unreachable!()
+    };
+    assert!(suggestions.iter().any(|suggestion| {
+        suggestion.kind == SuggestionKind::Bookmark && suggestion.title == "Rust std"
+    }));
+    main.send(Command::ResolveInput {
+        text: "std Vec".into(),
+    });
+    main.expect("inputResolved", |event| {
+        matches!(event, Event::InputResolved { url, search_engine: None, .. }
+            if url == "https://doc.rust-lang.org/std/?search=Vec")
+    });
+
+    let id = main.send(Command::SearchBookmarks {
+        text: None,
+        tag: Some("rust".into()),
+        limit: None,
+    });
+    main.expect("bookmarksFound", |event| {
+        matches!(event, Event::BookmarksFound { id: found, bookmarks }
+            if *found == Some(id) && titles(bookmarks) == ["Rust std"])
+    });
+
+    let id = main.send(Command::MoveBookmark {
+        bookmark_id: bookmarks[1].id,
+        parent_id: None,
+        index: Some(0),
+    });
+    main.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+    assert_eq!(titles(&expect_bookmarks(&other)), ["Rust std", "Docs"]);
+
+    let id = main.send(Command::UpdateBookmark {
+        bookmark_id: folder.id,
+        title: None,
+        url: Some("https://example.com/".into()),
+        tags: None,
+        keyword: None,
+    });
+    assert_eq!(main.expect_error(id), ErrorCode::InvalidArgument);
+    let id = main.send(Command::AddBookmark {
+        parent_id: None,
+        index: None,
+        title: "Broken".into(),
+        url: Some("nowhere".into()),
+        tags: None,
+        keyword: None,
+    });
+    assert_eq!(main.expect_error(id), ErrorCode::InvalidUrl);
+
+    let id = main.send(Command::ImportBookmarks {
+        html: FIREFOX.into(),
+        parent_id: Some(folder.id),
+    });
+    main.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+    assert_eq!(expect_bookmarks(&other).len(), 7);
+    main.send(Command::ExportBookmarks {});
+    main.expect("bookmarksExported", |event| {
+        matches!(event, Event::BookmarksExported { html, .. } if html.contains(">crates.io</A>"))
+    });
+
+    let id = main.send(Command::RemoveBookmark {
+        bookmark_id: folder.id,
+    });
+    main.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+    assert_eq!(titles(&expect_bookmarks(&other)), ["Rust std"]);
+    let id = main.send(Command::RemoveBookmark {
+        bookmark_id: folder.id,
+    });
+    assert_eq!(main.expect_error(id), ErrorCode::InvalidArgument);
+
+    // A client that joins later starts from the same bookmarks.
+    let mut late = main.connect(ClientId(3));
+    late.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    assert_eq!(titles(&expect_bookmarks(&late)), ["Rust std"]);
+
+    // Removed bookmarks are not suggested any more.
+    main.send(Command::RemoveBookmark {
+        bookmark_id: bookmarks[1].id,
+    });
+    expect_bookmarks(&main);
+    main.send(Command::Suggest {
+        text: "rust std".into(),
+        limit: None,
+    });
+    main.expect("suggestions", |event| {
+        matches!(event, Event::Suggestions { suggestions, .. }
+            if suggestions.iter().all(|suggestion| suggestion.kind != SuggestionKind::Bookmark))
+    });
+}
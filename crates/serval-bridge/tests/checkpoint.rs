@@ -0,0 +1,334 @@
+//! Saving the open tabs and bringing them back after a restart or a crash.
+
+mod support;
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command as Process, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serval_bridge::checkpoint::{
+    Checkpoint, CheckpointError, FormField, PageState, SavedTab, SessionFile,
+};
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::{Bridge, ClientId};
+use serval_protocol::{Command, ErrorCode, Event, HistoryEntry, TabSnapshot};
+use support::Harness;
+
+const SCENARIO: &str = r#"{
+  "pages": [
+    { "url": "https://example.com/", "title": "Example Domain", "loadMs": 10 },
+    { "url": "https://www.rust-lang.org/", "title": "Rust", "loadMs": 10 },
+    { "url": "https://servo.org/", "title": "Servo", "loadMs": 10 }
+  ]
+}"#;
+
+fn temp_dir(test: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("serval-session-test-{}-{test}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn entry(url: &str, title: &str) -> HistoryEntry {
+    HistoryEntry {
+        url: url.into(),
+        title: title.into(),
+    }
+}
+
+/// Tab `2`, back on example.com with two pages ahead of it.
+fn saved_tab() -> SavedTab {
+    SavedTab {
+        tab_id: "2".into(),
+        url: Some("https://example.com/".into()),
+        title: Some("Example Domain".into()),
+        entries: vec![
+            entry("https://example.com/", "Example Domain"),
+            entry("https://www.rust-lang.org/", "Rust"),
+            entry("https://servo.org/", "Servo"),
+        ],
+        index: 0,
+        state: Some(PageState {
+            scroll_x: 0.0,
+            scroll_y: 480.0,
+            fields: vec![FormField {
+                index: 2,
+                name: Some("q".into()),
+                value: Some("serval".into()),
+                checked: None,
+            }],
+        }),
+    }
+}
+
+fn start(file: SessionFile) -> Harness {
+    Harness::start_bridge(move || {
+        let scenario = Scenario::from_json(SCENARIO).unwrap();
+        Bridge::new(move |waker| MockEngine::new(waker, scenario, 200, 100))
+            .session_file(file)
+            .checkpoint_interval(Duration::from_millis(50))
+    })
+}
+
+fn ready(client: &mut Harness) {
+    client.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    client.expect("ready", |event| matches!(event, Event::Ready { .. }));
+}
+
+fn expect_snapshot(client: &Harness) -> Vec<TabSnapshot> {
+    let Event::TabsSnapshot { tabs } = client.expect("tabsSnapshot", |event| {
+        matches!(event, Event::TabsSnapshot { .. })
+    }) else {
+        unreachable!()
+    };
+    tabs
+}
+
+/// Waits for the bridge to save a checkpoint `done` is happy with.
+fn wait_for(file: &SessionFile, done: impl Fn(&Checkpoint) -> bool) -> Checkpoint {
+    let started = Instant::now();
+    loop {
+        if let Ok(Some(checkpoint)) = file.load()
+            && done(&checkpoint)
+        {
+            return checkpoint;
+        }
+        assert!(
+            started.elapsed() < support::TIMEOUT,
+            "timed out waiting for a checkpoint"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn session_files_round_trip() {
+    let dir = temp_dir("files");
+    let file = SessionFile::new(dir.join("profile/session.json"));
+    assert!(file.load().unwrap().is_none());
+
+    let checkpoint = Checkpoint::new(1_000, true, vec![saved_tab()]);
+    file.save(&checkpoint).unwrap();
+    assert_eq!(file.load().unwrap(), Some(checkpoint));
+    assert!(!dir.join("profile/session.json.partial").exists());
+
+    std::fs::write(file.path(), r#"{ "version": 99, "whatever": [] }"#).unwrap();
+    assert!(matches!(
+        file.load(),
+        Err(CheckpointError::NewerVersion { version: 99, .. })
+    ));
+    std::fs::write(file.path(), "{").unwrap();
+    assert!(matches!(file.load(), Err(CheckpointError::Json { .. })));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn page_states_restore_by_script() {
+    let script = saved_tab().state.unwrap().restore_script();
+    assert!(script.contains(r#""scrollY":480.0"#), "{script}");
+    assert!(script.contains(r#""value":"serval""#), "{script}");
+}
+
+#[test]
+fn the_bridge_saves_open_tabs() {
+    let dir = temp_dir("saves");
+    let file = SessionFile::new(dir.join("session.json"));
+    let mut bridge = start(file.clone());
+
+    // Nothing is open, and the bridge is running.
+    let empty = wait_for(&file, |checkpoint| !checkpoint.clean);
+    assert!(empty.tabs.is_empty());
+
+    bridge.navigate("1", "https://example.com/");
+    bridge.expect_loaded("1", "https://example.com/");
+    bridge.navigate("1", "https://servo.org/");
+    bridge.expect_loaded("1", "https://servo.org/");
+    let checkpoint = wait_for(&file, |checkpoint| {
+        checkpoint
+            .tabs
+            .first()
+            .is_some_and(|tab| tab.entries.len() == 2)
+    });
+    let tab = &checkpoint.tabs[0];
+    assert_eq!(tab.tab_id, "1".into());
+    assert_eq!(tab.url.as_deref(), Some("https://servo.org/"));
+    assert_eq!(tab.index, 1);
+    assert!(!checkpoint.clean);
+
+    bridge.send(Command::Close { tab_id: "1".into() });
+    wait_for(&file, |checkpoint| checkpoint.tabs.is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_session_that_shut_down_comes_back() {
+    let dir = temp_dir("clean");
+    let file = SessionFile::new(dir.join("session.json"));
+    file.save(&Checkpoint::new(1_000, true, vec![saved_tab()]))
+        .unwrap();
+
+    let mut bridge = start(file.clone());
+    // The restored tab loads its page again before the client asks for it.
+    bridge.expect_loaded("2", "https://example.com/");
+    ready(&mut bridge);
+    let tabs = expect_snapshot(&bridge);
+    assert_eq!(tabs, [saved_tab().snapshot()]);
+    // Its whole history came back.
+    bridge.send(Command::Forward { tab_id: "2".into() });
+    bridge.expect_loaded("2", "https://www.rust-lang.org/");
+
+    // Until it shuts down again, the session counts as crashed.
+    let checkpoint = wait_for(&file, |checkpoint| checkpoint.tabs[0].index == 1);
+    assert!(!checkpoint.clean);
+    // The restored state belonged to the page it left.
+    assert_eq!(checkpoint.tabs[0].state, None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_crashed_session_is_offered_first() {
+    let dir = temp_dir("crashed");
+    let file = SessionFile::new(dir.join("session.json"));
+    file.save(&Checkpoint::new(1_000, false, vec![saved_tab()]))
+        .unwrap();
+
+    let mut main = start(file.clone());
+    ready(&mut main);
+    let Event::CrashedSession { saved_at, tabs } = main.expect("crashedSession", |event| {
+        matches!(event, Event::CrashedSession { .. })
+    }) else {
+        unreachable!()
+    };
+    assert_eq!(saved_at, 1_000);
+    assert_eq!(tabs, [saved_tab().snapshot()]);
+    assert!(expect_snapshot(&main).is_empty());
+    // Asking is not answering: the offer outlives checkpoints.
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(file.load().unwrap().unwrap().saved_at, 1_000);
+
+    let mut other = main.connect(ClientId(2));
+    ready(&mut other);
+    other.expect("crashedSession", |event| {
+        matches!(event, Event::CrashedSession { .. })
+    });
+    let id = other.send(Command::RestoreSession {});
+    for client in [&main, &other] {
+        client.expect(
+            "answered crashedSession",
+            |event| matches!(event, Event::CrashedSession { tabs, .. } if tabs.is_empty()),
+        );
+        assert_eq!(expect_snapshot(client)[0].entries, saved_tab().entries);
+    }
+    other.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+    main.expect_loaded("2", "https://example.com/");
+
+    let id = main.send(Command::RestoreSession {});
+    assert_eq!(main.expect_error(id), ErrorCode::InvalidArgument);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_crashed_session_can_be_discarded() {
+    let dir = temp_dir("discarded");
+    let file = SessionFile::new(dir.join("session.json"));
+    file.save(&Checkpoint::new(1_000, false, vec![saved_tab()]))
+        .unwrap();
+
+    let mut bridge = start(file.clone());
+    let id = bridge.send(Command::DiscardSession {});
+    bridge.expect(
+        "answered crashedSession",
+        |event| matches!(event, Event::CrashedSession { tabs, .. } if tabs.is_empty()),
+    );
+    bridge.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+    wait_for(&file, |checkpoint| checkpoint.tabs.is_empty());
+
+    // Clients that connect later are not asked any more.
+    let mut other = bridge.connect(ClientId(2));
+    ready(&mut other);
+    other.expect("tabsSnapshot", |event| {
+        assert!(!matches!(event, Event::CrashedSession { .. }));
+        matches!(event, Event::TabsSnapshot { tabs } if tabs.is_empty())
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Runs `serval-bridge --stdio` on the mock engine, saving to `session`.
+fn spawn(session: &Path) -> std::process::Child {
+    let dir = session.parent().unwrap();
+    let scenario = dir.join("scenario.json");
+    std::fs::write(&scenario, SCENARIO).unwrap();
+    Process::new(env!("CARGO_BIN_EXE_serval-bridge"))
+        .args(["--stdio", "--mock"])
+        .arg(scenario)
+        .arg("--session")
+        .arg(session)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+#[test]
+fn the_bridge_saves_the_session_when_stdin_closes() {
+    let dir = temp_dir("stdin");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("session.json");
+
+    let mut child = spawn(&path);
+    let mut stdin = child.stdin.take().unwrap();
+    let mut events = BufReader::new(child.stdout.take().unwrap()).lines();
+    writeln!(stdin, r#"{{"type":"ready"}}"#).unwrap();
+    writeln!(
+        stdin,
+        r#"{{"type":"navigate","tabId":"1","url":"https://servo.org/"}}"#
+    )
+    .unwrap();
+    stdin.flush().unwrap();
+    for line in events.by_ref() {
+        let event = serval_protocol::decode_event(&line.unwrap()).unwrap();
+        if matches!(event, Event::LoadComplete { .. }) {
+            break;
+        }
+    }
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+
+    let checkpoint = SessionFile::new(&path).load().unwrap().unwrap();
+    assert!(checkpoint.clean);
+    assert_eq!(
+        checkpoint.tabs[0].entries,
+        [entry("https://servo.org/", "Servo")]
+    );
+
+    // The next run brings it back.
+    let mut child = spawn(&path);
+    let mut stdin = child.stdin.take().unwrap();
+    let mut events = BufReader::new(child.stdout.take().unwrap()).lines();
+    writeln!(stdin, r#"{{"type":"ready"}}"#).unwrap();
+    stdin.flush().unwrap();
+    let tabs = loop {
+        let line = events.next().expect("a tabsSnapshot").unwrap();
+        if let Event::TabsSnapshot { tabs } = serval_protocol::decode_event(&line).unwrap() {
+            break tabs;
+        }
+    };
+    assert_eq!(tabs[0].url.as_deref(), Some("https://servo.org/"));
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
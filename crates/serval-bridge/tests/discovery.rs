@@ -0,0 +1,183 @@
+//! Bridges publish how to reach them, and `--discover` finds or launches one.
+
+#![cfg(unix)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serval_bridge::discovery::{self, Discovery, NotFound};
+use serval_protocol::{Event, PROTOCOL_VERSION, decode_event};
+use tungstenite::Message;
+
+fn discovery_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("serval-discovery-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn bridge(path: &Path) -> Command {
+    let scenario = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_serval-bridge"));
+    command
+        .args(["--listen", "127.0.0.1:0", "--mock"])
+        .arg(scenario)
+        .arg("--discovery-file")
+        .arg(path)
+        .env_remove("SERVAL_BRIDGE_TOKEN");
+    command
+}
+
+fn wait_for(path: &Path) -> Discovery {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match discovery::find(path) {
+            Ok(found) => return found,
+            Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            Err(error) => panic!("no discovery file: {error}"),
+        }
+    }
+}
+
+fn stop(pid: u32) {
+    // SAFETY: plain `kill` of a process the test started.
+    unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+}
+
+/// Runs `command`, returning its stdout and what it logged. Its stderr goes
+/// to a file: a bridge it launches keeps it open.
+fn run(command: &mut Command) -> (bool, Vec<u8>, String) {
+    let log = std::env::temp_dir().join(format!(
+        "serval-discovery-test-{}/{:?}.log",
+        std::process::id(),
+        thread::current().id()
+    ));
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(fs::File::create(&log).unwrap())
+        .output()
+        .unwrap();
+    let logged = fs::read_to_string(&log).unwrap();
+    (output.status.success(), output.stdout, logged)
+}
+
+fn discover(path: &Path, launch: bool) -> (bool, Vec<u8>, String) {
+    let mut command = bridge(path);
+    command.arg("--discover");
+    if launch {
+        command.arg("--launch");
+    }
+    run(&mut command)
+}
+
+#[test]
+fn bridges_publish_how_to_reach_them() {
+    let path = discovery_path("published.json");
+    let mut child: Child = bridge(&path).stderr(Stdio::null()).spawn().unwrap();
+    let found = wait_for(&path);
+    assert_eq!(found.pid, child.id());
+    assert_eq!(found.protocol_version, PROTOCOL_VERSION);
+    let port = found.port.unwrap();
+    assert_eq!(
+        found.url.as_deref(),
+        Some(&*format!("ws://127.0.0.1:{port}"))
+    );
+    let mode = fs::metadata(&path).unwrap();
+    assert_eq!(
+        std::os::unix::fs::PermissionsExt::mode(&mode.permissions()) & 0o077,
+        0
+    );
+
+    // What it published is enough to connect.
+    let token = found.token.unwrap();
+    let (mut socket, _) =
+        tungstenite::connect(format!("{}/?token={token}", found.url.unwrap())).unwrap();
+    socket
+        .send(Message::text(r#"{"type":"ready","id":1}"#))
+        .unwrap();
+    loop {
+        let Message::Text(text) = socket.read().unwrap() else {
+            continue;
+        };
+        if matches!(decode_event(&text).unwrap(), Event::Ready { .. }) {
+            break;
+        }
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn files_of_bridges_that_are_gone_are_ignored() {
+    let path = discovery_path("gone.json");
+    assert!(matches!(discovery::find(&path), Err(NotFound::Missing(_))));
+
+    let mut exited = Command::new("true").spawn().unwrap();
+    let pid = exited.id();
+    exited.wait().unwrap();
+    Discovery {
+        pid,
+        ..Discovery::new()
+    }
+    .publish(&path)
+    .unwrap();
+    assert!(matches!(discovery::find(&path), Err(NotFound::Gone { .. })));
+
+    Discovery {
+        protocol_version: PROTOCOL_VERSION + 1,
+        ..Discovery::new()
+    }
+    .publish(&path)
+    .unwrap();
+    assert!(matches!(
+        discovery::find(&path),
+        Err(NotFound::Incompatible { .. })
+    ));
+}
+
+#[test]
+fn discovering_without_a_bridge_fails_clearly() {
+    let path = discovery_path("missing.json");
+    let (success, _, logged) = discover(&path, false);
+    assert!(!success);
+    assert!(logged.contains("no bridge runs"), "{logged}");
+}
+
+#[test]
+fn discovering_launches_a_bridge_when_none_runs() {
+    let path = discovery_path("launched.json");
+    let (success, stdout, logged) = discover(&path, true);
+    assert!(success, "{logged}");
+    let launched: Discovery = serde_json::from_slice(&stdout).unwrap();
+    assert!(launched.is_running());
+
+    // The next run finds it instead of launching another.
+    let (success, stdout, logged) = discover(&path, true);
+    assert!(success, "{logged}");
+    let found: Discovery = serde_json::from_slice(&stdout).unwrap();
+    assert_eq!(found, launched);
+    stop(launched.pid);
+}
+
+#[test]
+fn failed_launches_are_reported() {
+    let path = discovery_path("failed.json");
+    let (success, _, logged) = run(Command::new(env!("CARGO_BIN_EXE_serval-bridge"))
+        .args([
+            "--discover",
+            "--launch",
+            "--listen",
+            "127.0.0.1:0",
+            "--mock",
+        ])
+        .arg(path.with_extension("missing-scenario"))
+        .arg("--discovery-file")
+        .arg(&path));
+    assert!(!success);
+    assert!(logged.contains("exited before it listened"), "{logged}");
+}
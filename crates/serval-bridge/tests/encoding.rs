@@ -0,0 +1,264 @@
+//! Binary encodings negotiated in `ready`, over WebSocket and stdio.
+
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command as Process, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use serval_bridge::Bridge;
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::server::{self, Access};
+use serval_bridge::stdio::{FrameReader, Framing, write_frame};
+use serval_protocol::{Command, Encoding, Envelope, ErrorCode, Event, PROTOCOL_VERSION, Request};
+use tungstenite::Message;
+
+const TOKEN: &str = "0123456789abcdef";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn scenario_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json")
+}
+
+fn ready(encoding: Encoding) -> Request {
+    Request::with_id(
+        1,
+        Command::Ready {
+            protocol_version: None,
+            resume: None,
+            encoding: Some(encoding),
+            ack_frames: None,
+        },
+    )
+}
+
+fn navigate() -> Request {
+    Request::with_id(
+        2,
+        Command::Navigate {
+            tab_id: "1".into(),
+            url: "http://example.com/".to_owned(),
+        },
+    )
+}
+
+fn expect(events: &Receiver<Event>, what: &str, predicate: impl Fn(&Event) -> bool) -> Event {
+    loop {
+        match events.recv_timeout(Duration::from_secs(10)) {
+            Ok(event) if predicate(&event) => return event,
+            Ok(_) => {}
+            Err(_) => panic!("timed out waiting for {what}"),
+        }
+    }
+}
+
+fn expect_ready(events: &Receiver<Event>) -> Encoding {
+    let Event::Ready { encoding, .. } = expect(events, "ready", |event| {
+        matches!(event, Event::Ready { .. })
+    }) else {
+        unreachable!()
+    };
+    encoding
+}
+
+/// Waits for a page load with at least one tile, and checks the tile holds
+/// PNG bytes rather than base64.
+fn expect_page(events: &Receiver<Event>) {
+    expect(events, "ack", |event| matches!(event, Event::Ack { id: 2 }));
+    let Event::Frame { tiles, .. } = expect(events, "frame", |event| {
+        matches!(event, Event::Frame { .. })
+    }) else {
+        unreachable!()
+    };
+    assert!(tiles[0].data.as_bytes().starts_with(PNG_SIGNATURE));
+    expect(events, "loadComplete", |event| {
+        matches!(event, Event::LoadComplete { .. })
+    });
+}
+
+fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let access = Access::new(TOKEN, Vec::new());
+    thread::spawn(move || {
+        let scenario = Scenario::load(scenario_path()).unwrap();
+        let bridge = Bridge::new(|waker| MockEngine::new(waker, scenario, 200, 100));
+        server::serve(listener, bridge.inputs(), access);
+        bridge.run()
+    });
+    addr
+}
+
+#[test]
+fn websocket_clients_switch_to_binary_messages() {
+    for encoding in [Encoding::MessagePack, Encoding::Cbor] {
+        let addr = start_server();
+        let (mut socket, _) = tungstenite::connect(format!("ws://{addr}/?token={TOKEN}")).unwrap();
+        // The switch follows `ready` right away, without waiting for the
+        // answer.
+        let ready = serval_protocol::encode(&ready(encoding));
+        socket.send(Message::text(ready)).unwrap();
+        socket
+            .send(Message::binary(encoding.encode(&navigate())))
+            .unwrap();
+
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(message) = socket.read() {
+                let event = match message {
+                    Message::Text(text) => serval_protocol::decode_event(&text).unwrap(),
+                    Message::Binary(bytes) => encoding.decode::<Envelope>(&bytes).unwrap().event,
+                    _ => continue,
+                };
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+        });
+        assert_eq!(expect_ready(&events), encoding);
+        expect_page(&events);
+    }
+}
+
+#[test]
+fn refused_ready_keeps_websocket_clients_on_json() {
+    let addr = start_server();
+    let (mut socket, _) = tungstenite::connect(format!("ws://{addr}/?token={TOKEN}")).unwrap();
+    let mut request = ready(Encoding::MessagePack);
+    if let Command::Ready {
+        protocol_version, ..
+    } = &mut request.command
+    {
+        *protocol_version = Some(PROTOCOL_VERSION + 1);
+    }
+    // The bridge refuses the `ready`, so the message behind it is still
+    // read as JSON.
+    socket
+        .send(Message::text(serval_protocol::encode(&request)))
+        .unwrap();
+    socket
+        .send(Message::text(serval_protocol::encode(&navigate())))
+        .unwrap();
+
+    let (sender, events) = mpsc::channel();
+    thread::spawn(move || {
+        while let Ok(message) = socket.read() {
+            let event = match message {
+                Message::Text(text) => serval_protocol::decode_event(&text).unwrap(),
+                Message::Binary(_) => panic!("binary message after a refused ready"),
+                _ => continue,
+            };
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    });
+    assert_eq!(expect_ready(&events), Encoding::Json);
+    expect(&events, "unsupportedProtocol", |event| {
+        matches!(
+            event,
+            Event::Error {
+                code: ErrorCode::UnsupportedProtocol,
+                ..
+            }
+        )
+    });
+    expect_page(&events);
+}
+
+/// Runs `serval-bridge --stdio` with `framing`, returning its stdin and the
+/// events it writes, decoded with the encoding it announces in `ready`.
+fn start_stdio(framing: &str) -> (Child, ChildStdin, Receiver<Event>) {
+    let mut child = Process::new(env!("CARGO_BIN_EXE_serval-bridge"))
+        .args(["--stdio", "--framing", framing, "--mock"])
+        .arg(scenario_path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let framing = if framing == "length" {
+        Framing::Length
+    } else {
+        Framing::Lines
+    };
+
+    let (sender, events) = mpsc::channel();
+    thread::spawn(move || {
+        let mut frames = FrameReader::new(BufReader::new(stdout), framing);
+        let mut current = Encoding::Json;
+        while let Ok(Some(frame)) = frames.next_binary_frame() {
+            let event = current.decode::<Envelope>(&frame.unwrap()).unwrap().event;
+            if let Event::Ready { encoding, .. } = event {
+                current = encoding;
+            }
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    });
+    (child, stdin, events)
+}
+
+#[test]
+fn length_framed_stdio_carries_binary_messages() {
+    for encoding in [Encoding::MessagePack, Encoding::Cbor] {
+        let (mut child, mut stdin, events) = start_stdio("length");
+        let ready = serval_protocol::encode(&ready(encoding));
+        write_frame(&mut stdin, Framing::Length, ready).unwrap();
+        write_frame(&mut stdin, Framing::Length, encoding.encode(&navigate())).unwrap();
+        assert_eq!(expect_ready(&events), encoding);
+        expect_page(&events);
+        drop(stdin);
+        child.wait().unwrap();
+    }
+}
+
+#[test]
+fn line_framed_stdio_stays_on_json() {
+    let (mut child, mut stdin, events) = start_stdio("lines");
+    let ready = serval_protocol::encode(&ready(Encoding::MessagePack));
+    write_frame(&mut stdin, Framing::Lines, ready).unwrap();
+    assert_eq!(expect_ready(&events), Encoding::Json);
+
+    write_frame(
+        &mut stdin,
+        Framing::Lines,
+        serval_protocol::encode(&navigate()),
+    )
+    .unwrap();
+    expect_page(&events);
+    drop(stdin);
+    child.wait().unwrap();
+}
+
+#[test]
+fn malformed_binary_messages_are_rejected() {
+    let (mut child, mut stdin, events) = start_stdio("length");
+    let ready = serval_protocol::encode(&ready(Encoding::MessagePack));
+    write_frame(&mut stdin, Framing::Length, ready).unwrap();
+    // `{"type": "nope", "id": 7}`, then a truncated map.
+    write_frame(
+        &mut stdin,
+        Framing::Length,
+        b"\x82\xa4type\xa4nope\xa2id\x07",
+    )
+    .unwrap();
+    write_frame(&mut stdin, Framing::Length, b"\x82\xa4type").unwrap();
+
+    let error = |id: Option<u64>| {
+        move |event: &Event| {
+            matches!(
+                event,
+                Event::Error { id: got, code: ErrorCode::InvalidMessage, .. } if *got == id
+            )
+        }
+    };
+    expect(&events, "unknown type", error(Some(7)));
+    expect(&events, "syntax error", error(None));
+    drop(stdin);
+    child.wait().unwrap();
+}
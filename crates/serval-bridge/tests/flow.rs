@@ -0,0 +1,106 @@
+//! Flow control towards clients that read slower than pages change.
+
+mod support;
+
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use serval_bridge::Bridge;
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::outbox::FlowLimits;
+use serval_protocol::{Command, Event};
+use support::Harness;
+
+/// Long enough for the mock engine to play every step of `slow.test`.
+const SETTLE: Duration = Duration::from_millis(600);
+
+fn scenario() -> Scenario {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    Scenario::load(path).unwrap()
+}
+
+fn ready(client: &mut Harness, ack_frames: bool) {
+    client.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: Some(ack_frames),
+    });
+    client.expect("ready", |event| matches!(event, Event::Ready { .. }));
+}
+
+fn is_frame(event: &Event) -> bool {
+    matches!(event, Event::Frame { .. })
+}
+
+#[test]
+fn clients_that_fall_behind_get_only_the_latest_title() {
+    let mut main = Harness::start(|waker| MockEngine::new(waker, scenario(), 200, 100));
+    main.navigate("1", "https://slow.test/");
+    thread::sleep(SETTLE);
+
+    let events = main.collect("loadComplete", |event| {
+        matches!(event, Event::LoadComplete { .. })
+    });
+    let titles: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::TitleChange { title, .. } => Some(title.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(titles, ["Slow"]);
+    let histories = events
+        .iter()
+        .filter(|event| matches!(event, Event::HistoryChanged { .. }))
+        .count();
+    assert_eq!(histories, 1);
+    assert!(main.counters().coalesced() >= 4);
+}
+
+#[test]
+fn unacknowledged_frames_wait_for_frame_shown() {
+    let mut main = Harness::start(|waker| MockEngine::new(waker, scenario(), 200, 100));
+    main.navigate("1", "https://example.com/");
+    main.expect_loaded("1", "https://example.com/");
+    ready(&mut main, true);
+
+    // The window lets two frames through.
+    for width in [101, 102] {
+        main.resize("1", width as f32, 100.0, 1.0);
+        main.expect(
+            "a frame",
+            |event| matches!(event, Event::Frame { width: got, .. } if *got == width),
+        );
+    }
+    for width in [103, 104, 105] {
+        main.resize("1", width as f32, 100.0, 1.0);
+    }
+    main.expect_viewport("1", 105, 100, 1.0);
+    thread::sleep(Duration::from_millis(100));
+    assert!(!main.pending().iter().any(is_frame));
+    assert!(main.counters().dropped() >= 2);
+
+    main.send(Command::FrameShown { tab_id: "1".into() });
+    main.expect("the latest frame", |event| {
+        matches!(event, Event::Frame { width: 105, .. })
+    });
+}
+
+#[test]
+fn clients_too_far_behind_are_dropped() {
+    let main = Harness::start_bridge(|| {
+        Bridge::new(|waker| MockEngine::new(waker, scenario(), 200, 100)).flow_limits(FlowLimits {
+            capacity: 8,
+            ..FlowLimits::default()
+        })
+    });
+    let mut other = main.connect(serval_bridge::ClientId(2));
+    for tab in ["1", "2", "3", "4"] {
+        other.navigate(tab, "https://example.com/");
+        other.expect_loaded(tab, "https://example.com/");
+    }
+    main.expect_dropped();
+    assert!(main.counters().dropped() > 8);
+}
@@ -0,0 +1,86 @@
+//! The `ready` handshake: protocol version and capabilities.
+
+mod support;
+
+use std::time::Duration;
+
+use serval_bridge::engine::{MockEngine, ProcessEngine, Scenario};
+use serval_protocol::{Capability, Command, ErrorCode, Event, PROTOCOL_VERSION};
+use support::Harness;
+
+fn expect_ready(bridge: &Harness) -> (u32, Option<String>, Vec<Capability>) {
+    let event = bridge.expect("ready", |event| matches!(event, Event::Ready { .. }));
+    let Event::Ready {
+        protocol_version,
+        servo_version,
+        capabilities,
+        ..
+    } = event
+    else {
+        unreachable!()
+    };
+    (protocol_version, servo_version, capabilities)
+}
+
+#[test]
+fn ready_announces_the_version_and_capabilities() {
+    let mut bridge = Harness::start(|waker| MockEngine::new(waker, Scenario::default(), 200, 100));
+    let id = bridge.send(Command::Ready {
+        protocol_version: Some(PROTOCOL_VERSION),
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    assert_eq!(
+        expect_ready(&bridge),
+        (PROTOCOL_VERSION, None, MockEngine::CAPABILITIES.to_vec())
+    );
+    bridge.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+}
+
+#[test]
+fn other_protocol_versions_are_refused_after_ready() {
+    let mut bridge = Harness::start(|waker| MockEngine::new(waker, Scenario::default(), 200, 100));
+    let id = bridge.send(Command::Ready {
+        protocol_version: Some(PROTOCOL_VERSION + 1),
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    assert_eq!(expect_ready(&bridge).0, PROTOCOL_VERSION);
+    assert_eq!(bridge.expect_error(id), ErrorCode::UnsupportedProtocol);
+}
+
+#[test]
+fn the_supervisor_adds_crash_recovery() {
+    let mut bridge = Harness::start(|waker| {
+        ProcessEngine::new(
+            waker,
+            env!("CARGO_BIN_EXE_serval-fake-engine"),
+            Vec::new(),
+            Duration::from_secs(10),
+        )
+        .describing(&[Capability::Frames, Capability::Input], Some("1.2.3"))
+    });
+    bridge.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    assert_eq!(
+        expect_ready(&bridge),
+        (
+            PROTOCOL_VERSION,
+            Some("1.2.3".to_owned()),
+            vec![
+                Capability::Frames,
+                Capability::Input,
+                Capability::CrashRecovery
+            ]
+        )
+    );
+}
@@ -0,0 +1,196 @@
+//! Input forwarding, checked with the fake content process from
+//! `tests/support/fake_engine.rs`, which echoes every event it receives into
+//! its title, and, with the `servo` feature, with Servo against
+//! `tests/fixtures/input-echo.html`, which does the same from the page.
+
+mod support;
+
+use std::time::Duration;
+
+use serval_bridge::engine::ProcessEngine;
+use serval_protocol::{
+    Command, ErrorCode, Event, InputEvent, KeyInput, KeyLocation, Modifiers, PointerType, TabId,
+    WheelDeltaMode,
+};
+use support::Harness;
+
+fn start() -> Harness {
+    Harness::start(|waker| {
+        ProcessEngine::new(
+            waker,
+            env!("CARGO_BIN_EXE_serval-fake-engine"),
+            Vec::new(),
+            Duration::from_secs(10),
+        )
+    })
+}
+
+fn key(key: &str, code: &str, modifiers: Modifiers) -> KeyInput {
+    KeyInput {
+        key: key.to_owned(),
+        code: code.to_owned(),
+        location: KeyLocation::Standard,
+        modifiers,
+        repeat: false,
+        is_composing: false,
+    }
+}
+
+/// One event of each kind, with the title `input-echo.html` gives it.
+fn events() -> Vec<(InputEvent, &'static str)> {
+    let shift = Modifiers {
+        shift: true,
+        ..Modifiers::default()
+    };
+    vec![
+        // Mouse events arrive at page coordinates.
+        (
+            InputEvent::PointerMove {
+                x: 10.0,
+                y: 20.0,
+                pointer_type: PointerType::Mouse,
+                pointer_id: 0,
+            },
+            "mousemove 10 20",
+        ),
+        (
+            InputEvent::PointerDown {
+                x: 30.0,
+                y: 40.0,
+                pointer_type: PointerType::Mouse,
+                pointer_id: 0,
+                button: 2,
+            },
+            "mousedown 30 40 2",
+        ),
+        (
+            InputEvent::PointerUp {
+                x: 30.0,
+                y: 40.0,
+                pointer_type: PointerType::Mouse,
+                pointer_id: 0,
+                button: 2,
+            },
+            "mouseup 30 40 2",
+        ),
+        // Touch events carry their identifier.
+        (
+            InputEvent::PointerDown {
+                x: 50.0,
+                y: 60.0,
+                pointer_type: PointerType::Touch,
+                pointer_id: 7,
+                button: 0,
+            },
+            "touchstart 50 60 7",
+        ),
+        // Wheel deltas keep the DOM direction.
+        (
+            InputEvent::Wheel {
+                x: 5.0,
+                y: 5.0,
+                delta_x: 0.0,
+                delta_y: 3.0,
+                delta_z: 0.0,
+                delta_mode: WheelDeltaMode::Line,
+            },
+            "wheel 5 5 0 3 1",
+        ),
+        // Key events carry key, code and modifiers.
+        (
+            InputEvent::KeyDown(key("A", "KeyA", shift)),
+            "keydown A KeyA 0 shift",
+        ),
+        (
+            InputEvent::KeyUp(key("Enter", "Enter", Modifiers::default())),
+            "keyup Enter Enter 0 none",
+        ),
+        // Composition reaches the focused field. `document.title` strips
+        // trailing whitespace.
+        (
+            InputEvent::CompositionStart {
+                data: String::new(),
+            },
+            "compositionstart",
+        ),
+        (
+            InputEvent::CompositionUpdate {
+                data: "にほ".to_owned(),
+            },
+            "compositionupdate にほ",
+        ),
+        (
+            InputEvent::CompositionEnd {
+                data: "日本".to_owned(),
+            },
+            "compositionend 日本",
+        ),
+    ]
+}
+
+#[test]
+fn input_reaches_its_tab() {
+    let mut bridge = start();
+    for tab in ["1", "2"] {
+        bridge.navigate(tab, "about:blank");
+        bridge.expect_loaded(tab, "about:blank");
+    }
+
+    // Alternating between the tabs, each event lands in the one it names
+    // and nowhere else.
+    for (index, (event, _)) in events().into_iter().enumerate() {
+        let (tab, other) = if index % 2 == 0 {
+            ("1", "2")
+        } else {
+            ("2", "1")
+        };
+        let title = format!("{event:?}");
+        let id = bridge.input(tab, event);
+        let (tab, other) = (TabId::from(tab), TabId::from(other));
+        let events = bridge.collect(&format!("title `{title}`"), |event| {
+            matches!(event, Event::TitleChange { tab_id, title: changed }
+                if *tab_id == tab && *changed == title)
+        });
+        assert!(events.contains(&Event::Ack { id }));
+        assert!(
+            !events.iter().any(|event| matches!(
+                event,
+                Event::TitleChange { tab_id, .. } if *tab_id == other
+            )),
+            "{title} reached tab {other}"
+        );
+    }
+}
+
+#[test]
+fn input_for_closed_tabs_is_refused() {
+    let mut bridge = start();
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+    bridge.send(Command::Close { tab_id: "1".into() });
+
+    let id = bridge.input("1", InputEvent::PointerLeave);
+    assert_eq!(bridge.expect_error(id), ErrorCode::TabNotFound);
+}
+
+/// Servo can only be started once per process, so this is the only test that
+/// uses it.
+#[cfg(feature = "servo")]
+#[test]
+fn input_reaches_the_page() {
+    use std::path::Path;
+
+    use serval_bridge::engine::ServoEngine;
+    use url::Url;
+
+    let mut bridge = Harness::start(|waker| ServoEngine::new(waker, 400, 300));
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/input-echo.html");
+    let url = Url::from_file_path(path).unwrap();
+    bridge.navigate("1", url.as_str());
+    bridge.expect_loaded("1", url.as_str());
+
+    for (event, title) in events() {
+        bridge.input("1", event);
+        bridge.expect_title("1", title);
+    }
+}
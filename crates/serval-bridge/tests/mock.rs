@@ -0,0 +1,200 @@
+//! The scripted mock engine, played from `tests/fixtures/scenario.json`.
+
+mod support;
+
+use std::path::Path;
+
+use serval_bridge::ClientId;
+use serval_bridge::engine::{MockEngine, ProcessEngine, Scenario, ScenarioError};
+use serval_protocol::{Command, ErrorCode, Event};
+use support::{Harness, TIMEOUT};
+
+fn scenario() -> Scenario {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    Scenario::load(path).unwrap()
+}
+
+fn start() -> Harness {
+    let scenario = scenario();
+    Harness::start(move |waker| MockEngine::new(waker, scenario, 200, 100))
+}
+
+/// The events of a load, with frames left out, as `kind url-or-title`.
+fn summary(events: &[Event]) -> Vec<String> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::LoadStart { url, .. } => Some(format!("loadStart {url}")),
+            Event::UrlChange { url, .. } => Some(format!("urlChange {url}")),
+            Event::TitleChange { title, .. } => Some(format!("titleChange {title}")),
+            Event::LoadComplete { url, .. } => Some(format!("loadComplete {url}")),
+            Event::TabCrashed { url, .. } => {
+                Some(format!("tabCrashed {}", url.as_deref().unwrap_or("")))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn load(bridge: &mut Harness, tab: &str, url: &str) -> Vec<String> {
+    bridge.navigate(tab, url);
+    summary(&bridge.collect("loadComplete", |event| {
+        matches!(event, Event::LoadComplete { .. })
+    }))
+}
+
+#[test]
+fn redirects_replace_the_url() {
+    let mut bridge = start();
+    assert_eq!(
+        load(&mut bridge, "1", "http://example.com/"),
+        [
+            "loadStart http://example.com/",
+            "urlChange https://example.com/",
+            "titleChange Example Domain",
+            "loadComplete https://example.com/",
+        ]
+    );
+
+    bridge.navigate("1", "about:blank");
+    let events = bridge.collect("loadComplete", |event| {
+        matches!(event, Event::LoadComplete { .. })
+    });
+    let entries = events.iter().rev().find_map(|event| match event {
+        Event::HistoryChanged { entries, .. } => Some(entries),
+        _ => None,
+    });
+    let urls: Vec<_> = entries.unwrap().iter().map(|entry| &entry.url).collect();
+    assert_eq!(urls, ["https://example.com/", "about:blank"]);
+}
+
+#[test]
+fn redirect_loops_stop() {
+    let mut bridge = start();
+    let events = load(&mut bridge, "1", "https://loop.test/");
+    assert_eq!(events.first().unwrap(), "loadStart https://loop.test/");
+    assert_eq!(events.last().unwrap(), "loadComplete https://loop.test/");
+    assert_eq!(events.len(), 22);
+}
+
+#[test]
+fn titles_change_while_loading() {
+    let mut bridge = start();
+    assert_eq!(
+        load(&mut bridge, "1", "https://slow.test/"),
+        [
+            "loadStart https://slow.test/",
+            "titleChange Loading",
+            "titleChange Almost",
+            "titleChange Slow",
+            "loadComplete https://slow.test/",
+        ]
+    );
+}
+
+#[test]
+fn loaded_pages_are_painted() {
+    let mut bridge = start();
+    bridge.resize("1", 100.0, 50.0, 2.0);
+    bridge.navigate("1", "https://example.com/");
+    bridge.expect_viewport("1", 200, 100, 1.0);
+    let frame = bridge.expect("frame", |event| matches!(event, Event::Frame { .. }));
+    let Event::Frame { width, height, .. } = frame else {
+        unreachable!()
+    };
+    assert_eq!((width, height), (200, 100));
+}
+
+#[test]
+fn host_lookups_follow_the_scenario() {
+    let mut bridge = start();
+    let id = bridge.navigate("1", "https://nowhere.test/");
+    assert_eq!(bridge.expect_error(id), ErrorCode::DnsFailure);
+    // Hosts the scenario does not list do not exist either.
+    let id = bridge.navigate("1", "https://unknown.test/");
+    assert_eq!(bridge.expect_error(id), ErrorCode::DnsFailure);
+}
+
+#[test]
+fn scripted_crashes_stop_the_load() {
+    let mut bridge = start();
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+    bridge.navigate("1", "https://crash.test/");
+    let Event::TabCrashed { url, signal, .. } = bridge.expect_crash("1") else {
+        unreachable!()
+    };
+    assert_eq!(url.as_deref(), Some("https://crash.test/"));
+    assert_eq!(signal, Some(11));
+
+    let id = bridge.send(Command::Refresh { tab_id: "1".into() });
+    assert_eq!(bridge.expect_error(id), ErrorCode::TabCrashed);
+
+    // Reloading replays the script, crash included.
+    bridge.send(Command::ReloadCrashed { tab_id: "1".into() });
+    bridge.expect("loadStart", |event| {
+        matches!(event, Event::LoadStart { .. })
+    });
+    bridge.expect_crash("1");
+}
+
+#[test]
+fn runs_are_repeatable() {
+    let run = || {
+        let mut bridge = start();
+        let mut events = load(&mut bridge, "1", "http://example.com/");
+        events.extend(load(&mut bridge, "1", "https://slow.test/"));
+        events
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn content_processes_play_the_scenario() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    let mut bridge = Harness::start(move |waker| {
+        ProcessEngine::new(
+            waker,
+            env!("CARGO_BIN_EXE_serval-bridge"),
+            vec!["--content-process".into(), "--mock".into(), path.into()],
+            TIMEOUT,
+        )
+        .resolving_hosts()
+    });
+    assert_eq!(
+        load(&mut bridge, "1", "http://example.com/"),
+        [
+            "loadStart http://example.com/",
+            "urlChange https://example.com/",
+            "titleChange Example Domain",
+            "loadComplete https://example.com/",
+        ]
+    );
+    // Failures come back from the content process to the client that asked.
+    let other = bridge.connect(ClientId(2));
+    let id = bridge.navigate("2", "https://nowhere.test/");
+    assert_eq!(bridge.expect_error(id), ErrorCode::DnsFailure);
+    let id = bridge.navigate("1", "https://example.com/");
+    bridge.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+    bridge.expect_loaded("1", "https://example.com/");
+    assert!(
+        !other
+            .pending()
+            .iter()
+            .any(|event| matches!(event, Event::Error { .. } | Event::Ack { .. }))
+    );
+}
+
+#[test]
+fn scenarios_are_checked() {
+    let error = Scenario::from_json(r#"{ "pages": [{ "url": "x", "color": "blue" }] }"#);
+    assert!(matches!(error, Err(ScenarioError::Color { .. })));
+    let error = Scenario::from_json(r#"{ "pages": [{ "url": "x", "loadMs": "soon" }] }"#);
+    assert!(matches!(error, Err(ScenarioError::Json(_))));
+
+    let dev = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../scenarios/dev.json");
+    Scenario::load(dev).unwrap();
+}
@@ -0,0 +1,196 @@
+//! What text typed in the address bar loads.
+
+mod support;
+
+use std::path::Path;
+
+use serval_bridge::Bridge;
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::omnibox::{self, SearchEngine, SearchEngines, SearchEnginesError};
+use serval_protocol::{Command, ErrorCode, Event};
+use support::Harness;
+
+/// The URL `input` loads with the built-in engines.
+fn resolve(input: &str) -> String {
+    omnibox::resolve(input, &SearchEngines::default())
+        .unwrap()
+        .url
+        .into()
+}
+
+fn google(terms: &str) -> String {
+    format!("https://www.google.com/search?q={terms}")
+}
+
+#[test]
+fn addresses_gain_a_scheme() {
+    for (input, url) in [
+        ("example.com", "https://example.com/"),
+        (
+            "  www.example.co.uk/path?q=1  ",
+            "https://www.example.co.uk/path?q=1",
+        ),
+        ("nodejs.org", "https://nodejs.org/"),
+        ("example.com:8443", "http://example.com:8443/"),
+        ("localhost", "http://localhost/"),
+        ("localhost:3000", "http://localhost:3000/"),
+        ("app.localhost:5173/x", "http://app.localhost:5173/x"),
+        ("192.168.1.1", "http://192.168.1.1/"),
+        ("10.0.0.2:8080/admin", "http://10.0.0.2:8080/admin"),
+        ("[::1]:8080", "http://[::1]:8080/"),
+        ("[2001:db8::1]", "http://[2001:db8::1]/"),
+        ("intranet/path", "http://intranet/path"),
+        ("intranet:8080", "http://intranet:8080/"),
+        ("wiki.corp/page", "http://wiki.corp/page"),
+    ] {
+        assert_eq!(resolve(input), url, "{input}");
+    }
+}
+
+#[test]
+fn schemes_are_kept() {
+    for (input, url) in [
+        ("http://example.com", "http://example.com/"),
+        ("https://localhost:3000/a", "https://localhost:3000/a"),
+        ("file:///etc/hosts", "file:///etc/hosts"),
+        ("about:blank", "about:blank"),
+        ("data:text/plain,hi", "data:text/plain,hi"),
+        ("ftp://example.com/", "ftp://example.com/"),
+    ] {
+        assert_eq!(resolve(input), url, "{input}");
+    }
+    #[cfg(unix)]
+    assert_eq!(resolve("/etc/hosts"), "file:///etc/hosts");
+}
+
+#[test]
+fn everything_else_is_a_search() {
+    for (input, terms) in [
+        ("servo", "servo"),
+        ("node.js", "node.js"),
+        ("3.14", "3.14"),
+        ("co.uk", "co.uk"),
+        ("rust borrow checker", "rust+borrow+checker"),
+        ("example.com is down", "example.com+is+down"),
+        ("?example.com", "example.com"),
+        ("a&b=c", "a%26b%3Dc"),
+        ("mailto:someone", "mailto%3Asomeone"),
+    ] {
+        assert_eq!(resolve(input), google(terms), "{input}");
+    }
+    assert_eq!(omnibox::resolve("   ", &SearchEngines::default()), None);
+    assert_eq!(omnibox::resolve("?", &SearchEngines::default()), None);
+}
+
+#[test]
+fn keywords_pick_the_engine() {
+    let engines = SearchEngines::default();
+    let resolved = omnibox::resolve("w Servo (software)", &engines).unwrap();
+    assert_eq!(
+        resolved.url.as_str(),
+        "https://en.wikipedia.org/w/index.php?search=Servo+%28software%29"
+    );
+    assert_eq!(resolved.search_engine.as_deref(), Some("Wikipedia"));
+
+    // Keywords win over addresses, and are only keywords when followed by
+    // terms.
+    let resolved = omnibox::resolve("D example.com", &engines).unwrap();
+    assert_eq!(
+        resolved.url.as_str(),
+        "https://duckduckgo.com/?q=example.com"
+    );
+    assert_eq!(resolve("w"), google("w"));
+
+    let resolved = omnibox::resolve("example.com", &engines).unwrap();
+    assert_eq!(resolved.search_engine, None);
+}
+
+#[test]
+fn search_engines_are_configurable() {
+    let engines = SearchEngines::from_json(
+        r#"{
+            "default": "Local",
+            "engines": [
+                { "name": "Other", "keyword": "o", "url": "https://other.test/?q=%s" },
+                { "name": "Local", "url": "http://search.local/find/%s" }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let resolved = omnibox::resolve("serval", &engines).unwrap();
+    assert_eq!(resolved.url.as_str(), "http://search.local/find/serval");
+    assert_eq!(resolved.search_engine.as_deref(), Some("Local"));
+    assert_eq!(
+        omnibox::resolve("o serval", &engines).unwrap().url.as_str(),
+        "https://other.test/?q=serval"
+    );
+
+    assert!(matches!(
+        SearchEngines::from_json(r#"{ "default": "Nope", "engines": [] }"#),
+        Err(SearchEnginesError::UnknownDefault(name)) if name == "Nope"
+    ));
+    assert!(matches!(
+        SearchEngines::new(vec![SearchEngine::new("Bad", None, "https://bad.test/")]),
+        Err(SearchEnginesError::Url { .. })
+    ));
+    assert!(matches!(
+        SearchEngines::new(Vec::new()),
+        Err(SearchEnginesError::Empty)
+    ));
+
+    // Terms in the host would not always make a URL.
+    let in_host = SearchEngine::new("Host", Some("h"), "https://%s.example/");
+    assert!(matches!(
+        SearchEngines::new(vec![in_host.clone()]),
+        Err(SearchEnginesError::Url { .. })
+    ));
+    assert_eq!(in_host.search("either/or"), None);
+}
+
+#[test]
+fn clients_resolve_input_through_the_bridge() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    let scenario = Scenario::load(path).unwrap();
+    let engines = SearchEngines::new(vec![SearchEngine::new(
+        "Test",
+        Some("t"),
+        "https://search.test/?q=%s",
+    )])
+    .unwrap();
+    let mut bridge = Harness::start_bridge(move || {
+        Bridge::new(|waker| MockEngine::new(waker, scenario, 200, 100)).search_engines(engines)
+    });
+
+    let id = bridge.send(Command::ResolveInput {
+        text: "localhost:3000".into(),
+    });
+    let resolved = bridge.expect("inputResolved", |event| {
+        matches!(event, Event::InputResolved { .. })
+    });
+    assert_eq!(
+        resolved,
+        Event::InputResolved {
+            id: Some(id),
+            url: "http://localhost:3000/".into(),
+            search_engine: None,
+        }
+    );
+
+    let id = bridge.send(Command::ResolveInput {
+        text: "serval browser".into(),
+    });
+    let resolved = bridge.expect("inputResolved", |event| {
+        matches!(event, Event::InputResolved { .. })
+    });
+    assert_eq!(
+        resolved,
+        Event::InputResolved {
+            id: Some(id),
+            url: "https://search.test/?q=serval+browser".into(),
+            search_engine: Some("Test".into()),
+        }
+    );
+
+    let id = bridge.send(Command::ResolveInput { text: " ".into() });
+    assert_eq!(bridge.expect_error(id), ErrorCode::InvalidArgument);
+}
@@ -0,0 +1,235 @@
+//! Tab isolation, driven through the bridge with the fake content process
+//! from `tests/support/fake_engine.rs`.
+
+mod support;
+
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use serval_bridge::engine::ProcessEngine;
+use serval_protocol::{Command, ErrorCode, Event, InputEvent, KeyInput, Modifiers};
+use support::{Harness, TIMEOUT};
+
+fn start(hang_timeout: Duration) -> Harness {
+    Harness::start(move |waker| {
+        ProcessEngine::new(
+            waker,
+            env!("CARGO_BIN_EXE_serval-fake-engine"),
+            Vec::new(),
+            hang_timeout,
+        )
+    })
+}
+
+#[test]
+fn crash_reports_exit_status_and_last_url() {
+    let mut bridge = start(TIMEOUT);
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+
+    bridge.navigate("1", "about:crash");
+    let Event::TabCrashed {
+        url,
+        exit_code,
+        signal,
+        hung,
+        ..
+    } = bridge.expect_crash("1")
+    else {
+        unreachable!()
+    };
+    assert_eq!(url.as_deref(), Some("about:blank"));
+    assert!(!hung);
+    if cfg!(unix) {
+        assert_eq!(exit_code, None);
+        assert_eq!(signal, Some(6), "aborting raises SIGABRT");
+    }
+}
+
+#[test]
+fn exit_code_is_reported() {
+    let mut bridge = start(TIMEOUT);
+    bridge.navigate("1", "about:exit");
+    let Event::TabCrashed {
+        url,
+        exit_code,
+        signal,
+        ..
+    } = bridge.expect_crash("1")
+    else {
+        unreachable!()
+    };
+    assert_eq!(url, None);
+    assert_eq!(exit_code, Some(3));
+    assert_eq!(signal, None);
+}
+
+#[test]
+fn hung_process_is_killed() {
+    let mut bridge = start(Duration::from_millis(400));
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+
+    bridge.navigate("1", "about:hang");
+    let Event::TabCrashed { url, hung, .. } = bridge.expect_crash("1") else {
+        unreachable!()
+    };
+    assert!(hung);
+    assert_eq!(url.as_deref(), Some("about:blank"));
+}
+
+#[test]
+fn process_that_stops_reading_is_killed() {
+    // Long enough that only the unread commands can give it away.
+    let mut bridge = start(TIMEOUT * 6);
+    bridge.navigate("2", "about:blank");
+    bridge.expect_loaded("2", "about:blank");
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+
+    bridge.navigate("1", "about:stall");
+    // Enough to fill the pipe to the process and the queue in front of it.
+    let text = "x".repeat(1 << 10);
+    for _ in 0..1_000 {
+        bridge.input("1", InputEvent::CompositionUpdate { data: text.clone() });
+    }
+    let Event::TabCrashed { url, hung, .. } = bridge.expect_crash("1") else {
+        unreachable!()
+    };
+    assert!(hung);
+    assert_eq!(url.as_deref(), Some("about:blank"));
+
+    // The bridge kept going for the other tabs.
+    bridge.navigate("2", "about:blank#00ff00");
+    bridge.expect_loaded("2", "about:blank#00ff00");
+}
+
+#[test]
+fn reload_crashed_restarts_the_tab() {
+    let mut bridge = start(TIMEOUT);
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+    bridge.navigate("1", "about:crash");
+    bridge.expect_crash("1");
+
+    let id = bridge.send(Command::Refresh { tab_id: "1".into() });
+    assert_eq!(bridge.expect_error(id), ErrorCode::TabCrashed);
+
+    bridge.send(Command::ReloadCrashed { tab_id: "1".into() });
+    bridge.expect_loaded("1", "about:blank");
+
+    let id = bridge.send(Command::ReloadCrashed { tab_id: "1".into() });
+    assert_eq!(bridge.expect_error(id), ErrorCode::TabNotCrashed);
+    let id = bridge.send(Command::ReloadCrashed { tab_id: "2".into() });
+    assert_eq!(bridge.expect_error(id), ErrorCode::TabNotFound);
+}
+
+#[test]
+fn navigating_a_crashed_tab_restarts_it() {
+    let mut bridge = start(TIMEOUT);
+    bridge.navigate("1", "about:crash");
+    bridge.expect_crash("1");
+
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+}
+
+#[test]
+fn crash_only_affects_its_tab() {
+    let mut bridge = start(TIMEOUT);
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+    bridge.navigate("2", "about:blank");
+    bridge.expect_loaded("2", "about:blank");
+
+    bridge.navigate("1", "about:crash");
+    bridge.expect_crash("1");
+
+    bridge.navigate("2", "about:blank#again");
+    bridge.expect_loaded("2", "about:blank#again");
+}
+
+#[test]
+fn input_reaches_the_content_process() {
+    let mut bridge = start(TIMEOUT);
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+
+    let key = InputEvent::KeyDown(KeyInput {
+        key: "A".to_owned(),
+        code: "KeyA".to_owned(),
+        location: Default::default(),
+        modifiers: Modifiers {
+            shift: true,
+            ..Modifiers::default()
+        },
+        repeat: false,
+        is_composing: false,
+    });
+    bridge.input("1", key.clone());
+    bridge.expect_title("1", &format!("{key:?}"));
+
+    let id = bridge.input("2", InputEvent::PointerLeave);
+    assert_eq!(bridge.expect_error(id), ErrorCode::TabNotFound);
+}
+
+#[test]
+fn viewport_carries_over_to_new_content_processes() {
+    let mut bridge = start(TIMEOUT);
+    // Sizes sent before the tab opens apply once it does.
+    bridge.resize("1", 400.0, 300.0, 2.0);
+    bridge.navigate("1", "about:blank");
+    bridge.expect_viewport("1", 800, 600, 1.0);
+
+    bridge.send(Command::Zoom {
+        tab_id: "1".into(),
+        factor: 1.5,
+    });
+    bridge.expect_viewport("1", 800, 600, 1.5);
+
+    bridge.navigate("1", "about:crash");
+    bridge.expect_crash("1");
+    bridge.navigate("1", "about:blank");
+    bridge.expect_viewport("1", 800, 600, 1.5);
+}
+
+#[test]
+fn invalid_viewports_are_rejected() {
+    let mut bridge = start(TIMEOUT);
+    let id = bridge.resize("1", 400.0, 300.0, 0.0);
+    assert_eq!(bridge.expect_error(id), ErrorCode::InvalidArgument);
+    let id = bridge.resize("1", f32::NAN, 300.0, 1.0);
+    assert_eq!(bridge.expect_error(id), ErrorCode::InvalidArgument);
+    let id = bridge.send(Command::Zoom {
+        tab_id: "1".into(),
+        factor: -1.0,
+    });
+    assert_eq!(bridge.expect_error(id), ErrorCode::InvalidArgument);
+}
+
+#[test]
+fn heartbeats_stay_between_the_supervisor_and_its_content_processes() {
+    // The bridge itself as content process, which has a session, bookmarks
+    // and a `ready` of its own to tell about.
+    let scenario = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    let mut bridge = Harness::start(move |waker| {
+        ProcessEngine::new(
+            waker,
+            env!("CARGO_BIN_EXE_serval-bridge"),
+            vec!["--content-process".into(), "--mock".into(), scenario.into()],
+            Duration::from_millis(400),
+        )
+        .resolving_hosts()
+    });
+    bridge.navigate("1", "https://example.com/");
+    bridge.expect_loaded("1", "https://example.com/");
+    thread::sleep(Duration::from_millis(200));
+    bridge.pending();
+
+    // Several heartbeats go out, and the tab counts as alive.
+    thread::sleep(Duration::from_millis(600));
+    assert_eq!(bridge.pending(), []);
+    bridge.navigate("1", "https://example.com/");
+    bridge.expect_loaded("1", "https://example.com/");
+}
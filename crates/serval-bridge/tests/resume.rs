@@ -0,0 +1,147 @@
+//! Clients that reconnect resume from the last event they saw.
+
+mod support;
+
+use std::path::Path;
+
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::{Bridge, ClientId};
+use serval_protocol::{Command, Event, Resume, TabId};
+use support::Harness;
+
+fn scenario() -> Scenario {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    Scenario::load(path).unwrap()
+}
+
+/// Sends `ready` and returns the session id, the latest sequence number and
+/// whether the bridge resumed.
+fn ready(client: &mut Harness, resume: Option<Resume>) -> (String, u64, bool) {
+    client.send(Command::Ready {
+        protocol_version: None,
+        resume,
+        encoding: None,
+        ack_frames: None,
+    });
+    let event = client.expect("ready", |event| matches!(event, Event::Ready { .. }));
+    let Event::Ready {
+        session_id,
+        latest_seq,
+        resumed,
+        ..
+    } = event
+    else {
+        unreachable!()
+    };
+    (session_id, latest_seq, resumed)
+}
+
+fn is_loaded(tab: &str) -> impl Fn(&Event) -> bool {
+    let tab = TabId::from(tab);
+    move |event| matches!(event, Event::LoadComplete { tab_id, .. } if *tab_id == tab)
+}
+
+#[test]
+fn reconnecting_clients_get_the_events_they_missed() {
+    let mut main = Harness::start(|waker| MockEngine::new(waker, scenario(), 200, 100));
+    let mut other = main.connect(ClientId(2));
+    let (session_id, _, _) = ready(&mut main, None);
+    main.navigate("1", "about:blank");
+    let last = main
+        .collect_envelopes("loadComplete", is_loaded("1"))
+        .iter()
+        .filter_map(|envelope| envelope.seq)
+        .max()
+        .unwrap();
+    main.disconnect();
+
+    other.navigate("2", "http://example.com/");
+    other.expect("loadComplete", is_loaded("2"));
+
+    let mut back = other.connect(ClientId(3));
+    let resume = Resume {
+        session_id,
+        seq: last,
+    };
+    let (_, latest, resumed) = ready(&mut back, Some(resume));
+    assert!(resumed);
+    let missed: Vec<_> = back
+        .collect_envelopes("replayed loadComplete", is_loaded("2"))
+        .into_iter()
+        .filter(|envelope| envelope.seq.is_some())
+        .collect();
+    let seqs: Vec<_> = missed.iter().filter_map(|envelope| envelope.seq).collect();
+    assert!(seqs[0] > last);
+    assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(seqs[seqs.len() - 1] <= latest);
+    // The replay is queued at once, so titles and history changes that later
+    // ones supersede may be coalesced away; nothing else goes missing.
+    let gaps = (seqs[seqs.len() - 1] - last) as usize - seqs.len();
+    assert!(gaps as u64 <= back.counters().coalesced());
+    assert!(missed.iter().any(|envelope| matches!(
+        &envelope.event,
+        Event::LoadStart { tab_id, url } if tab_id.as_str() == "2" && url == "http://example.com/"
+    )));
+    assert!(
+        missed
+            .iter()
+            .all(|envelope| !matches!(envelope.event, Event::Frame { .. } | Event::Ready { .. }))
+    );
+}
+
+#[test]
+fn clients_too_far_behind_get_a_snapshot() {
+    let mut main = Harness::start_bridge(|| {
+        Bridge::new(|waker| MockEngine::new(waker, scenario(), 200, 100)).replay_capacity(2)
+    });
+    let mut other = main.connect(ClientId(2));
+    let (session_id, seq, _) = ready(&mut main, None);
+    main.disconnect();
+
+    other.navigate("1", "http://example.com/");
+    other.expect("loadComplete", is_loaded("1"));
+
+    let mut back = other.connect(ClientId(3));
+    let (_, _, resumed) = ready(&mut back, Some(Resume { session_id, seq }));
+    assert!(!resumed);
+    let snapshot = back.expect("tabsSnapshot", |event| {
+        matches!(event, Event::TabsSnapshot { .. })
+    });
+    let Event::TabsSnapshot { tabs } = snapshot else {
+        unreachable!()
+    };
+    assert_eq!(tabs.len(), 1);
+    assert_eq!(tabs[0].url.as_deref(), Some("https://example.com/"));
+}
+
+#[test]
+fn sequence_numbers_of_another_session_are_ignored() {
+    let mut main = Harness::start(|waker| MockEngine::new(waker, scenario(), 200, 100));
+    let (session_id, seq, _) = ready(&mut main, None);
+    let resume = Resume {
+        session_id: format!("not-{session_id}"),
+        seq,
+    };
+    let (_, _, resumed) = ready(&mut main, Some(resume));
+    assert!(!resumed);
+    main.expect("tabsSnapshot", |event| {
+        matches!(event, Event::TabsSnapshot { .. })
+    });
+}
+
+#[test]
+fn ready_keeps_its_sequence_number_apart_from_the_envelope() {
+    let mut main = Harness::start(|waker| MockEngine::new(waker, scenario(), 200, 100));
+    main.navigate("1", "about:blank");
+    main.expect("loadComplete", is_loaded("1"));
+    main.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    let ready = main.expect_envelope("ready", |event| matches!(event, Event::Ready { .. }));
+    assert!(ready.seq.is_none());
+    let text = serval_protocol::encode(&ready);
+    assert_eq!(serval_protocol::decode_envelope(&text).unwrap(), ready);
+}
@@ -0,0 +1,96 @@
+//! The WebSocket handshake: tokens and origins, against the mock engine.
+
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+use serval_bridge::Bridge;
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::server::{self, Access};
+use serval_protocol::Event;
+use tungstenite::Message;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::StatusCode;
+
+const TOKEN: &str = "0123456789abcdef";
+
+fn start() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let access = Access::new(TOKEN, vec!["tauri://localhost".to_owned()]);
+    thread::spawn(move || {
+        let bridge = Bridge::new(|waker| MockEngine::new(waker, Scenario::default(), 200, 100));
+        server::serve(listener, bridge.inputs(), access);
+        bridge.run()
+    });
+    addr
+}
+
+fn connect(addr: SocketAddr, query: &str, origin: Option<&str>) -> Result<(), StatusCode> {
+    let mut request = format!("ws://{addr}/{query}")
+        .into_client_request()
+        .unwrap();
+    if let Some(origin) = origin {
+        request
+            .headers_mut()
+            .insert("origin", origin.parse().unwrap());
+    }
+    let mut socket = match tungstenite::connect(request) {
+        Ok((socket, _)) => socket,
+        Err(tungstenite::Error::Http(response)) => return Err(response.status()),
+        Err(error) => panic!("{error}"),
+    };
+
+    socket
+        .send(Message::text(r#"{"type":"ready","id":1}"#))
+        .unwrap();
+    loop {
+        let Message::Text(text) = socket.read().unwrap() else {
+            continue;
+        };
+        if let Event::Ack { id: 1 } = serval_protocol::decode_event(&text).unwrap() {
+            return Ok(());
+        }
+    }
+}
+
+#[test]
+fn clients_need_the_token() {
+    let addr = start();
+    let query = format!("?token={TOKEN}");
+    assert_eq!(connect(addr, &query, None), Ok(()));
+    assert_eq!(connect(addr, "", None), Err(StatusCode::UNAUTHORIZED));
+    assert_eq!(
+        connect(addr, "?token=0123456789abcdee", None),
+        Err(StatusCode::UNAUTHORIZED)
+    );
+    assert_eq!(
+        connect(addr, &format!("?token={TOKEN}0"), None),
+        Err(StatusCode::UNAUTHORIZED)
+    );
+}
+
+#[test]
+fn browsers_need_an_allowed_origin() {
+    let addr = start();
+    let query = format!("?token={TOKEN}");
+    for origin in [
+        "http://localhost:5173",
+        "http://127.0.0.1:4173",
+        "http://[::1]:5173",
+        "tauri://localhost",
+    ] {
+        assert_eq!(connect(addr, &query, Some(origin)), Ok(()), "{origin}");
+    }
+    for origin in [
+        "https://evil.example",
+        "http://localhost.evil.example",
+        "null",
+        "file://",
+    ] {
+        assert_eq!(
+            connect(addr, &query, Some(origin)),
+            Err(StatusCode::FORBIDDEN),
+            "{origin}"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Several clients attached to one session: shared events, tab ownership and
+//! `tabsSnapshot`.
+
+mod support;
+
+use std::path::Path;
+
+use serval_bridge::ClientId;
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::session::SessionRegistry;
+use serval_protocol::{Command, ErrorCode, Event, TabSnapshot};
+use support::{CLIENT, Harness};
+
+const OTHER: ClientId = ClientId(2);
+
+fn start() -> Harness {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    let scenario = Scenario::load(path).unwrap();
+    Harness::start(move |waker| MockEngine::new(waker, scenario, 200, 100))
+}
+
+fn expect_snapshot(client: &Harness) -> Vec<TabSnapshot> {
+    let event = client.expect("tabsSnapshot", |event| {
+        matches!(event, Event::TabsSnapshot { .. })
+    });
+    let Event::TabsSnapshot { tabs } = event else {
+        unreachable!()
+    };
+    tabs
+}
+
+fn owners(tabs: &[TabSnapshot]) -> Vec<(&str, Option<u64>)> {
+    tabs.iter()
+        .map(|tab| (tab.tab_id.as_str(), tab.owner))
+        .collect()
+}
+
+#[test]
+fn late_joiners_catch_up_after_ready() {
+    let mut main = start();
+    main.navigate("1", "http://example.com/");
+    main.expect_loaded("1", "https://example.com/");
+    main.navigate("2", "about:blank");
+    main.expect_loaded("2", "about:blank");
+
+    let mut late = main.connect(OTHER);
+    late.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    let ready = late.expect("ready", |event| matches!(event, Event::Ready { .. }));
+    assert!(matches!(ready, Event::Ready { client_id: 2, .. }));
+    let tabs = expect_snapshot(&late);
+    assert_eq!(owners(&tabs), [("1", Some(1)), ("2", Some(1))]);
+    let example = &tabs[0];
+    assert_eq!(example.url.as_deref(), Some("https://example.com/"));
+    assert_eq!(example.title.as_deref(), Some("Example Domain"));
+    assert_eq!(example.entries.len(), 1);
+    assert!(!example.loading && !example.crashed);
+}
+
+#[test]
+fn every_client_sees_every_tab() {
+    let mut main = start();
+    let viewer = main.connect(OTHER);
+    main.navigate("1", "http://example.com/");
+    viewer.expect_title("1", "Example Domain");
+    viewer.expect_loaded("1", "https://example.com/");
+}
+
+#[test]
+fn only_the_owner_commands_a_tab_until_it_is_claimed() {
+    let mut main = start();
+    main.navigate("1", "about:blank");
+    main.expect_loaded("1", "about:blank");
+    let mut other = main.connect(OTHER);
+
+    let refused = other.navigate("1", "http://example.com/");
+    assert_eq!(other.expect_error(refused), ErrorCode::NotTabOwner);
+
+    other.send(Command::ClaimTab { tab_id: "1".into() });
+    assert_eq!(owners(&expect_snapshot(&main)), [("1", Some(2))]);
+    assert_eq!(owners(&expect_snapshot(&other)), [("1", Some(2))]);
+    other.navigate("1", "http://example.com/");
+    main.expect_loaded("1", "https://example.com/");
+
+    let refused = main.send(Command::Refresh { tab_id: "1".into() });
+    assert_eq!(main.expect_error(refused), ErrorCode::NotTabOwner);
+}
+
+#[test]
+fn tabs_of_clients_that_leave_go_to_the_next_one() {
+    let mut main = start();
+    let mut other = main.connect(OTHER);
+    other.navigate("1", "about:blank");
+    assert_eq!(owners(&expect_snapshot(&main)), [("1", Some(2))]);
+
+    other.disconnect();
+    assert_eq!(owners(&expect_snapshot(&main)), [("1", None)]);
+
+    main.send(Command::Refresh { tab_id: "1".into() });
+    assert_eq!(owners(&expect_snapshot(&main)), [("1", Some(CLIENT.0))]);
+
+    main.send(Command::Close { tab_id: "1".into() });
+    assert_eq!(owners(&expect_snapshot(&main)), []);
+}
+
+#[test]
+fn commands_for_unknown_tabs_open_nothing() {
+    let mut registry = SessionRegistry::new();
+    assert!(!registry.authorize(CLIENT, &"9".into()).unwrap());
+    assert_eq!(registry.tabs(), []);
+
+    let mut main = start();
+    let back = main.send(Command::Back { tab_id: "9".into() });
+    assert_eq!(main.expect_error(back), ErrorCode::TabNotFound);
+    let refresh = main.send(Command::Refresh { tab_id: "9".into() });
+    assert_eq!(main.expect_error(refresh), ErrorCode::TabNotFound);
+    let invalid = main.navigate("9", "http://[::1");
+    assert_eq!(main.expect_error(invalid), ErrorCode::InvalidUrl);
+
+    let mut late = main.connect(OTHER);
+    late.send(Command::Ready {
+        protocol_version: None,
+        resume: None,
+        encoding: None,
+        ack_frames: None,
+    });
+    assert_eq!(expect_snapshot(&late), []);
+    // The first navigation that goes through opens the tab.
+    main.navigate("9", "about:blank");
+    assert_eq!(owners(&expect_snapshot(&late)), [("9", Some(CLIENT.0))]);
+}
@@ -0,0 +1,146 @@
+//! Screenshots through `serval_bridge::shot`, taken with the fake content
+//! process from `tests/support/fake_engine.rs` and, with the `servo` feature,
+//! with Servo against `tests/fixtures/shot.html` served over loopback HTTP.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serval_bridge::Waker;
+use serval_bridge::engine::{ProcessEngine, Viewport};
+use serval_bridge::shot::{self, Options, Screenshot, ShotError};
+use serval_protocol::ErrorCode;
+use url::Url;
+
+fn fake_engine(waker: Waker) -> ProcessEngine {
+    ProcessEngine::new(
+        waker,
+        env!("CARGO_BIN_EXE_serval-fake-engine"),
+        Vec::new(),
+        Duration::from_secs(10),
+    )
+}
+
+fn options(url: &str, network_idle: Duration) -> Options {
+    Options {
+        url: url.to_owned(),
+        viewport: Viewport::new(200.0, 100.0, 2.0).unwrap(),
+        network_idle,
+        timeout: Duration::from_secs(10),
+    }
+}
+
+fn fixture() -> Url {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/shot.html");
+    Url::from_file_path(path).unwrap()
+}
+
+fn pixel(screenshot: &Screenshot, x: u32, y: u32) -> [u8; 4] {
+    let start = (y * screenshot.width + x) as usize * 4;
+    screenshot.pixels[start..start + 4].try_into().unwrap()
+}
+
+#[test]
+fn captures_the_viewport_in_device_pixels() {
+    let url = format!("{}#3366cc", fixture());
+    let screenshot = shot::capture(fake_engine, &options(&url, Duration::ZERO)).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (400, 200));
+    assert_eq!(pixel(&screenshot, 0, 0), [0x33, 0x66, 0xcc, 0xff]);
+    assert_eq!(pixel(&screenshot, 399, 199), [0x33, 0x66, 0xcc, 0xff]);
+
+    let png = screenshot.to_png().unwrap();
+    let decoder = png::Decoder::new(std::io::Cursor::new(png));
+    let info = decoder.read_info().unwrap().info().clone();
+    assert_eq!((info.width, info.height), (400, 200));
+}
+
+#[test]
+fn waits_for_the_page_to_go_quiet() {
+    let idle = shot::capture(
+        fake_engine,
+        &options("about:slow-paint#ff0000", Duration::from_secs(1)),
+    )
+    .unwrap();
+    assert_eq!(pixel(&idle, 10, 10), [0xff, 0, 0, 0xff]);
+
+    let eager = shot::capture(
+        fake_engine,
+        &options("about:slow-paint#ff0000", Duration::ZERO),
+    )
+    .unwrap();
+    assert_eq!(pixel(&eager, 10, 10), [0xff, 0xff, 0xff, 0xff]);
+}
+
+#[test]
+fn pdf_page_has_the_css_size() {
+    let screenshot = shot::capture(fake_engine, &options("about:blank", Duration::ZERO)).unwrap();
+    let pdf = screenshot.to_pdf().unwrap();
+    let text = String::from_utf8_lossy(&pdf);
+
+    assert!(text.starts_with("%PDF-1.4\n"));
+    assert!(text.ends_with("%%EOF\n"));
+    // 200 × 100 CSS pixels at 96 per inch.
+    assert!(text.contains("/MediaBox [0 0 150.00 75.00]"));
+    assert!(text.contains("/Width 400 /Height 200"));
+
+    let (_, tail) = text.rsplit_once("startxref\n").unwrap();
+    let xref: usize = tail.lines().next().unwrap().parse().unwrap();
+    assert!(pdf[xref..].starts_with(b"xref\n0 6\n"));
+}
+
+#[test]
+fn failures_are_reported() {
+    let result = shot::capture(fake_engine, &options("not a url", Duration::ZERO));
+    assert!(matches!(
+        result,
+        Err(ShotError::Rejected {
+            code: ErrorCode::InvalidUrl,
+            ..
+        })
+    ));
+
+    let result = shot::capture(fake_engine, &options("about:crash", Duration::ZERO));
+    assert!(matches!(result, Err(ShotError::Crashed)));
+
+    let mut hang = options("about:hang", Duration::ZERO);
+    hang.timeout = Duration::from_millis(500);
+    let result = shot::capture(fake_engine, &hang);
+    assert!(matches!(result, Err(ShotError::Timeout(_))));
+}
+
+/// Servo can only be started once per process, so this is the only test that
+/// uses it.
+#[cfg(feature = "servo")]
+#[test]
+fn servo_captures_a_page_over_http() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use serval_bridge::engine::ServoEngine;
+
+    let page = std::fs::read(fixture().to_file_path().unwrap()).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/shot.html", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            // Skip the request; every path serves the fixture.
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                line.clear();
+            }
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                page.len()
+            );
+            let _ = stream.write_all(&page);
+        }
+    });
+
+    let options = options(&url, Duration::from_millis(300));
+    let screenshot = shot::capture(|waker| ServoEngine::new(waker, 400, 200), &options).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (400, 200));
+    assert_eq!(pixel(&screenshot, 200, 100), [0x33, 0x66, 0xcc, 0xff]);
+}
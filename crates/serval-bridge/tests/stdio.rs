@@ -0,0 +1,158 @@
+//! The stdio transport: framing, recovery from malformed frames, and
+//! `serval-bridge --stdio` playing the mock engine.
+
+use std::io::{BufReader, Cursor, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use serval_bridge::stdio::{FrameError, FrameReader, Framing, MAX_FRAME_LEN, write_frame};
+use serval_protocol::{ErrorCode, Event};
+
+fn read_frames(input: &[u8], framing: Framing) -> Vec<Result<String, FrameError>> {
+    let mut reader = FrameReader::new(Cursor::new(input), framing);
+    let mut frames = Vec::new();
+    while let Some(frame) = reader.next_frame().unwrap() {
+        frames.push(frame);
+    }
+    frames
+}
+
+#[test]
+fn frames_round_trip() {
+    for framing in [Framing::Lines, Framing::Length] {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, framing, r#"{"type":"ready"}"#).unwrap();
+        write_frame(&mut stream, framing, r#"{"title":"naïve"}"#).unwrap();
+        let frames: Vec<_> = read_frames(&stream, framing)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(frames, [r#"{"type":"ready"}"#, r#"{"title":"naïve"}"#]);
+    }
+}
+
+#[test]
+fn length_frames_resync_after_a_broken_header() {
+    let input = b"garbage\n{\"type\":\"ready\"}\n\nContent-Length: 2\r\n\r\n{}\
+                  Content-Length: nope\r\nmore garbage\r\n\r\n\
+                  Content-Type: application/json\r\nContent-Length: 3\r\n\r\n[1]";
+    let frames = read_frames(input, Framing::Length);
+    assert_eq!(frames.len(), 4, "{frames:?}");
+    assert!(matches!(&frames[0], Err(FrameError::Header(line)) if line == "garbage"));
+    assert_eq!(frames[1].as_deref().unwrap(), "{}");
+    assert!(matches!(&frames[2], Err(FrameError::Header(_))));
+    assert_eq!(frames[3].as_deref().unwrap(), "[1]");
+}
+
+#[test]
+fn bad_frames_are_skipped() {
+    let mut input = b"\xff\xfe\n".to_vec();
+    input.extend(vec![b'x'; MAX_FRAME_LEN + 10]);
+    input.extend(b"\n{}\n");
+    let frames = read_frames(&input, Framing::Lines);
+    assert!(matches!(frames[0], Err(FrameError::Utf8)));
+    assert!(matches!(frames[1], Err(FrameError::TooLong(len)) if len == MAX_FRAME_LEN + 10));
+    assert_eq!(frames[2].as_deref().unwrap(), "{}");
+
+    let input = format!("Content-Length: {}\r\n\r\n", MAX_FRAME_LEN + 1);
+    let mut input = input.into_bytes();
+    input.extend(vec![b' '; MAX_FRAME_LEN + 1]);
+    input.extend(b"Content-Length: 2\r\n\r\n{}");
+    let frames = read_frames(&input, Framing::Length);
+    assert!(matches!(frames[0], Err(FrameError::TooLong(_))));
+    assert_eq!(frames[1].as_deref().unwrap(), "{}");
+}
+
+#[test]
+fn bridge_serves_length_frames_over_stdio() {
+    let scenario = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_serval-bridge"))
+        .args(["--stdio", "--framing", "length", "--mock"])
+        .arg(scenario)
+        .env("RUST_LOG", "debug")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+
+    let (sender, events) = mpsc::channel();
+    thread::spawn(move || {
+        let mut frames = FrameReader::new(BufReader::new(stdout), Framing::Length);
+        while let Ok(Some(frame)) = frames.next_frame() {
+            // Anything but well-formed events would break the stream.
+            let event = serval_protocol::decode_event(&frame.unwrap()).unwrap();
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    });
+    let expect = |what: &str, predicate: &dyn Fn(&Event) -> bool| loop {
+        match events.recv_timeout(Duration::from_secs(10)) {
+            Ok(event) if predicate(&event) => return event,
+            Ok(_) => {}
+            Err(_) => panic!("timed out waiting for {what}"),
+        }
+    };
+
+    // A frame split across writes.
+    let ready = r#"{"type":"ready","id":1}"#;
+    write!(
+        stdin,
+        "Content-Length: {}\r\n\r\n{}",
+        ready.len(),
+        &ready[..10]
+    )
+    .unwrap();
+    stdin.flush().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    stdin.write_all(&ready.as_bytes()[10..]).unwrap();
+    stdin.flush().unwrap();
+    expect("ack", &|event| matches!(event, Event::Ack { id: 1 }));
+
+    // Garbage, then two frames in one write.
+    let mut batch = b"{\"type\":\"ready\"}\n".to_vec();
+    write_frame(&mut batch, Framing::Length, r#"{"type":"nope","id":2}"#).unwrap();
+    write_frame(
+        &mut batch,
+        Framing::Length,
+        r#"{"type":"navigate","id":3,"tabId":"1","url":"http://example.com/"}"#,
+    )
+    .unwrap();
+    stdin.write_all(&batch).unwrap();
+    stdin.flush().unwrap();
+    expect("invalidFrame", &|event| {
+        matches!(
+            event,
+            Event::Error {
+                code: ErrorCode::InvalidFrame,
+                ..
+            }
+        )
+    });
+    expect("invalidMessage", &|event| {
+        matches!(
+            event,
+            Event::Error {
+                id: Some(2),
+                code: ErrorCode::InvalidMessage,
+                ..
+            }
+        )
+    });
+    expect(
+        "loadComplete",
+        &|event| matches!(event, Event::LoadComplete { url, .. } if url == "https://example.com/"),
+    );
+
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(logs.contains("skipping frame"), "{logs}");
+}
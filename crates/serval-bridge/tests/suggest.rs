@@ -0,0 +1,293 @@
+//! Address bar suggestions.
+
+mod support;
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::omnibox::{self, OpenTab, Places, SearchEngines};
+use serval_protocol::{Command, Event, Highlight, Suggestion, SuggestionKind, TabId};
+use support::Harness;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn suggest(text: &str, places: &Places, tabs: &[OpenTab]) -> Vec<Suggestion> {
+    omnibox::suggest(text, 8, places, tabs, &SearchEngines::default())
+}
+
+fn summary(suggestions: &[Suggestion]) -> Vec<(SuggestionKind, &str)> {
+    suggestions
+        .iter()
+        .map(|suggestion| (suggestion.kind, suggestion.url.as_str()))
+        .collect()
+}
+
+fn highlight(start: u32, end: u32) -> Highlight {
+    Highlight { start, end }
+}
+
+#[test]
+fn the_first_suggestion_is_what_enter_loads() {
+    let places = Places::new();
+    let suggestions = suggest("example.com", &places, &[]);
+    assert_eq!(
+        summary(&suggestions),
+        [(SuggestionKind::Url, "https://example.com/")]
+    );
+
+    let suggestions = suggest("w servo", &places, &[]);
+    assert_eq!(suggestions[0].kind, SuggestionKind::Search);
+    assert_eq!(suggestions[0].title, "servo");
+    assert_eq!(suggestions[0].search_engine.as_deref(), Some("Wikipedia"));
+
+    assert!(suggest("  ", &places, &[]).is_empty());
+}
+
+#[test]
+fn pages_rank_by_frecency() {
+    let now = SystemTime::now();
+    let mut places = Places::new();
+    // Often, but long ago.
+    for day in 0..5 {
+        places.visit(
+            "https://old.test/",
+            "Rust old",
+            now - DAY * (200 + day),
+            false,
+        );
+    }
+    // As often, lately.
+    for day in 0..5 {
+        places.visit(
+            "https://recent.test/",
+            "Rust recent",
+            now - DAY * day,
+            false,
+        );
+    }
+    // Once, lately, but typed.
+    places.visit("https://typed.test/", "Rust typed", now, true);
+    // Once, lately.
+    places.visit("https://once.test/", "Rust once", now, false);
+    places.visit("https://other.test/", "Something else", now, false);
+
+    let suggestions = suggest("rust", &places, &[]);
+    assert_eq!(
+        summary(&suggestions[1..]),
+        [
+            (SuggestionKind::History, "https://recent.test/"),
+            (SuggestionKind::History, "https://typed.test/"),
+            (SuggestionKind::History, "https://once.test/"),
+            (SuggestionKind::History, "https://old.test/"),
+        ]
+    );
+}
+
+#[test]
+fn every_word_has_to_match() {
+    let now = SystemTime::now();
+    let mut places = Places::new();
+    places.visit("https://docs.rs/serde", "serde - Rust", now, false);
+    places.visit("https://serde.rs/", "Overview · Serde", now, false);
+    places.visit(
+        "https://www.rust-lang.org/",
+        "Rust Programming Language",
+        now,
+        false,
+    );
+
+    let suggestions = suggest("Rust serde", &places, &[]);
+    assert_eq!(
+        summary(&suggestions[1..]),
+        [(SuggestionKind::History, "https://docs.rs/serde")]
+    );
+
+    // Addresses starting with the first word rank first, ignoring `www.`.
+    places.visit(
+        "https://crates.io/search?q=rust",
+        "Search crates",
+        now,
+        false,
+    );
+    places.visit(
+        "https://crates.io/search?q=rust",
+        "Search crates",
+        now,
+        false,
+    );
+    let suggestions = suggest("rust", &places, &[]);
+    assert_eq!(suggestions[1].url, "https://www.rust-lang.org/");
+}
+
+#[test]
+fn matches_are_highlighted_in_utf16_code_units() {
+    let mut places = Places::new();
+    places.visit(
+        "https://example.com/caf%C3%A9",
+        "🦀 Café CAFÉ menu",
+        SystemTime::now(),
+        false,
+    );
+    let suggestions = suggest("café men", &places, &[]);
+    let page = &suggestions[1];
+    // The crab takes two code units.
+    assert_eq!(
+        page.title_highlights,
+        [highlight(3, 7), highlight(8, 12), highlight(13, 16)]
+    );
+    assert_eq!(page.url_highlights, []);
+
+    let suggestions = suggest("exa", &places, &[]);
+    assert_eq!(suggestions[1].url_highlights, [highlight(8, 11)]);
+}
+
+#[test]
+fn open_tabs_come_before_history() {
+    let mut places = Places::new();
+    let now = SystemTime::now();
+    places.visit("https://servo.org/", "Servo", now, true);
+    places.visit("https://github.com/servo/servo", "servo/servo", now, false);
+
+    let tab = TabId::from("7");
+    let tabs = [OpenTab {
+        tab_id: &tab,
+        url: "https://github.com/servo/servo",
+        title: "servo/servo",
+    }];
+    let suggestions = suggest("servo", &places, &tabs);
+    assert_eq!(
+        summary(&suggestions[1..]),
+        [
+            (SuggestionKind::OpenTab, "https://github.com/servo/servo"),
+            (SuggestionKind::History, "https://servo.org/"),
+        ]
+    );
+    assert_eq!(suggestions[1].tab_id, Some(tab));
+}
+
+#[test]
+fn bookmarks_are_suggested_unvisited() {
+    let mut places = Places::new();
+    places.bookmark("https://servo.org/", "Servo, the embeddable engine", true);
+    places.visit(
+        "https://servo.org/blog",
+        "Servo blog",
+        SystemTime::now(),
+        false,
+    );
+    let suggestions = suggest("servo", &places, &[]);
+    assert_eq!(
+        summary(&suggestions[1..]),
+        [
+            (SuggestionKind::Bookmark, "https://servo.org/"),
+            (SuggestionKind::History, "https://servo.org/blog"),
+        ]
+    );
+
+    // Pages never visited are forgotten along with their bookmark.
+    places.bookmark("https://servo.org/", "", false);
+    assert_eq!(places.len(), 1);
+    places.bookmark("https://servo.org/blog", "", true);
+    places.forget("https://servo.org/blog");
+    assert_eq!(
+        summary(&suggest("servo", &places, &[])[1..]),
+        [(SuggestionKind::Bookmark, "https://servo.org/blog")]
+    );
+}
+
+#[test]
+fn search_engines_are_suggested_by_keyword_and_name() {
+    let places = Places::new();
+    let suggestions = suggest("wiki", &places, &[]);
+    let keyword = &suggestions[1];
+    assert_eq!(keyword.kind, SuggestionKind::Keyword);
+    assert_eq!(keyword.title, "Wikipedia");
+    assert_eq!(keyword.keyword.as_deref(), Some("w"));
+    assert_eq!(keyword.title_highlights, [highlight(0, 4)]);
+
+    let suggestions = suggest("d", &places, &[]);
+    assert!(
+        suggestions
+            .iter()
+            .any(|suggestion| suggestion.search_engine.as_deref() == Some("DuckDuckGo"))
+    );
+}
+
+/// How fast this runs is measured by `benches/suggest.rs`.
+#[test]
+fn suggestions_rank_large_histories_like_a_full_sort() {
+    let now = SystemTime::now();
+    let mut places = Places::new();
+    for i in 0..100_000u32 {
+        let url = format!("https://site{}.example/articles/{i}", i % 997);
+        let title = format!("Article {i} about topic {}", i % 113);
+        places.visit(&url, &title, now - DAY * (i % 365), i % 7 == 0);
+    }
+    assert_eq!(places.len(), 100_000);
+
+    let typed = "site42 topic 7";
+    let words = ["site42", "topic", "7"];
+    let suggestions = suggest(typed, &places, &[]);
+    assert_eq!(suggestions.len(), 8);
+    assert!(suggestions[1..].iter().all(|suggestion| {
+        let text = format!("{} {}", suggestion.url, suggestion.title);
+        words.iter().all(|word| text.contains(word))
+    }));
+
+    // Ranking keeps only the best pages while it scans, and still finds the
+    // ones sorting every match would. Every match starts with `site42`, so
+    // frecency alone orders them.
+    let mut matches: Vec<u64> = places
+        .iter()
+        .filter(|page| {
+            let text = format!("{} {}", page.url, page.title);
+            words.iter().all(|word| text.contains(word))
+        })
+        .map(|page| page.frecency())
+        .collect();
+    matches.sort_unstable_by(|a, b| b.cmp(a));
+    let suggested: Vec<u64> = suggestions[1..]
+        .iter()
+        .map(|suggestion| places.get(&suggestion.url).unwrap().frecency())
+        .collect();
+    assert_eq!(suggested, matches[..7]);
+}
+
+#[test]
+fn clients_get_suggestions_from_the_pages_tabs_visited() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json");
+    let scenario = Scenario::load(path).unwrap();
+    let mut bridge = Harness::start(move |waker| MockEngine::new(waker, scenario, 200, 100));
+    bridge.navigate("1", "https://example.com/");
+    bridge.expect_loaded("1", "https://example.com/");
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+
+    let id = bridge.send(Command::Suggest {
+        text: "exam".into(),
+        limit: Some(4),
+    });
+    let Event::Suggestions {
+        id: answered,
+        text,
+        suggestions,
+    } = bridge.expect("suggestions", |event| {
+        matches!(event, Event::Suggestions { .. })
+    })
+    else {
+        unreachable!()
+    };
+    assert_eq!((answered, text.as_str()), (Some(id), "exam"));
+    assert_eq!(
+        summary(&suggestions),
+        [
+            (
+                SuggestionKind::Search,
+                "https://www.google.com/search?q=exam"
+            ),
+            (SuggestionKind::History, "https://example.com/"),
+        ]
+    );
+    assert_eq!(suggestions[1].url_highlights, [highlight(8, 12)]);
+}
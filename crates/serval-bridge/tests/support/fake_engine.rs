@@ -0,0 +1,152 @@
+//! A content process that pretends to load pages, for testing the supervisor.
+//!
+//! It speaks the stdio protocol and loads every URL instantly, except for a
+//! few that misbehave on command:
+//!
+//! - `about:crash` aborts the process,
+//! - `about:exit` exits with status 3,
+//! - `about:hang` stops responding, but still exits once stdin closes,
+//! - `about:stall` stops reading stdin and never exits by itself.
+//!
+//! Input events are echoed back as the tab's title, in their `Debug` form, and
+//! `resize` and `zoom` are confirmed with `viewportChanged`. A loaded page is
+//! painted again at its new size after a `resize`.
+//!
+//! Once loaded, a page paints one frame filled with the colour its fragment
+//! names as `rrggbb`, or white. `about:slow-paint` first paints white and
+//! only paints its colour 300 ms after loading.
+
+use std::io::{self, BufRead, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use serval_bridge::frames::FrameEncoder;
+use serval_protocol::{Command, Encoding, Event, PROTOCOL_VERSION, TabId};
+
+fn main() {
+    let (mut width, mut height, mut device_pixel_ratio, mut zoom) = (800.0, 600.0, 1.0, 1.0);
+    let mut frames = FrameEncoder::new();
+    // The colour of the loaded page, if any.
+    let mut page = None;
+    let mut hung = false;
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { return };
+        if hung {
+            continue;
+        }
+        let Ok(request) = serval_protocol::decode_command(&line) else {
+            continue;
+        };
+        match request.command {
+            Command::Ready { .. } => emit(Event::Ready {
+                protocol_version: PROTOCOL_VERSION,
+                servo_version: None,
+                capabilities: Vec::new(),
+                client_id: 0,
+                session_id: "fake".to_owned(),
+                latest_seq: 0,
+                resumed: false,
+                encoding: Encoding::Json,
+            }),
+            Command::Ping {} => emit(Event::Ack {
+                id: request.id.unwrap_or_default(),
+            }),
+            Command::Navigate { url, .. } if url == "about:crash" => process::abort(),
+            Command::Navigate { url, .. } if url == "about:exit" => process::exit(3),
+            Command::Navigate { url, .. } if url == "about:hang" => hung = true,
+            Command::Navigate { url, .. } if url == "about:stall" => loop {
+                thread::sleep(Duration::from_secs(60));
+            },
+            Command::Navigate { tab_id, url } => {
+                emit(Event::LoadStart {
+                    tab_id: tab_id.clone(),
+                    url: url.clone(),
+                });
+                emit(Event::UrlChange {
+                    tab_id: tab_id.clone(),
+                    url: url.clone(),
+                });
+                emit(Event::LoadComplete {
+                    tab_id: tab_id.clone(),
+                    url: url.clone(),
+                });
+
+                let size = device_size(width, height, device_pixel_ratio);
+                let color = url
+                    .rsplit_once('#')
+                    .and_then(|(_, fragment)| u32::from_str_radix(fragment, 16).ok())
+                    .unwrap_or(0xffffff);
+                if url.starts_with("about:slow-paint") {
+                    paint(&mut frames, &tab_id, size, 0xffffff);
+                    thread::sleep(Duration::from_millis(300));
+                }
+                paint(&mut frames, &tab_id, size, color);
+                page = Some(color);
+            }
+            Command::Input { tab_id, event } => emit(Event::TitleChange {
+                tab_id,
+                title: format!("{event:?}"),
+            }),
+            Command::Resize {
+                tab_id,
+                width: css_width,
+                height: css_height,
+                device_pixel_ratio: ratio,
+            } => {
+                (width, height, device_pixel_ratio) = (css_width, css_height, ratio);
+                emit(viewport(
+                    tab_id.clone(),
+                    width,
+                    height,
+                    device_pixel_ratio,
+                    zoom,
+                ));
+                if let Some(color) = page {
+                    let size = device_size(width, height, device_pixel_ratio);
+                    paint(&mut frames, &tab_id, size, color);
+                }
+            }
+            Command::Zoom { tab_id, factor } => {
+                zoom = factor;
+                emit(viewport(tab_id, width, height, device_pixel_ratio, zoom));
+            }
+            Command::Close { .. } => return,
+            _ => {}
+        }
+    }
+}
+
+fn device_size(width: f32, height: f32, device_pixel_ratio: f32) -> (u32, u32) {
+    (
+        (width * device_pixel_ratio).round() as u32,
+        (height * device_pixel_ratio).round() as u32,
+    )
+}
+
+fn viewport(tab_id: TabId, width: f32, height: f32, device_pixel_ratio: f32, zoom: f32) -> Event {
+    let (width, height) = device_size(width, height, device_pixel_ratio);
+    Event::ViewportChanged {
+        tab_id,
+        width,
+        height,
+        device_pixel_ratio,
+        zoom,
+    }
+}
+
+/// Paints a frame filled with `rgb`.
+fn paint(frames: &mut FrameEncoder, tab_id: &TabId, (width, height): (u32, u32), rgb: u32) {
+    let [_, r, g, b] = rgb.to_be_bytes();
+    let pixels = [r, g, b, 0xff].repeat(width as usize * height as usize);
+    if let Some(frame) = frames.encode(tab_id.clone(), width, height, &pixels) {
+        emit(frame);
+    }
+}
+
+fn emit(event: Event) {
+    // The supervisor went away; nobody is left to tell.
+    if writeln!(io::stdout(), "{}", serval_protocol::encode(&event)).is_err() {
+        process::exit(0);
+    }
+}
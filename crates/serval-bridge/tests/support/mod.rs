@@ -0,0 +1,214 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serval_bridge::engine::Engine;
+use serval_bridge::outbox::{self, FlowCounters, Outgoing};
+use serval_bridge::{Bridge, ClientId, Input, Waker};
+use serval_protocol::{Command, Envelope, ErrorCode, Event, InputEvent, Request, TabId};
+
+pub const CLIENT: ClientId = ClientId(1);
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Harness {
+    client: ClientId,
+    inputs: Sender<Input>,
+    events: Outgoing,
+    next_id: u64,
+}
+
+impl Harness {
+    /// Runs a bridge around the engine built by `engine` on a thread of its
+    /// own and connects to it.
+    pub fn start<E: Engine>(engine: impl FnOnce(Waker) -> E + Send + 'static) -> Self {
+        Self::start_bridge(move || Bridge::new(engine))
+    }
+
+    /// Runs the bridge built by `bridge` on a thread of its own and connects
+    /// to it.
+    pub fn start_bridge<E: Engine>(bridge: impl FnOnce() -> Bridge<E> + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let bridge = bridge();
+            sender.send(bridge.inputs()).unwrap();
+            bridge.run()
+        });
+        let inputs = receiver.recv().unwrap();
+        Self::connect_to(inputs, CLIENT)
+    }
+
+    /// Connects another client to the same bridge.
+    pub fn connect(&self, client: ClientId) -> Self {
+        Self::connect_to(self.inputs.clone(), client)
+    }
+
+    fn connect_to(inputs: Sender<Input>, client: ClientId) -> Self {
+        let (events, receiver) = outbox::channel();
+        inputs.send(Input::Connected { client, events }).unwrap();
+        Self {
+            client,
+            inputs,
+            events: receiver,
+            next_id: 1,
+        }
+    }
+
+    pub fn disconnect(self) {
+        self.inputs.send(Input::Disconnected(self.client)).unwrap();
+    }
+
+    /// Sends `command` and returns its request id.
+    pub fn send(&mut self, command: Command) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.inputs
+            .send(Input::Command {
+                client: self.client,
+                request: Request::with_id(id, command),
+            })
+            .unwrap();
+        id
+    }
+
+    pub fn navigate(&mut self, tab: &str, url: &str) -> u64 {
+        self.send(Command::Navigate {
+            tab_id: tab.into(),
+            url: url.to_owned(),
+        })
+    }
+
+    pub fn input(&mut self, tab: &str, event: InputEvent) -> u64 {
+        self.send(Command::Input {
+            tab_id: tab.into(),
+            event,
+        })
+    }
+
+    pub fn resize(&mut self, tab: &str, width: f32, height: f32, device_pixel_ratio: f32) -> u64 {
+        self.send(Command::Resize {
+            tab_id: tab.into(),
+            width,
+            height,
+            device_pixel_ratio,
+        })
+    }
+
+    /// What the bridge dropped or coalesced on the way to this client.
+    pub fn counters(&self) -> Arc<FlowCounters> {
+        self.events.counters()
+    }
+
+    /// Waits for the first event matching `predicate`, skipping others.
+    pub fn expect(&self, what: &str, predicate: impl Fn(&Event) -> bool) -> Event {
+        self.expect_envelope(what, predicate).event
+    }
+
+    /// Like [`Harness::expect`], with the event's sequence number.
+    pub fn expect_envelope(&self, what: &str, predicate: impl Fn(&Event) -> bool) -> Envelope {
+        loop {
+            match self.events.recv_timeout(TIMEOUT) {
+                Ok(envelope) if predicate(&envelope.event) => return envelope,
+                Ok(_) => {}
+                Err(_) => panic!("timed out waiting for {what}"),
+            }
+        }
+    }
+
+    /// Takes the events already waiting, without waiting for more.
+    pub fn pending(&self) -> Vec<Event> {
+        std::iter::from_fn(|| self.events.try_recv().ok())
+            .map(|envelope| envelope.event)
+            .collect()
+    }
+
+    /// Waits for the bridge to drop this client.
+    pub fn expect_dropped(&self) {
+        loop {
+            match self.events.recv_timeout(TIMEOUT) {
+                Ok(_) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+                Err(RecvTimeoutError::Timeout) => panic!("timed out waiting to be dropped"),
+            }
+        }
+    }
+
+    /// Returns every event up to and including the first one matching
+    /// `predicate`.
+    pub fn collect(&self, what: &str, predicate: impl Fn(&Event) -> bool) -> Vec<Event> {
+        self.collect_envelopes(what, predicate)
+            .into_iter()
+            .map(|envelope| envelope.event)
+            .collect()
+    }
+
+    /// Like [`Harness::collect`], with the events' sequence numbers.
+    pub fn collect_envelopes(
+        &self,
+        what: &str,
+        predicate: impl Fn(&Event) -> bool,
+    ) -> Vec<Envelope> {
+        let mut envelopes = Vec::new();
+        loop {
+            match self.events.recv_timeout(TIMEOUT) {
+                Ok(envelope) => {
+                    let done = predicate(&envelope.event);
+                    envelopes.push(envelope);
+                    if done {
+                        return envelopes;
+                    }
+                }
+                Err(_) => panic!("timed out waiting for {what}"),
+            }
+        }
+    }
+
+    pub fn expect_loaded(&self, tab: &str, url: &str) {
+        let tab = TabId::from(tab);
+        self.expect("loadComplete", |event| {
+            matches!(event, Event::LoadComplete { tab_id, url: loaded }
+                if *tab_id == tab && loaded == url)
+        });
+    }
+
+    pub fn expect_title(&self, tab: &str, title: &str) {
+        let tab = TabId::from(tab);
+        self.expect(&format!("title `{title}`"), |event| {
+            matches!(event, Event::TitleChange { tab_id, title: changed }
+                if *tab_id == tab && changed == title)
+        });
+    }
+
+    /// Waits for the tab's viewport to become `width` × `height` device
+    /// pixels at `zoom`.
+    pub fn expect_viewport(&self, tab: &str, width: u32, height: u32, zoom: f32) -> Event {
+        let tab = TabId::from(tab);
+        self.expect(&format!("{width}×{height} viewport at {zoom}"), |event| {
+            matches!(event, Event::ViewportChanged { tab_id, width: w, height: h, zoom: z, .. }
+                if *tab_id == tab && (*w, *h) == (width, height) && *z == zoom)
+        })
+    }
+
+    pub fn expect_crash(&self, tab: &str) -> Event {
+        let tab = TabId::from(tab);
+        self.expect(
+            "tabCrashed",
+            |event| matches!(event, Event::TabCrashed { tab_id, .. } if *tab_id == tab),
+        )
+    }
+
+    pub fn expect_error(&self, id: u64) -> ErrorCode {
+        let event = self.expect(
+            "error",
+            |event| matches!(event, Event::Error { id: Some(error), .. } if *error == id),
+        );
+        let Event::Error { code, .. } = event else {
+            unreachable!()
+        };
+        code
+    }
+}
@@ -0,0 +1,118 @@
+//! The Unix domain socket transport, serving the mock engine.
+
+#![cfg(unix)]
+
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::{Bridge, unix};
+use serval_protocol::{ErrorCode, Event};
+
+fn socket_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("serval-unix-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn start(path: &Path) {
+    let listener = unix::bind(path).unwrap();
+    let scenario =
+        Scenario::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenario.json"))
+            .unwrap();
+    thread::spawn(move || {
+        let bridge = Bridge::new(|waker| MockEngine::new(waker, scenario, 200, 100));
+        unix::serve(listener, bridge.inputs());
+        bridge.run()
+    });
+}
+
+struct Client {
+    stream: UnixStream,
+    events: mpsc::Receiver<Event>,
+}
+
+impl Client {
+    fn connect(path: &Path) -> Self {
+        let stream = UnixStream::connect(path).unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { return };
+                let event = serval_protocol::decode_event(&line).unwrap();
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+        });
+        Self { stream, events }
+    }
+
+    fn send(&mut self, line: &str) {
+        writeln!(self.stream, "{line}").unwrap();
+    }
+
+    fn expect(&self, what: &str, predicate: impl Fn(&Event) -> bool) -> Event {
+        loop {
+            match self.events.recv_timeout(Duration::from_secs(10)) {
+                Ok(event) if predicate(&event) => return event,
+                Ok(_) => {}
+                Err(_) => panic!("timed out waiting for {what}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn clients_share_the_bridge() {
+    let path = socket_path("shared.sock");
+    start(&path);
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let mut first = Client::connect(&path);
+    let second = Client::connect(&path);
+    first.send(r#"{"type":"ready","id":1}"#);
+    first.expect("ack", |event| matches!(event, Event::Ack { id: 1 }));
+
+    first.send("not json");
+    first.expect("invalidMessage", |event| {
+        matches!(
+            event,
+            Event::Error {
+                code: ErrorCode::InvalidMessage,
+                ..
+            }
+        )
+    });
+
+    first.send(r#"{"type":"navigate","tabId":"1","url":"https://example.com/"}"#);
+    for client in [&first, &second] {
+        client.expect("loadComplete", |event| {
+            matches!(event, Event::LoadComplete { url, .. } if url == "https://example.com/")
+        });
+    }
+}
+
+#[test]
+fn stale_sockets_are_replaced() {
+    let path = socket_path("stale.sock");
+    // A socket nobody listens on any more.
+    drop(UnixListener::bind(&path).unwrap());
+    start(&path);
+    let mut client = Client::connect(&path);
+    client.send(r#"{"type":"ready","id":1}"#);
+    client.expect("ack", |event| matches!(event, Event::Ack { id: 1 }));
+
+    let error = unix::bind(&path).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AddrInUse);
+}
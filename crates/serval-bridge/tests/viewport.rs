@@ -0,0 +1,111 @@
+//! Resizing and zooming tabs, with the fake content process from
+//! `tests/support/fake_engine.rs` and, with the `servo` feature, Servo
+//! webviews.
+
+mod support;
+
+use std::time::Duration;
+
+use serval_bridge::engine::ProcessEngine;
+use serval_protocol::{Command, Event, TabId};
+use support::Harness;
+
+fn start() -> Harness {
+    Harness::start(|waker| {
+        ProcessEngine::new(
+            waker,
+            env!("CARGO_BIN_EXE_serval-fake-engine"),
+            Vec::new(),
+            Duration::from_secs(10),
+        )
+    })
+}
+
+/// Resizes tab 1 to 200×100 CSS pixels at a device pixel ratio of 2, checks
+/// the viewport and the frame that follow, then zooms it.
+fn check_viewport(bridge: &mut Harness) {
+    bridge.resize("1", 200.0, 100.0, 2.0);
+    let Event::ViewportChanged {
+        device_pixel_ratio, ..
+    } = bridge.expect_viewport("1", 400, 200, 1.0)
+    else {
+        unreachable!()
+    };
+    assert_eq!(device_pixel_ratio, 2.0);
+    bridge.expect("a 400×200 frame", |event| {
+        matches!(
+            event,
+            Event::Frame {
+                width: 400,
+                height: 200,
+                ..
+            }
+        )
+    });
+
+    bridge.send(Command::Zoom {
+        tab_id: "1".into(),
+        factor: 1.5,
+    });
+    bridge.expect_viewport("1", 400, 200, 1.5);
+}
+
+#[test]
+fn frames_follow_the_viewport() {
+    let mut bridge = start();
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+    check_viewport(&mut bridge);
+}
+
+#[test]
+fn resizing_one_tab_leaves_the_others_alone() {
+    let mut bridge = start();
+    for tab in ["1", "2"] {
+        bridge.navigate(tab, "about:blank");
+        // Pages paint once they loaded.
+        let tab = TabId::from(tab);
+        bridge.expect(
+            "first frame",
+            |event| matches!(event, Event::Frame { tab_id, .. } if *tab_id == tab),
+        );
+    }
+
+    let untouched = |events: Vec<Event>, tab: TabId| {
+        assert!(!events.iter().any(|event| matches!(
+            event,
+            Event::ViewportChanged { tab_id, .. } | Event::Frame { tab_id, .. } if *tab_id == tab
+        )));
+    };
+
+    bridge.resize("2", 300.0, 200.0, 1.0);
+    let events = bridge.collect("tab 2 at 300×200", |event| {
+        matches!(event, Event::Frame { tab_id, width: 300, height: 200, .. }
+            if *tab_id == TabId::from("2"))
+    });
+    untouched(events, "1".into());
+
+    bridge.send(Command::Zoom {
+        tab_id: "1".into(),
+        factor: 2.0,
+    });
+    let events = bridge.collect("tab 1 at 2×", |event| {
+        matches!(event, Event::ViewportChanged { tab_id, zoom, .. }
+            if *tab_id == TabId::from("1") && *zoom == 2.0)
+    });
+    untouched(events, "2".into());
+}
+
+/// Servo can only be started once per process, so this is the only test that
+/// uses it.
+#[cfg(feature = "servo")]
+#[test]
+fn servo_frames_follow_the_viewport() {
+    use serval_bridge::engine::ServoEngine;
+
+    let mut bridge = Harness::start(|waker| ServoEngine::new(waker, 400, 300));
+    bridge.navigate("1", "about:blank");
+    bridge.expect_viewport("1", 400, 300, 1.0);
+    bridge.expect_loaded("1", "about:blank");
+    check_viewport(&mut bridge);
+}
@@ -0,0 +1,287 @@
+//! The history of every tab, and the `serval://history` page listing it.
+
+mod support;
+
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serval_bridge::Bridge;
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_bridge::pages::{HISTORY_URL, Pages};
+use serval_bridge::visits::{Query, VisitStore};
+use serval_protocol::{Command, Event, Transition, Visit};
+use support::Harness;
+use url::Url;
+
+const SCENARIO: &str = r#"{
+  "pages": [
+    { "url": "https://example.com/", "title": "Example Domain", "loadMs": 10 },
+    { "url": "https://www.rust-lang.org/", "title": "Rust", "loadMs": 10 },
+    {
+      "url": "https://links.test/",
+      "title": "Links",
+      "loadMs": 10,
+      "script": [{ "at": 30, "follow": "https://www.rust-lang.org/" }]
+    }
+  ]
+}"#;
+
+fn visit(url: &str, title: &str, visited_at: u64) -> Visit {
+    Visit {
+        url: url.into(),
+        title: title.into(),
+        visited_at,
+        transition: Transition::Link,
+        referrer: None,
+    }
+}
+
+fn urls(visits: &[Visit]) -> Vec<&str> {
+    visits.iter().map(|visit| visit.url.as_str()).collect()
+}
+
+fn query(store: &VisitStore, text: &str, from: Option<u64>, to: Option<u64>) -> Vec<Visit> {
+    store
+        .query(&Query {
+            text: text.into(),
+            from,
+            to,
+            limit: None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn visits_are_queried_by_text_and_time() {
+    let mut store = VisitStore::in_memory().unwrap();
+    store
+        .record(&visit(
+            "https://www.rust-lang.org/",
+            "Rust Programming Language",
+            1_000,
+        ))
+        .unwrap();
+    store
+        .record(&visit("https://docs.rs/serde", "serde - Rust", 2_000))
+        .unwrap();
+    store
+        .record(&visit("https://example.com/", "Café Example", 3_000))
+        .unwrap();
+    // Loads without a title keep the one the page had.
+    store
+        .record(&visit("https://www.rust-lang.org/", "", 4_000))
+        .unwrap();
+
+    let all = query(&store, "", None, None);
+    assert_eq!(
+        urls(&all),
+        [
+            "https://www.rust-lang.org/",
+            "https://example.com/",
+            "https://docs.rs/serde",
+            "https://www.rust-lang.org/",
+        ]
+    );
+    assert_eq!(all[0].title, "Rust Programming Language");
+
+    assert_eq!(
+        urls(&query(&store, "RUST serde", None, None)),
+        ["https://docs.rs/serde"]
+    );
+    assert_eq!(
+        urls(&query(&store, "CAFÉ", None, None)),
+        ["https://example.com/"]
+    );
+    // From is inclusive, to is not.
+    assert_eq!(
+        urls(&query(&store, "", Some(2_000), Some(4_000))),
+        ["https://example.com/", "https://docs.rs/serde"]
+    );
+
+    let newest = store
+        .query(&Query {
+            limit: Some(1),
+            ..Query::default()
+        })
+        .unwrap();
+    assert_eq!(newest[0].visited_at, 4_000);
+}
+
+#[test]
+fn visits_are_deleted_by_site_and_time() {
+    let mut store = VisitStore::in_memory().unwrap();
+    for (url, at) in [
+        ("https://example.com/", 1_000),
+        ("https://www.example.com/a", 2_000),
+        ("https://notexample.com/", 3_000),
+        ("https://servo.org/", 4_000),
+        ("https://servo.org/blog", 5_000),
+    ] {
+        store.record(&visit(url, "", at)).unwrap();
+    }
+
+    assert_eq!(store.delete(Some("Example.com"), None, None).unwrap(), 2);
+    assert_eq!(
+        urls(&query(&store, "", None, None)),
+        [
+            "https://servo.org/blog",
+            "https://servo.org/",
+            "https://notexample.com/"
+        ]
+    );
+
+    assert_eq!(store.delete(None, Some(4_500), None).unwrap(), 1);
+    assert_eq!(store.delete(None, None, Some(3_500)).unwrap(), 1);
+    assert_eq!(urls(&query(&store, "", None, None)), ["https://servo.org/"]);
+    // Pages left without visits are not suggested any more.
+    assert_eq!(store.places().unwrap().len(), 1);
+}
+
+#[test]
+fn history_outlives_the_bridge() {
+    let dir = std::env::temp_dir().join(format!("serval-visits-test-{}", std::process::id()));
+    let path = dir.join("profile/history.sqlite");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut store = VisitStore::open(&path).unwrap();
+    store
+        .record(&visit("https://servo.org/", "Servo", 1_000))
+        .unwrap();
+    store
+        .retitle("https://servo.org/", "Servo, the engine")
+        .unwrap();
+    drop(store);
+
+    let store = VisitStore::open(&path).unwrap();
+    let visits = query(&store, "engine", None, None);
+    assert_eq!(urls(&visits), ["https://servo.org/"]);
+    let places = store.places().unwrap();
+    assert_eq!(places.len(), 1);
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_bridge_records_how_tabs_got_to_pages() {
+    let store = Arc::new(Mutex::new(VisitStore::in_memory().unwrap()));
+    let visits = store.clone();
+    let mut bridge = Harness::start_bridge(move || {
+        let scenario = Scenario::from_json(SCENARIO).unwrap();
+        let pages = Pages::new(visits.clone());
+        Bridge::new(move |waker| MockEngine::new(waker, scenario, 200, 100).pages(pages))
+            .visits(visits)
+    });
+    let started = SystemTime::now();
+
+    bridge.navigate("1", "https://example.com/");
+    bridge.expect_loaded("1", "https://example.com/");
+    bridge.navigate("1", "https://links.test/");
+    bridge.expect_loaded("1", "https://links.test/");
+    // The page follows a link.
+    bridge.expect_loaded("1", "https://www.rust-lang.org/");
+    bridge.send(Command::Back { tab_id: "1".into() });
+    bridge.expect_loaded("1", "https://links.test/");
+    bridge.send(Command::Refresh { tab_id: "1".into() });
+    bridge.expect_loaded("1", "https://links.test/");
+    bridge.expect_loaded("1", "https://www.rust-lang.org/");
+    // Pages the address bar does not suggest are not history either.
+    bridge.navigate("1", "about:blank");
+    bridge.expect_loaded("1", "about:blank");
+
+    let id = bridge.send(Command::QueryHistory {
+        text: None,
+        from: None,
+        to: None,
+        limit: None,
+    });
+    let Event::HistoryVisits {
+        id: answered,
+        visits,
+    } = bridge.expect("historyVisits", |event| {
+        matches!(event, Event::HistoryVisits { .. })
+    })
+    else {
+        unreachable!()
+    };
+    assert_eq!(answered, Some(id));
+    let summary: Vec<_> = visits
+        .iter()
+        .map(|visit| {
+            (
+                visit.url.as_str(),
+                visit.transition,
+                visit.referrer.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "https://www.rust-lang.org/",
+                Transition::Link,
+                Some("https://links.test/")
+            ),
+            ("https://links.test/", Transition::Reload, None),
+            ("https://links.test/", Transition::BackForward, None),
+            (
+                "https://www.rust-lang.org/",
+                Transition::Link,
+                Some("https://links.test/")
+            ),
+            ("https://links.test/", Transition::Typed, None),
+            ("https://example.com/", Transition::Typed, None),
+        ]
+    );
+    assert_eq!(visits[0].title, "Rust");
+    let started = serval_bridge::visits::millis(started);
+    assert!(visits.iter().all(|visit| visit.visited_at >= started));
+
+    // The history page lists them, and is not history itself.
+    bridge.navigate("1", HISTORY_URL);
+    bridge.expect_title("1", "History");
+    bridge.expect_loaded("1", HISTORY_URL);
+    let page = Pages::new(store.clone()).render(&Url::parse(HISTORY_URL).unwrap());
+    assert!(
+        page.html
+            .contains("<a href=\"https://www.rust-lang.org/\">Rust</a>")
+    );
+    let page = Pages::new(store.clone()).render(&Url::parse("serval://history?q=exam").unwrap());
+    assert!(page.html.contains("Example Domain"));
+    assert!(!page.html.contains("rust-lang"));
+
+    let id = bridge.send(Command::DeleteHistory {
+        site: Some("links.test".into()),
+        from: None,
+        to: None,
+    });
+    bridge.expect(
+        "ack",
+        |event| matches!(event, Event::Ack { id: acked } if *acked == id),
+    );
+    let left = store.lock().unwrap().query(&Query::default()).unwrap();
+    assert_eq!(
+        urls(&left),
+        [
+            "https://www.rust-lang.org/",
+            "https://www.rust-lang.org/",
+            "https://example.com/"
+        ]
+    );
+}
+
+#[test]
+fn unknown_pages_are_not_found() {
+    let pages = Pages::new(Arc::new(Mutex::new(VisitStore::in_memory().unwrap())));
+    let page = pages.render(&Url::parse("serval://nothing").unwrap());
+    assert_eq!(page.title, "Not found");
+
+    // Titles and addresses are escaped.
+    let mut store = VisitStore::in_memory().unwrap();
+    store
+        .record(&visit("https://x.test/?a=1&b=2", "<b>bold</b>", 0))
+        .unwrap();
+    let page = Pages::new(Arc::new(Mutex::new(store))).render(&Url::parse(HISTORY_URL).unwrap());
+    assert!(page.html.contains("&lt;b&gt;bold&lt;/b&gt;"));
+    assert!(page.html.contains("https://x.test/?a=1&amp;b=2"));
+}
@@ -0,0 +1,149 @@
+//! Throughput of every encoding for the traffic the bridge carries most:
+//! navigation events, bursts of pointer input and frame tiles.
+//!
+//! ```sh
+//! cargo bench -p serval-protocol
+//! ```
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use serval_protocol::{
+    Blob, Command, Encoding, Envelope, Event, FrameTile, HistoryEntry, InputEvent, Message,
+    PointerType, Request, TabId,
+};
+
+const ENCODINGS: [Encoding; 3] = [Encoding::Json, Encoding::MessagePack, Encoding::Cbor];
+
+/// What one page load sends to the frontend.
+fn navigation() -> Vec<Envelope> {
+    let url = "https://example.com/articles/2024/encoding-benchmarks?ref=home".to_owned();
+    let entries: Vec<_> = (0..8)
+        .map(|i| HistoryEntry {
+            url: format!("https://example.com/articles/{i}"),
+            title: format!("Article {i} — Example"),
+        })
+        .collect();
+    let tab_id = TabId::from("tab-3b1f0a42");
+    [
+        Event::LoadStart {
+            tab_id: tab_id.clone(),
+            url: url.clone(),
+        },
+        Event::UrlChange {
+            tab_id: tab_id.clone(),
+            url: url.clone(),
+        },
+        Event::TitleChange {
+            tab_id: tab_id.clone(),
+            title: "Encoding benchmarks — Example".to_owned(),
+        },
+        Event::HistoryChanged {
+            tab_id: tab_id.clone(),
+            index: entries.len() - 1,
+            entries,
+        },
+        Event::LoadComplete { tab_id, url },
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(seq, event)| Envelope::sequenced(seq as u64 + 1, event))
+    .collect()
+}
+
+/// A second of pointer movement at 120 Hz.
+fn input_burst() -> Vec<Request> {
+    (0..120)
+        .map(|i| {
+            Request::new(Command::Input {
+                tab_id: "tab-3b1f0a42".into(),
+                event: InputEvent::PointerMove {
+                    x: 100.0 + i as f32 * 1.5,
+                    y: 240.25 + i as f32 * 0.75,
+                    pointer_type: PointerType::Mouse,
+                    pointer_id: 1,
+                },
+            })
+        })
+        .collect()
+}
+
+/// A full 1280 × 768 repaint in 256-pixel tiles. PNG data hardly compresses
+/// further, so pseudo-random bytes of a typical tile size stand in for it.
+fn frame() -> Vec<Envelope> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut bytes = |len: usize| -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    };
+    let tiles = (0..768)
+        .step_by(256)
+        .flat_map(|y| (0..1280).step_by(256).map(move |x| (x, y)))
+        .map(|(x, y)| FrameTile {
+            x,
+            y,
+            width: 256,
+            height: 256,
+            data: Blob(bytes(24 * 1024)),
+        })
+        .collect();
+    vec![
+        Event::Frame {
+            tab_id: "tab-3b1f0a42".into(),
+            width: 1280,
+            height: 768,
+            tiles,
+        }
+        .into(),
+    ]
+}
+
+fn bench<M: Message>(c: &mut Criterion, name: &str, messages: &[M]) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    for encoding in ENCODINGS {
+        let encoded: Vec<_> = messages
+            .iter()
+            .map(|message| encoding.encode(message))
+            .collect();
+        let size: usize = encoded.iter().map(Vec::len).sum();
+        println!("{name}: {size} bytes as {encoding}");
+
+        group.bench_function(BenchmarkId::new("encode", encoding), |b| {
+            b.iter(|| {
+                for message in messages {
+                    black_box(encoding.encode(black_box(message)));
+                }
+            })
+        });
+        group.bench_function(BenchmarkId::new("decode", encoding), |b| {
+            b.iter(|| {
+                for bytes in &encoded {
+                    black_box(encoding.decode::<M>(black_box(bytes)).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn navigation_events(c: &mut Criterion) {
+    bench(c, "navigation events", &navigation());
+}
+
+fn input_bursts(c: &mut Criterion) {
+    bench(c, "input bursts", &input_burst());
+}
+
+fn frame_tiles(c: &mut Criterion) {
+    bench(c, "frame tiles", &frame());
+}
+
+criterion_group!(benches, navigation_events, input_bursts, frame_tiles);
+criterion_main!(benches);
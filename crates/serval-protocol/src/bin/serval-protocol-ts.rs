@@ -0,0 +1,5 @@
+//! Prints the frontend's TypeScript protocol bindings to stdout.
+
+fn main() {
+    print!("{}", serval_protocol::typescript::bindings());
+}
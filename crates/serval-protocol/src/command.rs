@@ -0,0 +1,320 @@
+use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
+use ts_rs::TS;
+
+use crate::{BookmarkId, Encoding, InputEvent, Message, Seq, TabId};
+
+/// Correlates a command with the `ack` or `error` event that answers it.
+pub type RequestId = u64;
+
+/// A command as it travels on the wire, optionally tagged with a request id:
+///
+/// ```json
+/// { "type": "navigate", "id": 7, "tabId": "1", "url": "https://example.com" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(rename = "ServoRequest")]
+pub struct Request {
+    /// When set, the bridge answers the command with an `ack` or `error`
+    /// event carrying the same id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub id: Option<RequestId>,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+impl Request {
+    /// A request the bridge does not answer.
+    pub fn new(command: Command) -> Self {
+        Self { id: None, command }
+    }
+
+    /// A request the bridge answers with an `ack` or `error` event.
+    pub fn with_id(id: RequestId, command: Command) -> Self {
+        Self {
+            id: Some(id),
+            command,
+        }
+    }
+}
+
+/// The last event a client saw, from the `sessionId` and `seq` of the
+/// bridge's events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct Resume {
+    pub session_id: String,
+    #[ts(type = "number")]
+    pub seq: Seq,
+}
+
+/// A message sent by the frontend to the engine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, IntoStaticStr, VariantNames)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+#[strum(serialize_all = "camelCase")]
+#[ts(rename = "ServoCommand")]
+pub enum Command {
+    /// The frontend is up and wants to receive events. Answered with
+    /// [`Event::Ready`], and with an `unsupportedProtocol` error when
+    /// `protocol_version` is not [`PROTOCOL_VERSION`](crate::PROTOCOL_VERSION).
+    Ready {
+        /// The protocol version the frontend speaks. Clients that leave it
+        /// out check the version in the `ready` event themselves.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        protocol_version: Option<u32>,
+        /// Where a client that reconnects left off. The bridge replays the
+        /// events since, or sends a `tabsSnapshot` when it no longer has
+        /// them all.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        resume: Option<Resume>,
+        /// The encoding of every message after this one, in both directions.
+        /// Defaults to JSON; transports that only carry text ignore it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        encoding: Option<Encoding>,
+        /// Whether the client acknowledges every frame it showed with
+        /// `frameShown`. The bridge then sends a tab no more than a few
+        /// frames ahead of what the client showed, merging the frames in
+        /// between.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        ack_frames: Option<bool>,
+    },
+    /// Load `url` in the tab, creating the tab's webview if needed.
+    Navigate { tab_id: TabId, url: String },
+    /// Go one step back in the tab's session history.
+    Back { tab_id: TabId },
+    /// Go one step forward in the tab's session history.
+    Forward { tab_id: TabId },
+    /// Reload the tab's current page.
+    Refresh { tab_id: TabId },
+    /// Close the tab and release its webview.
+    Close { tab_id: TabId },
+    /// Restart the crashed tab and load the last URL it showed.
+    ReloadCrashed { tab_id: TabId },
+    /// Take control of the tab from the client controlling it, e.g. to move
+    /// it to another window.
+    ClaimTab { tab_id: TabId },
+    /// The client showed the oldest frame of the tab it had not
+    /// acknowledged yet. Only sent by clients that set `ackFrames` in
+    /// `ready`, and by any of them, whether they control the tab or not.
+    FrameShown { tab_id: TabId },
+    /// Deliver user input to the tab's page.
+    Input { tab_id: TabId, event: InputEvent },
+    /// Resize the tab's content area to `width` × `height` CSS pixels shown
+    /// at `device_pixel_ratio` device pixels per CSS pixel. The bridge
+    /// confirms with a `viewportChanged` event.
+    Resize {
+        tab_id: TabId,
+        width: f32,
+        height: f32,
+        device_pixel_ratio: f32,
+    },
+    /// Set the tab's page zoom, 1 being 100%. The bridge confirms with a
+    /// `viewportChanged` event.
+    Zoom { tab_id: TabId, factor: f32 },
+    /// Find out what `text` typed in the address bar loads: the address it
+    /// spells, with a scheme added where it lacks one, or a search. The
+    /// bridge answers with an `inputResolved` event instead of an `ack`.
+    ResolveInput { text: String },
+    /// Suggest what `text`, typed so far in the address bar, may be heading
+    /// for. Answered with a `suggestions` event instead of an `ack`; sent on
+    /// every keystroke.
+    Suggest {
+        text: String,
+        /// How many suggestions to send at most, 8 when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        limit: Option<u32>,
+    },
+    /// Look up the pages visited, newest visit first. Answered with a
+    /// `historyVisits` event instead of an `ack`.
+    QueryHistory {
+        /// Words every visit's URL or title must contain, ignoring case.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        text: Option<String>,
+        /// Only visits at or after this time, in milliseconds since the Unix
+        /// epoch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        from: Option<u64>,
+        /// Only visits before this time, in milliseconds since the Unix
+        /// epoch. The `visitedAt` of the last visit of an answer asks for the
+        /// page of visits after it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        to: Option<u64>,
+        /// How many visits to send at most, 100 when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        limit: Option<u32>,
+    },
+    /// Forget the visits to `site` and its subdomains, those between `from`
+    /// and `to`, or those matching both. Without any of them, forgets every
+    /// visit.
+    DeleteHistory {
+        /// A host name, like `example.com`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        site: Option<String>,
+        /// As in `queryHistory`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        from: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        to: Option<u64>,
+    },
+    /// Does nothing. Sent with an id, its `ack` tells the sender the other
+    /// end still answers.
+    Ping {},
+    /// Reopen the tabs of the session that ended in a crash, announced with
+    /// `crashedSession`.
+    RestoreSession {},
+    /// Forget the session that ended in a crash instead of restoring it.
+    DiscardSession {},
+    /// Bookmark `url`, or add a folder when it is missing. Answered with a
+    /// `bookmarkAdded` event instead of an `ack`.
+    AddBookmark {
+        /// The folder to add it to, the top level when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        parent_id: Option<BookmarkId>,
+        /// Its place in the folder, the end when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        index: Option<u32>,
+        title: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        tags: Option<Vec<String>>,
+        /// A word that loads the bookmark when typed in the address bar.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        keyword: Option<String>,
+    },
+    /// Change what is set of a bookmark or folder, leaving the rest. An
+    /// empty `keyword` removes it.
+    UpdateBookmark {
+        #[ts(type = "number")]
+        bookmark_id: BookmarkId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        tags: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        keyword: Option<String>,
+    },
+    /// Move a bookmark or folder to `index` in the folder `parentId`, as in
+    /// `addBookmark`.
+    MoveBookmark {
+        #[ts(type = "number")]
+        bookmark_id: BookmarkId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        parent_id: Option<BookmarkId>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        index: Option<u32>,
+    },
+    /// Remove a bookmark, or a folder with everything in it.
+    RemoveBookmark {
+        #[ts(type = "number")]
+        bookmark_id: BookmarkId,
+    },
+    /// Look up bookmarks, not folders, in the order they are listed.
+    /// Answered with a `bookmarksFound` event instead of an `ack`.
+    SearchBookmarks {
+        /// Words every bookmark's URL, title, tags or keyword must contain,
+        /// ignoring case.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        text: Option<String>,
+        /// Only bookmarks with this tag, ignoring case.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        tag: Option<String>,
+        /// How many bookmarks to send at most, 100 when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        limit: Option<u32>,
+    },
+    /// Add the bookmarks of a Netscape bookmark file, as Firefox and Chrome
+    /// export them, to the end of the folder `parentId`.
+    ImportBookmarks {
+        html: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        parent_id: Option<BookmarkId>,
+    },
+    /// Write every bookmark as a Netscape bookmark file. Answered with a
+    /// `bookmarksExported` event instead of an `ack`.
+    ExportBookmarks {},
+}
+
+impl Command {
+    /// The tab this command targets, if any.
+    pub fn tab_id(&self) -> Option<&TabId> {
+        match self {
+            Command::Ready { .. }
+            | Command::Ping {}
+            | Command::ResolveInput { .. }
+            | Command::Suggest { .. }
+            | Command::QueryHistory { .. }
+            | Command::DeleteHistory { .. }
+            | Command::RestoreSession {}
+            | Command::DiscardSession {}
+            | Command::AddBookmark { .. }
+            | Command::UpdateBookmark { .. }
+            | Command::MoveBookmark { .. }
+            | Command::RemoveBookmark { .. }
+            | Command::SearchBookmarks { .. }
+            | Command::ImportBookmarks { .. }
+            | Command::ExportBookmarks {} => None,
+            Command::Navigate { tab_id, .. }
+            | Command::Back { tab_id }
+            | Command::Forward { tab_id }
+            | Command::Refresh { tab_id }
+            | Command::Close { tab_id }
+            | Command::ReloadCrashed { tab_id }
+            | Command::ClaimTab { tab_id }
+            | Command::FrameShown { tab_id }
+            | Command::Input { tab_id, .. }
+            | Command::Resize { tab_id, .. }
+            | Command::Zoom { tab_id, .. } => Some(tab_id),
+        }
+    }
+}
+
+impl Message for Command {
+    fn kind(&self) -> &'static str {
+        self.into()
+    }
+}
+
+impl VariantNames for Request {
+    const VARIANTS: &'static [&'static str] = Command::VARIANTS;
+}
+
+impl Message for Request {
+    fn kind(&self) -> &'static str {
+        self.command.kind()
+    }
+}
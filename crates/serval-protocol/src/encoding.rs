@@ -0,0 +1,356 @@
+use std::fmt;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::de::{self, DeserializeOwned, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use ts_rs::TS;
+
+use crate::{DecodeError, Message, RequestId};
+
+/// How messages are serialized on the wire, chosen by the client in `ready`.
+///
+/// Every encoding carries the same messages: the binary ones write objects as
+/// maps keyed by the same camelCase names, so only the bytes differ. JSON is
+/// what browsers speak; MessagePack and CBOR are smaller and faster, and
+/// carry [`Blob`]s as raw bytes instead of base64.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(rename = "ServoEncoding")]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Encoding {
+    /// Whether messages in this encoding are bytes rather than text.
+    pub fn is_binary(self) -> bool {
+        self != Encoding::Json
+    }
+
+    /// Encodes a message.
+    pub fn encode<M: Message>(self, message: &M) -> Vec<u8> {
+        match self {
+            Encoding::Json => serde_json::to_vec(message).ok(),
+            Encoding::MessagePack => rmp_serde::to_vec_named(message).ok(),
+            Encoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(message, &mut bytes)
+                    .ok()
+                    .map(|()| bytes)
+            }
+        }
+        .expect("protocol messages always serialize")
+    }
+
+    /// Decodes a message, telling apart malformed input, unknown `type` tags
+    /// and known messages with a bad payload.
+    pub fn decode<M: Message>(self, bytes: &[u8]) -> Result<M, DecodeError> {
+        if self == Encoding::Json {
+            return decode_json(bytes);
+        }
+
+        let header: Header = self.deserialize(bytes).map_err(|source| {
+            // Well-formed, but something else than a map.
+            if self.deserialize::<IgnoredAny>(bytes).is_ok() {
+                DecodeError::NotAnObject { encoding: self }
+            } else {
+                DecodeError::Syntax {
+                    encoding: self,
+                    source,
+                }
+            }
+        })?;
+        let Some(kind) = header.kind else {
+            return Err(DecodeError::MissingType);
+        };
+        let id = header.id;
+        let Some(&kind) = M::VARIANTS.iter().find(|known| **known == kind) else {
+            return Err(DecodeError::UnknownType { kind, id });
+        };
+
+        self.deserialize(bytes)
+            .map_err(|source| DecodeError::InvalidPayload { kind, id, source })
+    }
+
+    fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, BoxError> {
+        Ok(match self {
+            Encoding::Json => serde_json::from_slice(bytes)?,
+            Encoding::MessagePack => rmp_serde::from_slice(bytes)?,
+            Encoding::Cbor => ciborium::from_reader(bytes)?,
+        })
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Json => "JSON",
+            Encoding::MessagePack => "MessagePack",
+            Encoding::Cbor => "CBOR",
+        })
+    }
+}
+
+pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+fn decode_json<M: Message>(bytes: &[u8]) -> Result<M, DecodeError> {
+    let value: Value = serde_json::from_slice(bytes).map_err(|source| DecodeError::Syntax {
+        encoding: Encoding::Json,
+        source: source.into(),
+    })?;
+    let Value::Object(fields) = &value else {
+        return Err(DecodeError::NotAnObject {
+            encoding: Encoding::Json,
+        });
+    };
+    let Some(Value::String(kind)) = fields.get("type") else {
+        return Err(DecodeError::MissingType);
+    };
+    let id = fields.get("id").and_then(Value::as_u64);
+    let Some(&kind) = M::VARIANTS.iter().find(|known| *known == kind) else {
+        return Err(DecodeError::UnknownType {
+            kind: kind.clone(),
+            id,
+        });
+    };
+
+    serde_json::from_value(value).map_err(|source| DecodeError::InvalidPayload {
+        kind,
+        id,
+        source: source.into(),
+    })
+}
+
+/// The fields of a binary message needed to report errors against it,
+/// read before the message itself. Other fields are skipped.
+struct Header {
+    kind: Option<String>,
+    id: Option<RequestId>,
+}
+
+impl<'de> Deserialize<'de> for Header {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HeaderVisitor;
+
+        impl<'de> Visitor<'de> for HeaderVisitor {
+            type Value = Header;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Header, A::Error> {
+                let mut header = Header {
+                    kind: None,
+                    id: None,
+                };
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "type" => header.kind = map.next_value::<Value>()?.as_str().map(Into::into),
+                        "id" => header.id = map.next_value::<Value>()?.as_u64(),
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(header)
+            }
+        }
+
+        deserializer.deserialize_map(HeaderVisitor)
+    }
+}
+
+/// Binary data, such as an image tile or a downloaded file. Travels as raw
+/// bytes in the binary encodings and as a base64 string in JSON, so fields
+/// of this type are `string`s in TypeScript.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Blob(pub Vec<u8>);
+
+impl Blob {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Blob {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Serialize for Blob {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&BASE64.encode(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Blob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BlobVisitor;
+
+        impl<'de> Visitor<'de> for BlobVisitor {
+            type Value = Blob;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("bytes or a base64 string")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Blob, E> {
+                BASE64.decode(text).map(Blob).map_err(E::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Blob, E> {
+                Ok(Blob(bytes.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Blob, E> {
+                Ok(Blob(bytes))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Blob, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(Blob(bytes))
+            }
+        }
+
+        // Messages are buffered before their payload is decoded, which loses
+        // whether the input was human-readable, so accept either form.
+        deserializer.deserialize_any(BlobVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Command, Request};
+
+    const ENCODINGS: [Encoding; 3] = [Encoding::Json, Encoding::MessagePack, Encoding::Cbor];
+
+    /// `value` as `encoding` writes it, whether or not it is a message.
+    fn bytes(encoding: Encoding, value: &Value) -> Vec<u8> {
+        match encoding {
+            Encoding::Json => serde_json::to_vec(value).unwrap(),
+            Encoding::MessagePack => rmp_serde::to_vec_named(value).unwrap(),
+            Encoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes).unwrap();
+                bytes
+            }
+        }
+    }
+
+    fn decode_request(encoding: Encoding, value: Value) -> Result<Request, DecodeError> {
+        encoding.decode(&bytes(encoding, &value))
+    }
+
+    #[test]
+    fn requests_round_trip_with_their_id_beside_the_command() {
+        let navigate = Command::Navigate {
+            tab_id: "1".into(),
+            url: "https://example.com/".to_owned(),
+        };
+        let request = Request::with_id(7, navigate.clone());
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({ "type": "navigate", "id": 7, "tabId": "1", "url": "https://example.com/" })
+        );
+        assert_eq!(
+            serde_json::to_value(Request::new(navigate.clone())).unwrap(),
+            json!({ "type": "navigate", "tabId": "1", "url": "https://example.com/" })
+        );
+        for encoding in ENCODINGS {
+            let decoded: Request = encoding.decode(&encoding.encode(&request)).unwrap();
+            assert_eq!(decoded, request, "{encoding}");
+        }
+    }
+
+    #[test]
+    fn unknown_types_keep_the_request_id() {
+        for encoding in ENCODINGS {
+            let error =
+                decode_request(encoding, json!({ "type": "teleport", "id": 3 })).unwrap_err();
+            assert!(
+                matches!(&error, DecodeError::UnknownType { kind, id: Some(3) } if kind == "teleport"),
+                "{encoding}: {error:?}"
+            );
+            assert_eq!(error.request_id(), Some(3));
+        }
+    }
+
+    #[test]
+    fn invalid_payloads_keep_the_request_id() {
+        for encoding in ENCODINGS {
+            let message = json!({ "id": 4, "type": "navigate", "tabId": "1", "url": 5 });
+            let error = decode_request(encoding, message).unwrap_err();
+            assert!(
+                matches!(
+                    error,
+                    DecodeError::InvalidPayload {
+                        kind: "navigate",
+                        id: Some(4),
+                        ..
+                    }
+                ),
+                "{encoding}: {error:?}"
+            );
+            assert_eq!(error.request_id(), Some(4));
+        }
+    }
+
+    #[test]
+    fn messages_without_a_type_have_no_request_id() {
+        for encoding in ENCODINGS {
+            let error = decode_request(encoding, json!({ "id": 5 })).unwrap_err();
+            assert!(matches!(error, DecodeError::MissingType), "{encoding}");
+            assert_eq!(error.request_id(), None);
+        }
+    }
+
+    #[test]
+    fn non_objects_are_named_in_their_encoding() {
+        for encoding in ENCODINGS {
+            let error = decode_request(encoding, json!([1, 2])).unwrap_err();
+            assert!(
+                matches!(error, DecodeError::NotAnObject { encoding: named } if named == encoding),
+                "{encoding}: {error:?}"
+            );
+            assert_eq!(
+                error.to_string(),
+                format!("{encoding} message is not an object")
+            );
+            assert_eq!(error.request_id(), None);
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_a_syntax_error() {
+        let inputs: [(Encoding, &[u8]); 3] = [
+            (Encoding::Json, b"{\"type\":"),
+            // A map of one entry that ends before its key.
+            (Encoding::MessagePack, &[0x81]),
+            (Encoding::Cbor, &[0xa1]),
+        ];
+        for (encoding, bytes) in inputs {
+            let error = encoding.decode::<Request>(bytes).unwrap_err();
+            assert!(
+                matches!(error, DecodeError::Syntax { encoding: named, .. } if named == encoding),
+                "{encoding}: {error:?}"
+            );
+            assert_eq!(error.request_id(), None);
+        }
+    }
+}
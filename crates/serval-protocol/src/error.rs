@@ -0,0 +1,41 @@
+use thiserror::Error;
+
+use crate::RequestId;
+use crate::encoding::{BoxError, Encoding};
+
+/// Why an incoming message could not be decoded.
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("message is not valid {encoding}: {source}")]
+    Syntax {
+        encoding: Encoding,
+        #[source]
+        source: BoxError,
+    },
+    #[error("{encoding} message is not an object")]
+    NotAnObject { encoding: Encoding },
+    #[error("message has no string `type` field")]
+    MissingType,
+    #[error("unknown message type `{kind}`")]
+    UnknownType { kind: String, id: Option<RequestId> },
+    #[error("invalid `{kind}` message: {source}")]
+    InvalidPayload {
+        kind: &'static str,
+        id: Option<RequestId>,
+        #[source]
+        source: BoxError,
+    },
+}
+
+impl DecodeError {
+    /// The request id of the offending message, if it could be read, so the
+    /// error can be reported against the right request.
+    pub fn request_id(&self) -> Option<RequestId> {
+        match self {
+            DecodeError::UnknownType { id, .. } | DecodeError::InvalidPayload { id, .. } => *id,
+            DecodeError::Syntax { .. }
+            | DecodeError::NotAnObject { .. }
+            | DecodeError::MissingType => None,
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
+use ts_rs::TS;
+
+use crate::{Blob, Encoding, Message, RequestId, TabId};
+
+/// Orders the events of a session, see [`Envelope`].
+pub type Seq = u64;
+
+/// An event as it travels on the wire. Events every client receives carry a
+/// sequence number, which a client that reconnects hands back in `ready` to
+/// have the events it missed replayed:
+///
+/// ```json
+/// { "type": "titleChange", "seq": 42, "tabId": "1", "title": "Example" }
+/// ```
+///
+/// Answers to a single client (`ready`, `ack`, `error`, `inputResolved`,
+/// `suggestions`, `historyVisits`, `bookmarkAdded`, `bookmarksFound`,
+/// `bookmarksExported`, the `crashedSession` and `bookmarksChanged`
+/// following `ready`) and frames, which are brought up to date on connection
+/// instead, carry none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(rename = "ServoEnvelope")]
+pub struct Envelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub seq: Option<Seq>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl Envelope {
+    pub fn sequenced(seq: Seq, event: Event) -> Self {
+        Self {
+            seq: Some(seq),
+            event,
+        }
+    }
+}
+
+impl From<Event> for Envelope {
+    fn from(event: Event) -> Self {
+        Self { seq: None, event }
+    }
+}
+
+impl VariantNames for Envelope {
+    const VARIANTS: &'static [&'static str] = Event::VARIANTS;
+}
+
+impl Message for Envelope {
+    fn kind(&self) -> &'static str {
+        self.event.kind()
+    }
+}
+
+/// A message sent by the engine to the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, IntoStaticStr, VariantNames)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+#[strum(serialize_all = "camelCase")]
+#[ts(rename = "ServoEvent")]
+pub enum Event {
+    /// The engine is up and accepts commands.
+    Ready {
+        /// The protocol version the bridge speaks, `PROTOCOL_VERSION`.
+        protocol_version: u32,
+        /// The version of the embedded Servo, missing for engines that are
+        /// not Servo.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        servo_version: Option<String>,
+        /// What the engine supports beyond loading pages.
+        capabilities: Vec<Capability>,
+        /// The id the bridge knows this client by, as found in the `owner`
+        /// of `tabsSnapshot` tabs.
+        #[ts(type = "number")]
+        client_id: u64,
+        /// Identifies the bridge's session; sequence numbers only mean
+        /// something within it.
+        session_id: String,
+        /// The sequence number of the latest event of the session. Named
+        /// apart from the envelope's `seq`, which `ready` does not carry.
+        #[ts(type = "number")]
+        latest_seq: Seq,
+        /// Whether the events the client missed since the `resume` point of
+        /// its `ready` follow. Otherwise a `tabsSnapshot` follows.
+        resumed: bool,
+        /// The encoding of every message after this one, in both directions:
+        /// the one asked for in `ready` if the transport can carry it.
+        encoding: Encoding,
+    },
+    /// Every open tab of the session, in the order they were opened. Sent
+    /// after `ready` and to every client whenever a tab opens, closes or
+    /// changes owner; the other events keep the tabs up to date in between.
+    TabsSnapshot { tabs: Vec<TabSnapshot> },
+    /// The command with request id `id` was accepted.
+    Ack {
+        #[ts(type = "number")]
+        id: RequestId,
+    },
+    /// A command was rejected. `id` is missing when the command carried no
+    /// request id or could not be decoded far enough to find it.
+    Error {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        id: Option<RequestId>,
+        code: ErrorCode,
+        message: String,
+    },
+    /// Answers `resolveInput`.
+    InputResolved {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        id: Option<RequestId>,
+        /// The URL to navigate to.
+        url: String,
+        /// The name of the search engine `url` searches with, missing when
+        /// the text was an address.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        search_engine: Option<String>,
+    },
+    /// Answers `suggest`, best suggestion first. The first one is what
+    /// pressing Enter loads, as `resolveInput` would answer.
+    Suggestions {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        id: Option<RequestId>,
+        /// The text the suggestions are for, to tell them from the answers
+        /// to earlier keystrokes.
+        text: String,
+        suggestions: Vec<Suggestion>,
+    },
+    /// Answers `queryHistory`, newest visit first.
+    HistoryVisits {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        id: Option<RequestId>,
+        visits: Vec<Visit>,
+    },
+    /// The bridge crashed the last time it ran, leaving `tabs` open, as they
+    /// were at `savedAt`, in milliseconds since the Unix epoch. Sent after
+    /// `ready` while no client answered with `restoreSession` or
+    /// `discardSession`, then to every client with no tabs.
+    CrashedSession {
+        #[ts(type = "number")]
+        saved_at: u64,
+        tabs: Vec<TabSnapshot>,
+    },
+    /// Answers `addBookmark` with what was added.
+    BookmarkAdded {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        id: Option<RequestId>,
+        bookmark: Bookmark,
+    },
+    /// Answers `searchBookmarks`.
+    BookmarksFound {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        id: Option<RequestId>,
+        bookmarks: Vec<Bookmark>,
+    },
+    /// Answers `exportBookmarks` with a Netscape bookmark file.
+    BookmarksExported {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        id: Option<RequestId>,
+        html: String,
+    },
+    /// Every bookmark and folder, each folder followed by what it holds.
+    /// Sent after `ready` and to every client whenever a bookmark is added,
+    /// changed, moved or removed.
+    BookmarksChanged { bookmarks: Vec<Bookmark> },
+    /// The tab started loading `url`.
+    LoadStart { tab_id: TabId, url: String },
+    /// The tab's URL changed, e.g. after a redirect.
+    UrlChange { tab_id: TabId, url: String },
+    /// The document title of the tab changed.
+    TitleChange { tab_id: TabId, title: String },
+    /// The tab finished loading `url`.
+    LoadComplete { tab_id: TabId, url: String },
+    /// The tab's session history changed. `index` points at the entry being
+    /// shown, so the tab can go back if it is above zero and forward if it is
+    /// below the last entry.
+    HistoryChanged {
+        tab_id: TabId,
+        entries: Vec<HistoryEntry>,
+        index: usize,
+    },
+    /// The process rendering the tab died or stopped responding. The tab
+    /// stays crashed until it is navigated or sent `reloadCrashed`.
+    TabCrashed {
+        tab_id: TabId,
+        /// The last URL the tab showed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        url: Option<String>,
+        /// The exit code, when the process exited on its own.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        exit_code: Option<i32>,
+        /// The signal that killed the process, on Unix.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        signal: Option<i32>,
+        /// Whether the supervisor killed the process for not responding.
+        hung: bool,
+    },
+    /// The tab rendered a new frame of `width` × `height` device pixels.
+    /// Only the tiles that changed since the previous frame are included;
+    /// when the size changes, the tiles cover the whole frame.
+    Frame {
+        tab_id: TabId,
+        width: u32,
+        height: u32,
+        tiles: Vec<FrameTile>,
+    },
+    /// The tab's viewport changed after a `resize` or `zoom` command, or when
+    /// the tab opened. Frames that follow are `width` × `height` device
+    /// pixels.
+    ViewportChanged {
+        tab_id: TabId,
+        width: u32,
+        height: u32,
+        device_pixel_ratio: f32,
+        zoom: f32,
+    },
+}
+
+impl Event {
+    /// The tab this event is about, if any.
+    pub fn tab_id(&self) -> Option<&TabId> {
+        match self {
+            Event::Ready { .. }
+            | Event::TabsSnapshot { .. }
+            | Event::Ack { .. }
+            | Event::Error { .. }
+            | Event::InputResolved { .. }
+            | Event::Suggestions { .. }
+            | Event::HistoryVisits { .. }
+            | Event::CrashedSession { .. }
+            | Event::BookmarkAdded { .. }
+            | Event::BookmarksFound { .. }
+            | Event::BookmarksExported { .. }
+            | Event::BookmarksChanged { .. } => None,
+            Event::LoadStart { tab_id, .. }
+            | Event::UrlChange { tab_id, .. }
+            | Event::TitleChange { tab_id, .. }
+            | Event::LoadComplete { tab_id, .. }
+            | Event::HistoryChanged { tab_id, .. }
+            | Event::TabCrashed { tab_id, .. }
+            | Event::Frame { tab_id, .. }
+            | Event::ViewportChanged { tab_id, .. } => Some(tab_id),
+        }
+    }
+}
+
+impl Message for Event {
+    fn kind(&self) -> &'static str {
+        self.into()
+    }
+}
+
+/// One entry of a tab's joint session history, including same-document
+/// entries created by fragment navigations and `history.pushState`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: String,
+}
+
+/// A visit to a page, as recorded when it finished loading.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct Visit {
+    pub url: String,
+    /// The page title as of the latest visit to `url`.
+    pub title: String,
+    /// When the page finished loading, in milliseconds since the Unix
+    /// epoch.
+    #[ts(type = "number")]
+    pub visited_at: u64,
+    pub transition: Transition,
+    /// The page the tab showed before, for visits by `link`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub referrer: Option<String>,
+}
+
+/// Identifies a bookmark or folder.
+pub type BookmarkId = u64;
+
+/// A bookmark, or a folder of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct Bookmark {
+    #[ts(type = "number")]
+    pub id: BookmarkId,
+    /// The folder holding it, missing at the top level.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub parent_id: Option<BookmarkId>,
+    /// Its place in the folder, from 0.
+    pub index: u32,
+    pub title: String,
+    /// What the bookmark loads, missing for folders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub url: Option<String>,
+    /// Sorted, and compared ignoring case.
+    pub tags: Vec<String>,
+    /// A word that loads the bookmark when typed in the address bar. When
+    /// `url` holds `%s`, the words typed after the keyword replace it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub keyword: Option<String>,
+    /// When it was added, in milliseconds since the Unix epoch.
+    #[ts(type = "number")]
+    pub added_at: u64,
+}
+
+/// How a tab came to visit a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum Transition {
+    /// A client sent `navigate`, e.g. for an address typed in the address
+    /// bar or a suggestion picked.
+    Typed,
+    /// The page navigated itself, e.g. when a link was followed.
+    Link,
+    /// A client sent `refresh` or `reloadCrashed`.
+    Reload,
+    /// A client sent `back` or `forward`.
+    BackForward,
+}
+
+/// A suggestion of the address bar.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    /// What choosing the suggestion loads. For `keyword` suggestions, the
+    /// engine's page for an empty search.
+    pub url: String,
+    /// The page title, the search terms of `search` suggestions or the
+    /// engine name of `keyword` suggestions.
+    pub title: String,
+    /// The parts of `url` that match the typed text.
+    pub url_highlights: Vec<Highlight>,
+    /// The parts of `title` that match the typed text.
+    pub title_highlights: Vec<Highlight>,
+    /// The open tab to switch to, for `openTab` suggestions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub tab_id: Option<TabId>,
+    /// The search engine of `search` and `keyword` suggestions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub search_engine: Option<String>,
+    /// The keyword to type before search terms, for `keyword` suggestions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub keyword: Option<String>,
+}
+
+/// Where a [`Suggestion`] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum SuggestionKind {
+    /// The address the typed text spells.
+    Url,
+    /// A search for the typed text.
+    Search,
+    /// A search engine whose keyword starts with the typed text.
+    Keyword,
+    /// An open tab to switch to rather than load again.
+    OpenTab,
+    /// A bookmarked page.
+    Bookmark,
+    /// A page visited before, ranked by how often and how recently.
+    History,
+}
+
+/// A range of text, in UTF-16 code units like JavaScript string indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+pub struct Highlight {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// The state of a tab as a client that just connected needs it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct TabSnapshot {
+    pub tab_id: TabId,
+    /// The client controlling the tab. Other clients see its events but
+    /// have their commands for it refused until they send `claimTab`. A tab
+    /// whose owner left goes to the next client that sends it a command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub owner: Option<u64>,
+    /// The URL the tab shows, missing until it starts loading one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub title: Option<String>,
+    /// The tab's session history, as in `historyChanged`.
+    pub entries: Vec<HistoryEntry>,
+    pub index: usize,
+    pub loading: bool,
+    pub crashed: bool,
+}
+
+/// A damaged rectangle of a frame, in device pixels from the top left
+/// corner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+pub struct FrameTile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// The rectangle's pixels as a PNG, base64-encoded in JSON.
+    #[ts(type = "string")]
+    pub data: Blob,
+}
+
+/// An optional feature of the engine, announced in [`Event::Ready`]. The
+/// frontend hides what the engine does not support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum Capability {
+    /// Tabs stream [`Event::Frame`]s.
+    Frames,
+    /// Tabs take `input` commands.
+    Input,
+    /// Tabs follow `resize` and `zoom` commands.
+    Viewport,
+    /// Tabs report [`Event::HistoryChanged`] and go back and forward.
+    History,
+    /// Crashed tabs are reported with [`Event::TabCrashed`] and restarted
+    /// with `reloadCrashed`; the other tabs keep running.
+    CrashRecovery,
+    /// Pages can download files.
+    Downloads,
+    /// Pages can be inspected with developer tools.
+    Devtools,
+}
+
+/// Machine-readable reason carried by [`Event::Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// The message could not be decoded.
+    InvalidMessage,
+    /// A stdio frame was malformed. The transport skipped to the next frame.
+    InvalidFrame,
+    /// `ready` asked for a protocol version the bridge does not speak.
+    UnsupportedProtocol,
+    /// The URL could not be parsed.
+    InvalidUrl,
+    /// The URL's scheme may not be loaded.
+    BlockedScheme,
+    /// The URL's host name does not resolve.
+    DnsFailure,
+    /// The command targets a tab the engine does not know.
+    TabNotFound,
+    /// The command targets a tab another client controls.
+    NotTabOwner,
+    /// The command needs a live tab, but the tab crashed.
+    TabCrashed,
+    /// `reloadCrashed` targets a tab that did not crash.
+    TabNotCrashed,
+    /// A size, pixel ratio or zoom factor is not a positive finite number,
+    /// or `resolveInput` got blank text.
+    InvalidArgument,
+    /// The engine failed for another reason.
+    Internal,
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use strum::IntoStaticStr;
+use ts_rs::TS;
+
+/// User input for a tab, carried by [`Command::Input`](crate::Command::Input).
+///
+/// Coordinates are CSS pixels relative to the top left corner of the tab's
+/// content area. Field names follow the DOM events the frontend receives.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, IntoStaticStr)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+#[strum(serialize_all = "camelCase")]
+pub enum InputEvent {
+    /// A pointer moved.
+    PointerMove {
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        pointer_type: PointerType,
+        /// Tells touch points apart; ignored for the mouse.
+        #[serde(default)]
+        pointer_id: i32,
+    },
+    /// A mouse button was pressed, or a touch or pen made contact.
+    PointerDown {
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        pointer_type: PointerType,
+        #[serde(default)]
+        pointer_id: i32,
+        /// The DOM `button`: 0 primary, 1 auxiliary, 2 secondary, 3 back,
+        /// 4 forward.
+        #[serde(default)]
+        button: i16,
+    },
+    /// A mouse button was released, or a touch or pen lifted.
+    PointerUp {
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        pointer_type: PointerType,
+        #[serde(default)]
+        pointer_id: i32,
+        #[serde(default)]
+        button: i16,
+    },
+    /// The system stopped tracking a touch or pen.
+    PointerCancel {
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        pointer_id: i32,
+    },
+    /// The mouse left the content area.
+    PointerLeave,
+    /// The wheel or touchpad scrolled. Positive deltas scroll right and down,
+    /// as in DOM `WheelEvent`s.
+    Wheel {
+        x: f32,
+        y: f32,
+        delta_x: f64,
+        delta_y: f64,
+        #[serde(default)]
+        delta_z: f64,
+        #[serde(default)]
+        delta_mode: WheelDeltaMode,
+    },
+    /// A key was pressed.
+    KeyDown(KeyInput),
+    /// A key was released.
+    KeyUp(KeyInput),
+    /// An input method started composing text.
+    CompositionStart { data: String },
+    /// The text being composed changed.
+    CompositionUpdate { data: String },
+    /// Composition finished; `data` is the text to insert.
+    CompositionEnd { data: String },
+}
+
+impl InputEvent {
+    /// The `type` tag of this event.
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+}
+
+/// A key event, as the DOM `KeyboardEvent` describes it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyInput {
+    /// The key value, e.g. `"a"`, `"A"` or `"Enter"`.
+    pub key: String,
+    /// The physical key, e.g. `"KeyA"` or `"Enter"`.
+    pub code: String,
+    #[serde(default)]
+    pub location: KeyLocation,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+    #[serde(default)]
+    pub repeat: bool,
+    /// Whether the key is part of an input method composition.
+    #[serde(default)]
+    pub is_composing: bool,
+}
+
+/// Modifier keys held during a key event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(default)]
+pub struct Modifiers {
+    pub alt: bool,
+    pub ctrl: bool,
+    pub meta: bool,
+    pub shift: bool,
+}
+
+/// What produced a pointer event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum PointerType {
+    #[default]
+    Mouse,
+    Touch,
+    Pen,
+}
+
+/// Unit of the deltas of a wheel event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum WheelDeltaMode {
+    #[default]
+    Pixel,
+    Line,
+    Page,
+}
+
+/// Where a key is on the keyboard, as the DOM `KeyboardEvent.location`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyLocation {
+    #[default]
+    Standard,
+    Left,
+    Right,
+    Numpad,
+}
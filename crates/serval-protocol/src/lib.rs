@@ -0,0 +1,188 @@
+//! Serval wire protocol.
+//!
+//! This crate is the single source of truth for the messages exchanged between
+//! the React frontend and the Servo backend bridge. Every message is an object
+//! with a `type` tag and camelCase fields, for example in JSON:
+//!
+//! ```json
+//! { "type": "navigate", "tabId": "1", "url": "https://example.com" }
+//! ```
+//!
+//! JSON is the default encoding; clients may switch to MessagePack or CBOR
+//! in `ready` (see [`Encoding`]).
+//!
+//! Messages sent by the frontend are [`Command`]s wrapped in a [`Request`],
+//! messages sent by the engine are [`Event`]s wrapped in an [`Envelope`].
+//! The TypeScript declarations used by the frontend are generated from these
+//! types (see [`typescript`]), so they cannot drift.
+
+mod command;
+mod encoding;
+mod error;
+mod event;
+mod input;
+pub mod typescript;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use strum::VariantNames;
+use ts_rs::TS;
+
+pub use command::{Command, Request, RequestId, Resume};
+pub use encoding::{Blob, Encoding};
+pub use error::DecodeError;
+pub use event::{
+    Bookmark, BookmarkId, Capability, Envelope, ErrorCode, Event, FrameTile, Highlight,
+    HistoryEntry, Seq, Suggestion, SuggestionKind, TabSnapshot, Transition, Visit,
+};
+pub use input::{InputEvent, KeyInput, KeyLocation, Modifiers, PointerType, WheelDeltaMode};
+
+/// Version of the protocol defined by this crate, exchanged in `ready`.
+/// Bumped on every change that older peers cannot ignore; peers only talk to
+/// the same version.
+///
+/// New commands count, since older bridges refuse them, as do new or
+/// renamed fields of `ready`. New events and new optional fields do not:
+/// frontends skip events they do not know and unknown fields are ignored.
+///
+/// - 1: navigation, tabs, frames, input and viewports.
+/// - 2: `ping`, sessions shared between clients and resumed after
+///   reconnecting, binary encodings, flow control, the address bar,
+///   history, saved sessions and bookmarks.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Identifier the frontend assigns to a browser tab.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, TS)]
+pub struct TabId(pub String);
+
+impl TabId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for TabId {
+    fn from(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+}
+
+impl From<String> for TabId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl std::fmt::Display for TabId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A message that can travel over the bridge in one direction.
+pub trait Message: Serialize + DeserializeOwned + VariantNames {
+    /// The `type` tag of this message.
+    fn kind(&self) -> &'static str;
+}
+
+/// Decodes a frontend → engine message.
+pub fn decode_command(text: &str) -> Result<Request, DecodeError> {
+    decode(text)
+}
+
+/// Decodes an engine → frontend message.
+pub fn decode_event(text: &str) -> Result<Event, DecodeError> {
+    decode(text)
+}
+
+/// Decodes an engine → frontend message with its sequence number.
+pub fn decode_envelope(text: &str) -> Result<Envelope, DecodeError> {
+    decode(text)
+}
+
+/// Decodes a JSON message, telling apart malformed JSON, unknown `type` tags
+/// and known messages with a bad payload.
+pub fn decode<M: Message>(text: &str) -> Result<M, DecodeError> {
+    Encoding::Json.decode(text.as_bytes())
+}
+
+/// Encodes a message as a JSON string.
+pub fn encode<M: Message>(message: &M) -> String {
+    serde_json::to_string(message).expect("protocol messages always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let request = Request::with_id(
+            7,
+            Command::Navigate {
+                tab_id: "1".into(),
+                url: "https://example.com/".into(),
+            },
+        );
+        let text = encode(&request);
+        assert_eq!(
+            text,
+            r#"{"id":7,"type":"navigate","tabId":"1","url":"https://example.com/"}"#
+        );
+        assert_eq!(decode_command(&text).unwrap(), request);
+
+        let event = Event::TitleChange {
+            tab_id: "1".into(),
+            title: "Example".into(),
+        };
+        assert_eq!(decode_event(&encode(&event)).unwrap(), event);
+    }
+
+    #[test]
+    fn unknown_types_are_named() {
+        let error =
+            decode_command(r#"{"id":3,"type":"titleChange","tabId":"1","title":"x"}"#).unwrap_err();
+        assert_eq!(error.request_id(), Some(3));
+        assert!(matches!(error, DecodeError::UnknownType { kind, .. } if kind == "titleChange"));
+    }
+
+    #[test]
+    fn bad_payloads_name_their_type() {
+        let error = decode_command(r#"{"id":4,"type":"navigate","tabId":"1"}"#).unwrap_err();
+        assert_eq!(error.request_id(), Some(4));
+        assert!(matches!(
+            error,
+            DecodeError::InvalidPayload {
+                kind: "navigate",
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("url"), "{error}");
+    }
+
+    #[test]
+    fn messages_need_a_string_type() {
+        assert!(matches!(
+            decode_command(r#"{"tabId":"1"}"#),
+            Err(DecodeError::MissingType)
+        ));
+        assert!(matches!(
+            decode_command(r#"{"type":1}"#),
+            Err(DecodeError::MissingType)
+        ));
+    }
+
+    #[test]
+    fn messages_must_be_json_objects() {
+        assert!(matches!(
+            decode_command(r#"["navigate"]"#),
+            Err(DecodeError::NotAnObject {
+                encoding: Encoding::Json
+            })
+        ));
+        assert!(matches!(
+            decode_command("{type: navigate}"),
+            Err(DecodeError::Syntax { .. })
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+//! TypeScript bindings for the frontend.
+//!
+//! The output of [`bindings`] is checked in as `src/backend/protocol.ts`.
+//! Regenerate it after changing any message type:
+//!
+//! ```sh
+//! cargo run -p serval-protocol --bin serval-protocol-ts > src/backend/protocol.ts
+//! ```
+
+use std::fmt::Write;
+
+use strum::VariantNames;
+use ts_rs::{Config, TS};
+
+use crate::{
+    Bookmark, Capability, Command, Encoding, Envelope, ErrorCode, Event, FrameTile, Highlight,
+    HistoryEntry, InputEvent, KeyInput, KeyLocation, Modifiers, PROTOCOL_VERSION, PointerType,
+    Request, Resume, Suggestion, SuggestionKind, TabId, TabSnapshot, Transition, Visit,
+    WheelDeltaMode,
+};
+
+/// Renders every protocol type as a single TypeScript module.
+pub fn bindings() -> String {
+    let cfg = Config::new();
+    let mut out = String::from(
+        "// Generated by `cargo run -p serval-protocol --bin serval-protocol-ts`.\n\
+         // Do not edit by hand; change the Rust types in crates/serval-protocol instead.\n",
+    );
+
+    for decl in [
+        TabId::decl(&cfg),
+        Command::decl(&cfg),
+        Request::decl(&cfg),
+        Resume::decl(&cfg),
+        Encoding::decl(&cfg),
+        InputEvent::decl(&cfg),
+        KeyInput::decl(&cfg),
+        Modifiers::decl(&cfg),
+        PointerType::decl(&cfg),
+        WheelDeltaMode::decl(&cfg),
+        KeyLocation::decl(&cfg),
+        Event::decl(&cfg),
+        Envelope::decl(&cfg),
+        Capability::decl(&cfg),
+        ErrorCode::decl(&cfg),
+        TabSnapshot::decl(&cfg),
+        HistoryEntry::decl(&cfg),
+        FrameTile::decl(&cfg),
+        Suggestion::decl(&cfg),
+        SuggestionKind::decl(&cfg),
+        Highlight::decl(&cfg),
+        Visit::decl(&cfg),
+        Transition::decl(&cfg),
+        Bookmark::decl(&cfg),
+    ] {
+        writeln!(out, "\nexport {decl}").unwrap();
+    }
+
+    writeln!(out, "\nexport const PROTOCOL_VERSION = {PROTOCOL_VERSION};").unwrap();
+    writeln!(
+        out,
+        "\nexport type ServoMessage = ServoRequest | ServoEnvelope;"
+    )
+    .unwrap();
+    write_types(&mut out, "COMMAND_TYPES", Command::VARIANTS);
+    write_types(&mut out, "EVENT_TYPES", Event::VARIANTS);
+    out
+}
+
+fn write_types(out: &mut String, name: &str, variants: &[&str]) {
+    let list = variants
+        .iter()
+        .map(|variant| format!("'{variant}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(out, "\nexport const {name} = [{list}] as const;").unwrap();
+}
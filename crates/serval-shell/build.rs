@@ -0,0 +1,5 @@
+fn main() {
+    // Checks `tauri.conf.json` and the capabilities, and embeds them.
+    #[cfg(feature = "tauri")]
+    tauri_build::build();
+}
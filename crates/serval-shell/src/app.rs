@@ -0,0 +1,72 @@
+//! The Tauri application: windows showing the Serval frontend, connected to
+//! the shell's bridge.
+//!
+//! Pages reach the bridge through two commands, `servo_connect` and
+//! `servo_post`, and hear back through [`EVENT`](crate::EVENT) events sent to
+//! their window only, so every window is a client of its own.
+
+use log::warn;
+use tauri::{
+    Emitter, EventTarget, Manager, State, WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+    WindowEvent,
+};
+
+use crate::{BACKEND_SCRIPT, EVENT, Shell};
+
+/// The label of the window opened at startup.
+pub const MAIN_WINDOW: &str = "main";
+
+/// Opens the main window and runs the application until it quits.
+pub fn run(shell: Shell) -> tauri::Result<()> {
+    tauri::Builder::default()
+        .manage(shell)
+        .invoke_handler(tauri::generate_handler![servo_connect, servo_post])
+        .setup(|app| {
+            open_window(app.handle(), MAIN_WINDOW)?;
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let WindowEvent::Destroyed = event {
+                window.state::<Shell>().disconnect(window.label());
+            }
+        })
+        .run(tauri::generate_context!())
+}
+
+/// Opens a window showing the frontend, built with Vite, as `label`.
+pub fn open_window(app: &tauri::AppHandle, label: &str) -> tauri::Result<WebviewWindow> {
+    WebviewWindowBuilder::new(app, label, WebviewUrl::default())
+        .title("Serval")
+        .inner_size(1280.0, 800.0)
+        .min_inner_size(480.0, 320.0)
+        .initialization_script(BACKEND_SCRIPT)
+        .build()
+}
+
+/// Connects the calling window once its page listens for events.
+#[tauri::command]
+fn servo_connect(window: WebviewWindow, shell: State<'_, Shell>) {
+    let label = window.label().to_owned();
+    let target = EventTarget::webview_window(label.clone());
+    shell.connect(&label, move |envelope| {
+        match window.emit_to(target.clone(), EVENT, envelope) {
+            Ok(()) => true,
+            Err(error) => {
+                warn!("cannot deliver an event to `{}`: {error}", window.label());
+                false
+            }
+        }
+    });
+}
+
+/// Passes a message from the calling window's page to the bridge.
+#[tauri::command]
+fn servo_post(
+    window: WebviewWindow,
+    shell: State<'_, Shell>,
+    message: String,
+) -> Result<(), String> {
+    shell
+        .post(window.label(), &message)
+        .map_err(|error| error.to_string())
+}
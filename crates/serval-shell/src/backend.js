@@ -0,0 +1,44 @@
+// Installs `window.__SERVO_BACKEND__` for the Serval frontend in the Tauri
+// shell. Messages to the bridge go through the `servo_post` command; its
+// messages come back as `servo-event` events for this window and are
+// re-posted to the page like any platform-provided backend does.
+(() => {
+  if (window.__SERVO_BACKEND__) {
+    return;
+  }
+
+  // Commands go out one at a time so the bridge sees them in order, and
+  // wait until the window is connected.
+  let outgoing = null;
+  const pending = [];
+  const send = (message) => {
+    outgoing = outgoing
+      .then(() => window.__TAURI__.core.invoke('servo_post', { message }))
+      .catch((error) => console.error('[Serval] Cannot reach the shell:', error));
+  };
+
+  window.__SERVO_BACKEND__ = {
+    postMessage(message) {
+      const text = JSON.stringify(message);
+      if (outgoing) {
+        send(text);
+      } else {
+        pending.push(text);
+      }
+    },
+  };
+
+  const connect = async () => {
+    const { core, webviewWindow } = window.__TAURI__;
+    await webviewWindow.getCurrentWebviewWindow().listen('servo-event', ({ payload }) => {
+      window.postMessage({ source: 'servo-backend', message: payload }, '*');
+    });
+    // Connecting again, after a reload, starts over as a new client.
+    await core.invoke('servo_connect');
+    outgoing = Promise.resolve();
+    for (const text of pending.splice(0)) {
+      send(text);
+    }
+  };
+  connect().catch((error) => console.error('[Serval] Cannot connect to the shell:', error));
+})();
@@ -0,0 +1,42 @@
+//! The shell without a window, for tests and scripted frontends.
+//!
+//! A single webview, [`LABEL`], is served over a pair of streams instead of a
+//! page: every line read is a message the page would pass to
+//! `window.__SERVO_BACKEND__.postMessage`, and every line written a message
+//! the backend would post to it. Both go through the same [`Shell`] as the
+//! messages of real windows.
+
+use std::io::{self, BufRead, Write};
+
+use crate::Shell;
+
+/// The label of the headless webview.
+pub const LABEL: &str = "main";
+
+/// Connects [`LABEL`] and serves it over `input` and `output` until `input`
+/// ends, then disconnects it.
+pub fn serve(
+    shell: &Shell,
+    input: impl BufRead,
+    mut output: impl Write + Send + 'static,
+) -> io::Result<()> {
+    shell.connect(LABEL, move |envelope| {
+        writeln!(output, "{}", serval_protocol::encode(&envelope))
+            .and_then(|()| output.flush())
+            .is_ok()
+    });
+    let served = serve_lines(shell, input);
+    shell.disconnect(LABEL);
+    served
+}
+
+fn serve_lines(shell: &Shell, input: impl BufRead) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        shell.post(LABEL, &line).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
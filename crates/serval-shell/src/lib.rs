@@ -0,0 +1,24 @@
+//! Serval desktop shell.
+//!
+//! The shell runs the [`serval_bridge`] loop in-process and hosts the Serval
+//! frontend in Tauri windows. Every window is a client of the bridge: the
+//! [`BACKEND_SCRIPT`] injected into its page installs
+//! `window.__SERVO_BACKEND__` on top of Tauri commands and events, and the
+//! [`Shell`] connects the two ends.
+//!
+//! The Tauri window is behind the `tauri` feature. Without it, or with
+//! `--headless`, the [`headless`] module stands in for the page.
+
+#[cfg(feature = "tauri")]
+pub mod app;
+pub mod headless;
+mod shell;
+
+pub use shell::{NotConnected, Shell};
+
+/// Installs `window.__SERVO_BACKEND__` in the shell's pages, before the
+/// frontend's own scripts run.
+pub const BACKEND_SCRIPT: &str = include_str!("backend.js");
+
+/// The event carrying the bridge's messages to a page.
+pub const EVENT: &str = "servo-event";
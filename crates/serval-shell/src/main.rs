@@ -0,0 +1,108 @@
+//! `serval-shell`: the Serval desktop app, with the Servo bridge built in.
+
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use log::error;
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_shell::{Shell, headless};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Width of new webviews, in device pixels.
+    #[arg(long, default_value_t = 1024)]
+    width: u32,
+
+    /// Height of new webviews, in device pixels.
+    #[arg(long, default_value_t = 768)]
+    height: u32,
+
+    /// Play the scripted scenario in this JSON file instead of running Servo.
+    /// See `scenarios/dev.json`.
+    #[arg(long, value_name = "FILE")]
+    mock: Option<PathBuf>,
+
+    /// Run without a window. The messages a page would send to the backend
+    /// are read from stdin, one JSON object per line, and the messages for
+    /// it written to stdout. Logs go to stderr.
+    #[arg(long)]
+    headless: bool,
+}
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    let Some(shell) = start(&args) else {
+        return ExitCode::FAILURE;
+    };
+
+    if args.headless {
+        return match headless::serve(&shell, io::stdin().lock(), io::stdout()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                error!("{error}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    run_window(shell)
+}
+
+fn start(args: &Args) -> Option<Shell> {
+    let Some(path) = &args.mock else {
+        return start_servo(args);
+    };
+    let scenario = match Scenario::load(path) {
+        Ok(scenario) => scenario,
+        Err(error) => {
+            error!("{}: {error}", path.display());
+            return None;
+        }
+    };
+    let (width, height) = (args.width, args.height);
+    Some(Shell::start(move |waker| {
+        MockEngine::new(waker, scenario, width, height)
+    }))
+}
+
+#[cfg(feature = "servo")]
+fn start_servo(args: &Args) -> Option<Shell> {
+    use serval_bridge::engine::ServoEngine;
+
+    let (width, height) = (args.width, args.height);
+    Some(Shell::start(move |waker| {
+        ServoEngine::new(waker, width, height)
+    }))
+}
+
+#[cfg(not(feature = "servo"))]
+fn start_servo(_args: &Args) -> Option<Shell> {
+    error!(
+        "serval-shell was built without an engine; rebuild it with `--features servo` \
+         or play a scenario with `--mock`"
+    );
+    None
+}
+
+#[cfg(feature = "tauri")]
+fn run_window(shell: Shell) -> ExitCode {
+    match serval_shell::app::run(shell) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            error!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(not(feature = "tauri"))]
+fn run_window(_shell: Shell) -> ExitCode {
+    error!(
+        "serval-shell was built without a window; rebuild it with `--features tauri` \
+         or run it `--headless`"
+    );
+    ExitCode::FAILURE
+}
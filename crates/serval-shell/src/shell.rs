@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+
+use log::{info, warn};
+use serval_bridge::engine::Engine;
+use serval_bridge::outbox::{self, Outbox};
+use serval_bridge::{Bridge, ClientId, Input, Waker};
+use serval_protocol::{Command, Envelope, ErrorCode, Event};
+use thiserror::Error;
+
+/// A message for a webview that never connected or already disconnected.
+#[derive(Debug, Error)]
+#[error("webview `{0}` is not connected to the bridge")]
+pub struct NotConnected(pub String);
+
+/// The webviews of the shell, each a client of the in-process bridge.
+///
+/// Webviews are known by their label. A page connects once it listens for
+/// events and is delivered every event for it from then on; connecting the
+/// same label again, as a reloaded page does, starts over as a new client.
+pub struct Shell {
+    inputs: Sender<Input>,
+    next_client: AtomicU64,
+    webviews: Mutex<HashMap<String, Connection>>,
+}
+
+struct Connection {
+    client: ClientId,
+    /// Where answers to malformed messages go.
+    replies: Outbox,
+}
+
+impl Shell {
+    /// Runs a bridge around the engine built by `engine` on a thread of its
+    /// own, which the engine lives on.
+    pub fn start<E: Engine>(engine: impl FnOnce(Waker) -> E + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("bridge".to_owned())
+            .spawn(move || {
+                let bridge = Bridge::new(engine);
+                let _ = sender.send(bridge.inputs());
+                bridge.run()
+            })
+            .expect("cannot start the bridge thread");
+        let inputs = receiver
+            .recv()
+            .expect("the bridge thread panicked while starting");
+        Self::new(inputs)
+    }
+
+    /// Connects webviews to the bridge fed by `inputs`.
+    pub fn new(inputs: Sender<Input>) -> Self {
+        Self {
+            inputs,
+            next_client: AtomicU64::new(1),
+            webviews: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Connects the webview `label`, handing its events to `deliver` on a
+    /// thread of its own until `deliver` fails or the webview disconnects.
+    pub fn connect(&self, label: &str, mut deliver: impl FnMut(Envelope) -> bool + Send + 'static) {
+        self.disconnect(label);
+
+        let client = ClientId(self.next_client.fetch_add(1, Ordering::Relaxed));
+        let (events, outgoing) = outbox::channel();
+        let replies = events.clone();
+        thread::spawn(move || {
+            for envelope in outgoing {
+                if !deliver(envelope) {
+                    warn!("{client}: the webview stopped taking events");
+                    return;
+                }
+            }
+        });
+        if self
+            .inputs
+            .send(Input::Connected { client, events })
+            .is_err()
+        {
+            return;
+        }
+        info!("webview `{label}` is {client}");
+        self.webviews()
+            .insert(label.to_owned(), Connection { client, replies });
+    }
+
+    /// Passes `message`, a command in JSON, from the webview `label` to the
+    /// bridge. Malformed messages are answered with an `error` event, like
+    /// the other transports do.
+    pub fn post(&self, label: &str, message: &str) -> Result<(), NotConnected> {
+        let webviews = self.webviews();
+        let Some(connection) = webviews.get(label) else {
+            return Err(NotConnected(label.to_owned()));
+        };
+        let client = connection.client;
+        match serval_protocol::decode_command(message) {
+            Ok(mut request) => {
+                // Tauri's IPC carries text, so the webview stays on JSON.
+                if let Command::Ready { encoding, .. } = &mut request.command {
+                    *encoding = None;
+                }
+                let _ = self.inputs.send(Input::Command { client, request });
+            }
+            Err(error) => {
+                warn!("{client}: rejecting message: {error}");
+                let _ = connection.replies.send(Event::Error {
+                    id: error.request_id(),
+                    code: ErrorCode::InvalidMessage,
+                    message: error.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Disconnects the webview `label`, which releases its tabs.
+    pub fn disconnect(&self, label: &str) {
+        if let Some(connection) = self.webviews().remove(label) {
+            let _ = self.inputs.send(Input::Disconnected(connection.client));
+        }
+    }
+
+    fn webviews(&self) -> MutexGuard<'_, HashMap<String, Connection>> {
+        self.webviews
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
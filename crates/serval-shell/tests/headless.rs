@@ -0,0 +1,67 @@
+//! `serval-shell --headless`, with the page's messages on stdin and stdout.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serval_protocol::{Event, PROTOCOL_VERSION};
+
+#[test]
+fn headless_shells_serve_a_page_over_stdio() {
+    let scenario = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../scenarios/dev.json");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_serval-shell"))
+        .arg("--headless")
+        .arg("--mock")
+        .arg(scenario)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(
+        stdin,
+        r#"{{"type":"ready","id":1,"protocolVersion":{PROTOCOL_VERSION}}}"#
+    )
+    .unwrap();
+    writeln!(
+        stdin,
+        r#"{{"type":"navigate","id":2,"tabId":"1","url":"https://example.com/"}}"#
+    )
+    .unwrap();
+
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut ready = false;
+    loop {
+        let line = lines
+            .next()
+            .expect("the shell stopped before the page loaded")
+            .unwrap();
+        match serval_protocol::decode_event(&line).unwrap() {
+            Event::Ready { .. } => ready = true,
+            Event::LoadComplete { url, .. } => {
+                assert_eq!(url, "https://example.com/");
+                break;
+            }
+            _ => {}
+        }
+    }
+    assert!(ready);
+
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn shells_without_an_engine_refuse_to_start() {
+    if cfg!(feature = "servo") {
+        return;
+    }
+    let status = Command::new(env!("CARGO_BIN_EXE_serval-shell"))
+        .arg("--headless")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
@@ -0,0 +1,154 @@
+//! Webviews talking to the in-process bridge through the shell.
+
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use serval_bridge::engine::{MockEngine, Scenario};
+use serval_protocol::{Encoding, ErrorCode, Event, PROTOCOL_VERSION};
+use serval_shell::Shell;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn shell() -> Shell {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../scenarios/dev.json");
+    let scenario = Scenario::load(path).unwrap();
+    Shell::start(move |waker| MockEngine::new(waker, scenario, 200, 100))
+}
+
+/// Connects `label`, returning the events delivered to it.
+fn connect(shell: &Shell, label: &str) -> Receiver<Event> {
+    let (sender, events) = mpsc::channel();
+    shell.connect(label, move |envelope| sender.send(envelope.event).is_ok());
+    events
+}
+
+fn expect(events: &Receiver<Event>, what: &str, predicate: impl Fn(&Event) -> bool) -> Event {
+    loop {
+        match events.recv_timeout(TIMEOUT) {
+            Ok(event) if predicate(&event) => return event,
+            Ok(_) => {}
+            Err(_) => panic!("timed out waiting for {what}"),
+        }
+    }
+}
+
+/// Sends `ready` for `label` and returns its client id and encoding.
+fn ready(shell: &Shell, label: &str, events: &Receiver<Event>) -> (u64, Encoding) {
+    shell
+        .post(
+            label,
+            &format!(
+                r#"{{"type":"ready","id":1,"protocolVersion":{PROTOCOL_VERSION},"encoding":"messagePack"}}"#
+            ),
+        )
+        .unwrap();
+    let Event::Ready {
+        client_id,
+        encoding,
+        ..
+    } = expect(events, "ready", |event| {
+        matches!(event, Event::Ready { .. })
+    })
+    else {
+        unreachable!()
+    };
+    (client_id, encoding)
+}
+
+#[test]
+fn webviews_drive_the_bridge() {
+    let shell = shell();
+    let events = connect(&shell, "main");
+    assert_eq!(ready(&shell, "main", &events).1, Encoding::Json);
+
+    shell
+        .post(
+            "main",
+            r#"{"type":"navigate","id":2,"tabId":"1","url":"https://example.com/"}"#,
+        )
+        .unwrap();
+    expect(&events, "ack", |event| {
+        matches!(event, Event::Ack { id: 2 })
+    });
+    expect(&events, "frame", |event| {
+        matches!(event, Event::Frame { .. })
+    });
+    expect(
+        &events,
+        "loadComplete",
+        |event| matches!(event, Event::LoadComplete { url, .. } if url == "https://example.com/"),
+    );
+}
+
+#[test]
+fn malformed_messages_are_answered() {
+    let shell = shell();
+    let events = connect(&shell, "main");
+    shell.post("main", r#"{"type":"nope","id":7}"#).unwrap();
+    expect(&events, "error", |event| {
+        matches!(
+            event,
+            Event::Error {
+                id: Some(7),
+                code: ErrorCode::InvalidMessage,
+                ..
+            }
+        )
+    });
+}
+
+#[test]
+fn messages_need_a_connected_webview() {
+    let shell = shell();
+    let events = connect(&shell, "main");
+    shell.disconnect("main");
+    assert!(shell.post("main", r#"{"type":"ready"}"#).is_err());
+    assert!(shell.post("other", r#"{"type":"ready"}"#).is_err());
+    // The bridge lets go of the webview's events.
+    while events.recv_timeout(TIMEOUT).is_ok() {}
+}
+
+#[test]
+fn every_window_is_a_client_of_its_own() {
+    let shell = shell();
+    let main = connect(&shell, "main");
+    let other = connect(&shell, "other");
+    let (main_id, _) = ready(&shell, "main", &main);
+    let (other_id, _) = ready(&shell, "other", &other);
+    assert_ne!(main_id, other_id);
+
+    shell
+        .post(
+            "main",
+            r#"{"type":"navigate","id":2,"tabId":"1","url":"https://example.com/"}"#,
+        )
+        .unwrap();
+    expect(&main, "loadComplete", |event| {
+        matches!(event, Event::LoadComplete { .. })
+    });
+    shell
+        .post("other", r#"{"type":"refresh","id":2,"tabId":"1"}"#)
+        .unwrap();
+    expect(&other, "notTabOwner", |event| {
+        matches!(
+            event,
+            Event::Error {
+                code: ErrorCode::NotTabOwner,
+                ..
+            }
+        )
+    });
+}
+
+#[test]
+fn reloaded_pages_start_over() {
+    let shell = shell();
+    let before = connect(&shell, "main");
+    let (first, _) = ready(&shell, "main", &before);
+
+    let after = connect(&shell, "main");
+    let (second, _) = ready(&shell, "main", &after);
+    assert_ne!(first, second);
+    while before.recv_timeout(TIMEOUT).is_ok() {}
+}
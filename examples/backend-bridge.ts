@@ -11,14 +11,8 @@
  * - HTTP REST API
  */
 
-// Type definitions matching ServoBackend.ts
-interface ServoMessage {
-  type: 'navigate' | 'back' | 'forward' | 'refresh' | 'close' | 'ready' | 'titleChange' | 'urlChange' | 'loadStart' | 'loadComplete';
-  tabId?: string;
-  url?: string;
-  title?: string;
-  data?: unknown;
-}
+// Protocol types generated from the serval-protocol crate
+import type { ServoEvent, ServoRequest } from '../src/backend/protocol';
 
 /**
  * WebSocket Bridge Implementation
@@ -46,7 +40,7 @@ class WebSocketServoBridge {
 
       this.ws.onmessage = (event) => {
         try {
-          const message: ServoMessage = JSON.parse(event.data);
+          const message: ServoEvent = JSON.parse(event.data);
           this.handleMessageFromServo(message);
         } catch (e) {
           console.error('Failed to parse message from Servo:', e);
@@ -70,13 +64,13 @@ class WebSocketServoBridge {
   private setupBackendInterface(): void {
     // Expose interface to the window object
     (window as any).__SERVO_BACKEND__ = {
-      postMessage: (message: ServoMessage) => {
+      postMessage: (message: ServoRequest) => {
         this.sendMessageToServo(message);
       }
     };
   }
 
-  private sendMessageToServo(message: ServoMessage): void {
+  private sendMessageToServo(message: ServoRequest): void {
     if (this.ws && this.ws.readyState === WebSocket.OPEN) {
       this.ws.send(JSON.stringify(message));
     } else {
@@ -84,7 +78,7 @@ class WebSocketServoBridge {
     }
   }
 
-  private handleMessageFromServo(message: ServoMessage): void {
+  private handleMessageFromServo(message: ServoEvent): void {
     // Forward message to the React frontend
     window.postMessage({
       source: 'servo-backend',
@@ -119,13 +113,13 @@ class ElectronServoBridge {
 
     // Set up the interface
     (window as any).__SERVO_BACKEND__ = {
-      postMessage: (message: ServoMessage) => {
+      postMessage: (message: ServoRequest) => {
         ipcRenderer.send('servo-command', message);
       }
     };
 
     // Listen for messages from Servo (via Electron main process)
-    ipcRenderer.on('servo-event', (_event: any, message: ServoMessage) => {
+    ipcRenderer.on('servo-event', (_event: any, message: ServoEvent) => {
       window.postMessage({
         source: 'servo-backend',
         message: message
@@ -149,71 +143,72 @@ class MockServoBridge {
 
   private setupMockBackend(): void {
     (window as any).__SERVO_BACKEND__ = {
-      postMessage: (message: ServoMessage) => {
+      postMessage: (message: ServoRequest) => {
         this.handleCommand(message);
       }
     };
   }
 
-  private handleCommand(message: ServoMessage): void {
-    const { type, tabId, url } = message;
-
-    switch (type) {
-      case 'navigate':
-        if (tabId && url) {
-          // Simulate navigation
+  private handleCommand(message: ServoRequest): void {
+    switch (message.type) {
+      case 'navigate': {
+        const { tabId, url } = message;
+        // Simulate navigation
+        setTimeout(() => {
+          // Send load start event
+          this.sendEvent({
+            type: 'loadStart',
+            tabId,
+            url
+          });
+
+          // Simulate loading delay
           setTimeout(() => {
-            // Send load start event
+            // Update tab info
+            const title = this.extractTitleFromUrl(url);
+            this.tabs.set(tabId, {
+              url,
+              title,
+              history: [...(this.tabs.get(tabId)?.history || []), url]
+            });
+
+            // Send title change
             this.sendEvent({
-              type: 'loadStart',
+              type: 'titleChange',
               tabId,
-              url
+              title
             });
 
-            // Simulate loading delay
-            setTimeout(() => {
-              // Update tab info
-              const title = this.extractTitleFromUrl(url);
-              this.tabs.set(tabId, {
-                url,
-                title,
-                history: [...(this.tabs.get(tabId)?.history || []), url]
-              });
-
-              // Send title change
-              this.sendEvent({
-                type: 'titleChange',
-                tabId,
-                title
-              });
-
-              // Send load complete
-              this.sendEvent({
-                type: 'loadComplete',
-                tabId,
-                url
-              });
-            }, 500);
-          }, 100);
-        }
+            // Send load complete
+            this.sendEvent({
+              type: 'loadComplete',
+              tabId,
+              url
+            });
+          }, 500);
+        }, 100);
         break;
+      }
 
       case 'back':
       case 'forward':
       case 'refresh':
         // Simulate navigation history
-        console.log(`Mock: ${type} for tab ${tabId}`);
+        console.log(`Mock: ${message.type} for tab ${message.tabId}`);
         break;
 
       case 'close':
-        if (tabId) {
-          this.tabs.delete(tabId);
-        }
+        this.tabs.delete(message.tabId);
         break;
     }
+
+    // Acknowledge commands that expect an answer
+    if (message.id !== undefined) {
+      this.sendEvent({ type: 'ack', id: message.id });
+    }
   }
 
-  private sendEvent(message: ServoMessage): void {
+  private sendEvent(message: ServoEvent): void {
     window.postMessage({
       source: 'servo-backend',
       message
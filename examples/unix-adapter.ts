@@ -0,0 +1,67 @@
+/**
+ * Local adapter for `serval-bridge --unix`
+ *
+ * Pages cannot open Unix domain sockets, so the shell hosting Serval (e.g.
+ * Electron's main process) connects to the bridge and relays messages. The
+ * bridge only accepts processes of its own user, so the adapter must run as
+ * that user.
+ *
+ * Messages travel one JSON object per line in both directions.
+ */
+
+import { createConnection, type Socket } from 'node:net';
+import { tmpdir, userInfo } from 'node:os';
+import { join } from 'node:path';
+
+// Protocol types generated from the serval-protocol crate
+import type { ServoEvent, ServoRequest } from '../src/backend/protocol';
+
+/** The bridge's default socket, as chosen by `serval-bridge --unix` */
+export function defaultSocketPath(): string {
+  const runtime = process.env.XDG_RUNTIME_DIR;
+  if (runtime) {
+    return join(runtime, 'serval', 'bridge.sock');
+  }
+  return join(tmpdir(), `serval-${userInfo().uid}`, 'bridge.sock');
+}
+
+export interface UnixBridgeConnection {
+  send(message: ServoRequest): void;
+  close(): void;
+}
+
+/**
+ * Connects to the bridge at `socketPath` and calls `onEvent` for every event
+ * it sends. Partial lines are buffered until they are complete, so messages
+ * split across or merged into reads arrive intact.
+ */
+export function connectUnixBridge(
+  socketPath: string,
+  onEvent: (event: ServoEvent) => void,
+  onClose?: (error?: Error) => void,
+): UnixBridgeConnection {
+  const socket: Socket = createConnection(socketPath);
+  let buffer = '';
+
+  socket.setEncoding('utf8');
+  socket.on('data', (chunk: string) => {
+    buffer += chunk;
+    let end: number;
+    while ((end = buffer.indexOf('\n')) >= 0) {
+      const line = buffer.slice(0, end);
+      buffer = buffer.slice(end + 1);
+      if (line.trim()) {
+        onEvent(JSON.parse(line) as ServoEvent);
+      }
+    }
+  });
+  socket.on('error', (error) => onClose?.(error));
+  socket.on('close', () => onClose?.());
+
+  return {
+    send: (message) => {
+      socket.write(JSON.stringify(message) + '\n');
+    },
+    close: () => socket.end(),
+  };
+}
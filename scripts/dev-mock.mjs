@@ -0,0 +1,35 @@
+// Starts the mock engine and the Vite dev server with a shared per-launch
+// token, the way a launcher would for Servo.
+//
+//   npm run dev:mock [-- <scenario.json>]
+
+import { spawn } from 'node:child_process';
+import { randomBytes } from 'node:crypto';
+
+const scenario = process.argv[2] ?? 'scenarios/dev.json';
+const token = randomBytes(32).toString('hex');
+
+const children = [
+  spawn('cargo', ['run', '-p', 'serval-bridge', '--', '--mock', scenario], {
+    stdio: 'inherit',
+    env: { ...process.env, SERVAL_BRIDGE_TOKEN: token },
+  }),
+  spawn('npx', ['vite'], {
+    stdio: 'inherit',
+    env: { ...process.env, VITE_SERVO_MODE: 'servo', VITE_SERVO_TOKEN: token },
+  }),
+];
+
+const stop = () => {
+  for (const child of children) {
+    child.kill();
+  }
+};
+for (const child of children) {
+  child.on('exit', (code) => {
+    stop();
+    process.exitCode = code ?? 1;
+  });
+}
+process.on('SIGINT', stop);
+process.on('SIGTERM', stop);
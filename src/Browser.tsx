@@ -1,19 +1,134 @@
-import React, { useState } from 'react';
+import React, { useEffect, useRef, useState } from 'react';
 import TabBar from './components/TabBar';
 import type { Tab } from './components/TabBar';
+import type { HistoryEntry, TabSnapshot } from './backend/protocol';
 import AddressBar from './components/AddressBar';
 import ServoView from './components/ServoView';
 import { getServoBackend } from './backend/ServoBackend';
+import type { ServoEventOf } from './backend/ServoBackend';
 import './Browser.css';
 
+/** Zoom levels the zoom shortcuts step through */
+const ZOOM_LEVELS = [0.3, 0.5, 0.67, 0.8, 0.9, 1, 1.1, 1.25, 1.5, 1.75, 2, 2.5, 3];
+
+/**
+ * The zoom level after `zoom` in `direction`, or 1 for a reset
+ */
+function stepZoom(zoom: number, direction: 'in' | 'out' | 'reset'): number {
+  switch (direction) {
+    case 'in':
+      return ZOOM_LEVELS.find((level) => level > zoom) ?? zoom;
+    case 'out':
+      return [...ZOOM_LEVELS].reverse().find((level) => level < zoom) ?? zoom;
+    case 'reset':
+      return 1;
+  }
+}
+
+/**
+ * An empty tab. Ids are unique across every window sharing the bridge.
+ */
+function createTab(): Tab {
+  return { id: crypto.randomUUID(), title: 'New Tab', url: '' };
+}
+
+/**
+ * Whether `tab` is the empty tab a window starts with, untouched
+ */
+function isBlank(tab: Tab): boolean {
+  return tab.url === '' && tab.history === undefined;
+}
+
+/**
+ * Bring the local tab list in line with the shared session: adopt the tabs
+ * other windows opened or the bridge restored, drop those they closed, and
+ * take over what the bridge knows about the others. Local tabs the bridge
+ * has never seen, like a tab that was just opened, are kept.
+ */
+function mergeTabs(tabs: Tab[], shared: TabSnapshot[], previouslyShared: Set<string>): Tab[] {
+  const byId = new Map(shared.map((tab) => [tab.tabId, tab]));
+  const merged = tabs
+    .filter((tab) => byId.has(tab.id) || !previouslyShared.has(tab.id))
+    .map((tab) => {
+      const remote = byId.get(tab.id);
+      byId.delete(tab.id);
+      return remote ? fromSnapshot(remote, tab) : tab;
+    });
+  return [...merged, ...[...byId.values()].map((remote) => fromSnapshot(remote))];
+}
+
+function fromSnapshot(remote: TabSnapshot, tab?: Tab): Tab {
+  return {
+    ...tab,
+    id: remote.tabId,
+    title: remote.title ?? tab?.title ?? 'New Tab',
+    url: remote.url ?? tab?.url ?? '',
+    history: { entries: remote.entries, index: remote.index },
+    owner: remote.owner,
+  };
+}
+
 const Browser: React.FC = () => {
-  const [tabs, setTabs] = useState<Tab[]>([
-    { id: '1', title: 'New Tab', url: '' },
-  ]);
-  const [activeTabId, setActiveTabId] = useState<string>('1');
+  const [tabs, setTabs] = useState<Tab[]>(() => [createTab()]);
+  const [activeTabId, setActiveTabId] = useState<string>(() => tabs[0].id);
   const servoBackend = getServoBackend();
+  // Tabs the last snapshot listed; gone from the next one means closed
+  const sharedTabIds = useRef<Set<string>>(new Set());
+  const [crashedSession, setCrashedSession] = useState(() => servoBackend.getCrashedSession());
+  const [bookmarks, setBookmarks] = useState(() => servoBackend.getBookmarks());
 
   const activeTab = tabs.find((tab) => tab.id === activeTabId);
+  const history = activeTab?.history;
+  const canGoBack = history !== undefined && history.index > 0;
+  const canGoForward =
+    history !== undefined && history.index < history.entries.length - 1;
+  const ownedElsewhere =
+    activeTab?.owner !== undefined && activeTab.owner !== servoBackend.getClientId();
+
+  useEffect(() => {
+    // Other windows attached to the same bridge open and close tabs too
+    const applySnapshot = (snapshot: ServoEventOf<'tabsSnapshot'>) => {
+      const previouslyShared = sharedTabIds.current;
+      sharedTabIds.current = new Set(snapshot.tabs.map((tab) => tab.tabId));
+      setTabs((prevTabs) => {
+        // Restored tabs replace the empty one the window started with
+        const local =
+          snapshot.tabs.length > 0 && prevTabs.length === 1 && isBlank(prevTabs[0])
+            ? []
+            : prevTabs;
+        const merged = mergeTabs(local, snapshot.tabs, previouslyShared);
+        return merged.length > 0 ? merged : [createTab()];
+      });
+    };
+    const latest = servoBackend.getTabsSnapshot();
+    if (latest) {
+      applySnapshot(latest);
+    }
+    servoBackend.on('tabsSnapshot', applySnapshot);
+    // Until a window answers, every window offers to restore the session
+    servoBackend.on('crashedSession', () => setCrashedSession(servoBackend.getCrashedSession()));
+    // Every window shows the same bookmarks
+    servoBackend.on('bookmarksChanged', () => setBookmarks(servoBackend.getBookmarks()));
+    return () => {
+      servoBackend.off('tabsSnapshot');
+      servoBackend.off('crashedSession');
+      servoBackend.off('bookmarksChanged');
+    };
+  }, [servoBackend]);
+
+  const answerCrashedSession = (restore: boolean) => {
+    setCrashedSession(null);
+    (restore ? servoBackend.restoreSession() : servoBackend.discardSession()).catch(
+      (error: Error) => console.error('Cannot answer for the crashed session:', error.message),
+    );
+  };
+
+  useEffect(() => {
+    // The active tab was closed in another window
+    if (!tabs.some((tab) => tab.id === activeTabId)) {
+      setActiveTabId(tabs[0].id);
+    }
+  }, [tabs, activeTabId]);
 
   const handleNavigate = (url: string) => {
     setTabs((prevTabs) =>
@@ -39,14 +154,52 @@ const Browser: React.FC = () => {
     );
   };
 
+  const handleHistoryChange = (entries: HistoryEntry[], index: number) => {
+    setTabs((prevTabs) =>
+      prevTabs.map((tab) =>
+        tab.id === activeTabId ? { ...tab, history: { entries, index } } : tab
+      )
+    );
+  };
+
+  const handleZoom = (direction: 'in' | 'out' | 'reset') => {
+    setTabs((prevTabs) =>
+      prevTabs.map((tab) =>
+        tab.id === activeTabId
+          ? { ...tab, zoom: stepZoom(tab.zoom ?? 1, direction) }
+          : tab
+      )
+    );
+  };
+
+  useEffect(() => {
+    // Ctrl (or Cmd) with +, - and 0 zooms the page, as in other browsers
+    const handleKeyDown = (event: KeyboardEvent) => {
+      if (!(event.ctrlKey || event.metaKey) || event.altKey) {
+        return;
+      }
+      const direction =
+        event.key === '+' || event.key === '=' ? 'in'
+        : event.key === '-' ? 'out'
+        : event.key === '0' ? 'reset'
+        : null;
+      if (direction) {
+        event.preventDefault();
+        handleZoom(direction);
+      }
+    };
+    window.addEventListener('keydown', handleKeyDown);
+    return () => window.removeEventListener('keydown', handleKeyDown);
+  });
+
   const handleNewTab = () => {
-    const newId = crypto.randomUUID();
-    const newTab: Tab = { id: newId, title: 'New Tab', url: '' };
+    const newTab = createTab();
     setTabs((prevTabs) => [...prevTabs, newTab]);
-    setActiveTabId(newId);
+    setActiveTabId(newTab.id);
   };
 
   const handleTabClose = (tabId: string) => {
+    servoBackend.closeTab(tabId);
     if (tabs.length === 1) {
       // If it's the last tab, create a new empty tab
       handleNewTab();
@@ -66,6 +219,21 @@ const Browser: React.FC = () => {
     });
   };
 
+  const pageBookmarks = bookmarks.filter(
+    (bookmark) => activeTab?.url && bookmark.url === activeTab.url,
+  );
+
+  const handleToggleBookmark = () => {
+    if (!activeTab?.url) {
+      return;
+    }
+    const done =
+      pageBookmarks.length > 0
+        ? Promise.all(pageBookmarks.map((bookmark) => servoBackend.removeBookmark(bookmark.id)))
+        : servoBackend.addBookmark({ title: activeTab.title, url: activeTab.url });
+    done.catch((error: Error) => console.error('Cannot change bookmarks:', error.message));
+  };
+
   const handleBack = () => {
     servoBackend.goBack(activeTabId);
   };
@@ -87,20 +255,40 @@ const Browser: React.FC = () => {
         onTabClose={handleTabClose}
         onNewTab={handleNewTab}
       />
+      {crashedSession && (
+        <div className="session-banner">
+          <span>
+            Serval closed unexpectedly with {crashedSession.tabs.length}{' '}
+            {crashedSession.tabs.length === 1 ? 'tab' : 'tabs'} open.
+          </span>
+          <button onClick={() => answerCrashedSession(true)}>Restore previous session</button>
+          <button className="secondary" onClick={() => answerCrashedSession(false)}>
+            Dismiss
+          </button>
+        </div>
+      )}
       <AddressBar
         url={activeTab?.url || ''}
         onNavigate={handleNavigate}
+        onSwitchTab={setActiveTabId}
         onBack={handleBack}
         onForward={handleForward}
         onRefresh={handleRefresh}
-        canGoBack={false}
-        canGoForward={false}
+        canGoBack={canGoBack}
+        canGoForward={canGoForward}
+        zoom={activeTab?.zoom ?? 1}
+        onResetZoom={() => handleZoom('reset')}
+        bookmarked={pageBookmarks.length > 0}
+        onToggleBookmark={handleToggleBookmark}
       />
       <ServoView
         tabId={activeTabId}
         url={activeTab?.url || ''}
+        zoom={activeTab?.zoom ?? 1}
+        ownedElsewhere={ownedElsewhere}
         onTitleChange={handleTitleChange}
         onUrlChange={handleUrlChange}
+        onHistoryChange={handleHistoryChange}
       />
     </div>
   );
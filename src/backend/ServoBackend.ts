@@ -5,11 +5,24 @@
  * Servo runs as a separate process and communicates with the React frontend through IPC.
  */
 
+import { EVENT_TYPES, PROTOCOL_VERSION } from './protocol';
+import type {
+  Bookmark,
+  Capability,
+  InputEvent,
+  ServoCommand,
+  ServoEnvelope,
+  ServoEvent,
+  ServoRequest,
+} from './protocol';
+
+export type { ServoCommand, ServoEnvelope, ServoEvent, ServoMessage, ServoRequest } from './protocol';
+
 // Extend Window interface for Servo backend
 declare global {
   interface Window {
     __SERVO_BACKEND__?: {
-      postMessage: (message: ServoMessage) => void;
+      postMessage: (message: ServoRequest) => void;
     };
   }
 }
@@ -33,12 +46,19 @@ export interface NavigationResponse {
   error?: string;
 }
 
-export interface ServoMessage {
-  type: 'navigate' | 'back' | 'forward' | 'refresh' | 'close' | 'ready' | 'titleChange' | 'urlChange' | 'loadStart' | 'loadComplete';
-  tabId?: string;
-  url?: string;
-  title?: string;
-  data?: unknown;
+/**
+ * A Servo event of a specific type, e.g. `ServoEventOf<'titleChange'>`
+ */
+export type ServoEventOf<K extends ServoEvent['type']> = Extract<ServoEvent, { type: K }>;
+
+/**
+ * What the bridge announced in its `ready` event
+ */
+export type EngineInfo = ServoEventOf<'ready'>;
+
+interface PendingRequest {
+  resolve: (answer: ServoEvent) => void;
+  reject: (error: Error) => void;
 }
 
 /**
@@ -47,8 +67,19 @@ export interface ServoMessage {
  */
 export class ServoBackend {
   private config: ServoBackendConfig;
-  private messageHandlers: Map<string, (message: ServoMessage) => void>;
+  private messageHandlers: Map<string, (message: ServoEvent) => void>;
+  private pendingRequests: Map<number, PendingRequest> = new Map();
+  private nextRequestId: number = 1;
   private connected: boolean = false;
+  private engineInfo: EngineInfo | null = null;
+  private tabsSnapshot: ServoEventOf<'tabsSnapshot'> | null = null;
+  private crashedSession: ServoEventOf<'crashedSession'> | null = null;
+  private bookmarks: Bookmark[] = [];
+  /** The bridge session the events came from, to resume it after a reconnect */
+  private sessionId: string | null = null;
+  /** The sequence number of the last session event received */
+  private lastSeq = 0;
+  private engineInfoListeners: Set<(info: EngineInfo) => void> = new Set();
 
   constructor(config: ServoBackendConfig = {}) {
     this.config = config;
@@ -63,7 +94,7 @@ export class ServoBackend {
     if (this.isServoAvailable()) {
       this.setupMessageHandlers();
       this.connected = true;
-      this.sendMessage({ type: 'ready' });
+      this.sendMessage({ type: 'ready', protocolVersion: PROTOCOL_VERSION, ackFrames: true });
     } else {
       if (this.config.debug) {
         console.warn('Servo backend not available.');
@@ -90,21 +121,75 @@ export class ServoBackend {
     // Listen for messages from Servo backend
     window.addEventListener('message', (event) => {
       if (event.data && event.data.source === 'servo-backend') {
-        this.handleMessage(event.data.message);
+        if (event.data.reconnected) {
+          this.resume();
+        } else {
+          this.handleMessage(event.data.message);
+        }
       }
     });
   }
 
+  /**
+   * Pick the session up again after the connection to the bridge dropped:
+   * the bridge replays the events missed in between, or sends the current
+   * tab list when it no longer has them all
+   */
+  private resume(): void {
+    const resume =
+      this.sessionId === null ? undefined : { sessionId: this.sessionId, seq: this.lastSeq };
+    this.sendMessage({
+      type: 'ready',
+      protocolVersion: PROTOCOL_VERSION,
+      resume,
+      ackFrames: true,
+    });
+  }
+
   /**
    * Handle incoming messages from Servo
    */
-  private handleMessage(message: ServoMessage): void {
-    // Validate message type before processing
-    const validTypes = ['navigate', 'back', 'forward', 'refresh', 'close', 'ready', 'titleChange', 'urlChange', 'loadStart', 'loadComplete'];
-    if (!validTypes.includes(message.type)) {
-      console.warn('Invalid message type received:', message.type);
+  private handleMessage(received: unknown): void {
+    // Only dispatch events shaped like the serval-protocol crate defines them
+    const invalid = invalidEvent(received);
+    if (invalid !== null) {
+      console.warn(`Invalid message received from Servo: ${invalid}`, received);
       return;
     }
+    const message = received as ServoEnvelope;
+
+    if (message.seq !== undefined) {
+      this.lastSeq = message.seq;
+    }
+    if (message.type === 'ready') {
+      this.sessionId = message.sessionId;
+      if (!message.resumed) {
+        // A tab list follows; it is as recent as the latest event
+        this.lastSeq = message.latestSeq;
+      }
+      this.engineInfo = message;
+      for (const listener of this.engineInfoListeners) {
+        listener(message);
+      }
+    } else if (message.type === 'tabsSnapshot') {
+      this.tabsSnapshot = message;
+    } else if (message.type === 'crashedSession') {
+      this.crashedSession = message.tabs.length > 0 ? message : null;
+    } else if (message.type === 'bookmarksChanged') {
+      this.bookmarks = message.bookmarks;
+    }
+
+    // Settle the request this message answers, if any: with an `ack`, an
+    // `error` or an answer carrying data like `inputResolved`
+    if ('id' in message && message.id !== undefined) {
+      const pending = this.pendingRequests.get(message.id);
+      this.pendingRequests.delete(message.id);
+      if (message.type === 'error') {
+        pending?.reject(new Error(message.message));
+      } else {
+        pending?.resolve(message);
+      }
+    }
 
     const handler = this.messageHandlers.get(message.type);
     if (handler) {
@@ -115,33 +200,151 @@ export class ServoBackend {
   /**
    * Send a message to Servo backend
    */
-  private sendMessage(message: ServoMessage): void {
+  private sendMessage(message: ServoRequest): boolean {
     if (!this.connected) {
       console.warn('Servo backend not connected');
-      return;
+      return false;
     }
 
     if (typeof window !== 'undefined' && window.__SERVO_BACKEND__) {
       window.__SERVO_BACKEND__.postMessage(message);
+      return true;
     }
+    return false;
+  }
+
+  /**
+   * Send a command and wait for the engine to answer or reject it
+   */
+  private request(command: ServoCommand): Promise<ServoEvent> {
+    return new Promise((resolve, reject) => {
+      const id = this.nextRequestId++;
+      this.pendingRequests.set(id, { resolve, reject });
+
+      if (!this.sendMessage({ ...command, id })) {
+        this.pendingRequests.delete(id);
+        reject(new Error('Servo backend not connected'));
+      }
+    });
   }
 
   /**
    * Navigate to a URL in a specific tab
+   *
+   * Resolves once the engine accepted or rejected the navigation.
    */
   navigate(tabId: string, url: string): Promise<NavigationResponse> {
-    return new Promise((resolve) => {
-      this.sendMessage({
-        type: 'navigate',
-        tabId,
-        url,
-      });
-
-      // In a real implementation, wait for response from Servo
-      setTimeout(() => {
-        resolve({ success: true, url });
-      }, 0);
-    });
+    return this.request({ type: 'navigate', tabId, url }).then(
+      () => ({ success: true, url }),
+      (error: Error) => ({ success: false, url, error: error.message }),
+    );
+  }
+
+  /**
+   * Find out what text typed in the address bar loads: the address it
+   * spells, fixed up, or a search
+   */
+  resolveInput(text: string): Promise<ServoEventOf<'inputResolved'>> {
+    return this.request({ type: 'resolveInput', text }) as Promise<ServoEventOf<'inputResolved'>>;
+  }
+
+  /**
+   * Suggestions for text typed in the address bar so far: what Enter loads
+   * first, then search keywords, open tabs, bookmarks and history
+   */
+  suggest(text: string, limit?: number): Promise<ServoEventOf<'suggestions'>> {
+    return this.request({ type: 'suggest', text, limit }) as Promise<ServoEventOf<'suggestions'>>;
+  }
+
+  /**
+   * Visits of every tab, newest first: those whose address or title contain
+   * every word of `text`, at or after `from` and before `to`, in
+   * milliseconds since the Unix epoch
+   */
+  queryHistory(
+    query: { text?: string; from?: number; to?: number; limit?: number } = {},
+  ): Promise<ServoEventOf<'historyVisits'>> {
+    return this.request({ type: 'queryHistory', ...query }) as Promise<ServoEventOf<'historyVisits'>>;
+  }
+
+  /**
+   * Forget the visits to `site` and its subdomains between `from` and `to`,
+   * or every visit when nothing is given
+   */
+  deleteHistory(range: { site?: string; from?: number; to?: number } = {}): Promise<void> {
+    return this.request({ type: 'deleteHistory', ...range }).then(() => undefined);
+  }
+
+  /**
+   * Bookmark `url`, or add a folder when it is missing, at `index` of the
+   * folder `parentId` or the end of the top level
+   */
+  addBookmark(bookmark: {
+    title: string;
+    url?: string;
+    parentId?: number;
+    index?: number;
+    tags?: string[];
+    keyword?: string;
+  }): Promise<Bookmark> {
+    return this.request({ type: 'addBookmark', ...bookmark }).then(
+      (answer) => (answer as ServoEventOf<'bookmarkAdded'>).bookmark,
+    );
+  }
+
+  /**
+   * Change the title, address, tags or keyword of a bookmark; an empty
+   * keyword removes it
+   */
+  updateBookmark(
+    bookmarkId: number,
+    changes: { title?: string; url?: string; tags?: string[]; keyword?: string },
+  ): Promise<void> {
+    return this.request({ type: 'updateBookmark', bookmarkId, ...changes }).then(() => undefined);
+  }
+
+  /**
+   * Move a bookmark or folder to `index` of the folder `parentId`, the top
+   * level when it is missing
+   */
+  moveBookmark(bookmarkId: number, parentId?: number, index?: number): Promise<void> {
+    return this.request({ type: 'moveBookmark', bookmarkId, parentId, index }).then(() => undefined);
+  }
+
+  /**
+   * Remove a bookmark, or a folder with everything in it
+   */
+  removeBookmark(bookmarkId: number): Promise<void> {
+    return this.request({ type: 'removeBookmark', bookmarkId }).then(() => undefined);
+  }
+
+  /**
+   * Bookmarks whose address, title, tags or keyword contain every word of
+   * `text`, with `tag` if given
+   */
+  searchBookmarks(
+    query: { text?: string; tag?: string; limit?: number } = {},
+  ): Promise<Bookmark[]> {
+    return this.request({ type: 'searchBookmarks', ...query }).then(
+      (answer) => (answer as ServoEventOf<'bookmarksFound'>).bookmarks,
+    );
+  }
+
+  /**
+   * Add the bookmarks of a bookmark file exported by Firefox, Chrome and
+   * others to the folder `parentId`
+   */
+  importBookmarks(html: string, parentId?: number): Promise<void> {
+    return this.request({ type: 'importBookmarks', html, parentId }).then(() => undefined);
+  }
+
+  /**
+   * Every bookmark as a bookmark file other browsers import
+   */
+  exportBookmarks(): Promise<string> {
+    return this.request({ type: 'exportBookmarks' }).then(
+      (answer) => (answer as ServoEventOf<'bookmarksExported'>).html,
+    );
   }
 
   /**
@@ -174,6 +377,85 @@ export class ServoBackend {
     });
   }
 
+  /**
+   * Deliver user input to the page shown in a tab
+   */
+  sendInput(tabId: string, event: InputEvent): void {
+    this.sendMessage({
+      type: 'input',
+      tabId,
+      event,
+    });
+  }
+
+  /**
+   * Resize a tab's content area, given in CSS pixels
+   */
+  resize(tabId: string, width: number, height: number, devicePixelRatio: number): void {
+    this.sendMessage({
+      type: 'resize',
+      tabId,
+      width,
+      height,
+      devicePixelRatio,
+    });
+  }
+
+  /**
+   * Set a tab's page zoom, 1 being 100%
+   */
+  setZoom(tabId: string, factor: number): void {
+    this.sendMessage({
+      type: 'zoom',
+      tabId,
+      factor,
+    });
+  }
+
+  /**
+   * Restart a crashed tab and load the last page it showed
+   */
+  reloadCrashed(tabId: string): void {
+    this.sendMessage({
+      type: 'reloadCrashed',
+      tabId,
+    });
+  }
+
+  /**
+   * Take control of a tab from the client controlling it
+   */
+  claimTab(tabId: string): void {
+    this.sendMessage({
+      type: 'claimTab',
+      tabId,
+    });
+  }
+
+  /**
+   * Tell the bridge a frame of a tab was painted, so it sends the next one
+   */
+  frameShown(tabId: string): void {
+    this.sendMessage({
+      type: 'frameShown',
+      tabId,
+    });
+  }
+
+  /**
+   * Reopen the tabs of the session that ended in a crash
+   */
+  restoreSession(): Promise<void> {
+    return this.request({ type: 'restoreSession' }).then(() => undefined);
+  }
+
+  /**
+   * Forget the session that ended in a crash
+   */
+  discardSession(): Promise<void> {
+    return this.request({ type: 'discardSession' }).then(() => undefined);
+  }
+
   /**
    * Close a tab
    */
@@ -187,17 +469,74 @@ export class ServoBackend {
   /**
    * Register a handler for a specific message type
    */
-  on(type: string, handler: (message: ServoMessage) => void): void {
-    this.messageHandlers.set(type, handler);
+  on<K extends ServoEvent['type']>(type: K, handler: (message: ServoEventOf<K>) => void): void {
+    this.messageHandlers.set(type, handler as (message: ServoEvent) => void);
   }
 
   /**
    * Remove a message handler
    */
-  off(type: string): void {
+  off(type: ServoEvent['type']): void {
     this.messageHandlers.delete(type);
   }
 
+  /**
+   * What the bridge announced, or null until it answered `ready`
+   */
+  getEngineInfo(): EngineInfo | null {
+    return this.engineInfo;
+  }
+
+  /**
+   * The id the bridge knows this frontend by, or null until it answered
+   * `ready`. Tabs whose `owner` differs are controlled by another client.
+   */
+  getClientId(): number | null {
+    return this.engineInfo?.clientId ?? null;
+  }
+
+  /**
+   * The latest tab list of the shared session, or null until the bridge
+   * sent one
+   */
+  getTabsSnapshot(): ServoEventOf<'tabsSnapshot'> | null {
+    return this.tabsSnapshot;
+  }
+
+  /**
+   * The session that ended in a crash, or null when there is none or a
+   * client restored or discarded it
+   */
+  getCrashedSession(): ServoEventOf<'crashedSession'> | null {
+    return this.crashedSession;
+  }
+
+  /**
+   * Every bookmark and folder, each folder followed by what it holds
+   */
+  getBookmarks(): Bookmark[] {
+    return this.bookmarks;
+  }
+
+  /**
+   * Call `listener` whenever the bridge announces itself; returns a function
+   * that removes the listener
+   */
+  onEngineInfo(listener: (info: EngineInfo) => void): () => void {
+    this.engineInfoListeners.add(listener);
+    return () => {
+      this.engineInfoListeners.delete(listener);
+    };
+  }
+
+  /**
+   * Check if the engine supports an optional feature. Nothing is supported
+   * until the bridge answered `ready`.
+   */
+  supports(capability: Capability): boolean {
+    return this.engineInfo?.capabilities.includes(capability) ?? false;
+  }
+
   /**
    * Check if connected to Servo backend
    */
@@ -210,10 +549,132 @@ export class ServoBackend {
    */
   destroy(): void {
     this.messageHandlers.clear();
+    this.engineInfoListeners.clear();
+    for (const pending of this.pendingRequests.values()) {
+      pending.reject(new Error('Servo backend destroyed'));
+    }
+    this.pendingRequests.clear();
     this.connected = false;
   }
 }
 
+/**
+ * Explain why the frontend cannot talk to a bridge, or return null when it
+ * speaks the same protocol version
+ */
+export function protocolMismatch(info: EngineInfo): string | null {
+  // Bridges from before the handshake answer a bare `ready`
+  const version: number | undefined = info.protocolVersion;
+  if (version === PROTOCOL_VERSION) {
+    return null;
+  }
+  if (version === undefined) {
+    return `The Servo bridge predates protocol versioning; this browser needs protocol version ${PROTOCOL_VERSION}.`;
+  }
+  const newer = version > PROTOCOL_VERSION ? 'a newer' : 'an older';
+  return `The Servo bridge speaks ${newer} protocol (version ${version}) than this browser (version ${PROTOCOL_VERSION}).`;
+}
+
+/**
+ * The JSON type of an event field, followed by `?` when the field may be left out
+ */
+type FieldKind = `${'string' | 'number' | 'boolean' | 'array' | 'object'}${'' | '?'}`;
+
+/**
+ * Every field of every event besides `type`, checked by the compiler against
+ * the event types in protocol.ts
+ */
+const EVENT_FIELDS: {
+  [T in ServoEvent['type']]: {
+    [K in Exclude<keyof Extract<ServoEvent, { type: T }>, 'type'>]-?: FieldKind;
+  };
+} = {
+  ready: {
+    protocolVersion: 'number',
+    servoVersion: 'string?',
+    capabilities: 'array',
+    clientId: 'number',
+    sessionId: 'string',
+    latestSeq: 'number',
+    resumed: 'boolean',
+    encoding: 'string',
+  },
+  tabsSnapshot: { tabs: 'array' },
+  ack: { id: 'number' },
+  error: { id: 'number?', code: 'string', message: 'string' },
+  inputResolved: { id: 'number?', url: 'string', searchEngine: 'string?' },
+  suggestions: { id: 'number?', text: 'string', suggestions: 'array' },
+  historyVisits: { id: 'number?', visits: 'array' },
+  crashedSession: { savedAt: 'number', tabs: 'array' },
+  bookmarkAdded: { id: 'number?', bookmark: 'object' },
+  bookmarksFound: { id: 'number?', bookmarks: 'array' },
+  bookmarksExported: { id: 'number?', html: 'string' },
+  bookmarksChanged: { bookmarks: 'array' },
+  loadStart: { tabId: 'string', url: 'string' },
+  urlChange: { tabId: 'string', url: 'string' },
+  titleChange: { tabId: 'string', title: 'string' },
+  loadComplete: { tabId: 'string', url: 'string' },
+  historyChanged: { tabId: 'string', entries: 'array', index: 'number' },
+  tabCrashed: {
+    tabId: 'string',
+    url: 'string?',
+    exitCode: 'number?',
+    signal: 'number?',
+    hung: 'boolean',
+  },
+  frame: { tabId: 'string', width: 'number', height: 'number', tiles: 'array' },
+  viewportChanged: {
+    tabId: 'string',
+    width: 'number',
+    height: 'number',
+    devicePixelRatio: 'number',
+    zoom: 'number',
+  },
+};
+
+function kindOf(value: unknown): string {
+  if (Array.isArray(value)) {
+    return 'array';
+  }
+  return value === null ? 'null' : typeof value;
+}
+
+/**
+ * Explain why a message received from the backend is not a Servo event, or
+ * return null when it is one
+ */
+function invalidEvent(message: unknown): string | null {
+  if (kindOf(message) !== 'object') {
+    return `message is ${kindOf(message)}, expected object`;
+  }
+  const fields = message as Record<string, unknown>;
+  const { type } = fields;
+  if (typeof type !== 'string') {
+    return `\`type\` is ${kindOf(type)}, expected string`;
+  }
+  if (!(EVENT_TYPES as readonly string[]).includes(type)) {
+    return `unknown event type \`${type}\``;
+  }
+  if (fields.seq !== undefined && typeof fields.seq !== 'number') {
+    return `\`seq\` is ${kindOf(fields.seq)}, expected number`;
+  }
+  const expected: Record<string, FieldKind> = EVENT_FIELDS[type as ServoEvent['type']];
+  for (const [name, kind] of Object.entries(expected)) {
+    const value = fields[name];
+    if (value === undefined) {
+      if (kind.endsWith('?')) {
+        continue;
+      }
+      return `\`${type}\` lacks \`${name}\``;
+    }
+    const wanted = kind.replace('?', '');
+    if (kindOf(value) !== wanted) {
+      return `\`${name}\` in \`${type}\` is ${kindOf(value)}, expected ${wanted}`;
+    }
+  }
+  return null;
+}
+
 // Singleton instance
 let backendInstance: ServoBackend | null = null;
 
@@ -0,0 +1,95 @@
+/**
+ * WebSocket Bridge
+ *
+ * Connects the frontend to a `serval-bridge` process over WebSocket. The
+ * bridge runs Servo or, for development, the scripted mock engine
+ * (`npm run dev:mock`). It only accepts clients presenting its per-launch
+ * token, which the launcher passes through `getConfig()`.
+ */
+
+import type { ServoEnvelope, ServoRequest } from './protocol';
+
+/** Delay between reconnection attempts */
+const RECONNECT_DELAY_MS = 1000;
+
+/**
+ * Installs `window.__SERVO_BACKEND__` on top of a WebSocket connection.
+ * Commands sent while the socket is down are queued until it (re)connects;
+ * events are re-posted to the window like a platform-provided backend would,
+ * and a reconnection is announced so the session can be resumed.
+ */
+export class WebSocketBridge {
+  private url: string;
+  /** `url` with the token, which must not end up in logs */
+  private connectUrl: string;
+  private debug: boolean;
+  private socket: WebSocket | null = null;
+  private queue: string[] = [];
+  private connectedBefore = false;
+
+  constructor(url: string, token?: string, debug = false) {
+    this.url = url;
+    this.debug = debug;
+    if (token) {
+      const withToken = new URL(url);
+      withToken.searchParams.set('token', token);
+      this.connectUrl = withToken.toString();
+    } else {
+      console.warn('[Serval] No bridge token configured; the bridge will refuse the connection');
+      this.connectUrl = url;
+    }
+    window.__SERVO_BACKEND__ = {
+      postMessage: (message: ServoRequest) => this.send(JSON.stringify(message)),
+    };
+    this.connect();
+  }
+
+  private connect(): void {
+    const socket = new WebSocket(this.connectUrl);
+    this.socket = socket;
+
+    socket.onopen = () => {
+      console.log(`[Serval] Connected to ${this.url}`);
+      for (const message of this.queue.splice(0)) {
+        socket.send(message);
+      }
+      if (this.connectedBefore) {
+        // Let ServoBackend resume the session it had
+        window.postMessage({ source: 'servo-backend', reconnected: true }, '*');
+      }
+      this.connectedBefore = true;
+    };
+
+    socket.onmessage = (event: MessageEvent<string>) => {
+      let message: ServoEnvelope;
+      try {
+        message = JSON.parse(event.data) as ServoEnvelope;
+      } catch (error) {
+        console.error('[Serval] Dropping malformed message from the bridge:', error);
+        return;
+      }
+      if (this.debug && message.type !== 'frame') {
+        console.log('[Serval] <-', message);
+      }
+      window.postMessage({ source: 'servo-backend', message }, '*');
+    };
+
+    socket.onclose = () => {
+      if (this.socket === socket) {
+        this.socket = null;
+        console.warn(
+          `[Serval] No bridge at ${this.url} or it refused the token, retrying in ${RECONNECT_DELAY_MS} ms`,
+        );
+        setTimeout(() => this.connect(), RECONNECT_DELAY_MS);
+      }
+    };
+  }
+
+  private send(message: string): void {
+    if (this.socket?.readyState === WebSocket.OPEN) {
+      this.socket.send(message);
+    } else {
+      this.queue.push(message);
+    }
+  }
+}
@@ -0,0 +1,464 @@
+// Generated by `cargo run -p serval-protocol --bin serval-protocol-ts`.
+// Do not edit by hand; change the Rust types in crates/serval-protocol instead.
+
+export type TabId = string;
+
+export type ServoCommand = { "type": "ready", 
+/**
+ * The protocol version the frontend speaks. Clients that leave it
+ * out check the version in the `ready` event themselves.
+ */
+protocolVersion?: number, 
+/**
+ * Where a client that reconnects left off. The bridge replays the
+ * events since, or sends a `tabsSnapshot` when it no longer has
+ * them all.
+ */
+resume?: Resume, 
+/**
+ * The encoding of every message after this one, in both directions.
+ * Defaults to JSON; transports that only carry text ignore it.
+ */
+encoding?: ServoEncoding, 
+/**
+ * Whether the client acknowledges every frame it showed with
+ * `frameShown`. The bridge then sends a tab no more than a few
+ * frames ahead of what the client showed, merging the frames in
+ * between.
+ */
+ackFrames?: boolean, } | { "type": "navigate", tabId: TabId, url: string, } | { "type": "back", tabId: TabId, } | { "type": "forward", tabId: TabId, } | { "type": "refresh", tabId: TabId, } | { "type": "close", tabId: TabId, } | { "type": "reloadCrashed", tabId: TabId, } | { "type": "claimTab", tabId: TabId, } | { "type": "frameShown", tabId: TabId, } | { "type": "input", tabId: TabId, event: InputEvent, } | { "type": "resize", tabId: TabId, width: number, height: number, devicePixelRatio: number, } | { "type": "zoom", tabId: TabId, factor: number, } | { "type": "resolveInput", text: string, } | { "type": "suggest", text: string, 
+/**
+ * How many suggestions to send at most, 8 when unset.
+ */
+limit?: number, } | { "type": "queryHistory", 
+/**
+ * Words every visit's URL or title must contain, ignoring case.
+ */
+text?: string, 
+/**
+ * Only visits at or after this time, in milliseconds since the Unix
+ * epoch.
+ */
+from?: number, 
+/**
+ * Only visits before this time, in milliseconds since the Unix
+ * epoch. The `visitedAt` of the last visit of an answer asks for the
+ * page of visits after it.
+ */
+to?: number, 
+/**
+ * How many visits to send at most, 100 when unset.
+ */
+limit?: number, } | { "type": "deleteHistory", 
+/**
+ * A host name, like `example.com`.
+ */
+site?: string, 
+/**
+ * As in `queryHistory`.
+ */
+from?: number, to?: number, } | { "type": "ping", } | { "type": "restoreSession", } | { "type": "discardSession", } | { "type": "addBookmark", 
+/**
+ * The folder to add it to, the top level when unset.
+ */
+parentId?: number, 
+/**
+ * Its place in the folder, the end when unset.
+ */
+index?: number, title: string, url?: string, tags?: Array<string>, 
+/**
+ * A word that loads the bookmark when typed in the address bar.
+ */
+keyword?: string, } | { "type": "updateBookmark", bookmarkId: number, title?: string, url?: string, tags?: Array<string>, keyword?: string, } | { "type": "moveBookmark", bookmarkId: number, parentId?: number, index?: number, } | { "type": "removeBookmark", bookmarkId: number, } | { "type": "searchBookmarks", 
+/**
+ * Words every bookmark's URL, title, tags or keyword must contain,
+ * ignoring case.
+ */
+text?: string, 
+/**
+ * Only bookmarks with this tag, ignoring case.
+ */
+tag?: string, 
+/**
+ * How many bookmarks to send at most, 100 when unset.
+ */
+limit?: number, } | { "type": "importBookmarks", html: string, parentId?: number, } | { "type": "exportBookmarks", };
+
+export type ServoRequest = { 
+/**
+ * When set, the bridge answers the command with an `ack` or `error`
+ * event carrying the same id.
+ */
+id?: number, } & ({ "type": "ready", 
+/**
+ * The protocol version the frontend speaks. Clients that leave it
+ * out check the version in the `ready` event themselves.
+ */
+protocolVersion?: number, 
+/**
+ * Where a client that reconnects left off. The bridge replays the
+ * events since, or sends a `tabsSnapshot` when it no longer has
+ * them all.
+ */
+resume?: Resume, 
+/**
+ * The encoding of every message after this one, in both directions.
+ * Defaults to JSON; transports that only carry text ignore it.
+ */
+encoding?: ServoEncoding, 
+/**
+ * Whether the client acknowledges every frame it showed with
+ * `frameShown`. The bridge then sends a tab no more than a few
+ * frames ahead of what the client showed, merging the frames in
+ * between.
+ */
+ackFrames?: boolean, } | { "type": "navigate", tabId: TabId, url: string, } | { "type": "back", tabId: TabId, } | { "type": "forward", tabId: TabId, } | { "type": "refresh", tabId: TabId, } | { "type": "close", tabId: TabId, } | { "type": "reloadCrashed", tabId: TabId, } | { "type": "claimTab", tabId: TabId, } | { "type": "frameShown", tabId: TabId, } | { "type": "input", tabId: TabId, event: InputEvent, } | { "type": "resize", tabId: TabId, width: number, height: number, devicePixelRatio: number, } | { "type": "zoom", tabId: TabId, factor: number, } | { "type": "resolveInput", text: string, } | { "type": "suggest", text: string, 
+/**
+ * How many suggestions to send at most, 8 when unset.
+ */
+limit?: number, } | { "type": "queryHistory", 
+/**
+ * Words every visit's URL or title must contain, ignoring case.
+ */
+text?: string, 
+/**
+ * Only visits at or after this time, in milliseconds since the Unix
+ * epoch.
+ */
+from?: number, 
+/**
+ * Only visits before this time, in milliseconds since the Unix
+ * epoch. The `visitedAt` of the last visit of an answer asks for the
+ * page of visits after it.
+ */
+to?: number, 
+/**
+ * How many visits to send at most, 100 when unset.
+ */
+limit?: number, } | { "type": "deleteHistory", 
+/**
+ * A host name, like `example.com`.
+ */
+site?: string, 
+/**
+ * As in `queryHistory`.
+ */
+from?: number, to?: number, } | { "type": "ping", } | { "type": "restoreSession", } | { "type": "discardSession", } | { "type": "addBookmark", 
+/**
+ * The folder to add it to, the top level when unset.
+ */
+parentId?: number, 
+/**
+ * Its place in the folder, the end when unset.
+ */
+index?: number, title: string, url?: string, tags?: Array<string>, 
+/**
+ * A word that loads the bookmark when typed in the address bar.
+ */
+keyword?: string, } | { "type": "updateBookmark", bookmarkId: number, title?: string, url?: string, tags?: Array<string>, keyword?: string, } | { "type": "moveBookmark", bookmarkId: number, parentId?: number, index?: number, } | { "type": "removeBookmark", bookmarkId: number, } | { "type": "searchBookmarks", 
+/**
+ * Words every bookmark's URL, title, tags or keyword must contain,
+ * ignoring case.
+ */
+text?: string, 
+/**
+ * Only bookmarks with this tag, ignoring case.
+ */
+tag?: string, 
+/**
+ * How many bookmarks to send at most, 100 when unset.
+ */
+limit?: number, } | { "type": "importBookmarks", html: string, parentId?: number, } | { "type": "exportBookmarks", });
+
+export type Resume = { sessionId: string, seq: number, };
+
+export type ServoEncoding = "json" | "messagePack" | "cbor";
+
+export type InputEvent = { "type": "pointerMove", x: number, y: number, pointerType: PointerType, 
+/**
+ * Tells touch points apart; ignored for the mouse.
+ */
+pointerId: number, } | { "type": "pointerDown", x: number, y: number, pointerType: PointerType, pointerId: number, 
+/**
+ * The DOM `button`: 0 primary, 1 auxiliary, 2 secondary, 3 back,
+ * 4 forward.
+ */
+button: number, } | { "type": "pointerUp", x: number, y: number, pointerType: PointerType, pointerId: number, button: number, } | { "type": "pointerCancel", x: number, y: number, pointerId: number, } | { "type": "pointerLeave" } | { "type": "wheel", x: number, y: number, deltaX: number, deltaY: number, deltaZ: number, deltaMode: WheelDeltaMode, } | { "type": "keyDown" } & KeyInput | { "type": "keyUp" } & KeyInput | { "type": "compositionStart", data: string, } | { "type": "compositionUpdate", data: string, } | { "type": "compositionEnd", data: string, };
+
+export type KeyInput = { 
+/**
+ * The key value, e.g. `"a"`, `"A"` or `"Enter"`.
+ */
+key: string, 
+/**
+ * The physical key, e.g. `"KeyA"` or `"Enter"`.
+ */
+code: string, location: KeyLocation, modifiers: Modifiers, repeat: boolean, 
+/**
+ * Whether the key is part of an input method composition.
+ */
+isComposing: boolean, };
+
+export type Modifiers = { alt: boolean, ctrl: boolean, meta: boolean, shift: boolean, };
+
+export type PointerType = "mouse" | "touch" | "pen";
+
+export type WheelDeltaMode = "pixel" | "line" | "page";
+
+export type KeyLocation = "standard" | "left" | "right" | "numpad";
+
+export type ServoEvent = { "type": "ready", 
+/**
+ * The protocol version the bridge speaks, `PROTOCOL_VERSION`.
+ */
+protocolVersion: number, 
+/**
+ * The version of the embedded Servo, missing for engines that are
+ * not Servo.
+ */
+servoVersion?: string, 
+/**
+ * What the engine supports beyond loading pages.
+ */
+capabilities: Array<Capability>, 
+/**
+ * The id the bridge knows this client by, as found in the `owner`
+ * of `tabsSnapshot` tabs.
+ */
+clientId: number, 
+/**
+ * Identifies the bridge's session; sequence numbers only mean
+ * something within it.
+ */
+sessionId: string, 
+/**
+ * The sequence number of the latest event of the session. Named
+ * apart from the envelope's `seq`, which `ready` does not carry.
+ */
+latestSeq: number, 
+/**
+ * Whether the events the client missed since the `resume` point of
+ * its `ready` follow. Otherwise a `tabsSnapshot` follows.
+ */
+resumed: boolean, 
+/**
+ * The encoding of every message after this one, in both directions:
+ * the one asked for in `ready` if the transport can carry it.
+ */
+encoding: ServoEncoding, } | { "type": "tabsSnapshot", tabs: Array<TabSnapshot>, } | { "type": "ack", id: number, } | { "type": "error", id?: number, code: ErrorCode, message: string, } | { "type": "inputResolved", id?: number, 
+/**
+ * The URL to navigate to.
+ */
+url: string, 
+/**
+ * The name of the search engine `url` searches with, missing when
+ * the text was an address.
+ */
+searchEngine?: string, } | { "type": "suggestions", id?: number, 
+/**
+ * The text the suggestions are for, to tell them from the answers
+ * to earlier keystrokes.
+ */
+text: string, suggestions: Array<Suggestion>, } | { "type": "historyVisits", id?: number, visits: Array<Visit>, } | { "type": "crashedSession", savedAt: number, tabs: Array<TabSnapshot>, } | { "type": "bookmarkAdded", id?: number, bookmark: Bookmark, } | { "type": "bookmarksFound", id?: number, bookmarks: Array<Bookmark>, } | { "type": "bookmarksExported", id?: number, html: string, } | { "type": "bookmarksChanged", bookmarks: Array<Bookmark>, } | { "type": "loadStart", tabId: TabId, url: string, } | { "type": "urlChange", tabId: TabId, url: string, } | { "type": "titleChange", tabId: TabId, title: string, } | { "type": "loadComplete", tabId: TabId, url: string, } | { "type": "historyChanged", tabId: TabId, entries: Array<HistoryEntry>, index: number, } | { "type": "tabCrashed", tabId: TabId, 
+/**
+ * The last URL the tab showed.
+ */
+url?: string, 
+/**
+ * The exit code, when the process exited on its own.
+ */
+exitCode?: number, 
+/**
+ * The signal that killed the process, on Unix.
+ */
+signal?: number, 
+/**
+ * Whether the supervisor killed the process for not responding.
+ */
+hung: boolean, } | { "type": "frame", tabId: TabId, width: number, height: number, tiles: Array<FrameTile>, } | { "type": "viewportChanged", tabId: TabId, width: number, height: number, devicePixelRatio: number, zoom: number, };
+
+export type ServoEnvelope = { seq?: number, } & ({ "type": "ready", 
+/**
+ * The protocol version the bridge speaks, `PROTOCOL_VERSION`.
+ */
+protocolVersion: number, 
+/**
+ * The version of the embedded Servo, missing for engines that are
+ * not Servo.
+ */
+servoVersion?: string, 
+/**
+ * What the engine supports beyond loading pages.
+ */
+capabilities: Array<Capability>, 
+/**
+ * The id the bridge knows this client by, as found in the `owner`
+ * of `tabsSnapshot` tabs.
+ */
+clientId: number, 
+/**
+ * Identifies the bridge's session; sequence numbers only mean
+ * something within it.
+ */
+sessionId: string, 
+/**
+ * The sequence number of the latest event of the session. Named
+ * apart from the envelope's `seq`, which `ready` does not carry.
+ */
+latestSeq: number, 
+/**
+ * Whether the events the client missed since the `resume` point of
+ * its `ready` follow. Otherwise a `tabsSnapshot` follows.
+ */
+resumed: boolean, 
+/**
+ * The encoding of every message after this one, in both directions:
+ * the one asked for in `ready` if the transport can carry it.
+ */
+encoding: ServoEncoding, } | { "type": "tabsSnapshot", tabs: Array<TabSnapshot>, } | { "type": "ack", id: number, } | { "type": "error", id?: number, code: ErrorCode, message: string, } | { "type": "inputResolved", id?: number, 
+/**
+ * The URL to navigate to.
+ */
+url: string, 
+/**
+ * The name of the search engine `url` searches with, missing when
+ * the text was an address.
+ */
+searchEngine?: string, } | { "type": "suggestions", id?: number, 
+/**
+ * The text the suggestions are for, to tell them from the answers
+ * to earlier keystrokes.
+ */
+text: string, suggestions: Array<Suggestion>, } | { "type": "historyVisits", id?: number, visits: Array<Visit>, } | { "type": "crashedSession", savedAt: number, tabs: Array<TabSnapshot>, } | { "type": "bookmarkAdded", id?: number, bookmark: Bookmark, } | { "type": "bookmarksFound", id?: number, bookmarks: Array<Bookmark>, } | { "type": "bookmarksExported", id?: number, html: string, } | { "type": "bookmarksChanged", bookmarks: Array<Bookmark>, } | { "type": "loadStart", tabId: TabId, url: string, } | { "type": "urlChange", tabId: TabId, url: string, } | { "type": "titleChange", tabId: TabId, title: string, } | { "type": "loadComplete", tabId: TabId, url: string, } | { "type": "historyChanged", tabId: TabId, entries: Array<HistoryEntry>, index: number, } | { "type": "tabCrashed", tabId: TabId, 
+/**
+ * The last URL the tab showed.
+ */
+url?: string, 
+/**
+ * The exit code, when the process exited on its own.
+ */
+exitCode?: number, 
+/**
+ * The signal that killed the process, on Unix.
+ */
+signal?: number, 
+/**
+ * Whether the supervisor killed the process for not responding.
+ */
+hung: boolean, } | { "type": "frame", tabId: TabId, width: number, height: number, tiles: Array<FrameTile>, } | { "type": "viewportChanged", tabId: TabId, width: number, height: number, devicePixelRatio: number, zoom: number, });
+
+export type Capability = "frames" | "input" | "viewport" | "history" | "crashRecovery" | "downloads" | "devtools";
+
+export type ErrorCode = "invalidMessage" | "invalidFrame" | "unsupportedProtocol" | "invalidUrl" | "blockedScheme" | "dnsFailure" | "tabNotFound" | "notTabOwner" | "tabCrashed" | "tabNotCrashed" | "invalidArgument" | "internal";
+
+export type TabSnapshot = { tabId: TabId, 
+/**
+ * The client controlling the tab. Other clients see its events but
+ * have their commands for it refused until they send `claimTab`. A tab
+ * whose owner left goes to the next client that sends it a command.
+ */
+owner?: number, 
+/**
+ * The URL the tab shows, missing until it starts loading one.
+ */
+url?: string, title?: string, 
+/**
+ * The tab's session history, as in `historyChanged`.
+ */
+entries: Array<HistoryEntry>, index: number, loading: boolean, crashed: boolean, };
+
+export type HistoryEntry = { url: string, title: string, };
+
+export type FrameTile = { x: number, y: number, width: number, height: number, 
+/**
+ * The rectangle's pixels as a PNG, base64-encoded in JSON.
+ */
+data: string, };
+
+export type Suggestion = { kind: SuggestionKind, 
+/**
+ * What choosing the suggestion loads. For `keyword` suggestions, the
+ * engine's page for an empty search.
+ */
+url: string, 
+/**
+ * The page title, the search terms of `search` suggestions or the
+ * engine name of `keyword` suggestions.
+ */
+title: string, 
+/**
+ * The parts of `url` that match the typed text.
+ */
+urlHighlights: Array<Highlight>, 
+/**
+ * The parts of `title` that match the typed text.
+ */
+titleHighlights: Array<Highlight>, 
+/**
+ * The open tab to switch to, for `openTab` suggestions.
+ */
+tabId?: TabId, 
+/**
+ * The search engine of `search` and `keyword` suggestions.
+ */
+searchEngine?: string, 
+/**
+ * The keyword to type before search terms, for `keyword` suggestions.
+ */
+keyword?: string, };
+
+export type SuggestionKind = "url" | "search" | "keyword" | "openTab" | "bookmark" | "history";
+
+export type Highlight = { start: number, end: number, };
+
+export type Visit = { url: string, 
+/**
+ * The page title as of the latest visit to `url`.
+ */
+title: string, 
+/**
+ * When the page finished loading, in milliseconds since the Unix
+ * epoch.
+ */
+visitedAt: number, transition: Transition, 
+/**
+ * The page the tab showed before, for visits by `link`.
+ */
+referrer?: string, };
+
+export type Transition = "typed" | "link" | "reload" | "backForward";
+
+export type Bookmark = { id: number, 
+/**
+ * The folder holding it, missing at the top level.
+ */
+parentId?: number, 
+/**
+ * Its place in the folder, from 0.
+ */
+index: number, title: string, 
+/**
+ * What the bookmark loads, missing for folders.
+ */
+url?: string, 
+/**
+ * Sorted, and compared ignoring case.
+ */
+tags: Array<string>, 
+/**
+ * A word that loads the bookmark when typed in the address bar. When
+ * `url` holds `%s`, the words typed after the keyword replace it.
+ */
+keyword?: string, 
+/**
+ * When it was added, in milliseconds since the Unix epoch.
+ */
+addedAt: number, };
+
+export const PROTOCOL_VERSION = 2;
+
+export type ServoMessage = ServoRequest | ServoEnvelope;
+
+export const COMMAND_TYPES = ['ready', 'navigate', 'back', 'forward', 'refresh', 'close', 'reloadCrashed', 'claimTab', 'frameShown', 'input', 'resize', 'zoom', 'resolveInput', 'suggest', 'queryHistory', 'deleteHistory', 'ping', 'restoreSession', 'discardSession', 'addBookmark', 'updateBookmark', 'moveBookmark', 'removeBookmark', 'searchBookmarks', 'importBookmarks', 'exportBookmarks'] as const;
+
+export const EVENT_TYPES = ['ready', 'tabsSnapshot', 'ack', 'error', 'inputResolved', 'suggestions', 'historyVisits', 'crashedSession', 'bookmarkAdded', 'bookmarksFound', 'bookmarksExported', 'bookmarksChanged', 'loadStart', 'urlChange', 'titleChange', 'loadComplete', 'historyChanged', 'tabCrashed', 'frame', 'viewportChanged'] as const;
@@ -1,48 +1,144 @@
-import React, { useState } from 'react';
+import React, { useRef, useState } from 'react';
 import type { KeyboardEvent } from 'react';
+import { getServoBackend } from '../backend/ServoBackend';
+import type { Highlight, Suggestion } from '../backend/protocol';
 import './AddressBar.css';
 
 interface AddressBarProps {
   url: string;
   onNavigate: (url: string) => void;
+  /** Show an open tab instead of loading its page again */
+  onSwitchTab: (tabId: string) => void;
   onBack: () => void;
   onForward: () => void;
   onRefresh: () => void;
   canGoBack: boolean;
   canGoForward: boolean;
+  /** Page zoom of the tab, 1 being 100% */
+  zoom: number;
+  onResetZoom: () => void;
+  /** Whether the page is bookmarked */
+  bookmarked: boolean;
+  onToggleBookmark: () => void;
+}
+
+/** What each kind of suggestion is labelled with in the list */
+const KIND_LABELS: Record<Suggestion['kind'], string> = {
+  url: 'Visit',
+  search: 'Search',
+  keyword: 'Search with',
+  openTab: 'Switch to tab',
+  bookmark: 'Bookmark',
+  history: '',
+};
+
+/**
+ * `text` with the `highlights` ranges, in UTF-16 code units like JavaScript
+ * string indices, in bold
+ */
+function highlighted(text: string, highlights: Highlight[]): React.ReactNode[] {
+  const parts: React.ReactNode[] = [];
+  let offset = 0;
+  for (const { start, end } of highlights) {
+    parts.push(text.slice(offset, start));
+    parts.push(<strong key={start}>{text.slice(start, end)}</strong>);
+    offset = end;
+  }
+  parts.push(text.slice(offset));
+  return parts;
 }
 
 const AddressBar: React.FC<AddressBarProps> = ({
   url,
   onNavigate,
+  onSwitchTab,
   onBack,
   onForward,
   onRefresh,
   canGoBack,
   canGoForward,
+  zoom,
+  onResetZoom,
+  bookmarked,
+  onToggleBookmark,
 }) => {
   const [inputValue, setInputValue] = useState(url);
+  const [suggestions, setSuggestions] = useState<Suggestion[]>([]);
+  const [selected, setSelected] = useState(0);
+  // The text typed last, to drop the answers to earlier keystrokes
+  const typed = useRef('');
+  const servoBackend = getServoBackend();
 
   React.useEffect(() => {
     setInputValue(url);
   }, [url]);
 
-  const handleKeyDown = (e: KeyboardEvent<HTMLInputElement>) => {
-    if (e.key === 'Enter') {
-      let targetUrl = inputValue.trim();
-      
-      // Add protocol if missing
-      if (!targetUrl.startsWith('http://') && !targetUrl.startsWith('https://')) {
-        // Check if it looks like a domain
-        if (targetUrl.includes('.') || targetUrl === 'localhost') {
-          targetUrl = 'https://' + targetUrl;
-        } else {
-          // Treat as a search query
-          targetUrl = `https://www.google.com/search?q=${encodeURIComponent(targetUrl)}`;
+  const closeSuggestions = () => {
+    typed.current = '';
+    setSuggestions([]);
+    setSelected(0);
+  };
+
+  const handleChange = (text: string) => {
+    setInputValue(text);
+    typed.current = text;
+    if (!text.trim()) {
+      closeSuggestions();
+      return;
+    }
+    servoBackend.suggest(text).then(
+      (answer) => {
+        if (answer.text === typed.current) {
+          setSuggestions(answer.suggestions);
+          setSelected(0);
         }
+      },
+      (error: Error) => console.error('Cannot suggest for address bar input:', error.message),
+    );
+  };
+
+  const choose = (suggestion: Suggestion) => {
+    closeSuggestions();
+    switch (suggestion.kind) {
+      case 'keyword':
+        // Searching takes terms after the keyword
+        handleChange(`${suggestion.keyword} `);
+        return;
+      case 'openTab':
+        setInputValue(url);
+        onSwitchTab(suggestion.tabId!);
+        return;
+      default:
+        setInputValue(suggestion.url);
+        onNavigate(suggestion.url);
+    }
+  };
+
+  const handleKeyDown = (e: KeyboardEvent<HTMLInputElement>) => {
+    if (suggestions.length > 0) {
+      switch (e.key) {
+        case 'ArrowDown':
+          e.preventDefault();
+          setSelected((selected + 1) % suggestions.length);
+          return;
+        case 'ArrowUp':
+          e.preventDefault();
+          setSelected((selected + suggestions.length - 1) % suggestions.length);
+          return;
+        case 'Escape':
+          closeSuggestions();
+          return;
+        case 'Enter':
+          choose(suggestions[selected]);
+          return;
       }
-      
-      onNavigate(targetUrl);
+    }
+    if (e.key === 'Enter' && inputValue.trim()) {
+      // The bridge tells addresses from searches
+      servoBackend.resolveInput(inputValue).then(
+        (resolved) => onNavigate(resolved.url),
+        (error: Error) => console.error('Cannot resolve address bar input:', error.message),
+      );
     }
   };
 
@@ -74,11 +170,56 @@ const AddressBar: React.FC<AddressBarProps> = ({
           type="text"
           className="url-input"
           value={inputValue}
-          onChange={(e) => setInputValue(e.target.value)}
+          onChange={(e) => handleChange(e.target.value)}
           onKeyDown={handleKeyDown}
+          onBlur={closeSuggestions}
           placeholder="Search or enter address"
         />
+        {suggestions.length > 0 && (
+          <ul className="suggestions">
+            {suggestions.map((suggestion, index) => (
+              <li
+                key={`${suggestion.kind} ${suggestion.url}`}
+                className={`suggestion suggestion-${suggestion.kind}${index === selected ? ' selected' : ''}`}
+                // Before the input loses focus and closes the list
+                onMouseDown={(e) => {
+                  e.preventDefault();
+                  choose(suggestion);
+                }}
+                onMouseEnter={() => setSelected(index)}
+              >
+                <span className="suggestion-title">
+                  {highlighted(suggestion.title, suggestion.titleHighlights)}
+                </span>
+                {!['url', 'search', 'keyword'].includes(suggestion.kind) && (
+                  <span className="suggestion-url">
+                    {highlighted(suggestion.url, suggestion.urlHighlights)}
+                  </span>
+                )}
+                {KIND_LABELS[suggestion.kind] && (
+                  <span className="suggestion-kind">
+                    {KIND_LABELS[suggestion.kind]}
+                    {suggestion.searchEngine && suggestion.kind === 'search' && ` with ${suggestion.searchEngine}`}
+                  </span>
+                )}
+              </li>
+            ))}
+          </ul>
+        )}
       </div>
+      {zoom !== 1 && (
+        <button className="zoom-button" onClick={onResetZoom} title="Reset zoom">
+          {Math.round(zoom * 100)}%
+        </button>
+      )}
+      <button
+        className={`bookmark-button${bookmarked ? ' bookmarked' : ''}`}
+        onClick={onToggleBookmark}
+        disabled={!url}
+        title={bookmarked ? 'Remove bookmark' : 'Bookmark this page'}
+      >
+        {bookmarked ? '★' : '☆'}
+      </button>
     </div>
   );
 };
@@ -0,0 +1,129 @@
+import type { ServoEventOf } from '../backend/ServoBackend';
+import type { FrameTile } from '../backend/protocol';
+
+/**
+ * Paints the frame tiles streamed by the bridge.
+ *
+ * Every tab gets an offscreen canvas holding its latest frame, so switching
+ * tabs shows the last picture right away instead of waiting for new damage.
+ * Tiles are decoded asynchronously but painted in the order they arrived.
+ *
+ * After a resize the old frame stays up until the first frame of the new size
+ * arrives, which is then shown at the pixel ratio its `viewportChanged` event
+ * announced, so the picture never stretches or tears.
+ *
+ * Every painted frame is reported through `onShown`, which the bridge waits
+ * for before sending more, so a page animating faster than tiles decode
+ * never piles frames up.
+ */
+export class FrameSurfaces {
+  private surfaces = new Map<string, Surface>();
+  private viewports = new Map<string, ServoEventOf<'viewportChanged'>>();
+  private queue: Promise<void> = Promise.resolve();
+  private target: HTMLCanvasElement | null = null;
+  private activeTabId: string | null = null;
+
+  private readonly onShown: (tabId: string) => void;
+
+  constructor(onShown: (tabId: string) => void = () => {}) {
+    this.onShown = onShown;
+  }
+
+  /**
+   * Show the frames of `tabId` in `canvas`
+   */
+  show(canvas: HTMLCanvasElement | null, tabId: string): void {
+    this.target = canvas;
+    this.activeTabId = tabId;
+    this.enqueue(async () => this.present());
+  }
+
+  /**
+   * Paint a frame into its tab's surface
+   */
+  paint = (frame: ServoEventOf<'frame'>): void => {
+    this.enqueue(() =>
+      this.paintFrame(frame).finally(() => this.onShown(frame.tabId)),
+    );
+  };
+
+  /**
+   * Remember the pixel ratio of a tab's upcoming frames
+   */
+  setViewport = (viewport: ServoEventOf<'viewportChanged'>): void => {
+    this.enqueue(async () => {
+      this.viewports.set(viewport.tabId, viewport);
+    });
+  };
+
+  private enqueue(task: () => Promise<void>): void {
+    this.queue = this.queue.then(task).catch((error) => {
+      console.error('[Serval] Failed to paint frame:', error);
+    });
+  }
+
+  private async paintFrame(frame: ServoEventOf<'frame'>): Promise<void> {
+    const bitmaps = await Promise.all(frame.tiles.map(decodeTile));
+
+    let surface = this.surfaces.get(frame.tabId);
+    if (!surface) {
+      surface = { canvas: document.createElement('canvas'), ratio: window.devicePixelRatio };
+      this.surfaces.set(frame.tabId, surface);
+    }
+    const { canvas } = surface;
+    if (canvas.width !== frame.width || canvas.height !== frame.height) {
+      canvas.width = frame.width;
+      canvas.height = frame.height;
+      const viewport = this.viewports.get(frame.tabId);
+      if (viewport && viewport.width === frame.width && viewport.height === frame.height) {
+        surface.ratio = viewport.devicePixelRatio;
+      }
+    }
+
+    const context = canvas.getContext('2d');
+    frame.tiles.forEach((tile, index) => {
+      context?.drawImage(bitmaps[index], tile.x, tile.y);
+      bitmaps[index].close();
+    });
+
+    if (frame.tabId === this.activeTabId) {
+      this.present();
+    }
+  }
+
+  /**
+   * Copy the active tab's surface to the visible canvas
+   */
+  private present(): void {
+    const target = this.target;
+    if (!target) {
+      return;
+    }
+
+    const surface = this.activeTabId ? this.surfaces.get(this.activeTabId) : undefined;
+    const width = surface?.canvas.width ?? 0;
+    const height = surface?.canvas.height ?? 0;
+    if (target.width !== width || target.height !== height) {
+      target.width = width;
+      target.height = height;
+    }
+    // Frames are in device pixels; show them at their CSS size.
+    const ratio = surface?.ratio ?? window.devicePixelRatio;
+    target.style.width = `${width / ratio}px`;
+    target.style.height = `${height / ratio}px`;
+    if (surface && width > 0 && height > 0) {
+      target.getContext('2d')?.drawImage(surface.canvas, 0, 0);
+    }
+  }
+}
+
+interface Surface {
+  canvas: HTMLCanvasElement;
+  /** Device pixels per CSS pixel of the frame in `canvas` */
+  ratio: number;
+}
+
+function decodeTile(tile: FrameTile): Promise<ImageBitmap> {
+  const bytes = Uint8Array.from(atob(tile.data), (char) => char.charCodeAt(0));
+  return createImageBitmap(new Blob([bytes], { type: 'image/png' }));
+}
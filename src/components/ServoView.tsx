@@ -1,49 +1,165 @@
-import React, { useEffect, useRef } from 'react';
-import { getServoBackend } from '../backend/ServoBackend';
-import type { ServoMessage } from '../backend/ServoBackend';
+import React, { useEffect, useRef, useState } from 'react';
+import { getServoBackend, protocolMismatch } from '../backend/ServoBackend';
+import type { EngineInfo, ServoEventOf } from '../backend/ServoBackend';
+import type { HistoryEntry } from '../backend/protocol';
+import { getConfig } from '../config';
+import { FrameSurfaces } from './FrameSurfaces';
+import { forwardInput } from './inputForwarding';
 import './ServoView.css';
 
 interface ServoViewProps {
   tabId: string;
   url: string;
+  /** Page zoom of the tab, 1 being 100% */
+  zoom: number;
+  /** Another window attached to the bridge controls the tab */
+  ownedElsewhere: boolean;
   onTitleChange: (title: string) => void;
   onUrlChange: (url: string) => void;
+  onHistoryChange: (entries: HistoryEntry[], index: number) => void;
 }
 
 /**
  * ServoView component integrates with the Servo browser engine.
  * It renders web content using Servo instead of an iframe.
  */
-const ServoView: React.FC<ServoViewProps> = ({ tabId, url, onTitleChange, onUrlChange }) => {
+const ServoView: React.FC<ServoViewProps> = ({
+  tabId,
+  url,
+  zoom,
+  ownedElsewhere,
+  onTitleChange,
+  onUrlChange,
+  onHistoryChange,
+}) => {
   const containerRef = useRef<HTMLDivElement>(null);
+  // The URL the engine last reported, which must not be navigated to again:
+  // doing so would turn every back/forward into a new history entry.
+  const engineUrl = useRef<string | null>(null);
+  const [crash, setCrash] = useState<ServoEventOf<'tabCrashed'> | null>(null);
+  const contentRef = useRef<HTMLDivElement>(null);
+  const canvasRef = useRef<HTMLCanvasElement>(null);
+  const imeRef = useRef<HTMLTextAreaElement>(null);
   const servoBackend = getServoBackend();
+  const [surfaces] = useState(
+    () => new FrameSurfaces((shownTabId) => servoBackend.frameShown(shownTabId)),
+  );
+  const [engineInfo, setEngineInfo] = useState<EngineInfo | null>(() =>
+    servoBackend.getEngineInfo(),
+  );
+  const mismatch = engineInfo && protocolMismatch(engineInfo);
+  const launchError = getConfig().servo.launchError;
+  // Features the engine lacks stay off until it announces them, and tabs of
+  // other windows are only watched
+  const usable = engineInfo !== null && !mismatch && !ownedElsewhere;
+  const supportsInput = usable && servoBackend.supports('input');
+  const supportsViewport = usable && servoBackend.supports('viewport');
+
+  useEffect(() => servoBackend.onEngineInfo(setEngineInfo), [servoBackend]);
+
+  useEffect(() => {
+    // Frames arrive for every tab; each one is kept so tab switches are instant
+    servoBackend.on('frame', surfaces.paint);
+    servoBackend.on('viewportChanged', surfaces.setViewport);
+    return () => {
+      servoBackend.off('frame');
+      servoBackend.off('viewportChanged');
+    };
+  }, [servoBackend, surfaces]);
+
+  useEffect(() => {
+    surfaces.show(canvasRef.current, tabId);
+  }, [surfaces, tabId]);
+
+  useEffect(() => {
+    // Keep the tab's webview as big as the content area, in device pixels
+    const content = contentRef.current;
+    if (!content || !supportsViewport) {
+      return;
+    }
+    const resize = () => {
+      const { width, height } = content.getBoundingClientRect();
+      servoBackend.resize(tabId, width, height, window.devicePixelRatio);
+    };
+    const observer = new ResizeObserver(resize);
+    observer.observe(content);
+    // Moving the window to another screen changes the pixel ratio without
+    // necessarily changing the CSS size; browsers report it as a resize.
+    window.addEventListener('resize', resize);
+    return () => {
+      observer.disconnect();
+      window.removeEventListener('resize', resize);
+    };
+  }, [servoBackend, tabId, supportsViewport]);
+
+  useEffect(() => {
+    if (supportsViewport) {
+      servoBackend.setZoom(tabId, zoom);
+    }
+  }, [servoBackend, tabId, zoom, supportsViewport]);
+
+  useEffect(() => {
+    const canvas = canvasRef.current;
+    const ime = imeRef.current;
+    if (!canvas || !ime || !supportsInput) {
+      return;
+    }
+    return forwardInput(canvas, ime, (event) => servoBackend.sendInput(tabId, event));
+  }, [servoBackend, tabId, supportsInput]);
 
   useEffect(() => {
     // Set up listeners for Servo backend events
-    const handleTitleChange = (message: ServoMessage) => {
+    const handleTitleChange = (message: ServoEventOf<'titleChange'>) => {
       if (message.tabId === tabId && message.title) {
         onTitleChange(message.title);
       }
     };
 
-    const handleUrlChange = (message: ServoMessage) => {
+    const handleUrlChange = (message: ServoEventOf<'urlChange'>) => {
       if (message.tabId === tabId && message.url) {
+        engineUrl.current = message.url;
         onUrlChange(message.url);
       }
     };
 
+    const handleHistoryChange = (message: ServoEventOf<'historyChanged'>) => {
+      if (message.tabId === tabId) {
+        onHistoryChange(message.entries, message.index);
+      }
+    };
+
+    const handleTabCrashed = (message: ServoEventOf<'tabCrashed'>) => {
+      if (message.tabId === tabId) {
+        setCrash(message);
+      }
+    };
+
+    const handleLoadStart = (message: ServoEventOf<'loadStart'>) => {
+      if (message.tabId === tabId) {
+        setCrash(null);
+      }
+    };
+
+    setCrash(null);
     servoBackend.on('titleChange', handleTitleChange);
     servoBackend.on('urlChange', handleUrlChange);
+    servoBackend.on('historyChanged', handleHistoryChange);
+    servoBackend.on('tabCrashed', handleTabCrashed);
+    servoBackend.on('loadStart', handleLoadStart);
 
     return () => {
       servoBackend.off('titleChange');
       servoBackend.off('urlChange');
+      servoBackend.off('historyChanged');
+      servoBackend.off('tabCrashed');
+      servoBackend.off('loadStart');
     };
-  }, [tabId, servoBackend, onTitleChange, onUrlChange]);
+  }, [tabId, servoBackend, onTitleChange, onUrlChange, onHistoryChange]);
 
   useEffect(() => {
-    // Navigate when URL changes
-    if (url && servoBackend.isConnected()) {
+    // Navigate when the URL was changed by the user, not by the engine
+    const controlled = servoBackend.isConnected() && !mismatch && !ownedElsewhere;
+    if (url && url !== engineUrl.current && controlled) {
       servoBackend.navigate(tabId, url).then((response) => {
         if (response.success) {
           console.log('Navigation successful:', response.url);
@@ -52,7 +168,7 @@ const ServoView: React.FC<ServoViewProps> = ({ tabId, url, onTitleChange, onUrlC
         }
       });
     }
-  }, [url, tabId, servoBackend]);
+  }, [url, tabId, servoBackend, mismatch, ownedElsewhere]);
 
   return (
     <div className="servo-view" ref={containerRef}>
@@ -63,6 +179,7 @@ const ServoView: React.FC<ServoViewProps> = ({ tabId, url, onTitleChange, onUrlC
             The Servo browser engine is not currently running or accessible.
             Please ensure Servo is properly configured and running.
           </p>
+          {launchError && <p className="servo-launch-error">{launchError}</p>}
           <p className="servo-info">
             To use Servo as the backend:
             <ul>
@@ -73,17 +190,62 @@ const ServoView: React.FC<ServoViewProps> = ({ tabId, url, onTitleChange, onUrlC
           </p>
         </div>
       )}
-      {servoBackend.isConnected() && !url && (
+      {mismatch && (
+        <div className="servo-unavailable">
+          <h2>Incompatible Servo Backend</h2>
+          <p>{mismatch}</p>
+          <p>
+            Update the browser and the Servo backend bridge to the same release,
+            then restart both.
+          </p>
+          {engineInfo?.servoVersion && (
+            <p className="servo-version">Servo {engineInfo.servoVersion}</p>
+          )}
+        </div>
+      )}
+      {servoBackend.isConnected() && !mismatch && ownedElsewhere && (
+        <div className="servo-shared-banner">
+          <span>This tab is controlled by another window.</span>
+          <button onClick={() => servoBackend.claimTab(tabId)}>Take over</button>
+        </div>
+      )}
+      {servoBackend.isConnected() && !mismatch && crash && (
+        <div className="servo-crashed">
+          <h2>This tab crashed</h2>
+          <p>{describeCrash(crash)}</p>
+          {crash.url && <p className="servo-crashed-url">{crash.url}</p>}
+          {servoBackend.supports('crashRecovery') && !ownedElsewhere && (
+            <button onClick={() => servoBackend.reloadCrashed(tabId)}>Reload</button>
+          )}
+        </div>
+      )}
+      {servoBackend.isConnected() && !mismatch && !crash && !url && (
         <div className="empty-state">
           <h2>Welcome to Serval Browser</h2>
           <p>Powered by Servo</p>
           <p>Enter a URL or search query to get started</p>
         </div>
       )}
-      {/* The actual rendering is handled by Servo through the backend */}
-      <div id={`servo-content-${tabId}`} className="servo-content" />
+      {/* Servo renders the page; the bridge streams its frames into this canvas */}
+      <div id={`servo-content-${tabId}`} className="servo-content" ref={contentRef}>
+        <canvas ref={canvasRef} className="servo-canvas" />
+        <textarea ref={imeRef} className="servo-ime" aria-hidden="true" tabIndex={-1} />
+      </div>
     </div>
   );
 };
 
+function describeCrash(crash: ServoEventOf<'tabCrashed'>): string {
+  if (crash.hung) {
+    return 'The page stopped responding and was stopped.';
+  }
+  if (crash.signal !== undefined) {
+    return `The page's process was killed by signal ${crash.signal}.`;
+  }
+  if (crash.exitCode !== undefined) {
+    return `The page's process exited with code ${crash.exitCode}.`;
+  }
+  return 'The page\'s process stopped unexpectedly.';
+}
+
 export default ServoView;
@@ -1,10 +1,22 @@
 import React from 'react';
+import type { HistoryEntry } from '../backend/protocol';
 import './TabBar.css';
 
 export interface Tab {
   id: string;
   title: string;
   url: string;
+  history?: TabHistory;
+  /** Page zoom, 1 being 100% */
+  zoom?: number;
+  /** The bridge client controlling the tab, when the session is shared */
+  owner?: number;
+}
+
+/** Session history of a tab, as last reported by the engine. */
+export interface TabHistory {
+  entries: HistoryEntry[];
+  index: number;
 }
 
 interface TabBarProps {
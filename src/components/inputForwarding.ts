@@ -0,0 +1,126 @@
+import type { InputEvent, KeyInput, KeyLocation, PointerType } from '../backend/protocol';
+
+const KEY_LOCATIONS: KeyLocation[] = ['standard', 'left', 'right', 'numpad'];
+
+/**
+ * Forward the user's input on the content canvas to the engine.
+ *
+ * Pointer and wheel events are taken from `canvas`, with coordinates in CSS
+ * pixels relative to it. Keyboard and IME input go through `ime`, a hidden
+ * text field that takes focus when the canvas is clicked, because browsers
+ * only run input methods for editable elements.
+ *
+ * Returns a function that removes the listeners again.
+ */
+export function forwardInput(
+  canvas: HTMLCanvasElement,
+  ime: HTMLTextAreaElement,
+  send: (event: InputEvent) => void,
+): () => void {
+  const pointer = (event: PointerEvent) => ({
+    x: event.offsetX,
+    y: event.offsetY,
+    pointerType: pointerType(event.pointerType),
+    pointerId: event.pointerId,
+  });
+
+  const onPointerMove = (event: PointerEvent) => {
+    send({ type: 'pointerMove', ...pointer(event) });
+  };
+  const onPointerDown = (event: PointerEvent) => {
+    event.preventDefault();
+    canvas.setPointerCapture(event.pointerId);
+    ime.focus({ preventScroll: true });
+    send({ type: 'pointerDown', ...pointer(event), button: event.button });
+  };
+  const onPointerUp = (event: PointerEvent) => {
+    send({ type: 'pointerUp', ...pointer(event), button: event.button });
+  };
+  const onPointerCancel = (event: PointerEvent) => {
+    send({ type: 'pointerCancel', x: event.offsetX, y: event.offsetY, pointerId: event.pointerId });
+  };
+  const onPointerLeave = (event: PointerEvent) => {
+    if (event.pointerType === 'mouse') {
+      send({ type: 'pointerLeave' });
+    }
+  };
+  const onWheel = (event: WheelEvent) => {
+    event.preventDefault();
+    send({
+      type: 'wheel',
+      x: event.offsetX,
+      y: event.offsetY,
+      deltaX: event.deltaX,
+      deltaY: event.deltaY,
+      deltaZ: event.deltaZ,
+      deltaMode: event.deltaMode === 2 ? 'page' : event.deltaMode === 1 ? 'line' : 'pixel',
+    });
+  };
+
+  const onKeyDown = (event: KeyboardEvent) => {
+    // Keys that drive an input method must reach it
+    if (!event.isComposing) {
+      event.preventDefault();
+    }
+    send({ type: 'keyDown', ...keyInput(event) });
+  };
+  const onKeyUp = (event: KeyboardEvent) => {
+    send({ type: 'keyUp', ...keyInput(event) });
+  };
+  const onCompositionStart = (event: CompositionEvent) => {
+    send({ type: 'compositionStart', data: event.data });
+  };
+  const onCompositionUpdate = (event: CompositionEvent) => {
+    send({ type: 'compositionUpdate', data: event.data });
+  };
+  const onCompositionEnd = (event: CompositionEvent) => {
+    send({ type: 'compositionEnd', data: event.data });
+    ime.value = '';
+  };
+
+  canvas.addEventListener('pointermove', onPointerMove);
+  canvas.addEventListener('pointerdown', onPointerDown);
+  canvas.addEventListener('pointerup', onPointerUp);
+  canvas.addEventListener('pointercancel', onPointerCancel);
+  canvas.addEventListener('pointerleave', onPointerLeave);
+  canvas.addEventListener('wheel', onWheel, { passive: false });
+  ime.addEventListener('keydown', onKeyDown);
+  ime.addEventListener('keyup', onKeyUp);
+  ime.addEventListener('compositionstart', onCompositionStart);
+  ime.addEventListener('compositionupdate', onCompositionUpdate);
+  ime.addEventListener('compositionend', onCompositionEnd);
+
+  return () => {
+    canvas.removeEventListener('pointermove', onPointerMove);
+    canvas.removeEventListener('pointerdown', onPointerDown);
+    canvas.removeEventListener('pointerup', onPointerUp);
+    canvas.removeEventListener('pointercancel', onPointerCancel);
+    canvas.removeEventListener('pointerleave', onPointerLeave);
+    canvas.removeEventListener('wheel', onWheel);
+    ime.removeEventListener('keydown', onKeyDown);
+    ime.removeEventListener('keyup', onKeyUp);
+    ime.removeEventListener('compositionstart', onCompositionStart);
+    ime.removeEventListener('compositionupdate', onCompositionUpdate);
+    ime.removeEventListener('compositionend', onCompositionEnd);
+  };
+}
+
+function pointerType(type: string): PointerType {
+  return type === 'touch' || type === 'pen' ? type : 'mouse';
+}
+
+function keyInput(event: KeyboardEvent): KeyInput {
+  return {
+    key: event.key,
+    code: event.code,
+    location: KEY_LOCATIONS[event.location] ?? 'standard',
+    modifiers: {
+      alt: event.altKey,
+      ctrl: event.ctrlKey,
+      meta: event.metaKey,
+      shift: event.shiftKey,
+    },
+    repeat: event.repeat,
+    isComposing: event.isComposing,
+  };
+}
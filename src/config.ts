@@ -4,15 +4,48 @@
  * This file configures how Serval connects to the Servo backend.
  */
 
+/**
+ * Settings a launcher hands to the page that started it, before the app loads
+ */
+export interface ServalLaunchConfig {
+  token?: string;
+
+  // Where the discovered or launched bridge listens
+  websocketUrl?: string;
+
+  // Why no bridge could be found or launched
+  error?: string;
+}
+
+declare global {
+  interface Window {
+    __SERVAL_LAUNCH__?: ServalLaunchConfig;
+  }
+}
+
 export interface ServalConfig {
   // Servo backend configuration
   servo: {
+    // 'auto' finds the running bridge or launches one; 'servo' connects to
+    // the configured one only
+    mode: 'auto' | 'servo';
+
     // Connection type
-    connectionType: 'websocket' | 'electron' | 'mock';
+    connectionType: 'websocket' | 'electron' | 'unix';
     
     // WebSocket URL (for WebSocket connection type)
     websocketUrl?: string;
+
+    // Per-launch secret the bridge requires in the WebSocket handshake
+    token?: string;
+
+    // Socket path of `serval-bridge --unix` (for Unix connection type); the
+    // bridge's default in the runtime directory when unset
+    unixSocketPath?: string;
     
+    // Why auto mode found no bridge, to show instead of the page
+    launchError?: string;
+
     // Enable debug logging
     debug?: boolean;
     
@@ -33,7 +66,8 @@ export interface ServalConfig {
 // Default configuration
 export const defaultConfig: ServalConfig = {
   servo: {
-    connectionType: 'mock',
+    mode: 'auto',
+    connectionType: 'websocket',
     websocketUrl: 'ws://localhost:8080',
     debug: true,
   },
@@ -47,11 +81,31 @@ export const defaultConfig: ServalConfig = {
 export function getConfig(): ServalConfig {
   const envWebSocketUrl = import.meta.env?.VITE_SERVO_WEBSOCKET_URL as string | undefined;
   const envDebug = import.meta.env?.VITE_SERVO_DEBUG === 'true';
+  const envToken = import.meta.env?.VITE_SERVO_TOKEN as string | undefined;
+  const launch = typeof window !== 'undefined' ? window.__SERVAL_LAUNCH__ : undefined;
+  const envConnection = import.meta.env?.VITE_SERVO_CONNECTION as string | undefined;
+  const envUnixSocket = import.meta.env?.VITE_SERVO_UNIX_SOCKET as string | undefined;
+  const envMode = import.meta.env?.VITE_SERVO_MODE as string | undefined;
+  if (envMode === 'iframe') {
+    console.warn("[Serval] VITE_SERVO_MODE=iframe is gone; pages always render in Servo. Using 'auto'.");
+  }
+  const mode = envMode === 'servo' || envMode === 'auto' ? envMode : defaultConfig.servo.mode;
+  const connectionType =
+    envConnection === 'websocket' || envConnection === 'electron' || envConnection === 'unix'
+      ? envConnection
+      : defaultConfig.servo.connectionType;
 
   return {
     servo: {
-      connectionType: defaultConfig.servo.connectionType,
-      websocketUrl: envWebSocketUrl || defaultConfig.servo.websocketUrl,
+      mode,
+      connectionType,
+      // The dev server discovers the bridge in auto mode
+      websocketUrl: launch?.websocketUrl || envWebSocketUrl || defaultConfig.servo.websocketUrl,
+      // A launcher that injected a token knows best; the environment covers
+      // `npm run dev` started by a script
+      token: launch?.token || envToken || defaultConfig.servo.token,
+      unixSocketPath: envUnixSocket || defaultConfig.servo.unixSocketPath,
+      launchError: launch?.error,
       debug: envDebug || defaultConfig.servo.debug,
       userAgent: defaultConfig.servo.userAgent,
     },
@@ -6,99 +6,7 @@
  */
 
 import { getConfig } from './config';
-
-// Type definitions for bridge implementations
-interface ServoMessage {
-  type: 'navigate' | 'back' | 'forward' | 'refresh' | 'close' | 'ready' | 'titleChange' | 'urlChange' | 'loadStart' | 'loadComplete';
-  tabId?: string;
-  url?: string;
-  title?: string;
-  data?: unknown;
-}
-
-/**
- * Mock Bridge for Development
- * Simulates Servo responses without requiring actual Servo installation
- */
-class MockServoBridge {
-  private tabs: Map<string, { url: string; title: string }> = new Map();
-
-  constructor() {
-    this.setupMockBackend();
-    console.log('[Serval] Mock Servo backend initialized (development mode)');
-  }
-
-  private setupMockBackend(): void {
-    window.__SERVO_BACKEND__ = {
-      postMessage: (message: ServoMessage) => {
-        this.handleCommand(message);
-      }
-    };
-  }
-
-  private handleCommand(message: ServoMessage): void {
-    const { type, tabId, url } = message;
-
-    switch (type) {
-      case 'navigate':
-        if (tabId && url) {
-          // Simulate navigation delay
-          setTimeout(() => {
-            this.sendEvent({ type: 'loadStart', tabId, url });
-            
-            setTimeout(() => {
-              const title = this.extractTitleFromUrl(url);
-              this.tabs.set(tabId, { url, title });
-              
-              this.sendEvent({ type: 'titleChange', tabId, title });
-              this.sendEvent({ type: 'loadComplete', tabId, url });
-            }, 300);
-          }, 50);
-        }
-        break;
-
-      case 'back':
-      case 'forward':
-      case 'refresh':
-        console.log(`[Mock Servo] ${type} for tab ${tabId}`);
-        if (tabId) {
-          const tab = this.tabs.get(tabId);
-          if (tab) {
-            setTimeout(() => {
-              this.sendEvent({ type: 'loadStart', tabId, url: tab.url });
-              setTimeout(() => {
-                this.sendEvent({ type: 'loadComplete', tabId, url: tab.url });
-              }, 200);
-            }, 50);
-          }
-        }
-        break;
-
-      case 'close':
-        if (tabId) {
-          this.tabs.delete(tabId);
-          console.log(`[Mock Servo] Closed tab ${tabId}`);
-        }
-        break;
-    }
-  }
-
-  private sendEvent(message: ServoMessage): void {
-    window.postMessage({
-      source: 'servo-backend',
-      message
-    }, '*');
-  }
-
-  private extractTitleFromUrl(url: string): string {
-    try {
-      const urlObj = new URL(url);
-      return urlObj.hostname;
-    } catch {
-      return 'New Tab';
-    }
-  }
-}
+import { WebSocketBridge } from './backend/WebSocketBridge';
 
 /**
  * Initialize the Servo backend bridge
@@ -112,13 +20,20 @@ export function initializeBackend(): void {
     return;
   }
 
-  // In development mode with mock connection, use MockServoBridge
-  const isDevelopment = import.meta.env?.DEV;
-  
-  if (isDevelopment && config.servo.connectionType === 'mock') {
-    new MockServoBridge();
+  if (config.servo.launchError) {
+    // Nothing listens where the bridge was expected; ServoView shows why
+    console.error(`[Serval] No Servo backend: ${config.servo.launchError}`);
+  } else if (config.servo.connectionType === 'websocket' && config.servo.websocketUrl) {
+    // `serval-bridge`, running Servo or the scripted mock engine
+    new WebSocketBridge(config.servo.websocketUrl, config.servo.token, config.servo.debug);
+  } else if (config.servo.connectionType === 'unix') {
+    // Pages cannot open Unix sockets; the shell's local adapter
+    // (examples/unix-adapter.ts) installs the backend instead
+    console.log(
+      `[Serval] Waiting for the local adapter to ${config.servo.unixSocketPath ?? 'the bridge socket'}`,
+    );
   } else {
-    // Production mode - backend bridge should be provided by the platform
+    // The backend bridge should be provided by the platform
     console.log('[Serval] Waiting for platform-provided Servo backend');
   }
 }
@@ -1,7 +1,69 @@
-import { defineConfig } from 'vite'
+import { spawn } from 'node:child_process'
+import { defineConfig, loadEnv } from 'vite'
+import type { Plugin } from 'vite'
 import react from '@vitejs/plugin-react'
 
+// What the page gets as window.__SERVAL_LAUNCH__ (ServalLaunchConfig)
+interface ServalLaunch {
+  websocketUrl?: string
+  token?: string
+  error?: string
+}
+
+/**
+ * `VITE_SERVO_MODE=auto` in development: finds the running `serval-bridge`
+ * through its discovery file, launching one when none runs, and hands the
+ * page its URL and token as `window.__SERVAL_LAUNCH__`. Pages can neither
+ * read the file nor start processes themselves.
+ *
+ * `SERVAL_BRIDGE` is the command that runs the bridge (`cargo run` by
+ * default) and `SERVAL_BRIDGE_ARGS` what it launches with.
+ */
+function discoverBridge(env: Record<string, string>): Plugin {
+  const [program, ...args] = (env.SERVAL_BRIDGE || 'cargo run -q -p serval-bridge --')
+    .split(' ')
+    .filter(Boolean)
+  const launchArgs = (env.SERVAL_BRIDGE_ARGS ?? '').split(' ').filter(Boolean)
+
+  const discover = () =>
+    new Promise<ServalLaunch>((resolve) => {
+      // Logs, the launched bridge's included, go to the dev server's terminal
+      const child = spawn(program, [...args, ...launchArgs, '--discover', '--launch'], {
+        stdio: ['ignore', 'pipe', 'inherit'],
+      })
+      let stdout = ''
+      child.stdout.on('data', (chunk) => (stdout += chunk))
+      child.on('error', (error) => resolve({ error: `cannot run ${program}: ${error.message}` }))
+      child.on('close', (code) => {
+        if (code !== 0) {
+          resolve({ error: 'no serval-bridge runs and none could be launched; see the dev server log' })
+          return
+        }
+        const found = JSON.parse(stdout)
+        if (!found.url) {
+          resolve({ error: `serval-bridge ${found.pid} only listens on ${found.socket}, which pages cannot reach` })
+          return
+        }
+        resolve({ websocketUrl: found.url, token: found.token })
+      })
+    })
+
+  return {
+    name: 'serval-discover-bridge',
+    apply: 'serve',
+    async transformIndexHtml() {
+      const launch = JSON.stringify(await discover()).replaceAll('<', '\\u003c')
+      return [{ tag: 'script', children: `window.__SERVAL_LAUNCH__ = ${launch}`, injectTo: 'head-prepend' }]
+    },
+  }
+}
+
 // https://vite.dev/config/
-export default defineConfig({
-  plugins: [react()],
+export default defineConfig(({ mode }) => {
+  const env = loadEnv(mode, process.cwd(), '')
+  // A launcher that passed a token started the bridge itself
+  const auto = (env.VITE_SERVO_MODE || 'auto') !== 'servo' && !env.VITE_SERVO_TOKEN
+  return {
+    plugins: [react(), ...(auto ? [discoverBridge(env)] : [])],
+  }
 })